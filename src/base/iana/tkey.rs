@@ -0,0 +1,35 @@
+//! TKEY modes.
+
+//------------ TkeyMode ------------------------------------------------------
+
+int_enum! {
+    /// TKEY mode numbers.
+    ///
+    /// The mode of a [TKEY] record determines the general scheme used for
+    /// establishing or deleting a shared secret key.
+    ///
+    /// For the currently registered values see the [IANA registration]. This
+    /// type is complete as of 2022-11-01.
+    ///
+    /// [TKEY]: ../../../rdata/rfc2930/index.html
+    /// [IANA registration]: https://www.iana.org/assignments/dns-parameters/dns-parameters.xhtml#tkey-modes
+    =>
+    TkeyMode, u16;
+
+    /// The key is assigned by the server.
+    (ServerAssignment => 1, b"server assignment")
+
+    /// The key is established via a Diffie-Hellman exchange.
+    (DiffieHellman => 2, b"Diffie-Hellman exchange")
+
+    /// The key is established via the GSS-API.
+    (GssApi => 3, b"GSS-API negotiation")
+
+    /// The key is assigned by the resolver.
+    (ResolverAssignment => 4, b"resolver assignment")
+
+    /// The key is deleted.
+    (KeyDeletion => 5, b"key deletion")
+}
+
+int_enum_str_decimal!(TkeyMode, u16);
@@ -0,0 +1,1340 @@
+//! JSON representation of DNS messages.
+//!
+//! [RFC 8427] defines how a DNS message can be represented as a JSON
+//! object. This module provides the types for this representation. They
+//! implement Serde’s `Serialize` and `Deserialize` traits, so they can be
+//! used with `serde_json` or any other self-describing format.
+//!
+//! A message can be represented in two forms. In the first form, each part
+//! of the message becomes a member of the message object: the header fields
+//! become members named after the fields, the first question is given
+//! through the `QNAME`, `QTYPE`, and `QCLASS` members, and the four sections
+//! become arrays of resource record objects. In the second form, the entire
+//! message is given as its wire format encoded in hex in the
+//! `messageOctetsHEX` member. Both forms can be present in the same object.
+//!
+//! The type [`MessageObject`] represents the message object and
+//! [`RecordObject`] the objects for questions and resource records. A
+//! message object for an existing message is created via
+//! [`MessageObject::from_message`] which takes [`JsonOptions`] to select
+//! the forms to include. Whether names are compressed in the original
+//! message can be described via the `compressedQNAME` and `compressedNAME`
+//! members. A message object can be turned into a message again with
+//! [`MessageObject::to_message`] or, if names should be compressed,
+//! [`MessageObject::to_compressed_message`].
+//!
+//! ```
+//! use domain::base::Message;
+//! use domain::base::json::{JsonOptions, MessageObject};
+//!
+//! let json = r#"{
+//!     "ID": 19678, "QR": 0, "Opcode": 0, "RD": 1,
+//!     "QNAME": "example.com", "QTYPE": 1, "QCLASS": 1
+//! }"#;
+//! let object: MessageObject = serde_json::from_str(json).unwrap();
+//! let msg = object.to_message().unwrap();
+//! assert_eq!(msg.header().id(), 19678);
+//!
+//! let object =
+//!     MessageObject::from_message(&msg, JsonOptions::new()).unwrap();
+//! assert_eq!(object.qname.as_deref(), Some("example.com"));
+//! ```
+//!
+//! [RFC 8427]: https://tools.ietf.org/html/rfc8427
+//! [`JsonOptions`]: struct.JsonOptions.html
+//! [`MessageObject`]: struct.MessageObject.html
+//! [`MessageObject::from_message`]: struct.MessageObject.html#method.from_message
+//! [`MessageObject::to_message`]: struct.MessageObject.html#method.to_message
+//! [`MessageObject::to_compressed_message`]: struct.MessageObject.html#method.to_compressed_message
+//! [`RecordObject`]: struct.RecordObject.html
+#![cfg(all(feature = "serde", feature = "std"))]
+#![cfg_attr(docsrs, doc(cfg(all(feature = "serde", feature = "std"))))]
+
+use super::iana::{Class, Opcode, Rcode, Rtype};
+use super::message::{Message, RecordSection};
+use super::message_builder::{
+    AdditionalBuilder, MessageBuilder, StaticCompressor,
+};
+use super::name::{Dname, ParsedDname, ToLabelIter};
+use super::octets::{
+    Compose, OctetsBuilder, OctetsRef, Parse, ParseError, Parser, ShortBuf,
+};
+use super::rdata::UnknownRecordData;
+use super::record::{ParsedRecord, Record};
+use crate::rdata::AllRecordData;
+use crate::utils::base16;
+use core::fmt;
+use core::str::FromStr;
+use serde::de::{IgnoredAny, MapAccess};
+use serde::ser::SerializeMap;
+use std::string::{String, ToString};
+use std::vec::Vec;
+
+//------------ JsonOptions ---------------------------------------------------
+
+/// Options for creating a message object from a message.
+///
+/// By default, the message is represented by its members only and name
+/// compression is not described.
+#[derive(Clone, Copy, Debug)]
+pub struct JsonOptions {
+    /// Whether to include the header and sections as members.
+    members: bool,
+
+    /// Whether to include the `messageOctetsHEX` member.
+    octets_hex: bool,
+
+    /// Whether to include the `compressedQNAME` and `compressedNAME` members.
+    compressed_names: bool,
+}
+
+impl JsonOptions {
+    /// Creates the default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether to represent the header and sections as members.
+    pub fn members(mut self, members: bool) -> Self {
+        self.members = members;
+        self
+    }
+
+    /// Sets whether to include the wire format in `messageOctetsHEX`.
+    pub fn octets_hex(mut self, octets_hex: bool) -> Self {
+        self.octets_hex = octets_hex;
+        self
+    }
+
+    /// Sets whether to describe how names are compressed.
+    ///
+    /// If enabled, question and record objects will contain the
+    /// `compressedNAME` member and the message object the
+    /// `compressedQNAME` member.
+    pub fn compressed_names(mut self, compressed_names: bool) -> Self {
+        self.compressed_names = compressed_names;
+        self
+    }
+}
+
+impl Default for JsonOptions {
+    fn default() -> Self {
+        JsonOptions {
+            members: true,
+            octets_hex: false,
+            compressed_names: false,
+        }
+    }
+}
+
+//------------ MessageObject -------------------------------------------------
+
+/// The JSON representation of a DNS message.
+///
+/// The type contains the members of the message object defined in
+/// [RFC 8427] that describe the message itself. All members are optional.
+/// When deserializing, unknown members are ignored. The flags are
+/// serialized as the integers 0 and 1 as in the examples of the RFC but
+/// can also be given as booleans when deserializing.
+///
+/// The section counts are informational only. When creating a message
+/// from the object, the counts are derived from the sections.
+///
+/// [RFC 8427]: https://tools.ietf.org/html/rfc8427
+#[derive(
+    Clone, Debug, Default, Eq, PartialEq, serde::Serialize, serde::Deserialize,
+)]
+pub struct MessageObject {
+    /// The message ID.
+    #[serde(rename = "ID", default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<u16>,
+
+    /// The QR flag.
+    #[serde(
+        rename = "QR",
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "flag"
+    )]
+    pub qr: Option<bool>,
+
+    /// The opcode.
+    #[serde(
+        rename = "Opcode",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub opcode: Option<u8>,
+
+    /// The AA flag.
+    #[serde(
+        rename = "AA",
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "flag"
+    )]
+    pub aa: Option<bool>,
+
+    /// The TC flag.
+    #[serde(
+        rename = "TC",
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "flag"
+    )]
+    pub tc: Option<bool>,
+
+    /// The RD flag.
+    #[serde(
+        rename = "RD",
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "flag"
+    )]
+    pub rd: Option<bool>,
+
+    /// The RA flag.
+    #[serde(
+        rename = "RA",
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "flag"
+    )]
+    pub ra: Option<bool>,
+
+    /// The AD flag.
+    #[serde(
+        rename = "AD",
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "flag"
+    )]
+    pub ad: Option<bool>,
+
+    /// The CD flag.
+    #[serde(
+        rename = "CD",
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "flag"
+    )]
+    pub cd: Option<bool>,
+
+    /// The response code from the header.
+    #[serde(
+        rename = "RCODE",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub rcode: Option<u8>,
+
+    /// The number of questions.
+    #[serde(
+        rename = "QDCOUNT",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub qdcount: Option<u16>,
+
+    /// The number of records in the answer section.
+    #[serde(
+        rename = "ANCOUNT",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub ancount: Option<u16>,
+
+    /// The number of records in the authority section.
+    #[serde(
+        rename = "NSCOUNT",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub nscount: Option<u16>,
+
+    /// The number of records in the additional section.
+    #[serde(
+        rename = "ARCOUNT",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub arcount: Option<u16>,
+
+    /// The name of the first question.
+    #[serde(
+        rename = "QNAME",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub qname: Option<String>,
+
+    /// How the name of the first question is compressed.
+    #[serde(
+        rename = "compressedQNAME",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub compressed_qname: Option<NameCompression>,
+
+    /// The record type of the first question.
+    #[serde(
+        rename = "QTYPE",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub qtype: Option<u16>,
+
+    /// The mnemonic of the record type of the first question.
+    #[serde(
+        rename = "QTYPEname",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub qtype_name: Option<String>,
+
+    /// The class of the first question.
+    #[serde(
+        rename = "QCLASS",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub qclass: Option<u16>,
+
+    /// The mnemonic of the class of the first question.
+    #[serde(
+        rename = "QCLASSname",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub qclass_name: Option<String>,
+
+    /// The questions.
+    #[serde(
+        rename = "questionRRs",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub question_rrs: Option<Vec<RecordObject>>,
+
+    /// The records of the answer section.
+    #[serde(
+        rename = "answerRRs",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub answer_rrs: Option<Vec<RecordObject>>,
+
+    /// The records of the authority section.
+    #[serde(
+        rename = "authorityRRs",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub authority_rrs: Option<Vec<RecordObject>>,
+
+    /// The records of the additional section.
+    #[serde(
+        rename = "additionalRRs",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub additional_rrs: Option<Vec<RecordObject>>,
+
+    /// The complete message in wire format.
+    #[serde(
+        rename = "messageOctetsHEX",
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "hex"
+    )]
+    pub message_octets_hex: Option<Vec<u8>>,
+}
+
+impl MessageObject {
+    /// Creates the message object for a message.
+    ///
+    /// The options determine which members are included. The data of
+    /// records is given both in hex via `RDATAHEX` and, for record types
+    /// known to this crate, in presentation format via a member named
+    /// `rdata` followed by the type’s mnemonic, e.g., `rdataNS`. Since the
+    /// hex data is meant to be usable outside of the message, names in it
+    /// are never compressed.
+    ///
+    /// Returns an error if the message cannot be parsed.
+    pub fn from_message<Octets>(
+        msg: &Message<Octets>,
+        options: JsonOptions,
+    ) -> Result<Self, ParseError>
+    where
+        Octets: AsRef<[u8]>,
+        for<'a> &'a Octets: OctetsRef,
+    {
+        let mut res = MessageObject::default();
+        if options.octets_hex {
+            res.message_octets_hex = Some(msg.as_slice().into());
+        }
+        if !options.members {
+            return Ok(res);
+        }
+
+        let header = msg.header();
+        res.id = Some(header.id());
+        res.qr = Some(header.qr());
+        res.opcode = Some(header.opcode().to_int());
+        res.aa = Some(header.aa());
+        res.tc = Some(header.tc());
+        res.rd = Some(header.rd());
+        res.ra = Some(header.ra());
+        res.ad = Some(header.ad());
+        res.cd = Some(header.cd());
+        res.rcode = Some(header.rcode().to_int());
+        let counts = msg.header_counts();
+        res.qdcount = Some(counts.qdcount());
+        res.ancount = Some(counts.ancount());
+        res.nscount = Some(counts.nscount());
+        res.arcount = Some(counts.arcount());
+
+        let mut questions = Vec::new();
+        let mut section = msg.question();
+        loop {
+            let pos = section.pos();
+            let question = match section.next() {
+                Some(question) => question?,
+                None => break,
+            };
+            questions.push(RecordObject {
+                name: Some(name_to_string(question.qname())),
+                compressed_name: if options.compressed_names {
+                    Some(NameCompression::at(msg.as_slice(), pos)?)
+                } else {
+                    None
+                },
+                rtype: Some(question.qtype().to_int()),
+                rtype_name: Some(question.qtype().to_string()),
+                class: Some(question.qclass().to_int()),
+                class_name: Some(question.qclass().to_string()),
+                ..Default::default()
+            });
+        }
+        if let Some(first) = questions.first() {
+            res.qname = first.name.clone();
+            res.compressed_qname = first.compressed_name.clone();
+            res.qtype = first.rtype;
+            res.qtype_name = first.rtype_name.clone();
+            res.qclass = first.class;
+            res.qclass_name = first.class_name.clone();
+            res.question_rrs = Some(questions);
+        }
+
+        res.answer_rrs =
+            RecordObject::from_section(msg, msg.answer()?, options)?;
+        res.authority_rrs =
+            RecordObject::from_section(msg, msg.authority()?, options)?;
+        res.additional_rrs =
+            RecordObject::from_section(msg, msg.additional()?, options)?;
+        Ok(res)
+    }
+
+    /// Creates a message from the message object.
+    ///
+    /// If the `messageOctetsHEX` member is present, the message is taken
+    /// from it and all other members are ignored. Otherwise the message is
+    /// built from the header members and the sections. If the
+    /// `questionRRs` member is missing, the question is taken from the
+    /// `QNAME`, `QTYPE`, and `QCLASS` members instead. Names are not
+    /// compressed in the created message.
+    pub fn to_message(&self) -> Result<Message<Vec<u8>>, JsonError> {
+        if let Some(octets) = self.message_octets_hex.as_ref() {
+            return Message::from_octets(octets.clone())
+                .map_err(|_| JsonError::ShortMessage);
+        }
+        self.compose(MessageBuilder::new_vec())
+            .map(AdditionalBuilder::into_message)
+    }
+
+    /// Creates a message with compressed names from the message object.
+    ///
+    /// This is the same as [`to_message`] except that the owner names of
+    /// records and the names of questions are compressed.
+    ///
+    /// [`to_message`]: #method.to_message
+    pub fn to_compressed_message(
+        &self,
+    ) -> Result<Message<Vec<u8>>, JsonError> {
+        if let Some(octets) = self.message_octets_hex.as_ref() {
+            return Message::from_octets(octets.clone())
+                .map_err(|_| JsonError::ShortMessage);
+        }
+        self.compose(MessageBuilder::from_target(StaticCompressor::new(
+            Vec::new(),
+        ))?)
+        .map(AdditionalBuilder::into_message)
+    }
+
+    /// Builds the message from the members.
+    fn compose<Target: OctetsBuilder + AsMut<[u8]>>(
+        &self,
+        mut builder: MessageBuilder<Target>,
+    ) -> Result<AdditionalBuilder<Target>, JsonError> {
+        let header = builder.header_mut();
+        header.set_id(self.id.unwrap_or(0));
+        header.set_qr(self.qr.unwrap_or(false));
+        let opcode = self.opcode.unwrap_or(0);
+        if opcode > 0x0F {
+            return Err(JsonError::InvalidOpcode);
+        }
+        let rcode = self.rcode.unwrap_or(0);
+        if rcode > 0x0F {
+            return Err(JsonError::InvalidRcode);
+        }
+        header.set_opcode(Opcode::from_int(opcode));
+        header.set_aa(self.aa.unwrap_or(false));
+        header.set_tc(self.tc.unwrap_or(false));
+        header.set_rd(self.rd.unwrap_or(false));
+        header.set_ra(self.ra.unwrap_or(false));
+        header.set_ad(self.ad.unwrap_or(false));
+        header.set_cd(self.cd.unwrap_or(false));
+        header.set_rcode(Rcode::from_int(rcode));
+
+        let mut builder = builder.question();
+        match self.question_rrs.as_ref() {
+            Some(questions) => {
+                for question in questions {
+                    builder.push((
+                        question.dname()?,
+                        question.rtype()?,
+                        question.class()?,
+                    ))?;
+                }
+            }
+            None => {
+                if let Some(qname) = self.qname.as_ref() {
+                    builder.push((
+                        str_to_dname(qname)?,
+                        rtype_from_parts(
+                            self.qtype,
+                            self.qtype_name.as_ref(),
+                        )?,
+                        class_from_parts(
+                            self.qclass,
+                            self.qclass_name.as_ref(),
+                        )?,
+                    ))?;
+                }
+            }
+        }
+
+        let mut builder = builder.answer();
+        for record in self.answer_rrs.iter().flatten() {
+            builder.push(record.to_record()?)?;
+        }
+        let mut builder = builder.authority();
+        for record in self.authority_rrs.iter().flatten() {
+            builder.push(record.to_record()?)?;
+        }
+        let mut builder = builder.additional();
+        for record in self.additional_rrs.iter().flatten() {
+            builder.push(record.to_record()?)?;
+        }
+        Ok(builder)
+    }
+}
+
+//------------ RecordObject --------------------------------------------------
+
+/// The JSON representation of a question or resource record.
+///
+/// Questions only use the members for the name, type, and class. All
+/// members are optional. When deserializing, unknown members are ignored.
+///
+/// The record data in presentation format is serialized as a member named
+/// `rdata` followed by the mnemonic of the record type, e.g., `rdataA`. If
+/// such a member is found when deserializing and neither `TYPE` nor
+/// `TYPEname` are present, the record type is taken from the member’s
+/// name.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct RecordObject {
+    /// The owner name in the `NAME` member.
+    pub name: Option<String>,
+
+    /// How the owner name is compressed in the `compressedNAME` member.
+    pub compressed_name: Option<NameCompression>,
+
+    /// The record type in the `TYPE` member.
+    pub rtype: Option<u16>,
+
+    /// The mnemonic of the record type in the `TYPEname` member.
+    pub rtype_name: Option<String>,
+
+    /// The class in the `CLASS` member.
+    pub class: Option<u16>,
+
+    /// The mnemonic of the class in the `CLASSname` member.
+    pub class_name: Option<String>,
+
+    /// The TTL in the `TTL` member.
+    pub ttl: Option<u32>,
+
+    /// The length of the record data in the message.
+    ///
+    /// This is kept in the `RDLENGTH` member. Because the data in the
+    /// message may contain compressed names, this may differ from the
+    /// length of the data in `rdata_hex`.
+    pub rdlength: Option<u16>,
+
+    /// The record data in wire format in the `RDATAHEX` member.
+    pub rdata_hex: Option<Vec<u8>>,
+
+    /// The record data in presentation format.
+    pub rdata: Option<String>,
+}
+
+impl RecordObject {
+    /// Creates the record objects for a section.
+    ///
+    /// Returns `None` if the section is empty.
+    fn from_section<Octets>(
+        msg: &Message<Octets>,
+        mut section: RecordSection<&Octets>,
+        options: JsonOptions,
+    ) -> Result<Option<Vec<Self>>, ParseError>
+    where
+        Octets: AsRef<[u8]>,
+        for<'a> &'a Octets: OctetsRef,
+    {
+        let mut res = Vec::new();
+        loop {
+            let pos = section.pos();
+            let record = match section.next() {
+                Some(record) => record?,
+                None => break,
+            };
+            res.push(Self::from_record(
+                msg.as_slice(),
+                pos,
+                record,
+                options,
+            )?);
+        }
+        Ok(if res.is_empty() { None } else { Some(res) })
+    }
+
+    /// Creates the record object for a record starting at `pos`.
+    fn from_record<Ref: OctetsRef>(
+        msg: &[u8],
+        pos: usize,
+        record: ParsedRecord<Ref>,
+        options: JsonOptions,
+    ) -> Result<Self, ParseError> {
+        let compression = NameCompression::at(msg, pos)?;
+        let mut parser = Parser::from_ref(msg);
+        parser.seek(pos + usize::from(compression.length) + 8)?;
+        let rdlength = parser.parse_u16()?;
+
+        let (rdata_hex, rdata) = match record
+            .to_record::<AllRecordData<_, _>>()?
+        {
+            Some(record) => {
+                let mut hex = Vec::new();
+                // Composing into a vec can’t fail.
+                record.data().compose(&mut hex).unwrap();
+                let text = match record.data() {
+                    AllRecordData::Opt(_) | AllRecordData::Unknown(_) => None,
+                    data => Some(data.to_string()),
+                };
+                (hex, text)
+            }
+            None => (parser.parse_octets(rdlength.into())?.into(), None),
+        };
+
+        Ok(RecordObject {
+            name: Some(name_to_string(record.owner())),
+            compressed_name: if options.compressed_names {
+                Some(compression)
+            } else {
+                None
+            },
+            rtype: Some(record.rtype().to_int()),
+            rtype_name: Some(record.rtype().to_string()),
+            class: Some(record.class().to_int()),
+            class_name: Some(record.class().to_string()),
+            ttl: Some(record.ttl()),
+            rdlength: Some(rdlength),
+            rdata_hex: Some(rdata_hex),
+            rdata,
+        })
+    }
+
+    /// Creates the record described by the object.
+    ///
+    /// If the `RDATAHEX` member is present, it is used for the record
+    /// data. Otherwise the data is taken from the presentation format which
+    /// is only supported if the `zonefile` feature is enabled.
+    fn to_record(&self) -> Result<DecodedRecord, JsonError> {
+        let rtype = self.rtype()?;
+        let data = match (self.rdata_hex.as_ref(), self.rdata.as_ref()) {
+            (Some(data), _) => data.clone(),
+            (None, Some(data)) => scan_rdata(rtype, data)?,
+            (None, None) => return Err(JsonError::MissingRdata),
+        };
+        Ok(Record::new(
+            self.dname()?,
+            self.class()?,
+            self.ttl.unwrap_or(0),
+            UnknownRecordData::from_octets(rtype, data),
+        ))
+    }
+
+    /// Returns the owner name.
+    fn dname(&self) -> Result<Dname<Vec<u8>>, JsonError> {
+        match self.name.as_ref() {
+            Some(name) => str_to_dname(name),
+            None => Err(JsonError::MissingName),
+        }
+    }
+
+    /// Returns the record type.
+    fn rtype(&self) -> Result<Rtype, JsonError> {
+        rtype_from_parts(self.rtype, self.rtype_name.as_ref())
+    }
+
+    /// Returns the class, defaulting to IN.
+    fn class(&self) -> Result<Class, JsonError> {
+        class_from_parts(self.class, self.class_name.as_ref())
+    }
+}
+
+/// The type of records created from record objects.
+type DecodedRecord = Record<Dname<Vec<u8>>, UnknownRecordData<Vec<u8>>>;
+
+//--- Serialize and Deserialize
+
+impl serde::Serialize for RecordObject {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        if let Some(name) = self.name.as_ref() {
+            map.serialize_entry("NAME", name)?;
+        }
+        if let Some(compressed) = self.compressed_name.as_ref() {
+            map.serialize_entry("compressedNAME", compressed)?;
+        }
+        if let Some(rtype) = self.rtype {
+            map.serialize_entry("TYPE", &rtype)?;
+        }
+        if let Some(rtype_name) = self.rtype_name.as_ref() {
+            map.serialize_entry("TYPEname", rtype_name)?;
+        }
+        if let Some(class) = self.class {
+            map.serialize_entry("CLASS", &class)?;
+        }
+        if let Some(class_name) = self.class_name.as_ref() {
+            map.serialize_entry("CLASSname", class_name)?;
+        }
+        if let Some(ttl) = self.ttl {
+            map.serialize_entry("TTL", &ttl)?;
+        }
+        if let Some(rdlength) = self.rdlength {
+            map.serialize_entry("RDLENGTH", &rdlength)?;
+        }
+        if let Some(data) = self.rdata_hex.as_ref() {
+            map.serialize_entry(
+                "RDATAHEX",
+                &base16::encode_display(data).to_string(),
+            )?;
+        }
+        if let Some(data) = self.rdata.as_ref() {
+            let rtype = match self.rtype() {
+                Ok(rtype) => rtype,
+                Err(err) => return Err(serde::ser::Error::custom(err)),
+            };
+            map.serialize_entry(&format_args!("rdata{}", rtype), data)?;
+        }
+        map.end()
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for RecordObject {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = RecordObject;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a resource record object")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(
+                self,
+                mut map: A,
+            ) -> Result<Self::Value, A::Error> {
+                let mut res = RecordObject::default();
+                let mut rdata_type = None;
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "NAME" => res.name = Some(map.next_value()?),
+                        "compressedNAME" => {
+                            res.compressed_name = Some(map.next_value()?)
+                        }
+                        "TYPE" => res.rtype = Some(map.next_value()?),
+                        "TYPEname" => {
+                            res.rtype_name = Some(map.next_value()?)
+                        }
+                        "CLASS" => res.class = Some(map.next_value()?),
+                        "CLASSname" => {
+                            res.class_name = Some(map.next_value()?)
+                        }
+                        "TTL" => res.ttl = Some(map.next_value()?),
+                        "RDLENGTH" => res.rdlength = Some(map.next_value()?),
+                        "RDATAHEX" => {
+                            let data = map.next_value::<String>()?;
+                            res.rdata_hex = Some(
+                                base16::decode(&data)
+                                    .map_err(serde::de::Error::custom)?,
+                            );
+                        }
+                        _ => {
+                            if let Some(rtype) = key.strip_prefix("rdata") {
+                                res.rdata = Some(map.next_value()?);
+                                rdata_type = Some(rtype.into());
+                            } else {
+                                map.next_value::<IgnoredAny>()?;
+                            }
+                        }
+                    }
+                }
+                if res.rtype.is_none() && res.rtype_name.is_none() {
+                    res.rtype_name = rdata_type;
+                }
+                Ok(res)
+            }
+        }
+
+        deserializer.deserialize_map(Visitor)
+    }
+}
+
+//------------ NameCompression -----------------------------------------------
+
+/// A description of how a name is compressed in a message.
+///
+/// This is the value of the `compressedQNAME` and `compressedNAME`
+/// members.
+#[derive(
+    Clone, Debug, Default, Eq, PartialEq, serde::Serialize, serde::Deserialize,
+)]
+pub struct NameCompression {
+    /// Whether the name is compressed.
+    #[serde(rename = "isCompressed", with = "flag_required")]
+    pub is_compressed: bool,
+
+    /// The number of octets the name occupies in the message.
+    ///
+    /// For a compressed name, this includes the compression pointer.
+    pub length: u16,
+}
+
+impl NameCompression {
+    /// Determines the compression of the name starting at `pos`.
+    fn at(msg: &[u8], pos: usize) -> Result<Self, ParseError> {
+        let mut parser = Parser::from_ref(msg);
+        parser.seek(pos)?;
+        let name = ParsedDname::parse(&mut parser)?;
+        let length = parser.pos() - pos;
+
+        // A pointer never results in a name of the same length, so we can
+        // compare lengths to find out if the name is compressed.
+        Ok(NameCompression {
+            is_compressed: length != name.len(),
+            length: length as u16,
+        })
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Converts a name into its string representation.
+///
+/// The trailing dot is omitted except for the root name which is `"."`.
+fn name_to_string<Ref: AsRef<[u8]>>(name: &ParsedDname<Ref>) -> String {
+    if name.is_root() {
+        ".".into()
+    } else {
+        name.to_string()
+    }
+}
+
+/// Converts a string into an absolute name.
+fn str_to_dname(s: &str) -> Result<Dname<Vec<u8>>, JsonError> {
+    if s == "." {
+        Ok(Dname::root_vec())
+    } else {
+        Dname::from_str(s).map_err(|_| JsonError::InvalidName)
+    }
+}
+
+/// Determines a record type from the numeric value or mnemonic.
+fn rtype_from_parts(
+    value: Option<u16>,
+    name: Option<&String>,
+) -> Result<Rtype, JsonError> {
+    match (value, name) {
+        (Some(value), _) => Ok(Rtype::from_int(value)),
+        (None, Some(name)) => {
+            Rtype::from_str(name).map_err(|_| JsonError::InvalidType)
+        }
+        (None, None) => Err(JsonError::MissingType),
+    }
+}
+
+/// Determines a class from the numeric value or mnemonic.
+///
+/// Returns IN if neither is given.
+fn class_from_parts(
+    value: Option<u16>,
+    name: Option<&String>,
+) -> Result<Class, JsonError> {
+    match (value, name) {
+        (Some(value), _) => Ok(Class::from_int(value)),
+        (None, Some(name)) => {
+            Class::from_str(name).map_err(|_| JsonError::InvalidClass)
+        }
+        (None, None) => Ok(Class::In),
+    }
+}
+
+/// Converts record data in presentation format into wire format.
+#[cfg(feature = "zonefile")]
+fn scan_rdata(rtype: Rtype, data: &str) -> Result<Vec<u8>, JsonError> {
+    use crate::zonefile::inplace::{Entry, Zonefile};
+
+    let mut zonefile = Zonefile::new();
+    zonefile.set_origin(Dname::root_bytes());
+    zonefile
+        .extend_from_slice(format!(". 0 IN {} {}\n", rtype, data).as_bytes());
+    match zonefile.next_entry() {
+        Ok(Some(Entry::Record(record))) => {
+            let mut res = Vec::new();
+            record.data().compose(&mut res)?;
+            Ok(res)
+        }
+        _ => Err(JsonError::InvalidRdata),
+    }
+}
+
+/// Converts record data in presentation format into wire format.
+///
+/// Without the `zonefile` feature, this is not supported.
+#[cfg(not(feature = "zonefile"))]
+fn scan_rdata(_rtype: Rtype, _data: &str) -> Result<Vec<u8>, JsonError> {
+    Err(JsonError::InvalidRdata)
+}
+
+//------------ flag ----------------------------------------------------------
+
+/// Serialization for optional flags.
+///
+/// Flags are serialized as the integers 0 and 1 but can be deserialized
+/// from booleans, too.
+mod flag {
+    pub fn serialize<S: serde::Serializer>(
+        value: &Option<bool>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match *value {
+            Some(value) => serializer.serialize_u8(value.into()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<bool>, D::Error> {
+        super::flag_required::deserialize(deserializer).map(Some)
+    }
+}
+
+/// Serialization for mandatory flags.
+mod flag_required {
+    use core::fmt;
+
+    pub fn serialize<S: serde::Serializer>(
+        value: &bool,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8((*value).into())
+    }
+
+    pub fn deserialize<'de, D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<bool, D::Error> {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = bool;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a boolean or the integers 0 or 1")
+            }
+
+            fn visit_bool<E: serde::de::Error>(
+                self,
+                v: bool,
+            ) -> Result<bool, E> {
+                Ok(v)
+            }
+
+            fn visit_u64<E: serde::de::Error>(
+                self,
+                v: u64,
+            ) -> Result<bool, E> {
+                match v {
+                    0 => Ok(false),
+                    1 => Ok(true),
+                    _ => Err(E::invalid_value(
+                        serde::de::Unexpected::Unsigned(v),
+                        &self,
+                    )),
+                }
+            }
+
+            fn visit_i64<E: serde::de::Error>(
+                self,
+                v: i64,
+            ) -> Result<bool, E> {
+                match v {
+                    0 => Ok(false),
+                    1 => Ok(true),
+                    _ => Err(E::invalid_value(
+                        serde::de::Unexpected::Signed(v),
+                        &self,
+                    )),
+                }
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+//------------ hex -----------------------------------------------------------
+
+/// Serialization for optional octets as a hex string.
+mod hex {
+    use crate::utils::base16;
+    use std::string::String;
+    use std::vec::Vec;
+
+    pub fn serialize<S: serde::Serializer>(
+        value: &Option<Vec<u8>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match value.as_ref() {
+            Some(value) => {
+                serializer.collect_str(&base16::encode_display(value))
+            }
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Vec<u8>>, D::Error> {
+        let data = <String as serde::Deserialize>::deserialize(deserializer)?;
+        base16::decode(&data)
+            .map(Some)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+//============ Error Types ===================================================
+
+//------------ JsonError -----------------------------------------------------
+
+/// A message object could not be converted into a message.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum JsonError {
+    /// A question or record lacks its name.
+    MissingName,
+
+    /// A name member does not contain a valid domain name.
+    InvalidName,
+
+    /// A question or record lacks its type.
+    MissingType,
+
+    /// The mnemonic of a record type is unknown.
+    InvalidType,
+
+    /// The mnemonic of a class is unknown.
+    InvalidClass,
+
+    /// The `Opcode` member doesn’t fit into the four bits of the header.
+    InvalidOpcode,
+
+    /// The `RCODE` member doesn’t fit into the four bits of the header.
+    InvalidRcode,
+
+    /// A record lacks its data.
+    MissingRdata,
+
+    /// The record data in presentation format is invalid.
+    ///
+    /// This is also returned if the record data is only given in
+    /// presentation format and the `zonefile` feature is not enabled.
+    InvalidRdata,
+
+    /// The `messageOctetsHEX` member is too short for a message.
+    ShortMessage,
+
+    /// The message would exceed its maximum size.
+    ShortBuf,
+}
+
+//--- From
+
+impl From<ShortBuf> for JsonError {
+    fn from(_: ShortBuf) -> Self {
+        JsonError::ShortBuf
+    }
+}
+
+//--- Display and Error
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            JsonError::MissingName => "missing name",
+            JsonError::InvalidName => "invalid domain name",
+            JsonError::MissingType => "missing record type",
+            JsonError::InvalidType => "unknown record type",
+            JsonError::InvalidClass => "unknown class",
+            JsonError::InvalidOpcode => "invalid opcode",
+            JsonError::InvalidRcode => "invalid response code",
+            JsonError::MissingRdata => "missing record data",
+            JsonError::InvalidRdata => "invalid record data",
+            JsonError::ShortMessage => "short message",
+            JsonError::ShortBuf => "message too long",
+        })
+    }
+}
+
+impl std::error::Error for JsonError {}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::Rtype;
+    use crate::rdata::{Mx, A};
+
+    fn message() -> Message<Vec<u8>> {
+        let mut msg =
+            MessageBuilder::from_target(StaticCompressor::new(Vec::new()))
+                .unwrap();
+        msg.header_mut().set_id(19678);
+        msg.header_mut().set_qr(true);
+        msg.header_mut().set_rd(true);
+        let mut msg = msg.question();
+        msg.push((Dname::vec_from_str("example.com").unwrap(), Rtype::Mx))
+            .unwrap();
+        let mut msg = msg.answer();
+        msg.push((
+            Dname::vec_from_str("example.com").unwrap(),
+            3600,
+            Mx::new(10, Dname::vec_from_str("mail.example.com").unwrap()),
+        ))
+        .unwrap();
+        let mut msg = msg.additional();
+        msg.push((
+            Dname::vec_from_str("mail.example.com").unwrap(),
+            3600,
+            A::from_octets(192, 0, 2, 1),
+        ))
+        .unwrap();
+        msg.into_message()
+    }
+
+    #[test]
+    fn members() {
+        let msg = message();
+        let json = serde_json::to_value(
+            MessageObject::from_message(&msg, JsonOptions::new()).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "ID": 19678, "QR": 1, "Opcode": 0, "AA": 0, "TC": 0,
+                "RD": 1, "RA": 0, "AD": 0, "CD": 0, "RCODE": 0,
+                "QDCOUNT": 1, "ANCOUNT": 1, "NSCOUNT": 0, "ARCOUNT": 1,
+                "QNAME": "example.com", "QTYPE": 15, "QTYPEname": "MX",
+                "QCLASS": 1, "QCLASSname": "IN",
+                "questionRRs": [{
+                    "NAME": "example.com", "TYPE": 15, "TYPEname": "MX",
+                    "CLASS": 1, "CLASSname": "IN"
+                }],
+                "answerRRs": [{
+                    "NAME": "example.com", "TYPE": 15, "TYPEname": "MX",
+                    "CLASS": 1, "CLASSname": "IN", "TTL": 3600,
+                    "RDLENGTH": 9,
+                    "RDATAHEX": "000A046D61696C076578616D706C6503636F6D00",
+                    "rdataMX": "10 mail.example.com."
+                }],
+                "additionalRRs": [{
+                    "NAME": "mail.example.com", "TYPE": 1, "TYPEname": "A",
+                    "CLASS": 1, "CLASSname": "IN", "TTL": 3600,
+                    "RDLENGTH": 4, "RDATAHEX": "C0000201",
+                    "rdataA": "192.0.2.1"
+                }]
+            })
+        );
+
+        let object: MessageObject = serde_json::from_value(json).unwrap();
+        let copy = object.to_message().unwrap();
+        assert_eq!(msg.to_string(), copy.to_string());
+        let compressed = object.to_compressed_message().unwrap();
+        assert_eq!(msg.to_string(), compressed.to_string());
+        assert!(compressed.as_slice().len() < copy.as_slice().len());
+    }
+
+    #[test]
+    fn compressed_names() {
+        let msg = message();
+        let object = MessageObject::from_message(
+            &msg,
+            JsonOptions::new().compressed_names(true),
+        )
+        .unwrap();
+        assert_eq!(
+            object.compressed_qname,
+            Some(NameCompression {
+                is_compressed: false,
+                length: 13
+            })
+        );
+        let answer = &object.answer_rrs.as_ref().unwrap()[0];
+        assert_eq!(
+            answer.compressed_name,
+            Some(NameCompression {
+                is_compressed: true,
+                length: 2
+            })
+        );
+        assert_eq!(
+            serde_json::to_value(&answer.compressed_name).unwrap(),
+            serde_json::json!({"isCompressed": 1, "length": 2})
+        );
+    }
+
+    #[test]
+    fn octets_hex() {
+        let msg = message();
+        let object = MessageObject::from_message(
+            &msg,
+            JsonOptions::new().members(false).octets_hex(true),
+        )
+        .unwrap();
+        let json = serde_json::to_string(&object).unwrap();
+        assert_eq!(
+            json,
+            format!(
+                "{{\"messageOctetsHEX\":\"{}\"}}",
+                base16::encode_string(msg.as_slice())
+            )
+        );
+        let object: MessageObject = serde_json::from_str(&json).unwrap();
+        assert_eq!(object.to_message().unwrap().as_slice(), msg.as_slice());
+
+        let object: MessageObject =
+            serde_json::from_str(r#"{"messageOctetsHEX": "0102"}"#).unwrap();
+        assert_eq!(object.to_message().err(), Some(JsonError::ShortMessage));
+    }
+
+    #[test]
+    #[cfg(feature = "zonefile")]
+    fn from_rfc_example() {
+        let object: MessageObject = serde_json::from_str(
+            r#"{
+                "ID": 32784, "QR": true, "Opcode": 0, "AA": 1, "TC": 0,
+                "RD": 0, "RA": 0, "AD": 0, "CD": 0, "RCODE": 0,
+                "QDCOUNT": 1, "ANCOUNT": 1, "NSCOUNT": 0, "ARCOUNT": 0,
+                "QNAME": "example.com.", "QTYPE": 1, "QCLASS": 1,
+                "answerRRs": [
+                    { "NAME": "example.com.", "TYPE": 1, "CLASS": 1,
+                      "TTL": 3600, "RDATAHEX": "C0000201" },
+                    { "NAME": "example.com.", "TTL": 3600,
+                      "rdataA": "192.0.2.2" }
+                ],
+                "dateString": "2019-02-04T12:00:00Z",
+                "comment": "ignored"
+            }"#,
+        )
+        .unwrap();
+        let msg = object.to_message().unwrap();
+        assert_eq!(msg.header().id(), 32784);
+        assert!(msg.header().qr());
+        assert!(msg.header().aa());
+        assert_eq!(msg.header_counts().ancount(), 2);
+        let question = msg.first_question().unwrap();
+        assert_eq!(question.qname().to_string(), "example.com");
+        assert_eq!(question.qtype(), Rtype::A);
+        let answer = MessageObject::from_message(&msg, JsonOptions::new())
+            .unwrap()
+            .answer_rrs
+            .unwrap();
+        assert_eq!(answer[0].rdata.as_deref(), Some("192.0.2.1"));
+        assert_eq!(answer[1].rdata.as_deref(), Some("192.0.2.2"));
+        assert_eq!(answer[1].rtype_name.as_deref(), Some("A"));
+    }
+
+    #[test]
+    fn errors() {
+        fn convert(json: &str) -> Option<JsonError> {
+            serde_json::from_str::<MessageObject>(json)
+                .unwrap()
+                .to_message()
+                .err()
+        }
+
+        assert_eq!(
+            convert(r#"{"answerRRs": [{"TYPE": 1, "RDATAHEX": ""}]}"#),
+            Some(JsonError::MissingName)
+        );
+        assert_eq!(
+            convert(r#"{"answerRRs": [{"NAME": "a", "RDATAHEX": ""}]}"#),
+            Some(JsonError::MissingType)
+        );
+        assert_eq!(
+            convert(r#"{"answerRRs": [{"NAME": "a", "TYPE": 1}]}"#),
+            Some(JsonError::MissingRdata)
+        );
+        assert_eq!(
+            convert(r#"{"answerRRs": [{"NAME": "a", "rdataA": "foo"}]}"#),
+            Some(JsonError::InvalidRdata)
+        );
+        assert_eq!(
+            convert(r#"{"QNAME": "a..b", "QTYPE": 1}"#),
+            Some(JsonError::InvalidName)
+        );
+        assert_eq!(
+            convert(r#"{"QR": 0, "Opcode": 16}"#),
+            Some(JsonError::InvalidOpcode)
+        );
+        assert_eq!(
+            convert(r#"{"RCODE": 16}"#),
+            Some(JsonError::InvalidRcode)
+        );
+        assert!(convert(r#"{"Opcode": 15, "RCODE": 15}"#).is_none());
+        assert!(
+            serde_json::from_str::<MessageObject>(r#"{"QR": 2}"#).is_err()
+        );
+    }
+}
@@ -11,19 +11,26 @@
 //! [`Message`]: struct.Message.html
 
 use super::header::{Header, HeaderCounts, HeaderSection};
-use super::iana::{Class, Rcode, Rtype};
+use super::iana::{
+    Class, ExtendedErrorCode, OptRcode, OptionCode, Rcode, Rtype,
+};
 use super::message_builder::{AdditionalBuilder, AnswerBuilder};
+use super::message_check::{rdata_name_layout, RdataField};
 use super::name::ParsedDname;
+use super::net::{Ipv4Addr, Ipv6Addr};
 use super::octets::{
-    OctetsBuilder, OctetsFrom, OctetsRef, Parse, ParseError, Parser, ShortBuf,
+    OctetsBuilder, OctetsExt, OctetsFrom, OctetsRef, Parse, ParseError,
+    Parser, ShortBuf,
 };
 use super::opt::{Opt, OptRecord};
 use super::question::Question;
-use super::rdata::ParseRecordData;
+use super::rdata::{ParseRecordData, UnknownRecordData};
 use super::record::{AsRecord, ParsedRecord, Record};
 use crate::rdata::rfc1035::Cname;
+use crate::rdata::AllRecordData;
+use core::fmt::Write;
 use core::marker::PhantomData;
-use core::{fmt, mem};
+use core::{cmp, fmt, mem};
 
 //------------ Message -------------------------------------------------------
 
@@ -533,7 +540,10 @@ where
     /// Drops the last additional record from the message.
     ///
     /// Does so by decreasing the ’arcount.’ Does, however, not change the
-    /// underlying octet sequence.
+    /// underlying octet sequence. Use [`retain_records`] for removing
+    /// arbitrary records from the octets sequence.
+    ///
+    /// [`retain_records`]: #method.retain_records
     ///
     /// # Panics
     ///
@@ -594,6 +604,146 @@ where
     }
 }
 
+/// # Presentation Format
+///
+impl<Octets> Message<Octets>
+where
+    Octets: AsRef<[u8]>,
+    for<'a> &'a Octets: OctetsRef,
+{
+    /// Returns a value that displays the message the way `dig` does.
+    ///
+    /// The `Display` implementation of `Message` uses this with the
+    /// default settings. Use this method if you want to change those via
+    /// the methods of the returned [`MessageDisplay`].
+    ///
+    /// [`MessageDisplay`]: struct.MessageDisplay.html
+    pub fn display(&self) -> MessageDisplay<'_, Octets> {
+        MessageDisplay::new(self)
+    }
+}
+
+/// # Editing
+///
+/// The methods in this block modify the message in place without parsing
+/// it into its components and building a new message. The header can be
+/// changed directly via [`header_mut`][Self::header_mut].
+///
+/// Removing records moves the following data to close the gap and adjusts
+/// all compression pointers accordingly. A compression pointer of a
+/// remaining name that points into a removed record is redirected if the
+/// name it points to is itself only a pointer to elsewhere in the message.
+/// If that isn’t the case, the record can’t be removed and an error is
+/// returned.
+impl<Octets: AsRef<[u8]> + AsMut<[u8]>> Message<Octets> {
+    /// Adjusts the TTL of all records by `delta` seconds.
+    ///
+    /// The resulting TTLs are clamped to the range between zero and the
+    /// maximum value of 2^31 - 1 defined in [RFC 2181]. As required by
+    /// that RFC, a stored TTL with the most significant bit set is treated
+    /// as zero before adjusting it. The TTL fields of OPT and TSIG records
+    /// are left alone as they have special meanings.
+    ///
+    /// [RFC 2181]: https://tools.ietf.org/html/rfc2181
+    pub fn adjust_ttls(&mut self, delta: i64) -> Result<(), ParseError> {
+        let mut walk = RecordWalk::new(self.as_slice())?;
+        while let Some(record) = walk.next(self.as_slice())? {
+            let rtype = record.rtype(self.as_slice());
+            if rtype == Rtype::Opt || rtype == Rtype::Tsig {
+                continue;
+            }
+            let pos = record.header + 4;
+            let field = &mut self.octets.as_mut()[pos..pos + 4];
+            let ttl =
+                u32::from_be_bytes([field[0], field[1], field[2], field[3]]);
+            let ttl = if ttl > i32::MAX as u32 { 0 } else { ttl };
+            let ttl = i64::from(ttl)
+                .saturating_add(delta)
+                .clamp(0, i64::from(i32::MAX));
+            field.copy_from_slice(&(ttl as u32).to_be_bytes());
+        }
+        Ok(())
+    }
+
+    /// Removes all records for which `op` returns `false`.
+    ///
+    /// The closure receives the section and the record for each record in
+    /// the answer, authority, and additional sections in turn. The octets
+    /// sequence is truncated to the new length of the message.
+    ///
+    /// Records are removed starting at the end of the message. If an error
+    /// happens, the records after the one that caused the error have
+    /// already been removed but the message is still consistent.
+    pub fn retain_records<F>(
+        &mut self,
+        mut op: F,
+    ) -> Result<(), RemoveRecordsError>
+    where
+        Octets: OctetsExt,
+        F: FnMut(Section, ParsedRecord<&[u8]>) -> bool,
+    {
+        let mut marks = RecordMarks::new();
+        let mut walk = RecordWalk::new(self.as_slice())?;
+        let mut index = 0;
+        while let Some(record) = walk.next(self.as_slice())? {
+            let mut parser = Parser::from_ref(self.as_slice());
+            parser.seek(record.start)?;
+            if !op(record.section, ParsedRecord::parse(&mut parser)?) {
+                marks.set(index)?;
+            }
+            index += 1;
+        }
+
+        let mut len = self.as_slice().len();
+        let mut res = Ok(());
+        while let Some(index) = marks.pop_last() {
+            match remove_record(&mut self.octets.as_mut()[..len], index) {
+                Ok(new_len) => len = new_len,
+                Err(err) => {
+                    res = Err(err);
+                    break;
+                }
+            }
+        }
+        self.octets.truncate(len);
+        res
+    }
+
+    /// Removes the OPT record from the message.
+    ///
+    /// If the message has an extended response code, only its lower four
+    /// bits will remain in the header.
+    pub fn remove_opt(&mut self) -> Result<(), RemoveRecordsError>
+    where
+        Octets: OctetsExt,
+    {
+        self.retain_records(|section, record| {
+            section != Section::Additional || record.rtype() != Rtype::Opt
+        })
+    }
+
+    /// Removes DNSSEC records not asked for from a response.
+    ///
+    /// If a query doesn’t have the DNSSEC OK bit set, [RFC 4035] demands
+    /// that RRSIG, NSEC, and NSEC3 records are only included in the
+    /// response if they have been asked for explicitly. This method removes
+    /// all such records from all sections unless their record type is the
+    /// question type of the first question.
+    ///
+    /// [RFC 4035]: https://tools.ietf.org/html/rfc4035
+    pub fn strip_dnssec(&mut self) -> Result<(), RemoveRecordsError>
+    where
+        Octets: OctetsExt,
+    {
+        let qtype = first_qtype(self.as_slice())?;
+        self.retain_records(|_, record| {
+            let rtype = record.rtype();
+            !matches!(rtype, Rtype::Rrsig | Rtype::Nsec | Rtype::Nsec3)
+                || Some(rtype) == qtype
+        })
+    }
+}
+
 //--- AsRef
 
 impl<Octets> AsRef<Octets> for Message<Octets> {
@@ -636,6 +786,18 @@ where
     }
 }
 
+//--- Display
+
+impl<Octets> fmt::Display for Message<Octets>
+where
+    Octets: AsRef<[u8]>,
+    for<'a> &'a Octets: OctetsRef,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.display().fmt(f)
+    }
+}
+
 //------------ QuestionSection ----------------------------------------------
 
 /// An iterator over the question section of a DNS message.
@@ -1055,6 +1217,726 @@ where
     }
 }
 
+//------------ MessageDisplay ------------------------------------------------
+
+/// A helper type for displaying a message in the style of `dig`.
+///
+/// A value of this type is returned by [`Message::display`]. Its `Display`
+/// implementation produces the same output as the `dig` tool from BIND
+/// minus the lines with query statistics: the header with opcode, status,
+/// and message ID, the flags and section counts, the OPT pseudo-section
+/// with the decoded EDNS options, and the records of the four sections in
+/// zonefile syntax. The fields of each line are aligned into columns using
+/// tabs exactly like `dig` does it, so the output can be compared against
+/// captured `dig` output.
+///
+/// The status shown is the full response code, including the upper bits
+/// carried in the OPT record if there is one.
+///
+/// Record data is normally printed in the presentation format of its
+/// record type. Record types not known to this crate are shown in the
+/// generic format defined in [RFC 3597]. Via [`generic_rdata`], this
+/// generic format can be used for all record data, similar to dig’s
+/// `+unknownformat` option.
+///
+/// [`Message::display`]: struct.Message.html#method.display
+/// [`generic_rdata`]: #method.generic_rdata
+/// [RFC 3597]: https://tools.ietf.org/html/rfc3597
+#[derive(Clone, Copy)]
+pub struct MessageDisplay<'a, Octets> {
+    /// The message to display.
+    message: &'a Message<Octets>,
+
+    /// Whether to show all record data in generic format.
+    generic_rdata: bool,
+}
+
+impl<'a, Octets> MessageDisplay<'a, Octets> {
+    /// Creates a new value with default settings for the given message.
+    fn new(message: &'a Message<Octets>) -> Self {
+        MessageDisplay {
+            message,
+            generic_rdata: false,
+        }
+    }
+
+    /// Sets whether all record data should be shown in generic format.
+    pub fn generic_rdata(mut self, generic_rdata: bool) -> Self {
+        self.generic_rdata = generic_rdata;
+        self
+    }
+}
+
+impl<'a, Octets> MessageDisplay<'a, Octets>
+where
+    Octets: AsRef<[u8]>,
+    for<'s> &'s Octets: OctetsRef,
+{
+    /// Writes the OPT pseudo-section.
+    fn fmt_opt<OptOctets: AsRef<[u8]>>(
+        opt: &OptRecord<OptOctets>,
+        f: &mut fmt::Formatter,
+    ) -> fmt::Result {
+        writeln!(f, ";; OPT PSEUDOSECTION:")?;
+        writeln!(
+            f,
+            "; EDNS: version: {}, flags:{}; udp: {}",
+            opt.version(),
+            if opt.dnssec_ok() { " do" } else { "" },
+            opt.udp_payload_size()
+        )?;
+        let mut parser = Parser::from_ref(opt.as_opt().as_slice());
+        while parser.remaining() > 0 {
+            let code = match parser.parse_u16() {
+                Ok(code) => OptionCode::from_int(code),
+                Err(_) => break,
+            };
+            let data = match parser
+                .parse_u16()
+                .and_then(|len| parser.parse_octets(len as usize))
+            {
+                Ok(data) => data,
+                Err(_) => break,
+            };
+            fmt_option(code, data, f)?;
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the records of a section.
+    ///
+    /// The heading of the section is only written if there is at least one
+    /// record to show. OPT records are skipped.
+    fn fmt_section(
+        &self,
+        heading: &str,
+        section: Result<RecordSection<&Octets>, ParseError>,
+        f: &mut fmt::Formatter,
+    ) -> fmt::Result {
+        let mut heading = Some(heading);
+        let section = match section {
+            Ok(section) => section,
+            Err(err) => return fmt_parse_error(err, f),
+        };
+        for record in section {
+            let record = match record {
+                Ok(record) => record,
+                Err(err) => return fmt_parse_error(err, f),
+            };
+            if record.rtype() == Rtype::Opt {
+                continue;
+            }
+            if let Some(heading) = heading.take() {
+                writeln!(f, "\n;; {} SECTION:", heading)?;
+            }
+            self.fmt_record(record, f)?;
+        }
+        Ok(())
+    }
+
+    /// Writes a single record.
+    fn fmt_record(
+        &self,
+        record: ParsedRecord<&Octets>,
+        f: &mut fmt::Formatter,
+    ) -> fmt::Result {
+        let mut line = Columns::new(f);
+        write!(line, "{}.", record.owner())?;
+        line.tab_to(24)?;
+        write!(line, "{}", record.ttl())?;
+        line.tab_to(32)?;
+        write!(line, "{}", record.class())?;
+        line.tab_to(40)?;
+        write!(line, "{}", record.rtype())?;
+        line.tab_to(48)?;
+        let data = if self.generic_rdata {
+            None
+        } else {
+            record.to_record::<AllRecordData<_, _>>().ok().flatten()
+        };
+        match data {
+            Some(data) => write!(line, "{}", data.data())?,
+            None => match record.to_record::<UnknownRecordData<_>>() {
+                Ok(Some(data)) => write!(line, "{}", data.data())?,
+                _ => line.write_str("; malformed record data")?,
+            },
+        }
+        writeln!(f)
+    }
+}
+
+//--- Display
+
+impl<'a, Octets> fmt::Display for MessageDisplay<'a, Octets>
+where
+    Octets: AsRef<[u8]>,
+    for<'s> &'s Octets: OctetsRef,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let header = self.message.header();
+        let counts = self.message.header_counts();
+        let opt = self.message.opt();
+        let rcode = match opt {
+            Some(ref opt) => opt.rcode(header),
+            None => header.rcode().into(),
+        };
+
+        write!(f, ";; ->>HEADER<<- opcode: {}, status: ", header.opcode())?;
+        fmt_rcode(rcode, f)?;
+        writeln!(f, ", id: {}", header.id())?;
+        f.write_str(";; flags:")?;
+        for (set, flag) in [
+            (header.qr(), "qr"),
+            (header.aa(), "aa"),
+            (header.tc(), "tc"),
+            (header.rd(), "rd"),
+            (header.ra(), "ra"),
+            (header.ad(), "ad"),
+            (header.cd(), "cd"),
+        ] {
+            if set {
+                write!(f, " {}", flag)?;
+            }
+        }
+        writeln!(
+            f,
+            "; QUERY: {}, ANSWER: {}, AUTHORITY: {}, ADDITIONAL: {}",
+            counts.qdcount(),
+            counts.ancount(),
+            counts.nscount(),
+            counts.arcount()
+        )?;
+
+        if let Some(ref opt) = opt {
+            writeln!(f)?;
+            Self::fmt_opt(opt, f)?;
+        }
+
+        let mut heading = Some(";; QUESTION SECTION:");
+        for question in self.message.question() {
+            let question = match question {
+                Ok(question) => question,
+                Err(err) => return fmt_parse_error(err, f),
+            };
+            if let Some(heading) = heading.take() {
+                writeln!(f, "\n{}", heading)?;
+            }
+            let mut line = Columns::new(f);
+            write!(line, ";{}.", question.qname())?;
+            line.tab_to(32)?;
+            write!(line, "{}", question.qclass())?;
+            line.tab_to(40)?;
+            write!(line, "{}", question.qtype())?;
+            writeln!(f)?;
+        }
+
+        self.fmt_section("ANSWER", self.message.answer(), f)?;
+        self.fmt_section("AUTHORITY", self.message.authority(), f)?;
+        self.fmt_section("ADDITIONAL", self.message.additional(), f)
+    }
+}
+
+//------------ Helpers for MessageDisplay ------------------------------------
+
+/// A writer that keeps track of the column for aligning output with tabs.
+struct Columns<'a, 'f> {
+    /// The formatter to write to.
+    f: &'a mut fmt::Formatter<'f>,
+
+    /// The current column.
+    pos: usize,
+}
+
+impl<'a, 'f> Columns<'a, 'f> {
+    /// Creates a new writer at the start of a line.
+    fn new(f: &'a mut fmt::Formatter<'f>) -> Self {
+        Columns { f, pos: 0 }
+    }
+
+    /// Advances to the given column.
+    ///
+    /// This uses tabs with a width of eight where possible and fills up
+    /// with spaces. There will always be at least one character of
+    /// separation, even if the column has already been reached. This is
+    /// the algorithm used by BIND’s `dig`.
+    fn tab_to(&mut self, col: usize) -> fmt::Result {
+        let col = cmp::max(col, self.pos + 1);
+        let tabs = col / 8 - self.pos / 8;
+        if tabs > 0 {
+            for _ in 0..tabs {
+                self.f.write_char('\t')?;
+            }
+            self.pos = col / 8 * 8;
+        }
+        while self.pos < col {
+            self.f.write_char(' ')?;
+            self.pos += 1;
+        }
+        Ok(())
+    }
+}
+
+impl<'a, 'f> fmt::Write for Columns<'a, 'f> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.pos += s.chars().count();
+        self.f.write_str(s)
+    }
+}
+
+/// Writes a response code the way `dig` names it.
+fn fmt_rcode(rcode: OptRcode, f: &mut fmt::Formatter) -> fmt::Result {
+    match OptRcode::from_int(rcode.to_int()) {
+        OptRcode::NotAuth => f.write_str("NOTAUTH"),
+        OptRcode::BadVers => f.write_str("BADVERS"),
+        OptRcode::Int(value @ 11..=15) => write!(f, "RESERVED{}", value),
+        rcode => fmt::Display::fmt(&rcode, f),
+    }
+}
+
+/// Writes a note about a parse error that stopped the output.
+fn fmt_parse_error(err: ParseError, f: &mut fmt::Formatter) -> fmt::Result {
+    writeln!(f, ";; Error parsing message: {}", err)
+}
+
+/// Writes a line for an EDNS option without the line feed.
+///
+/// Known options are decoded. If that fails or the option is unknown, its
+/// data is shown in hex.
+fn fmt_option(
+    code: OptionCode,
+    data: &[u8],
+    f: &mut fmt::Formatter,
+) -> fmt::Result {
+    match code {
+        OptionCode::Nsid => return fmt_option_hex("NSID", data, f),
+        OptionCode::Padding => return fmt_option_hex("PADDING", data, f),
+        OptionCode::ClientSubnet => {
+            if let Some(res) = fmt_client_subnet(data, f) {
+                return res;
+            }
+        }
+        OptionCode::Cookie if !data.is_empty() => {
+            f.write_str("; COOKIE: ")?;
+            for ch in data {
+                write!(f, "{:02x}", ch)?;
+            }
+            return Ok(());
+        }
+        OptionCode::Expire => match *data {
+            [] => return f.write_str("; EXPIRE"),
+            [a, b, c, d] => {
+                return write!(
+                    f,
+                    "; EXPIRE: {}",
+                    u32::from_be_bytes([a, b, c, d])
+                )
+            }
+            _ => {}
+        },
+        OptionCode::TcpKeepalive => match *data {
+            [] => return f.write_str("; TCP-KEEPALIVE"),
+            [a, b] => {
+                let timeout = u16::from_be_bytes([a, b]);
+                return write!(
+                    f,
+                    "; TCP-KEEPALIVE: {}.{} secs",
+                    timeout / 10,
+                    timeout % 10
+                );
+            }
+            _ => {}
+        },
+        OptionCode::ExtendedError if data.len() >= 2 => {
+            if let Ok(text) = core::str::from_utf8(&data[2..]) {
+                let code = ExtendedErrorCode::from_int(u16::from_be_bytes([
+                    data[0], data[1],
+                ]));
+                write!(f, "; EDE: {}", code.to_int())?;
+                if let Some(mnemonic) = code
+                    .to_mnemonic()
+                    .and_then(|m| core::str::from_utf8(m).ok())
+                {
+                    write!(f, " ({})", mnemonic)?;
+                }
+                if !text.is_empty() {
+                    write!(f, ": ({})", text)?;
+                }
+                return Ok(());
+            }
+        }
+        _ => {}
+    }
+    f.write_str("; OPT=")?;
+    fmt::Display::fmt(&code.to_int(), f)?;
+    fmt_option_hex("", data, f)
+}
+
+/// Writes option data as a hex dump followed by the printable characters.
+///
+/// If `name` is empty, only the data is written, otherwise it is preceded
+/// by the name in option line format.
+fn fmt_option_hex(
+    name: &str,
+    data: &[u8],
+    f: &mut fmt::Formatter,
+) -> fmt::Result {
+    if !name.is_empty() {
+        write!(f, "; {}", name)?;
+    }
+    if data.is_empty() {
+        return Ok(());
+    }
+    f.write_str(":")?;
+    for ch in data {
+        write!(f, " {:02x}", ch)?;
+    }
+    f.write_str(" (\"")?;
+    for &ch in data {
+        if ch.is_ascii_graphic() || ch == b' ' {
+            f.write_char(ch as char)?;
+        } else {
+            f.write_char('.')?;
+        }
+    }
+    f.write_str("\")")
+}
+
+/// Writes the line for an EDNS client subnet option.
+///
+/// The address is padded to its full length. Returns `None` without writing
+/// anything if the data is malformed.
+fn fmt_client_subnet(
+    data: &[u8],
+    f: &mut fmt::Formatter,
+) -> Option<fmt::Result> {
+    if data.len() < 4 {
+        return None;
+    }
+    let (source, scope, addr) = (data[2], data[3], &data[4..]);
+    match u16::from_be_bytes([data[0], data[1]]) {
+        1 if addr.len() <= 4 => {
+            let mut octets = [0u8; 4];
+            octets[..addr.len()].copy_from_slice(addr);
+            Some(write!(
+                f,
+                "; CLIENT-SUBNET: {}/{}/{}",
+                Ipv4Addr::from(octets),
+                source,
+                scope
+            ))
+        }
+        2 if addr.len() <= 16 => {
+            let mut octets = [0u8; 16];
+            octets[..addr.len()].copy_from_slice(addr);
+            Some(write!(
+                f,
+                "; CLIENT-SUBNET: {}/{}/{}",
+                Ipv6Addr::from(octets),
+                source,
+                scope
+            ))
+        }
+        _ => None,
+    }
+}
+
+//------------ Helpers for Editing -------------------------------------------
+
+/// The position of a record within a message.
+#[derive(Clone, Copy, Debug)]
+struct RecordPos {
+    /// The section the record is in.
+    section: Section,
+
+    /// The start of the record.
+    start: usize,
+
+    /// The start of the record header following the owner name.
+    header: usize,
+
+    /// The end of the record.
+    end: usize,
+}
+
+impl RecordPos {
+    /// Returns the record type of the record.
+    fn rtype(&self, msg: &[u8]) -> Rtype {
+        Rtype::from_int(u16::from_be_bytes([
+            msg[self.header],
+            msg[self.header + 1],
+        ]))
+    }
+}
+
+/// Walks over the positions of the records in a message.
+///
+/// The walker doesn’t hold on to the message so that it can be modified
+/// between steps as long as the records and counts stay the same.
+struct RecordWalk {
+    /// The start of the next record.
+    pos: usize,
+
+    /// The section of the next record.
+    section: Section,
+
+    /// The number of records remaining in the section.
+    remaining: u16,
+}
+
+impl RecordWalk {
+    /// Creates a new walker for the given message.
+    ///
+    /// Skips over the question section.
+    fn new(msg: &[u8]) -> Result<Self, ParseError> {
+        let counts = *HeaderCounts::for_message_slice(msg);
+        let mut parser = Parser::from_ref(msg);
+        parser.advance(mem::size_of::<HeaderSection>())?;
+        for _ in 0..counts.qdcount() {
+            ParsedDname::skip(&mut parser)?;
+            parser.advance(4)?;
+        }
+        Ok(RecordWalk {
+            pos: parser.pos(),
+            section: Section::first(),
+            remaining: Section::first().count(counts),
+        })
+    }
+
+    /// Returns the position of the next record.
+    fn next(&mut self, msg: &[u8]) -> Result<Option<RecordPos>, ParseError> {
+        while self.remaining == 0 {
+            self.section = match self.section.next_section() {
+                Some(section) => section,
+                None => return Ok(None),
+            };
+            self.remaining =
+                self.section.count(*HeaderCounts::for_message_slice(msg));
+        }
+        let mut parser = Parser::from_ref(msg);
+        parser.seek(self.pos)?;
+        ParsedDname::skip(&mut parser)?;
+        let header = parser.pos();
+        parser.advance(8)?;
+        let rdlen = parser.parse_u16()?;
+        parser.advance(rdlen.into())?;
+        let res = RecordPos {
+            section: self.section,
+            start: self.pos,
+            header,
+            end: parser.pos(),
+        };
+        self.pos = res.end;
+        self.remaining -= 1;
+        Ok(Some(res))
+    }
+}
+
+/// A set of records marked for removal.
+///
+/// Since a record is at least eleven octets long, a message can’t have more
+/// than 5956 records. The set is a bitmap big enough for that so we don’t
+/// need to allocate.
+struct RecordMarks([u64; 94]);
+
+impl RecordMarks {
+    /// Creates a new, empty set.
+    fn new() -> Self {
+        RecordMarks([0; 94])
+    }
+
+    /// Marks the record with the given index.
+    fn set(&mut self, index: usize) -> Result<(), ParseError> {
+        let word = self
+            .0
+            .get_mut(index / 64)
+            .ok_or_else(|| ParseError::form_error("too many records"))?;
+        *word |= 1 << (index % 64);
+        Ok(())
+    }
+
+    /// Removes and returns the largest marked index.
+    fn pop_last(&mut self) -> Option<usize> {
+        for (idx, word) in self.0.iter_mut().enumerate().rev() {
+            if *word != 0 {
+                let bit = 63 - word.leading_zeros() as usize;
+                *word &= !(1 << bit);
+                return Some(idx * 64 + bit);
+            }
+        }
+        None
+    }
+}
+
+/// Returns the compression pointer in the name starting at `start`.
+///
+/// Only the part of the name stored at `start` is considered which must end
+/// before `limit`. Returns the position of the pointer if there is one and
+/// the end of the stored part of the name.
+fn name_pointer(
+    msg: &[u8],
+    start: usize,
+    limit: usize,
+) -> Result<(Option<usize>, usize), ParseError> {
+    let mut pos = start;
+    loop {
+        let ltype = match msg.get(pos) {
+            Some(ltype) if pos < limit => *ltype,
+            _ => return Err(ParseError::ShortInput),
+        };
+        match ltype & 0xC0 {
+            0x00 => {
+                pos += usize::from(ltype) + 1;
+                if ltype == 0 {
+                    return Ok((None, pos));
+                }
+            }
+            0xC0 if pos + 2 <= limit => return Ok((Some(pos), pos + 2)),
+            0xC0 => return Err(ParseError::ShortInput),
+            _ => return Err(ParseError::form_error("invalid label type")),
+        }
+    }
+}
+
+/// Returns the target of the compression pointer at `pos`.
+fn pointer_target(msg: &[u8], pos: usize) -> usize {
+    usize::from(u16::from_be_bytes([msg[pos] & 0x3F, msg[pos + 1]]))
+}
+
+/// Calls `op` with the position of every compression pointer in a message.
+///
+/// This covers the names of all questions, the owners of all records, and
+/// the names in the record data of types given by [`rdata_name_layout`].
+/// The closure may change the pointers but nothing else.
+fn for_each_pointer<F, E>(msg: &mut [u8], mut op: F) -> Result<(), E>
+where
+    F: FnMut(&mut [u8], usize) -> Result<(), E>,
+    E: From<ParseError>,
+{
+    let mut pos = mem::size_of::<HeaderSection>();
+    for _ in 0..HeaderCounts::for_message_slice(msg).qdcount() {
+        let (ptr, end) = name_pointer(msg, pos, msg.len())?;
+        if let Some(ptr) = ptr {
+            op(msg, ptr)?;
+        }
+        pos = end + 4;
+    }
+    let mut walk = RecordWalk::new(msg)?;
+    while let Some(record) = walk.next(msg)? {
+        if let (Some(ptr), _) =
+            name_pointer(msg, record.start, record.header)?
+        {
+            op(msg, ptr)?;
+        }
+        let mut pos = record.header + 10;
+        for field in rdata_name_layout(record.rtype(msg)) {
+            match *field {
+                RdataField::Name => {
+                    let (ptr, end) = name_pointer(msg, pos, record.end)?;
+                    if let Some(ptr) = ptr {
+                        op(msg, ptr)?;
+                    }
+                    pos = end;
+                }
+                RdataField::Fixed(len) => pos += len,
+                RdataField::CharStr => match msg.get(pos) {
+                    Some(len) if pos < record.end => {
+                        pos += usize::from(*len) + 1
+                    }
+                    _ => return Err(ParseError::ShortInput.into()),
+                },
+                RdataField::Rest => break,
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Removes the record with the given index from a message.
+///
+/// Returns the new length of the message.
+fn remove_record(
+    msg: &mut [u8],
+    index: usize,
+) -> Result<usize, RemoveRecordsError> {
+    let mut walk = RecordWalk::new(msg)?;
+    let mut record = None;
+    for _ in 0..=index {
+        record = walk.next(msg)?;
+    }
+    let record = record.ok_or(ParseError::ShortInput)?;
+    let (start, end) = (record.start, record.end);
+    let removed = start..end;
+
+    // Check all pointers first so we don’t leave a half-edited message.
+    for_each_pointer(msg, |msg, ptr| {
+        if removed.contains(&ptr) {
+            return Ok(());
+        }
+        redirect_pointer(msg, pointer_target(msg, ptr), start, end)
+            .map(|_| ())
+            .ok_or(RemoveRecordsError::Referenced)
+    })?;
+    for_each_pointer(msg, |msg, ptr| -> Result<(), RemoveRecordsError> {
+        if removed.contains(&ptr) {
+            return Ok(());
+        }
+        let mut target =
+            redirect_pointer(msg, pointer_target(msg, ptr), start, end)
+                .ok_or(RemoveRecordsError::Referenced)?;
+        if target >= end {
+            target -= end - start;
+        }
+        msg[ptr..ptr + 2]
+            .copy_from_slice(&(0xC000 | target as u16).to_be_bytes());
+        Ok(())
+    })?;
+
+    msg.copy_within(end.., start);
+    let counts = HeaderCounts::for_message_slice_mut(msg);
+    match record.section {
+        Section::Answer => counts.dec_ancount(),
+        Section::Authority => counts.dec_nscount(),
+        Section::Additional => counts.dec_arcount(),
+    }
+    Ok(msg.len() - (end - start))
+}
+
+/// Redirects a pointer target out of the range from `start` to `end`.
+///
+/// If the target is within the range, it has to be a compression pointer
+/// itself which is then followed. Returns `None` if the target can’t be
+/// redirected.
+fn redirect_pointer(
+    msg: &[u8],
+    mut target: usize,
+    start: usize,
+    end: usize,
+) -> Option<usize> {
+    while (start..end).contains(&target) {
+        if target + 2 > end || msg[target] & 0xC0 != 0xC0 {
+            return None;
+        }
+        let next = pointer_target(msg, target);
+        if next >= target {
+            return None;
+        }
+        target = next;
+    }
+    Some(target)
+}
+
+/// Returns the question type of the first question if there is one.
+fn first_qtype(msg: &[u8]) -> Result<Option<Rtype>, ParseError> {
+    if HeaderCounts::for_message_slice(msg).qdcount() == 0 {
+        return Ok(None);
+    }
+    let mut parser = Parser::from_ref(msg);
+    parser.advance(mem::size_of::<HeaderSection>())?;
+    ParsedDname::skip(&mut parser)?;
+    Ok(Some(Rtype::from_int(parser.parse_u16()?)))
+}
+
 //============ Error Types ===================================================
 
 //------------ CopyRecordsError ----------------------------------------------
@@ -1097,6 +1979,42 @@ impl fmt::Display for CopyRecordsError {
 #[cfg(feature = "std")]
 impl std::error::Error for CopyRecordsError {}
 
+//------------ RemoveRecordsError --------------------------------------------
+
+/// An error occurred while removing records.
+#[derive(Clone, Copy, Debug)]
+pub enum RemoveRecordsError {
+    /// Parsing the message failed.
+    Parse(ParseError),
+
+    /// A record to be removed is referenced by a compression pointer.
+    Referenced,
+}
+
+//--- From
+
+impl From<ParseError> for RemoveRecordsError {
+    fn from(err: ParseError) -> Self {
+        RemoveRecordsError::Parse(err)
+    }
+}
+
+//--- Display and Error
+
+impl fmt::Display for RemoveRecordsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RemoveRecordsError::Parse(ref err) => err.fmt(f),
+            RemoveRecordsError::Referenced => {
+                f.write_str("record referenced by compression pointer")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for RemoveRecordsError {}
+
 //============ Testing =======================================================
 
 #[cfg(test)]
@@ -1107,7 +2025,9 @@ mod test {
     #[cfg(feature = "std")]
     use crate::base::name::Dname;
     #[cfg(feature = "std")]
-    use crate::rdata::{AllRecordData, Ns};
+    use crate::rdata::Ns;
+    #[cfg(feature = "std")]
+    use std::string::ToString;
     #[cfg(feature = "std")]
     use std::vec::Vec;
 
@@ -1235,4 +2155,349 @@ mod test {
             assert_eq!(0, msg.header_counts().arcount());
         }
     }
+
+    #[cfg(feature = "std")]
+    fn push_raw(
+        opt: &mut crate::base::message_builder::OptBuilder<Vec<u8>>,
+        code: u16,
+        data: &[u8],
+    ) {
+        opt.push_raw_option(code.into(), |target| target.append_slice(data))
+            .unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn display() {
+        use crate::base::iana::Class;
+        use crate::rdata::A;
+
+        let mut msg = MessageBuilder::new_vec();
+        msg.header_mut().set_id(4711);
+        msg.header_mut().set_qr(true);
+        msg.header_mut().set_rd(true);
+        msg.header_mut().set_ra(true);
+        let mut msg = msg.question();
+        msg.push((Dname::vec_from_str("example.com").unwrap(), Rtype::A))
+            .unwrap();
+        let mut msg = msg.answer();
+        msg.push((
+            Dname::vec_from_str("example.com").unwrap(),
+            3600,
+            crate::rdata::A::from_octets(192, 0, 2, 1),
+        ))
+        .unwrap();
+        msg.push((
+            Dname::vec_from_str("example.com").unwrap(),
+            Class::In,
+            3600,
+            UnknownRecordData::from_octets(
+                Rtype::Int(65400),
+                vec![0xab, 0xcd],
+            ),
+        ))
+        .unwrap();
+        let mut msg = msg.authority();
+        msg.push((
+            Dname::vec_from_str("example.com").unwrap(),
+            3600,
+            Ns::new(Dname::vec_from_str("ns.example.com").unwrap()),
+        ))
+        .unwrap();
+        let mut msg = msg.additional();
+        msg.opt(|opt| {
+            opt.set_udp_payload_size(1232);
+            opt.set_dnssec_ok(true);
+            push_raw(opt, 3, b"ns1");
+            push_raw(opt, 8, &[0, 1, 24, 0, 192, 0, 2]);
+            push_raw(opt, 10, &[1, 2, 3, 4, 5, 6, 7, 8]);
+            push_raw(opt, 15, &[0, 18]);
+            push_raw(opt, 15, b"\x00\x03cached");
+            push_raw(opt, 12, &[0, 0, 0]);
+            push_raw(opt, 65001, &[0x41, 0]);
+            Ok(())
+        })
+        .unwrap();
+        msg.push((
+            Dname::vec_from_str("ns.example.com").unwrap(),
+            3600,
+            A::from_octets(192, 0, 2, 53),
+        ))
+        .unwrap();
+        let msg = msg.into_message();
+
+        assert_eq!(
+            msg.to_string(),
+            ";; ->>HEADER<<- opcode: QUERY, status: NOERROR, id: 4711\n\
+             ;; flags: qr rd ra; \
+             QUERY: 1, ANSWER: 2, AUTHORITY: 1, ADDITIONAL: 2\n\
+             \n\
+             ;; OPT PSEUDOSECTION:\n\
+             ; EDNS: version: 0, flags: do; udp: 1232\n\
+             ; NSID: 6e 73 31 (\"ns1\")\n\
+             ; CLIENT-SUBNET: 192.0.2.0/24/0\n\
+             ; COOKIE: 0102030405060708\n\
+             ; EDE: 18 (Prohibited)\n\
+             ; EDE: 3 (Stale Answer): (cached)\n\
+             ; PADDING: 00 00 00 (\"...\")\n\
+             ; OPT=65001: 41 00 (\"A.\")\n\
+             \n\
+             ;; QUESTION SECTION:\n\
+             ;example.com.\t\t\tIN\tA\n\
+             \n\
+             ;; ANSWER SECTION:\n\
+             example.com.\t\t3600\tIN\tA\t192.0.2.1\n\
+             example.com.\t\t3600\tIN\tTYPE65400 \\# 2 ab cd\n\
+             \n\
+             ;; AUTHORITY SECTION:\n\
+             example.com.\t\t3600\tIN\tNS\tns.example.com.\n\
+             \n\
+             ;; ADDITIONAL SECTION:\n\
+             ns.example.com.\t\t3600\tIN\tA\t192.0.2.53\n"
+        );
+
+        assert_eq!(
+            msg.display()
+                .generic_rdata(true)
+                .to_string()
+                .lines()
+                .nth(17),
+            Some("example.com.\t\t3600\tIN\tA\t\\# 4 c0 00 02 01")
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn display_extended_rcode() {
+        let mut msg = MessageBuilder::new_vec();
+        msg.header_mut().set_id(12);
+        msg.header_mut().set_qr(true);
+        let mut msg = msg.additional();
+        msg.opt(|opt| {
+            opt.set_rcode(OptRcode::BadVers);
+            Ok(())
+        })
+        .unwrap();
+        let msg = msg.into_message();
+
+        assert_eq!(
+            msg.to_string(),
+            ";; ->>HEADER<<- opcode: QUERY, status: BADVERS, id: 12\n\
+             ;; flags: qr; QUERY: 0, ANSWER: 0, AUTHORITY: 0, ADDITIONAL: 1\n\
+             \n\
+             ;; OPT PSEUDOSECTION:\n\
+             ; EDNS: version: 0, flags:; udp: 0\n"
+        );
+    }
+
+    #[cfg(feature = "std")]
+    fn edit_test_message() -> Message<Vec<u8>> {
+        use crate::base::message_builder::StaticCompressor;
+        use crate::rdata::{Aaaa, A};
+
+        let name = |s| Dname::<Vec<u8>>::vec_from_str(s).unwrap();
+        let mut msg =
+            MessageBuilder::from_target(StaticCompressor::new(Vec::new()))
+                .unwrap()
+                .question();
+        msg.push((name("example.com"), Rtype::A)).unwrap();
+        let mut msg = msg.answer();
+        msg.push((name("example.com"), 3600, A::from_octets(192, 0, 2, 1)))
+            .unwrap();
+        msg.push((
+            name("example.com"),
+            3600,
+            UnknownRecordData::from_octets(Rtype::Rrsig, vec![0; 20]),
+        ))
+        .unwrap();
+        let mut msg = msg.authority();
+        msg.push((name("example.com"), 100, Ns::new(name("ns.example.com"))))
+            .unwrap();
+        let mut msg = msg.additional();
+        msg.push((name("ns.example.com"), 60, A::from_octets(192, 0, 2, 2)))
+            .unwrap();
+        msg.push((
+            name("ns.example.com"),
+            60,
+            Aaaa::new("2001:db8::2".parse().unwrap()),
+        ))
+        .unwrap();
+        msg.opt(|opt| {
+            opt.set_dnssec_ok(true);
+            opt.set_rcode(OptRcode::BadCookie);
+            Ok(())
+        })
+        .unwrap();
+        msg.into_message()
+    }
+
+    #[cfg(feature = "std")]
+    fn owners_and_types(
+        msg: &Message<Vec<u8>>,
+    ) -> Vec<(std::string::String, Rtype)> {
+        msg.answer()
+            .unwrap()
+            .chain(msg.authority().unwrap())
+            .chain(msg.additional().unwrap())
+            .map(|record| {
+                let record = record.unwrap();
+                (record.owner().to_string(), record.rtype())
+            })
+            .collect()
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn adjust_ttls() {
+        let mut msg = edit_test_message();
+        msg.adjust_ttls(-80).unwrap();
+        let ttls: Vec<_> = msg
+            .answer()
+            .unwrap()
+            .chain(msg.authority().unwrap())
+            .chain(msg.additional().unwrap())
+            .map(|record| record.unwrap().ttl())
+            .collect();
+        assert_eq!(&ttls[..5], &[3520, 3520, 20, 0, 0]);
+        let opt = msg.opt().unwrap();
+        assert!(opt.dnssec_ok());
+        assert_eq!(opt.rcode(msg.header()).to_int(), 23);
+
+        msg.adjust_ttls(i64::from(u32::MAX)).unwrap();
+        assert_eq!(
+            msg.answer().unwrap().next().unwrap().unwrap().ttl(),
+            i32::MAX as u32
+        );
+        msg.adjust_ttls(i64::MAX).unwrap();
+        assert_eq!(
+            msg.answer().unwrap().next().unwrap().unwrap().ttl(),
+            i32::MAX as u32
+        );
+        msg.adjust_ttls(i64::MIN).unwrap();
+        assert_eq!(msg.answer().unwrap().next().unwrap().unwrap().ttl(), 0);
+
+        // A TTL with the top bit set counts as zero.
+        let mut msg = MessageBuilder::new_vec().answer();
+        msg.push((
+            Dname::vec_from_str("example.com").unwrap(),
+            0x8000_0000,
+            crate::rdata::A::from_octets(192, 0, 2, 1),
+        ))
+        .unwrap();
+        let mut msg = msg.into_message();
+        msg.adjust_ttls(10).unwrap();
+        assert_eq!(msg.answer().unwrap().next().unwrap().unwrap().ttl(), 10);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn retain_records() {
+        use crate::base::message_check::MessageCheck;
+
+        let mut msg = edit_test_message();
+        msg.strip_dnssec().unwrap();
+        MessageCheck::new().check(&msg).unwrap();
+        assert_eq!(
+            owners_and_types(&msg),
+            [
+                ("example.com".into(), Rtype::A),
+                ("example.com".into(), Rtype::Ns),
+                ("ns.example.com".into(), Rtype::A),
+                ("ns.example.com".into(), Rtype::Aaaa),
+                ("".into(), Rtype::Opt),
+            ]
+        );
+
+        msg.retain_records(|section, record| {
+            section != Section::Additional || record.rtype() != Rtype::A
+        })
+        .unwrap();
+        MessageCheck::new().check(&msg).unwrap();
+        msg.remove_opt().unwrap();
+        MessageCheck::new().check(&msg).unwrap();
+        assert_eq!(
+            owners_and_types(&msg),
+            [
+                ("example.com".into(), Rtype::A),
+                ("example.com".into(), Rtype::Ns),
+                ("ns.example.com".into(), Rtype::Aaaa),
+            ]
+        );
+        assert_eq!(msg.header().rcode(), Rcode::from_int(23 & 0x0F));
+        assert!(msg.opt().is_none());
+
+        // Removing the NS record fails because the AAAA record’s owner
+        // points to its data.
+        let octets = msg.as_slice().to_vec();
+        assert!(matches!(
+            msg.retain_records(|_, record| record.rtype() != Rtype::Ns),
+            Err(RemoveRecordsError::Referenced)
+        ));
+        assert_eq!(msg.as_slice(), octets.as_slice());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn retain_records_pointers() {
+        // A question for a. and four A records in the additional section.
+        // The first and third have their owners point to the question,
+        // the latter via the owner of the first. The second one has its
+        // owner b. spelled out and the fourth one points to that.
+        let mut octets = vec![0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 4];
+        octets.extend_from_slice(b"\x01a\x00\x00\x01\x00\x01");
+        for (owner, addr) in [
+            (&b"\xc0\x0c"[..], 1),
+            (b"\x01b\x00", 2),
+            (b"\xc0\x13", 3),
+            (b"\xc0\x23", 4),
+        ] {
+            octets.extend_from_slice(owner);
+            octets.extend_from_slice(b"\x00\x01\x00\x01\0\0\0\0\x00\x04");
+            octets.extend_from_slice(&[192, 0, 2, addr]);
+        }
+        let mut msg = Message::from_octets(octets).unwrap();
+        let mut first = true;
+        msg.retain_records(|_, _| !mem::replace(&mut first, false))
+            .unwrap();
+        assert_eq!(msg.as_slice().len(), 68);
+        assert_eq!(
+            owners_and_types(&msg),
+            [
+                ("b".into(), Rtype::A),
+                ("a".into(), Rtype::A),
+                ("b".into(), Rtype::A),
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn retain_records_naptr() {
+        use crate::base::message_check::MessageCheck;
+
+        // A question for a., an A record for b. and a NAPTR record whose
+        // replacement points to the owner of the A record after three
+        // character strings.
+        let mut octets = vec![0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 2];
+        octets.extend_from_slice(b"\x01a\x00\x00\x01\x00\x01");
+        octets.extend_from_slice(b"\x01b\x00\x00\x01\x00\x01\0\0\0\0");
+        octets.extend_from_slice(b"\x00\x04\xc0\x00\x02\x01");
+        octets.extend_from_slice(b"\xc0\x0c\x00\x23\x00\x01\0\0\0\0");
+        octets.extend_from_slice(b"\x00\x0a\x00\x01\x00\x02");
+        octets.extend_from_slice(b"\x01u\x00\x00\xc0\x13");
+        let mut msg = Message::from_octets(octets).unwrap();
+        MessageCheck::new().check(&msg).unwrap();
+
+        let octets = msg.as_slice().to_vec();
+        assert!(matches!(
+            msg.retain_records(|_, record| record.rtype() != Rtype::A),
+            Err(RemoveRecordsError::Referenced)
+        ));
+        assert_eq!(msg.as_slice(), octets.as_slice());
+
+        msg.retain_records(|_, record| record.rtype() != Rtype::Naptr)
+            .unwrap();
+        MessageCheck::new().check(&msg).unwrap();
+        assert_eq!(owners_and_types(&msg), [("b".into(), Rtype::A)]);
+    }
 }
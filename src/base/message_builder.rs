@@ -292,6 +292,17 @@ impl<Target: OctetsBuilder + AsMut<[u8]>> MessageBuilder<Target> {
     fn counts_mut(&mut self) -> &mut HeaderCounts {
         HeaderCounts::for_message_slice_mut(self.target.as_mut())
     }
+
+    /// Rolls the message back to an earlier state.
+    ///
+    /// Truncates the underlying target to `len` octets and restores the
+    /// header counts to `counts`. Both values must have been taken from
+    /// this builder earlier.
+    #[cfg(feature = "std")]
+    pub(crate) fn roll_back(&mut self, len: usize, counts: HeaderCounts) {
+        self.target.truncate(len);
+        *self.counts_mut() = counts;
+    }
 }
 
 /// # Conversions
@@ -0,0 +1,905 @@
+//! Checking messages received from untrusted sources.
+//!
+//! A [`Message`] only checks that the octets sequence is long enough for
+//! the header when it is created. Problems with the content of the sections
+//! only show up as parse errors while iterating over them. For a server
+//! receiving messages from the Internet, it is often preferable to reject
+//! malformed messages up front.
+//!
+//! The [`MessageCheck`] type provided by this module walks over all the
+//! sections of a message and checks that their content is well-formed:
+//!
+//! * the number of questions and records matches the counts in the header
+//!   and there is no trailing data after the last record,
+//! * all compression pointers point backwards to a name that has started
+//!   before the name containing the pointer, which excludes both pointer
+//!   loops and forward pointers,
+//! * domain names do not exceed the length limit of 255 octets and the
+//!   configured limit on the number of labels,
+//! * the record data of all record types known to the crate can be parsed,
+//! * there is at most one OPT record, it is in the additional section, and
+//!   its owner is the root,
+//! * there is at most one TSIG record and it is the last record of the
+//!   additional section, and
+//! * the message does not exceed the configured size limit.
+//!
+//! If the checks pass, [`MessageCheck::normalize`] can produce a copy of the
+//! message with all names re-encoded and compression recomputed.
+//!
+//! [`Message`]: super::message::Message
+//! [`MessageCheck`]: struct.MessageCheck.html
+//! [`MessageCheck::normalize`]: struct.MessageCheck.html#method.normalize
+
+use super::header::HeaderSection;
+use super::iana::Rtype;
+use super::message::{Message, Section};
+#[cfg(feature = "std")]
+use super::message_builder::{MessageBuilder, StaticCompressor};
+use super::name::ParsedDname;
+use super::octets::Parser;
+#[cfg(feature = "std")]
+use super::octets::{OctetsRef, ShortBuf};
+use super::rdata::ParseRecordData;
+#[cfg(feature = "std")]
+use super::record::{ParsedRecord, Record};
+use crate::rdata::AllRecordData;
+use core::{fmt, mem};
+#[cfg(feature = "std")]
+use std::vec::Vec;
+
+//------------ MessageCheck --------------------------------------------------
+
+/// The configuration for checking a message.
+///
+/// A value of this type is created via [`new`][Self::new] or `default` and
+/// then configured through its builder-style methods. The checks can then
+/// be run via [`check`][Self::check] or [`normalize`][Self::normalize].
+#[derive(Clone, Copy, Debug)]
+pub struct MessageCheck {
+    /// The maximum size of the message in octets.
+    max_size: usize,
+
+    /// The maximum number of labels in a domain name including the root.
+    max_labels: usize,
+}
+
+impl MessageCheck {
+    /// Creates a new check with the default limits.
+    ///
+    /// By default, messages may be up to 65535 octets long and names may
+    /// have up to 128 labels, i.e., all messages and names allowed by the
+    /// protocol are accepted.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the maximum size of a message in octets.
+    pub fn max_size(mut self, max_size: u16) -> Self {
+        self.max_size = max_size.into();
+        self
+    }
+
+    /// Sets the maximum number of labels in a domain name.
+    ///
+    /// The root label is included in the count.
+    pub fn max_labels(mut self, max_labels: u8) -> Self {
+        self.max_labels = max_labels.into();
+        self
+    }
+
+    /// Checks the message.
+    ///
+    /// Returns an error describing the first problem found.
+    pub fn check<Octs: AsRef<[u8]>>(
+        &self,
+        msg: &Message<Octs>,
+    ) -> Result<(), CheckError> {
+        Walker::new(self, msg.as_slice()).walk()
+    }
+
+    /// Checks the message and returns a normalized copy.
+    ///
+    /// The copy contains the same header and the same questions and records
+    /// in the same order. The header counts are recalculated and all names
+    /// that may be compressed are compressed anew. Names in the record data
+    /// of types that must not be compressed will be decompressed.
+    ///
+    /// Since the copy differs from the original message on the wire, a TSIG
+    /// record will not verify for the copy.
+    #[cfg(feature = "std")]
+    pub fn normalize<Octs: AsRef<[u8]>>(
+        &self,
+        msg: &Message<Octs>,
+    ) -> Result<Message<Vec<u8>>, CheckError> {
+        self.check(msg)?;
+
+        // After the check, none of the parsing below can fail. We map any
+        // errors anyway to avoid panicking should the check be incomplete.
+        let msg = msg.for_slice();
+        let mut target =
+            MessageBuilder::from_target(StaticCompressor::new(Vec::new()))
+                .map_err(too_large)?;
+        *target.header_mut() = msg.header();
+        let mut target = target.question();
+        for question in msg.question() {
+            target
+                .push(question.map_err(malformed)?)
+                .map_err(too_large)?;
+        }
+        let mut section = msg.answer().map_err(malformed)?;
+        let mut target = target.answer();
+        for record in &mut section {
+            target
+                .push(parse_record(record.map_err(malformed)?)?)
+                .map_err(too_large)?;
+        }
+        let mut section = section
+            .next_section()
+            .map_err(malformed)?
+            .ok_or_else(|| malformed(()))?;
+        let mut target = target.authority();
+        for record in &mut section {
+            target
+                .push(parse_record(record.map_err(malformed)?)?)
+                .map_err(too_large)?;
+        }
+        let section = section
+            .next_section()
+            .map_err(malformed)?
+            .ok_or_else(|| malformed(()))?;
+        let mut target = target.additional();
+        for record in section {
+            target
+                .push(parse_record(record.map_err(malformed)?)?)
+                .map_err(too_large)?;
+        }
+        let res = target.into_message();
+        if res.as_slice().len() > self.max_size {
+            return Err(too_large(ShortBuf));
+        }
+        Ok(res)
+    }
+}
+
+impl Default for MessageCheck {
+    fn default() -> Self {
+        MessageCheck {
+            max_size: 0xFFFF,
+            max_labels: 128,
+        }
+    }
+}
+
+//------------ Walker --------------------------------------------------------
+
+/// The state of walking over a message.
+struct Walker<'a> {
+    /// The configuration of the check.
+    check: &'a MessageCheck,
+
+    /// The message octets.
+    msg: &'a [u8],
+
+    /// The current position in the message.
+    pos: usize,
+
+    /// Whether we have seen an OPT record.
+    opt: bool,
+
+    /// The position of a TSIG record if we have seen one.
+    tsig: Option<usize>,
+}
+
+impl<'a> Walker<'a> {
+    /// Creates a new walker.
+    fn new(check: &'a MessageCheck, msg: &'a [u8]) -> Self {
+        Walker {
+            check,
+            msg,
+            pos: mem::size_of::<HeaderSection>(),
+            opt: false,
+            tsig: None,
+        }
+    }
+
+    /// Walks over the message.
+    fn walk(mut self) -> Result<(), CheckError> {
+        if self.msg.len() > self.check.max_size {
+            return Err(CheckError::new(CheckErrorKind::TooLarge, 0));
+        }
+        let counts = HeaderSection::for_message_slice(self.msg).counts();
+        for _ in 0..counts.qdcount() {
+            self.question()?;
+        }
+        for _ in 0..counts.ancount() {
+            self.record(Section::Answer)?;
+        }
+        for _ in 0..counts.nscount() {
+            self.record(Section::Authority)?;
+        }
+        for _ in 0..counts.arcount() {
+            self.record(Section::Additional)?;
+        }
+        if self.pos < self.msg.len() {
+            return Err(self.error(CheckErrorKind::TrailingData));
+        }
+        Ok(())
+    }
+
+    /// Walks over a question.
+    fn question(&mut self) -> Result<(), CheckError> {
+        self.start_item()?;
+        self.pos = self.name(self.pos, self.msg.len())?.0;
+        self.advance(4, self.msg.len())
+    }
+
+    /// Walks over a record.
+    fn record(&mut self, section: Section) -> Result<(), CheckError> {
+        self.start_item()?;
+        let start = self.pos;
+        if let Some(tsig) = self.tsig {
+            return Err(CheckError::new(CheckErrorKind::MisplacedTsig, tsig));
+        }
+        let (end, owner_len) = self.name(self.pos, self.msg.len())?;
+        self.pos = end;
+        self.advance(10, self.msg.len())?;
+        let rtype = Rtype::from_int(self.u16_at(end));
+        let rdlen = usize::from(self.u16_at(end + 8));
+        let rdata_start = self.pos;
+        self.advance(rdlen, self.msg.len())?;
+
+        if rtype == Rtype::Opt {
+            if section != Section::Additional {
+                return Err(CheckError::new(
+                    CheckErrorKind::MisplacedOpt,
+                    start,
+                ));
+            }
+            if self.opt {
+                return Err(CheckError::new(
+                    CheckErrorKind::MultipleOpt,
+                    start,
+                ));
+            }
+            if owner_len != 1 {
+                return Err(CheckError::new(CheckErrorKind::BadOpt, start));
+            }
+            self.opt = true;
+        } else if rtype == Rtype::Tsig {
+            if section != Section::Additional {
+                return Err(CheckError::new(
+                    CheckErrorKind::MisplacedTsig,
+                    start,
+                ));
+            }
+            self.tsig = Some(start);
+        }
+
+        self.rdata(rtype, rdata_start, self.pos)
+    }
+
+    /// Checks the record data of a record.
+    ///
+    /// For the record types that may contain compressed names, the names
+    /// are walked like all other names. In addition, the record data of all
+    /// types known to the crate has to parse.
+    fn rdata(
+        &self,
+        rtype: Rtype,
+        start: usize,
+        end: usize,
+    ) -> Result<(), CheckError> {
+        let layout = rdata_name_layout(rtype);
+        let mut pos = start;
+        for field in layout {
+            pos = match *field {
+                RdataField::Name => self.name(pos, end)?.0,
+                RdataField::Fixed(len) if pos + len <= end => pos + len,
+                RdataField::CharStr if pos < end => {
+                    let len = usize::from(self.msg[pos]);
+                    if pos + 1 + len > end {
+                        return Err(CheckError::new(
+                            CheckErrorKind::BadRecordData,
+                            start,
+                        ));
+                    }
+                    pos + 1 + len
+                }
+                RdataField::Rest => end,
+                RdataField::Fixed(_) | RdataField::CharStr => {
+                    return Err(CheckError::new(
+                        CheckErrorKind::BadRecordData,
+                        start,
+                    ))
+                }
+            };
+        }
+        if !layout.is_empty() && pos != end {
+            return Err(CheckError::new(
+                CheckErrorKind::BadRecordData,
+                start,
+            ));
+        }
+
+        let mut parser = Parser::from_ref(self.msg);
+        parser.seek(start).map_err(|_| {
+            CheckError::new(CheckErrorKind::BadRecordData, start)
+        })?;
+        parser
+            .parse_block(end - start, |parser| {
+                AllRecordData::<&[u8], ParsedDname<&[u8]>>::parse_data(
+                    rtype, parser,
+                )
+            })
+            .map(|_| ())
+            .map_err(|_| {
+                CheckError::new(CheckErrorKind::BadRecordData, start)
+            })
+    }
+
+    /// Walks over a domain name starting at `start`.
+    ///
+    /// The uncompressed part of the name must end before `limit`. Returns
+    /// the position after the uncompressed part and the length of the
+    /// uncompressed name.
+    fn name(
+        &self,
+        start: usize,
+        limit: usize,
+    ) -> Result<(usize, usize), CheckError> {
+        let mut pos = start;
+        let mut end = None;
+        let mut earliest = start;
+        let mut len = 0;
+        let mut labels = 0;
+        loop {
+            let limit = if end.is_some() { self.msg.len() } else { limit };
+            let ltype = match self.msg.get(pos) {
+                Some(ltype) if pos < limit => *ltype,
+                _ => {
+                    return Err(CheckError::new(
+                        CheckErrorKind::ShortMessage,
+                        start,
+                    ))
+                }
+            };
+            match ltype & 0xC0 {
+                0x00 => {
+                    let label_len = usize::from(ltype);
+                    len += label_len + 1;
+                    labels += 1;
+                    if len > 255 {
+                        return Err(CheckError::new(
+                            CheckErrorKind::LongName,
+                            start,
+                        ));
+                    }
+                    if labels > self.check.max_labels {
+                        return Err(CheckError::new(
+                            CheckErrorKind::TooManyLabels,
+                            start,
+                        ));
+                    }
+                    if pos + 1 + label_len > limit {
+                        return Err(CheckError::new(
+                            CheckErrorKind::ShortMessage,
+                            start,
+                        ));
+                    }
+                    pos += 1 + label_len;
+                    if label_len == 0 {
+                        return Ok((end.unwrap_or(pos), len));
+                    }
+                }
+                0xC0 => {
+                    let low = match self.msg.get(pos + 1) {
+                        Some(low) if pos + 1 < limit => *low,
+                        _ => {
+                            return Err(CheckError::new(
+                                CheckErrorKind::ShortMessage,
+                                start,
+                            ))
+                        }
+                    };
+                    let target =
+                        usize::from(u16::from_be_bytes([ltype & 0x3F, low]));
+                    if target >= pos {
+                        return Err(CheckError::new(
+                            CheckErrorKind::ForwardPointer,
+                            pos,
+                        ));
+                    }
+                    if target >= earliest {
+                        return Err(CheckError::new(
+                            CheckErrorKind::PointerLoop,
+                            pos,
+                        ));
+                    }
+                    if target < mem::size_of::<HeaderSection>() {
+                        return Err(CheckError::new(
+                            CheckErrorKind::BadPointer,
+                            pos,
+                        ));
+                    }
+                    if end.is_none() {
+                        end = Some(pos + 2);
+                    }
+                    earliest = target;
+                    pos = target;
+                }
+                _ => {
+                    return Err(CheckError::new(
+                        CheckErrorKind::BadLabel,
+                        pos,
+                    ))
+                }
+            }
+        }
+    }
+
+    /// Makes sure there is data left for another question or record.
+    fn start_item(&self) -> Result<(), CheckError> {
+        if self.pos >= self.msg.len() {
+            Err(self.error(CheckErrorKind::CountMismatch))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Advances the position by `len` octets not going beyond `limit`.
+    fn advance(
+        &mut self,
+        len: usize,
+        limit: usize,
+    ) -> Result<(), CheckError> {
+        if self.pos + len > limit {
+            return Err(self.error(CheckErrorKind::ShortMessage));
+        }
+        self.pos += len;
+        Ok(())
+    }
+
+    /// Returns the 16 bit integer at the given position.
+    ///
+    /// The position must have been checked before.
+    fn u16_at(&self, pos: usize) -> u16 {
+        u16::from_be_bytes([self.msg[pos], self.msg[pos + 1]])
+    }
+
+    /// Creates an error at the current position.
+    fn error(&self, kind: CheckErrorKind) -> CheckError {
+        CheckError::new(kind, self.pos)
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// A field of record data as far as compressed names are concerned.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum RdataField {
+    /// A domain name that may be compressed.
+    Name,
+
+    /// A field of the given fixed length.
+    Fixed(usize),
+
+    /// A character string, i.e., a length octet and that many octets.
+    CharStr,
+
+    /// Any number of octets up to the end of the record data.
+    Rest,
+}
+
+/// Returns the layout of record data that may contain compressed names.
+///
+/// This includes the types from [RFC 1035] whose names may be compressed
+/// as well as the types that, according to [RFC 3597], receivers should
+/// decompress because they may have been compressed in the past. For all
+/// other types, the returned slice is empty.
+///
+/// [RFC 1035]: https://tools.ietf.org/html/rfc1035
+/// [RFC 3597]: https://tools.ietf.org/html/rfc3597
+pub(crate) fn rdata_name_layout(rtype: Rtype) -> &'static [RdataField] {
+    use RdataField::{CharStr, Fixed, Name, Rest};
+
+    match rtype {
+        Rtype::Ns
+        | Rtype::Md
+        | Rtype::Mf
+        | Rtype::Cname
+        | Rtype::Mb
+        | Rtype::Mg
+        | Rtype::Mr
+        | Rtype::Ptr => &[Name],
+        Rtype::Soa => &[Name, Name, Fixed(20)],
+        Rtype::Minfo | Rtype::Rp => &[Name, Name],
+        Rtype::Mx | Rtype::Afsdb | Rtype::Rt => &[Fixed(2), Name],
+        Rtype::Px => &[Fixed(2), Name, Name],
+        Rtype::Srv => &[Fixed(6), Name],
+        Rtype::Sig => &[Fixed(18), Name, Rest],
+        Rtype::Nxt => &[Name, Rest],
+        Rtype::Naptr => &[Fixed(4), CharStr, CharStr, CharStr, Name],
+        _ => &[],
+    }
+}
+
+/// The record type used for normalizing.
+#[cfg(feature = "std")]
+type NormalRecord<Ref> = Record<
+    ParsedDname<Ref>,
+    AllRecordData<<Ref as OctetsRef>::Range, ParsedDname<Ref>>,
+>;
+
+/// Parses a record for normalizing.
+#[cfg(feature = "std")]
+fn parse_record<Ref: OctetsRef>(
+    record: ParsedRecord<Ref>,
+) -> Result<NormalRecord<Ref>, CheckError> {
+    record
+        .into_record()
+        .map_err(malformed)?
+        .ok_or_else(|| malformed(()))
+}
+
+/// Converts any error into an error for malformed record data.
+#[cfg(feature = "std")]
+fn malformed<T>(_: T) -> CheckError {
+    CheckError::new(CheckErrorKind::BadRecordData, 0)
+}
+
+/// Converts a short buffer into an error for a message too large.
+#[cfg(feature = "std")]
+fn too_large(_: ShortBuf) -> CheckError {
+    CheckError::new(CheckErrorKind::TooLarge, 0)
+}
+
+//============ Error Types ===================================================
+
+//------------ CheckError ----------------------------------------------------
+
+/// A message failed a check.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct CheckError {
+    /// What is wrong with the message.
+    kind: CheckErrorKind,
+
+    /// The position in the message where the problem was found.
+    pos: usize,
+}
+
+impl CheckError {
+    /// Creates a new error.
+    fn new(kind: CheckErrorKind, pos: usize) -> Self {
+        CheckError { kind, pos }
+    }
+
+    /// Returns what is wrong with the message.
+    pub fn kind(self) -> CheckErrorKind {
+        self.kind
+    }
+
+    /// Returns the position in the message where the problem was found.
+    ///
+    /// For problems with a question or record, this is the start of the
+    /// question or record or the compression pointer in question. For
+    /// problems with the message as a whole, this is 0.
+    pub fn pos(self) -> usize {
+        self.pos
+    }
+}
+
+impl fmt::Display for CheckError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at position {}", self.kind, self.pos)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CheckError {}
+
+//------------ CheckErrorKind ------------------------------------------------
+
+/// The kinds of problems found by checking a message.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum CheckErrorKind {
+    /// The message is larger than allowed.
+    TooLarge,
+
+    /// The message ends in the middle of a question or record.
+    ShortMessage,
+
+    /// The message has fewer questions or records than the header says.
+    CountMismatch,
+
+    /// There is data after the last record.
+    TrailingData,
+
+    /// A domain name contains a label of an unknown type.
+    BadLabel,
+
+    /// A domain name is longer than 255 octets.
+    LongName,
+
+    /// A domain name has more labels than allowed.
+    TooManyLabels,
+
+    /// A compression pointer points to itself or further ahead.
+    ForwardPointer,
+
+    /// A compression pointer points into the name it is part of.
+    PointerLoop,
+
+    /// A compression pointer points into the message header.
+    BadPointer,
+
+    /// The record data of a record is malformed.
+    BadRecordData,
+
+    /// There is more than one OPT record.
+    MultipleOpt,
+
+    /// An OPT record is outside of the additional section.
+    MisplacedOpt,
+
+    /// The owner of the OPT record is not the root.
+    BadOpt,
+
+    /// A TSIG record is not the last record of the additional section.
+    MisplacedTsig,
+}
+
+impl fmt::Display for CheckErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            CheckErrorKind::TooLarge => "message too large",
+            CheckErrorKind::ShortMessage => "unexpected end of message",
+            CheckErrorKind::CountMismatch => "missing questions or records",
+            CheckErrorKind::TrailingData => "trailing data",
+            CheckErrorKind::BadLabel => "unknown label type",
+            CheckErrorKind::LongName => "domain name too long",
+            CheckErrorKind::TooManyLabels => "too many labels",
+            CheckErrorKind::ForwardPointer => "forward compression pointer",
+            CheckErrorKind::PointerLoop => "compression pointer loop",
+            CheckErrorKind::BadPointer => "compression pointer into header",
+            CheckErrorKind::BadRecordData => "malformed record data",
+            CheckErrorKind::MultipleOpt => "multiple OPT records",
+            CheckErrorKind::MisplacedOpt => "misplaced OPT record",
+            CheckErrorKind::BadOpt => "OPT record owner not root",
+            CheckErrorKind::MisplacedTsig => "misplaced TSIG record",
+        })
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+#[cfg(feature = "std")]
+mod test {
+    use super::*;
+    use crate::base::iana::Class;
+    use crate::base::name::Dname;
+    use crate::rdata::{Mx, Ns, A};
+    use core::str::FromStr;
+
+    fn name(s: &str) -> Dname<Vec<u8>> {
+        Dname::from_str(s).unwrap()
+    }
+
+    /// Creates a message with a question and an answer record.
+    fn simple() -> Vec<u8> {
+        let mut msg = MessageBuilder::new_vec().question();
+        msg.push((name("example.com"), Rtype::A)).unwrap();
+        let mut msg = msg.answer();
+        msg.push((name("example.com"), 60, A::from_octets(192, 0, 2, 1)))
+            .unwrap();
+        msg.finish()
+    }
+
+    /// Creates a message from raw sections with the given counts.
+    fn raw(counts: [u16; 4], sections: &[u8]) -> Message<Vec<u8>> {
+        let mut msg = vec![0; 4];
+        for count in counts {
+            msg.extend_from_slice(&count.to_be_bytes());
+        }
+        msg.extend_from_slice(sections);
+        Message::from_octets(msg).unwrap()
+    }
+
+    fn kind(msg: &Message<Vec<u8>>) -> Option<CheckErrorKind> {
+        MessageCheck::new().check(msg).err().map(CheckError::kind)
+    }
+
+    #[test]
+    fn good_message() {
+        let msg = Message::from_octets(simple()).unwrap();
+        assert_eq!(kind(&msg), None);
+        assert_eq!(
+            MessageCheck::new()
+                .max_size(20)
+                .check(&msg)
+                .err()
+                .map(CheckError::kind),
+            Some(CheckErrorKind::TooLarge)
+        );
+        assert_eq!(
+            MessageCheck::new()
+                .max_labels(2)
+                .check(&msg)
+                .err()
+                .map(CheckError::kind),
+            Some(CheckErrorKind::TooManyLabels)
+        );
+    }
+
+    #[test]
+    fn counts() {
+        let mut octets = simple();
+        octets.push(0);
+        assert_eq!(
+            kind(&Message::from_octets(octets).unwrap()),
+            Some(CheckErrorKind::TrailingData)
+        );
+
+        let mut octets = simple();
+        octets[9] = 2; // ancount
+        assert_eq!(
+            kind(&Message::from_octets(octets).unwrap()),
+            Some(CheckErrorKind::CountMismatch)
+        );
+
+        let mut octets = simple();
+        octets.pop();
+        assert_eq!(
+            kind(&Message::from_octets(octets).unwrap()),
+            Some(CheckErrorKind::ShortMessage)
+        );
+    }
+
+    #[test]
+    fn pointers() {
+        // A question for a.example pointing back into the header.
+        assert_eq!(
+            kind(&raw([1, 0, 0, 0], b"\x01a\xc0\x02\x00\x01\x00\x01")),
+            Some(CheckErrorKind::BadPointer)
+        );
+
+        // A pointer to itself.
+        let err = MessageCheck::new()
+            .check(&raw([1, 0, 0, 0], b"\x01a\xc0\x0e\x00\x01\x00\x01"))
+            .unwrap_err();
+        assert_eq!(err.kind(), CheckErrorKind::ForwardPointer);
+        assert_eq!(err.pos(), 14);
+
+        // A pointer to the start of its own name.
+        assert_eq!(
+            kind(&raw([1, 0, 0, 0], b"\x01a\xc0\x0c\x00\x01\x00\x01")),
+            Some(CheckErrorKind::PointerLoop)
+        );
+
+        // Three questions where the second one points to the first one
+        // and the third one points to the second one. This is fine.
+        assert_eq!(
+            kind(&raw(
+                [3, 0, 0, 0],
+                b"\x01a\x00\x00\x01\x00\x01\
+                  \x01b\xc0\x0c\x00\x01\x00\x01\
+                  \xc0\x13\x00\x01\x00\x01"
+            )),
+            None
+        );
+
+        // A name in record data pointing forward.
+        assert_eq!(
+            kind(&raw(
+                [0, 1, 0, 0],
+                b"\x00\x00\x02\x00\x01\x00\x00\x00\x00\x00\x02\xc0\x19\
+                  \x01a\x00"
+            )),
+            Some(CheckErrorKind::ForwardPointer)
+        );
+
+        // A bad label type.
+        assert_eq!(
+            kind(&raw([1, 0, 0, 0], b"\x41a\x00\x00\x01\x00\x01")),
+            Some(CheckErrorKind::BadLabel)
+        );
+    }
+
+    #[test]
+    fn record_data() {
+        // An A record with three octets of data.
+        assert_eq!(
+            kind(&raw(
+                [0, 1, 0, 0],
+                b"\x00\x00\x01\x00\x01\x00\x00\x00\x00\x00\x03\x01\x02\x03"
+            )),
+            Some(CheckErrorKind::BadRecordData)
+        );
+
+        // An NS record with trailing data after the name.
+        assert_eq!(
+            kind(&raw(
+                [0, 1, 0, 0],
+                b"\x00\x00\x02\x00\x01\x00\x00\x00\x00\x00\x02\x00\x00"
+            )),
+            Some(CheckErrorKind::BadRecordData)
+        );
+    }
+
+    #[test]
+    fn opt_and_tsig() {
+        let opt = b"\x00\x00\x29\x10\x00\x00\x00\x00\x00\x00\x00";
+        let mut tsig =
+            b"\x00\x00\xfa\x00\xff\x00\x00\x00\x00\x00\x11".to_vec();
+        tsig.extend_from_slice(&[0; 17]);
+
+        let mut sections = opt.to_vec();
+        sections.extend_from_slice(opt);
+        assert_eq!(
+            kind(&raw([0, 0, 0, 2], &sections)),
+            Some(CheckErrorKind::MultipleOpt)
+        );
+        assert_eq!(
+            kind(&raw([0, 1, 0, 0], opt)),
+            Some(CheckErrorKind::MisplacedOpt)
+        );
+        assert_eq!(
+            kind(&raw(
+                [0, 0, 0, 1],
+                b"\x01a\x00\x00\x29\x10\x00\x00\x00\x00\x00\x00\x00"
+            )),
+            Some(CheckErrorKind::BadOpt)
+        );
+
+        assert_eq!(kind(&raw([0, 0, 0, 1], &tsig)), None);
+        let mut sections = tsig.clone();
+        sections.extend_from_slice(opt);
+        let err = MessageCheck::new()
+            .check(&raw([0, 0, 0, 2], &sections))
+            .unwrap_err();
+        assert_eq!(err.kind(), CheckErrorKind::MisplacedTsig);
+        assert_eq!(err.pos(), 12);
+        assert_eq!(
+            kind(&raw([0, 0, 1, 0], &tsig)),
+            Some(CheckErrorKind::MisplacedTsig)
+        );
+    }
+
+    #[test]
+    fn normalize() {
+        let mut msg = MessageBuilder::new_vec().question();
+        msg.push((name("example.com"), Rtype::Mx)).unwrap();
+        let mut msg = msg.answer();
+        msg.push((
+            name("example.com"),
+            Class::In,
+            60,
+            Mx::new(10, name("mail.example.com")),
+        ))
+        .unwrap();
+        let mut msg = msg.authority();
+        msg.push((name("example.com"), 60, Ns::new(name("ns.example.com"))))
+            .unwrap();
+        let mut msg = msg.additional();
+        msg.opt(|opt| {
+            opt.set_udp_payload_size(1232);
+            Ok(())
+        })
+        .unwrap();
+        let msg = msg.into_message();
+
+        let normal = MessageCheck::new().normalize(&msg).unwrap();
+        assert!(normal.as_slice().len() < msg.as_slice().len());
+        assert_eq!(normal.header(), msg.header());
+        assert_eq!(normal.header_counts(), msg.header_counts());
+        assert_eq!(MessageCheck::new().check(&normal), Ok(()));
+        let left = msg.answer().unwrap().limit_to::<Mx<_>>().next();
+        let right = normal.answer().unwrap().limit_to::<Mx<_>>().next();
+        assert!(left.unwrap().unwrap() == right.unwrap().unwrap());
+        assert_eq!(
+            normal.opt().map(|opt| opt.udp_payload_size()),
+            Some(1232)
+        );
+    }
+}
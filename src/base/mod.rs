@@ -45,6 +45,8 @@
 //! sections to look at the questions and resource records. Similarly,
 //! a [`MessageBuilder`] takes a bytes vector (or creates one for you) and
 //! has functionality to build the sections of the message step-by-step.
+//! When answering queries, the [response](response/index.html) module
+//! helps with fitting the response into the available space.
 //!
 //!
 //! # Types for DNS Data
@@ -117,8 +119,10 @@ pub mod charstr;
 pub mod cmp;
 pub mod header;
 pub mod iana;
+pub mod json;
 pub mod message;
 pub mod message_builder;
+pub mod message_check;
 pub mod name;
 pub mod net;
 pub mod octets;
@@ -126,6 +130,7 @@ pub mod opt;
 pub mod question;
 pub mod rdata;
 pub mod record;
+pub mod response;
 pub mod scan;
 pub mod serial;
 pub mod str;
@@ -0,0 +1,687 @@
+//! Internationalized domain names.
+//!
+//! Domain names can only contain ASCII characters. Internationalized domain
+//! names as defined by IDNA2008 in [RFC 5890] through [RFC 5893] get around
+//! this limitation by encoding labels with non-ASCII characters – so-called
+//! U-labels – via [Punycode] into ASCII labels starting with `xn--` –
+//! so-called A-labels.
+//!
+//! Before conversion, user input is mapped as described in [UTS #46] using
+//! non-transitional processing. This lowercases the input, normalizes it,
+//! and converts characters such as the ideographic full stop into their
+//! canonical form. The resulting labels are then checked against the rules
+//! of IDNA2008, including the bidi rule of RFC 5893 and the CONTEXTJ rules
+//! for zero width joiners and non-joiners of [RFC 5892]. The STD3 ASCII
+//! rules are not applied, so labels such as `_tcp` are allowed.
+//!
+//! This module adds the methods `from_unicode` and `to_unicode` to
+//! [`Dname`], [`RelativeDname`], and [`UncertainDname`].
+//!
+//! [RFC 5890]: https://tools.ietf.org/html/rfc5890
+//! [RFC 5892]: https://tools.ietf.org/html/rfc5892
+//! [RFC 5893]: https://tools.ietf.org/html/rfc5893
+//! [Punycode]: https://tools.ietf.org/html/rfc3492
+//! [UTS #46]: https://www.unicode.org/reports/tr46/
+//! [`Dname`]: struct.Dname.html
+//! [`RelativeDname`]: struct.RelativeDname.html
+//! [`UncertainDname`]: enum.UncertainDname.html
+#![cfg(feature = "idn")]
+#![cfg_attr(docsrs, doc(cfg(feature = "idn")))]
+
+use super::super::octets::{EmptyBuilder, FromBuilder};
+use super::builder::{DnameBuilder, PushError};
+use super::dname::Dname;
+use super::label::Label;
+use super::relative::RelativeDname;
+use super::uncertain::UncertainDname;
+use core::fmt::{self, Write};
+use std::string::{String, ToString};
+use std::vec::Vec;
+use unicode_joining_type::{get_joining_type, JoiningType};
+use unicode_normalization::char::{
+    canonical_combining_class, is_combining_mark,
+};
+use unicode_normalization::is_nfc;
+
+//------------ Dname, RelativeDname, UncertainDname --------------------------
+
+/// # Internationalized Domain Names
+///
+impl<Octets: ?Sized> Dname<Octets> {
+    /// Creates an absolute domain name from its Unicode representation.
+    ///
+    /// The labels of the name are separated by dots. As with the
+    /// [`FromStr`] implementation, the name is considered absolute whether
+    /// it ends in a dot or not. Labels containing non-ASCII characters are
+    /// converted into A-labels. See the [module documentation] for the
+    /// exact processing.
+    ///
+    /// [`FromStr`]: #impl-FromStr
+    /// [module documentation]: index.html#internationalized-domain-names
+    pub fn from_unicode(s: &str) -> Result<Self, IdnaError>
+    where
+        Octets: FromBuilder,
+        <Octets as FromBuilder>::Builder: EmptyBuilder + AsMut<[u8]>,
+    {
+        if s == "." {
+            return DnameBuilder::<Octets::Builder>::new()
+                .into_dname()
+                .map_err(|err| IdnaError::push(s, err));
+        }
+        let (builder, _) = build::<Octets::Builder>(s)?;
+        builder.into_dname().map_err(|err| IdnaError::push(s, err))
+    }
+
+    /// Returns the Unicode representation of the name.
+    ///
+    /// A-labels are converted into U-labels. All other labels as well as
+    /// A-labels that don’t decode into valid U-labels are formatted as
+    /// by the [`Display`] implementation. As there, the trailing dot is
+    /// omitted.
+    ///
+    /// [`Display`]: #impl-Display
+    pub fn to_unicode(&self) -> String
+    where
+        Octets: AsRef<[u8]>,
+    {
+        labels_to_unicode(self.iter())
+    }
+}
+
+impl<Octets: ?Sized> RelativeDname<Octets> {
+    /// Creates a relative domain name from its Unicode representation.
+    ///
+    /// The labels of the name are separated by dots. An empty string
+    /// results in an empty name. Labels containing non-ASCII characters
+    /// are converted into A-labels. See the [module documentation] for the
+    /// exact processing.
+    ///
+    /// Returns an error with kind [`IdnaErrorKind::AbsoluteName`] if the
+    /// name ends in a dot.
+    ///
+    /// [module documentation]: index.html#internationalized-domain-names
+    /// [`IdnaErrorKind::AbsoluteName`]: enum.IdnaErrorKind.html#variant.AbsoluteName
+    pub fn from_unicode(s: &str) -> Result<Self, IdnaError>
+    where
+        Octets: FromBuilder,
+        <Octets as FromBuilder>::Builder: EmptyBuilder + AsMut<[u8]>,
+    {
+        if s.is_empty() {
+            return Ok(DnameBuilder::<Octets::Builder>::new().finish());
+        }
+        match build::<Octets::Builder>(s)? {
+            (builder, false) => Ok(builder.finish()),
+            (_, true) => Err(IdnaError::new(s, IdnaErrorKind::AbsoluteName)),
+        }
+    }
+
+    /// Returns the Unicode representation of the name.
+    ///
+    /// A-labels are converted into U-labels. All other labels as well as
+    /// A-labels that don’t decode into valid U-labels are formatted as
+    /// by the [`Display`] implementation.
+    ///
+    /// [`Display`]: #impl-Display
+    pub fn to_unicode(&self) -> String
+    where
+        Octets: AsRef<[u8]>,
+    {
+        labels_to_unicode(self.iter())
+    }
+}
+
+impl<Octets> UncertainDname<Octets> {
+    /// Creates a domain name from its Unicode representation.
+    ///
+    /// If the name ends in a dot, it is absolute, otherwise it is
+    /// relative. An empty string results in an empty relative name.
+    /// Labels containing non-ASCII characters are converted into A-labels.
+    /// See the [module documentation] for the exact processing.
+    ///
+    /// [module documentation]: index.html#internationalized-domain-names
+    pub fn from_unicode(s: &str) -> Result<Self, IdnaError>
+    where
+        Octets: FromBuilder,
+        <Octets as FromBuilder>::Builder: EmptyBuilder + AsMut<[u8]>,
+    {
+        if s.is_empty() {
+            Ok(DnameBuilder::<Octets::Builder>::new().finish().into())
+        } else if s == "." {
+            Ok(Dname::<Octets>::from_unicode(s)?.into())
+        } else {
+            match build::<Octets::Builder>(s)? {
+                (builder, false) => Ok(builder.finish().into()),
+                (builder, true) => builder
+                    .into_dname()
+                    .map(Into::into)
+                    .map_err(|err| IdnaError::push(s, err)),
+            }
+        }
+    }
+
+    /// Returns the Unicode representation of the name.
+    ///
+    /// A-labels are converted into U-labels. All other labels as well as
+    /// A-labels that don’t decode into valid U-labels are formatted as
+    /// by the [`Display`] implementation. As there, a final dot is added
+    /// to absolute names.
+    ///
+    /// [`Display`]: #impl-Display
+    pub fn to_unicode(&self) -> String
+    where
+        Octets: AsRef<[u8]>,
+    {
+        match *self {
+            UncertainDname::Absolute(ref name) => {
+                let mut res = name.to_unicode();
+                res.push('.');
+                res
+            }
+            UncertainDname::Relative(ref name) => name.to_unicode(),
+        }
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Returns the UTS #46 configuration we use.
+fn config() -> idna::Config {
+    idna::Config::default()
+        .use_std3_ascii_rules(false)
+        .transitional_processing(false)
+        .check_hyphens(true)
+        .verify_dns_length(false)
+}
+
+/// Converts a name into a builder with the A-labels of the name.
+///
+/// Returns the builder and whether the name ended in a dot.
+fn build<Builder>(s: &str) -> Result<(DnameBuilder<Builder>, bool), IdnaError>
+where
+    Builder: EmptyBuilder + AsMut<[u8]> + crate::base::octets::OctetsBuilder,
+{
+    let ascii = to_ascii(s)?;
+    let (ascii, absolute) = match ascii.strip_suffix('.') {
+        Some(ascii) => (ascii, true),
+        None => (ascii.as_str(), false),
+    };
+    let mut builder = DnameBuilder::new();
+    for label in ascii.split('.') {
+        if label.is_empty() {
+            return Err(IdnaError::new(s, IdnaErrorKind::EmptyLabel));
+        }
+        builder
+            .append_label(label.as_bytes())
+            .map_err(|err| IdnaError::push(label, err))?;
+    }
+    Ok((builder, absolute))
+}
+
+/// Converts a name into its ASCII form.
+fn to_ascii(s: &str) -> Result<String, IdnaError> {
+    // The idna crate happily accepts A-labels that decode into pure ASCII.
+    // These are invalid, so we need to check beforehand.
+    for label in s.split(is_label_separator) {
+        if is_alabel(label.as_bytes()) {
+            match idna::punycode::decode_to_string(&label[4..]) {
+                Some(decoded) if !decoded.is_ascii() => {}
+                _ => {
+                    return Err(IdnaError::new(
+                        label,
+                        IdnaErrorKind::Punycode,
+                    ))
+                }
+            }
+        }
+    }
+
+    let (unicode, _) = config().to_unicode(s);
+    match config().to_ascii(s) {
+        Ok(ascii) => {
+            // The idna crate doesn’t check all the rules, so we need to
+            // do the remaining checks ourselves.
+            for label in unicode.split('.') {
+                if let Err(kind) = check_extra(label) {
+                    return Err(IdnaError::new(label, kind));
+                }
+            }
+            Ok(ascii)
+        }
+        Err(_) => {
+            // Find out what went wrong. The only check left if all labels
+            // are fine is the bidi rule which applies to the whole name.
+            for label in unicode.split('.') {
+                if let Err(kind) = check_label(label) {
+                    return Err(IdnaError::new(label, kind));
+                }
+            }
+            Err(IdnaError::new(s, IdnaErrorKind::Bidi))
+        }
+    }
+}
+
+/// Returns whether a character is mapped to a full stop by UTS #46.
+fn is_label_separator(ch: char) -> bool {
+    matches!(ch, '.' | '\u{3002}' | '\u{FF0E}' | '\u{FF61}')
+}
+
+/// Returns whether a label has the ACE prefix of an A-label.
+fn is_alabel(label: &[u8]) -> bool {
+    label.len() >= 4 && label[..4].eq_ignore_ascii_case(b"xn--")
+}
+
+/// Checks a label after UTS #46 mapping.
+fn check_label(label: &str) -> Result<(), IdnaErrorKind> {
+    if is_alabel(label.as_bytes()) {
+        // A-labels have been decoded. If there still is one, decoding
+        // failed.
+        return Err(IdnaErrorKind::Punycode);
+    }
+    if label.starts_with('-') || label.ends_with('-') {
+        return Err(IdnaErrorKind::Hyphen);
+    }
+    if let Some(ch) = label.chars().next() {
+        if is_combining_mark(ch) {
+            return Err(IdnaErrorKind::LeadingCombiningMark);
+        }
+    }
+    if let Some(ch) = label.chars().find(|&ch| is_disallowed(ch)) {
+        return Err(IdnaErrorKind::DisallowedChar(ch));
+    }
+    if !is_nfc(label) {
+        return Err(IdnaErrorKind::NotNfc);
+    }
+    check_extra(label)
+}
+
+/// Checks the rules for U-labels not checked by the idna crate.
+fn check_extra(label: &str) -> Result<(), IdnaErrorKind> {
+    if label.is_ascii() {
+        return Ok(());
+    }
+    // RFC 5891, section 4.2.3.1: no hyphens in the third and fourth
+    // positions.
+    if label
+        .chars()
+        .skip(2)
+        .take(2)
+        .filter(|&ch| ch == '-')
+        .count()
+        == 2
+    {
+        return Err(IdnaErrorKind::Hyphen);
+    }
+    if !check_contextj(label) {
+        return Err(IdnaErrorKind::ContextJ);
+    }
+    Ok(())
+}
+
+/// Returns whether a non-ASCII character is disallowed.
+///
+/// The idna crate doesn’t provide access to its mapping table, so we
+/// convert the character on its own. Because a character may fail the
+/// bidi rule on its own – Arabic digits, for instance – or may not start
+/// a label, we also try it following a right-to-left character.
+fn is_disallowed(ch: char) -> bool {
+    if ch.is_ascii() {
+        // With the STD3 rules disabled, all ASCII characters but the dot
+        // are allowed.
+        return false;
+    }
+    config().to_ascii(&ch.to_string()).is_err()
+        && config().to_ascii(&format!("\u{5d0}{}", ch)).is_err()
+}
+
+/// Checks the CONTEXTJ rules from RFC 5892, appendix A.1 and A.2.
+fn check_contextj(label: &str) -> bool {
+    const VIRAMA: u8 = 9;
+
+    let chars: Vec<char> = label.chars().collect();
+    for (i, &ch) in chars.iter().enumerate() {
+        if ch != '\u{200C}' && ch != '\u{200D}' {
+            continue;
+        }
+        if i > 0 && canonical_combining_class(chars[i - 1]) == VIRAMA {
+            continue;
+        }
+        if ch == '\u{200D}' {
+            return false;
+        }
+
+        // ZERO WIDTH NON-JOINER: Needs a left or dual joining character
+        // before and a right or dual joining character after, skipping
+        // transparent characters.
+        let before = chars[..i]
+            .iter()
+            .rev()
+            .map(|&ch| get_joining_type(ch))
+            .find(|&jt| jt != JoiningType::Transparent);
+        let after = chars[i + 1..]
+            .iter()
+            .map(|&ch| get_joining_type(ch))
+            .find(|&jt| jt != JoiningType::Transparent);
+        if !matches!(
+            before,
+            Some(JoiningType::LeftJoining | JoiningType::DualJoining)
+        ) || !matches!(
+            after,
+            Some(JoiningType::RightJoining | JoiningType::DualJoining)
+        ) {
+            return false;
+        }
+    }
+    true
+}
+
+/// Converts a sequence of labels into a Unicode string.
+///
+/// The root label is skipped.
+fn labels_to_unicode<'a>(labels: impl Iterator<Item = &'a Label>) -> String {
+    let mut res = String::new();
+    for label in labels {
+        if label.is_root() {
+            continue;
+        }
+        if !res.is_empty() {
+            res.push('.');
+        }
+        match label_to_unicode(label) {
+            Some(label) => res.push_str(&label),
+            None => write!(res, "{}", label).unwrap(),
+        }
+    }
+    res
+}
+
+/// Converts an A-label into a U-label.
+///
+/// Returns `None` if the label is not an A-label or doesn’t convert into
+/// a valid U-label.
+fn label_to_unicode(label: &Label) -> Option<String> {
+    let label = label.as_slice();
+    if !is_alabel(label) {
+        return None;
+    }
+    let label = core::str::from_utf8(label).ok()?;
+    let (unicode, res) = config().to_unicode(label);
+    res.ok()?;
+    check_extra(&unicode).ok()?;
+    // The result must convert back into the same A-label.
+    if !config()
+        .to_ascii(&unicode)
+        .ok()?
+        .eq_ignore_ascii_case(label)
+    {
+        return None;
+    }
+    Some(unicode)
+}
+
+//============ Error Types ===================================================
+
+//------------ IdnaError -----------------------------------------------------
+
+/// A domain name could not be converted from its Unicode representation.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct IdnaError {
+    /// The offending label or the whole name.
+    label: String,
+
+    /// What went wrong.
+    kind: IdnaErrorKind,
+}
+
+impl IdnaError {
+    fn new(label: &str, kind: IdnaErrorKind) -> Self {
+        IdnaError {
+            label: label.into(),
+            kind,
+        }
+    }
+
+    fn push(label: &str, err: PushError) -> Self {
+        Self::new(
+            label,
+            match err {
+                PushError::LongLabel => IdnaErrorKind::LongLabel,
+                PushError::LongName => IdnaErrorKind::LongName,
+                PushError::ShortBuf => IdnaErrorKind::ShortBuf,
+            },
+        )
+    }
+
+    /// Returns the label that caused the error.
+    ///
+    /// For errors that concern the name as a whole, this is the complete
+    /// name as given. Otherwise it is the label after the UTS #46 mapping
+    /// has been applied.
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// Returns what went wrong.
+    pub fn kind(&self) -> IdnaErrorKind {
+        self.kind
+    }
+}
+
+//--- Display and Error
+
+impl fmt::Display for IdnaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid domain name '{}': {}", self.label, self.kind)
+    }
+}
+
+impl std::error::Error for IdnaError {}
+
+//------------ IdnaErrorKind -------------------------------------------------
+
+/// The kind of error that happened during Unicode conversion.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum IdnaErrorKind {
+    /// An empty label was encountered.
+    EmptyLabel,
+
+    /// A label is longer than 63 octets after conversion.
+    LongLabel,
+
+    /// The name is longer than 255 octets after conversion.
+    LongName,
+
+    /// A relative name was requested but the name ends in a dot.
+    AbsoluteName,
+
+    /// A label starting with `xn--` is not valid Punycode.
+    Punycode,
+
+    /// A label starts or ends with a hyphen.
+    ///
+    /// This is also used for U-labels that have hyphens in both the third
+    /// and fourth positions.
+    Hyphen,
+
+    /// A label starts with a combining mark.
+    LeadingCombiningMark,
+
+    /// A label contains a disallowed character.
+    DisallowedChar(char),
+
+    /// A label is not in Unicode Normalization Form C.
+    ///
+    /// Since the mapping normalizes its input, this can only happen for
+    /// A-labels.
+    NotNfc,
+
+    /// A zero width joiner or non-joiner is used in the wrong context.
+    ContextJ,
+
+    /// The name violates the bidi rule of RFC 5893.
+    Bidi,
+
+    /// The buffer is too short to contain the name.
+    ShortBuf,
+}
+
+//--- Display
+
+impl fmt::Display for IdnaErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            IdnaErrorKind::EmptyLabel => {
+                f.write_str("an empty label was encountered")
+            }
+            IdnaErrorKind::LongLabel => {
+                f.write_str("label length limit exceeded")
+            }
+            IdnaErrorKind::LongName => f.write_str("long domain name"),
+            IdnaErrorKind::AbsoluteName => f.write_str("absolute name"),
+            IdnaErrorKind::Punycode => f.write_str("invalid Punycode"),
+            IdnaErrorKind::Hyphen => f.write_str("misplaced hyphen"),
+            IdnaErrorKind::LeadingCombiningMark => {
+                f.write_str("label starts with a combining mark")
+            }
+            IdnaErrorKind::DisallowedChar(ch) => {
+                write!(f, "disallowed character U+{:04X}", u32::from(ch))
+            }
+            IdnaErrorKind::NotNfc => {
+                f.write_str("label is not in normalization form C")
+            }
+            IdnaErrorKind::ContextJ => {
+                f.write_str("zero width joiner or non-joiner not allowed")
+            }
+            IdnaErrorKind::Bidi => f.write_str("bidi rule violated"),
+            IdnaErrorKind::ShortBuf => crate::base::octets::ShortBuf.fmt(f),
+        }
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn dname(s: &str) -> Result<Dname<Vec<u8>>, IdnaError> {
+        Dname::from_unicode(s)
+    }
+
+    fn kind(s: &str) -> IdnaErrorKind {
+        dname(s).unwrap_err().kind()
+    }
+
+    #[test]
+    fn from_unicode() {
+        assert_eq!(
+            dname("bücher.example").unwrap().as_slice(),
+            b"\x0dxn--bcher-kva\x07example\0"
+        );
+        assert_eq!(
+            dname("Bücher.EXAMPLE.").unwrap().as_slice(),
+            b"\x0dxn--bcher-kva\x07example\0"
+        );
+        // Ideographic full stop and non-transitional sharp s.
+        assert_eq!(
+            dname("faß\u{3002}de").unwrap().to_string(),
+            "xn--fa-hia.de"
+        );
+        assert_eq!(
+            dname("_sip._tcp.example").unwrap().to_string(),
+            "_sip._tcp.example"
+        );
+        assert_eq!(dname(".").unwrap().as_slice(), b"\0");
+        assert_eq!(
+            dname("xn--bcher-kva.example").unwrap().to_string(),
+            "xn--bcher-kva.example"
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(kind(""), IdnaErrorKind::EmptyLabel);
+        assert_eq!(kind("a..example"), IdnaErrorKind::EmptyLabel);
+        assert_eq!(kind("xn--ab-.example"), IdnaErrorKind::Punycode);
+        assert_eq!(kind("-bücher.example"), IdnaErrorKind::Hyphen);
+        assert_eq!(kind("bü--cher.example"), IdnaErrorKind::Hyphen);
+        assert_eq!(
+            kind("\u{301}bücher.example"),
+            IdnaErrorKind::LeadingCombiningMark
+        );
+        assert_eq!(
+            kind("a\u{2028}b.example"),
+            IdnaErrorKind::DisallowedChar('\u{2028}')
+        );
+        assert_eq!(kind("a\u{200D}b.example"), IdnaErrorKind::ContextJ);
+        assert_eq!(kind("a\u{200C}b.example"), IdnaErrorKind::ContextJ);
+        assert_eq!(kind("a\u{5d0}.example"), IdnaErrorKind::Bidi);
+        assert_eq!(
+            kind(&format!("{}ü.example", "a".repeat(60))),
+            IdnaErrorKind::LongLabel
+        );
+        let err = dname("a.-b.example").unwrap_err();
+        assert_eq!(err.label(), "-b");
+        assert_eq!(
+            err.to_string(),
+            "invalid domain name '-b': misplaced hyphen"
+        );
+    }
+
+    #[test]
+    fn contextj() {
+        // ZWJ after a virama (Devanagari).
+        assert!(dname("\u{915}\u{94d}\u{200D}\u{937}").is_ok());
+        // ZWNJ between dual joining Arabic letters.
+        assert!(dname("\u{628}\u{200C}\u{628}").is_ok());
+    }
+
+    #[test]
+    fn to_unicode() {
+        assert_eq!(
+            dname("bücher.example").unwrap().to_unicode(),
+            "bücher.example"
+        );
+        assert_eq!(
+            Dname::<Vec<u8>>::from_chars("XN--BCHER-KVA.Example.".chars())
+                .unwrap()
+                .to_unicode(),
+            "bücher.Example"
+        );
+        // Invalid A-labels stay as they are.
+        assert_eq!(
+            Dname::<Vec<u8>>::from_chars("xn--ab-.a\\ b.".chars())
+                .unwrap()
+                .to_unicode(),
+            "xn--ab-.a\\ b"
+        );
+        assert_eq!(Dname::<Vec<u8>>::root().to_unicode(), "");
+    }
+
+    #[test]
+    fn relative_and_uncertain() {
+        let name = RelativeDname::<Vec<u8>>::from_unicode("bücher").unwrap();
+        assert_eq!(name.as_slice(), b"\x0dxn--bcher-kva");
+        assert_eq!(name.to_unicode(), "bücher");
+        assert_eq!(
+            RelativeDname::<Vec<u8>>::from_unicode("bücher.")
+                .unwrap_err()
+                .kind(),
+            IdnaErrorKind::AbsoluteName
+        );
+        assert!(RelativeDname::<Vec<u8>>::from_unicode("")
+            .unwrap()
+            .is_empty());
+
+        let name =
+            UncertainDname::<Vec<u8>>::from_unicode("bücher.").unwrap();
+        assert!(name.is_absolute());
+        assert_eq!(name.to_unicode(), "bücher.");
+        let name = UncertainDname::<Vec<u8>>::from_unicode("bücher").unwrap();
+        assert!(name.is_relative());
+        assert_eq!(name.to_unicode(), "bücher");
+        assert!(UncertainDname::<Vec<u8>>::from_unicode(".")
+            .unwrap()
+            .is_absolute());
+    }
+}
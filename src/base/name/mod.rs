@@ -47,11 +47,40 @@
 //! Alternatively, you can use [`DnameBuilder`] to construct a name manually
 //! from individual labels.
 //!
+//! While the name types accept any octets in their labels, the
+//! [`check_syntax`] method of [`ToDname`] and [`ToRelativeDname`] checks
+//! a name against the stricter rules for host names, service names, or
+//! mailboxes described by [`NameSyntax`].
+//!
+//! For collections of values keyed by domain names that need to consider
+//! the name hierarchy – finding the closest enclosing zone, for instance –
+//! the module provides [`NameTree`]. Determining the public suffix and
+//! registrable domain of a name via the Public Suffix List is provided by
+//! the [`psl`] module.
+//!
+//! # Internationalized Domain Names
+//!
+//! If the `idn` feature is enabled, the name types gain `from_unicode` and
+//! `to_unicode` methods that convert between names containing non-ASCII
+//! characters and their ASCII form as defined by IDNA2008. User input is
+//! processed as described in [UTS #46] using non-transitional processing.
+//! Labels are checked against the IDNA2008 rules, including the bidi rule
+//! and the CONTEXTJ rules for zero width joiners and non-joiners. The STD3
+//! ASCII rules are not applied, so labels such as `_tcp` are allowed.
+//! Errors are reported via [`IdnaError`].
+//!
+//! [UTS #46]: https://www.unicode.org/reports/tr46/
+//!
 //! [`Bytes`]: ../../../bytes/struct.Bytes.html
+//! [`check_syntax`]: trait.ToDname.html#method.check_syntax
 //! [`Dname`]: struct.Dname.html
 //! [`DnameBuilder`]: struct.DnameBuilder.html
 //! [`FromStr`]: ../../../std/str/trait.FromStr.html
+//! [`IdnaError`]: struct.IdnaError.html
+//! [`NameSyntax`]: enum.NameSyntax.html
+//! [`NameTree`]: struct.NameTree.html
 //! [`ParsedDname`]: struct.ParsedDname.html
+//! [`psl`]: psl/index.html
 //! [`RelativeDname`]: struct.RelativeDname.html
 //! [`ToDname`]: trait.ToDname.html
 //! [`ToRelativeDname`]: trait.ToRelativeDname.html
@@ -62,6 +91,8 @@ pub use self::builder::{
 };
 pub use self::chain::{Chain, ChainIter, LongChainError, UncertainChainIter};
 pub use self::dname::{Dname, DnameError};
+#[cfg(feature = "idn")]
+pub use self::idn::{IdnaError, IdnaErrorKind};
 pub use self::label::{
     Label, LabelTypeError, LongLabelError, OwnedLabel, SliceLabelsIter,
     SplitLabelError,
@@ -70,16 +101,25 @@ pub use self::parsed::{ParsedDname, ParsedDnameIter, ParsedSuffixIter};
 pub use self::relative::{
     DnameIter, RelativeDname, RelativeDnameError, StripSuffixError,
 };
+#[cfg(feature = "zonefile")]
+pub(crate) use self::syntax::{check_hostname_label, check_service_label};
+pub use self::syntax::{NameSyntax, SpecialUse, Violation, ViolationKind};
 pub use self::traits::{
     ToDname, ToEitherDname, ToLabelIter, ToRelativeDname,
 };
+#[cfg(feature = "std")]
+pub use self::tree::{Iter as NameTreeIter, NameTree};
 pub use self::uncertain::UncertainDname;
 
 mod builder;
 mod chain;
 mod dname;
+mod idn;
 mod label;
 mod parsed;
+pub mod psl;
 mod relative;
+mod syntax;
 mod traits;
+mod tree;
 mod uncertain;
@@ -0,0 +1,570 @@
+//! The Public Suffix List.
+//!
+//! The [Public Suffix List] collects the domain names under which
+//! independent parties can register names, such as `com` or `co.uk`. These
+//! names are called *public suffixes*. The name directly below a public
+//! suffix – `example.co.uk`, for instance – is the *registrable domain* of
+//! all the names at or below it. This is what DMARC calls the
+//! organizational domain and what web browsers use to decide the scope of
+//! cookies.
+//!
+//! This module provides [`PublicSuffixList`] which compiles the rules of
+//! the list into a tree of labels and determines the public suffix and
+//! the registrable domain of a name following the [algorithm] published
+//! with the list. This includes wildcard and exception rules. Since the
+//! list distinguishes between suffixes operated under ICANN policy and
+//! suffixes submitted by private parties, lookups can be restricted to
+//! the former.
+//!
+//! The list itself is not included. It needs to be loaded from a file,
+//! usually a copy of [`public_suffix_list.dat`].
+//!
+//! Rules containing non-ASCII characters are converted into A-labels if
+//! the `idn` feature is enabled and skipped otherwise.
+//!
+//! [Public Suffix List]: https://publicsuffix.org/
+//! [algorithm]: https://github.com/publicsuffix/list/wiki/Format
+//! [`public_suffix_list.dat`]: https://publicsuffix.org/list/public_suffix_list.dat
+//! [`PublicSuffixList`]: struct.PublicSuffixList.html
+#![cfg(feature = "std")]
+#![cfg_attr(docsrs, doc(cfg(feature = "std")))]
+
+use super::dname::Dname;
+use super::tree::NameTree;
+use std::fs;
+use std::io::{self, BufRead, Read};
+use std::path::Path;
+use std::str::FromStr;
+use std::vec::Vec;
+
+//------------ Section -------------------------------------------------------
+
+/// The section of the list a rule appears in.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Section {
+    /// The rule is for a domain operated under ICANN policy.
+    Icann,
+
+    /// The rule was submitted by the private operator of a domain.
+    Private,
+}
+
+//------------ PublicSuffixList ----------------------------------------------
+
+/// The compiled rules of the Public Suffix List.
+///
+/// A value can be created from the content of the list via [`load`] or
+/// [`parse`]. Alternatively, rules can be added one by one via
+/// [`add_rule`].
+///
+/// The [`public_suffix`] and [`registrable_domain`] methods determine
+/// the respective part of a domain name using all rules. The variants
+/// [`icann_public_suffix`] and [`icann_registrable_domain`] only consider
+/// rules from the ICANN section of the list.
+///
+/// [`load`]: #method.load
+/// [`parse`]: #method.parse
+/// [`add_rule`]: #method.add_rule
+/// [`public_suffix`]: #method.public_suffix
+/// [`registrable_domain`]: #method.registrable_domain
+/// [`icann_public_suffix`]: #method.icann_public_suffix
+/// [`icann_registrable_domain`]: #method.icann_registrable_domain
+#[derive(Clone, Debug, Default)]
+pub struct PublicSuffixList {
+    /// The rules keyed by the name they are for.
+    ///
+    /// Wildcard rules are keyed by the name without the wildcard label.
+    rules: NameTree<Rules>,
+}
+
+impl PublicSuffixList {
+    /// Creates a new, empty list.
+    ///
+    /// With an empty list, the public suffix of each name is its top-level
+    /// label.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads the list from a file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, io::Error> {
+        let mut res = Self::new();
+        res.parse(&mut fs::File::open(path)?)?;
+        Ok(res)
+    }
+
+    /// Parses the content of the list from a reader and adds its rules.
+    ///
+    /// Each line contains one rule. Lines starting with `//` are comments.
+    /// Rules between the `===BEGIN PRIVATE DOMAINS===` and
+    /// `===END PRIVATE DOMAINS===` comments are taken to be from the
+    /// private section, all others from the ICANN section. Lines that
+    /// don’t contain a valid rule are skipped, so only reading can fail.
+    pub fn parse<R: Read>(
+        &mut self,
+        reader: &mut R,
+    ) -> Result<(), io::Error> {
+        let mut section = Section::Icann;
+        for line in io::BufReader::new(reader).lines() {
+            let line = line?;
+            let line = line.trim();
+            if let Some(comment) = line.strip_prefix("//") {
+                let comment = comment.trim();
+                if comment == "===BEGIN PRIVATE DOMAINS===" {
+                    section = Section::Private
+                } else if comment == "===END PRIVATE DOMAINS===" {
+                    section = Section::Icann
+                }
+                continue;
+            }
+            if let Some(rule) = line.split_whitespace().next() {
+                let _ = self.add_rule(rule, section);
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds a single rule.
+    ///
+    /// The rule is given in the format of the list: a domain name without
+    /// a trailing dot, optionally prefixed with `*.` for a wildcard rule or
+    /// `!` for an exception rule.
+    ///
+    /// Returns an error if the rule is not valid.
+    pub fn add_rule(
+        &mut self,
+        rule: &str,
+        section: Section,
+    ) -> Result<(), InvalidRule> {
+        let (name, kind) = if let Some(name) = rule.strip_prefix('!') {
+            (name, RuleKind::Exception)
+        } else if let Some(name) = rule.strip_prefix("*.") {
+            (name, RuleKind::Wildcard)
+        } else {
+            (rule, RuleKind::Exact)
+        };
+        if name.is_empty() || name.starts_with('.') || name.ends_with('.') {
+            return Err(InvalidRule);
+        }
+        let name = rule_name(name)?;
+        if kind == RuleKind::Exception && name.label_count() < 3 {
+            // An exception for a top-level domain makes no sense.
+            return Err(InvalidRule);
+        }
+        if self.rules.get(&name).is_none() {
+            self.rules.insert(&name, Rules::new(name.label_count() - 1));
+        }
+        let rules = self.rules.get_mut(&name).unwrap();
+        let target = match kind {
+            RuleKind::Exact => &mut rules.exact,
+            RuleKind::Wildcard => &mut rules.wildcard,
+            RuleKind::Exception => &mut rules.exception,
+        };
+        *target = Some(section);
+        Ok(())
+    }
+
+    /// Returns whether the list contains no rules.
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Returns the public suffix of a name.
+    ///
+    /// If no rule matches, the top-level label of the name is its public
+    /// suffix. Only for the root name, `None` is returned.
+    pub fn public_suffix<'a, Octets>(
+        &self,
+        name: &'a Dname<Octets>,
+    ) -> Option<PublicSuffix<'a>>
+    where
+        Octets: AsRef<[u8]> + ?Sized,
+    {
+        self.lookup(name, true)
+    }
+
+    /// Returns the registrable domain of a name.
+    ///
+    /// This is the public suffix of the name plus one label. If the name
+    /// is a public suffix itself, returns `None`.
+    pub fn registrable_domain<'a, Octets>(
+        &self,
+        name: &'a Dname<Octets>,
+    ) -> Option<&'a Dname<[u8]>>
+    where
+        Octets: AsRef<[u8]> + ?Sized,
+    {
+        self.lookup(name, true)?.registrable_domain(name)
+    }
+
+    /// Returns the public suffix of a name considering ICANN rules only.
+    ///
+    /// This is the same as [`public_suffix`] except that rules from the
+    /// private section of the list are ignored.
+    ///
+    /// [`public_suffix`]: #method.public_suffix
+    pub fn icann_public_suffix<'a, Octets>(
+        &self,
+        name: &'a Dname<Octets>,
+    ) -> Option<PublicSuffix<'a>>
+    where
+        Octets: AsRef<[u8]> + ?Sized,
+    {
+        self.lookup(name, false)
+    }
+
+    /// Returns the registrable domain of a name considering ICANN rules only.
+    ///
+    /// This is the same as [`registrable_domain`] except that rules from
+    /// the private section of the list are ignored.
+    ///
+    /// [`registrable_domain`]: #method.registrable_domain
+    pub fn icann_registrable_domain<'a, Octets>(
+        &self,
+        name: &'a Dname<Octets>,
+    ) -> Option<&'a Dname<[u8]>>
+    where
+        Octets: AsRef<[u8]> + ?Sized,
+    {
+        self.lookup(name, false)?.registrable_domain(name)
+    }
+
+    /// Determines the public suffix of a name.
+    fn lookup<'a, Octets>(
+        &self,
+        name: &'a Dname<Octets>,
+        private: bool,
+    ) -> Option<PublicSuffix<'a>>
+    where
+        Octets: AsRef<[u8]> + ?Sized,
+    {
+        let count = name.label_count() - 1;
+        if count == 0 {
+            return None;
+        }
+        let allowed = |section: Option<Section>| {
+            section.filter(|&section| private || section == Section::Icann)
+        };
+
+        // The prevailing rule is an exception rule if any matches or else
+        // the rule matching the most labels. Without a match, the implicit
+        // rule `*` applies.
+        let mut exception = None;
+        let mut longest = None;
+        for (_, rules) in self.rules.enclosers(name) {
+            if let Some(section) = allowed(rules.exception) {
+                exception = Some((rules.labels - 1, section));
+            }
+            if let Some(section) = allowed(rules.exact) {
+                longest = Some((rules.labels, section));
+            }
+            if count > rules.labels {
+                if let Some(section) = allowed(rules.wildcard) {
+                    longest = Some((rules.labels + 1, section));
+                }
+            }
+        }
+        let (labels, section) = match exception.or(longest) {
+            Some((labels, section)) => (labels, Some(section)),
+            None => (1, None),
+        };
+        Some(PublicSuffix {
+            name: suffix(name, count - labels),
+            section,
+        })
+    }
+}
+
+//--- FromStr
+
+impl FromStr for PublicSuffixList {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut res = Self::new();
+        res.parse(&mut s.as_bytes())?;
+        Ok(res)
+    }
+}
+
+//------------ PublicSuffix --------------------------------------------------
+
+/// The public suffix of a domain name.
+#[derive(Clone, Copy, Debug)]
+pub struct PublicSuffix<'a> {
+    /// The public suffix.
+    name: &'a Dname<[u8]>,
+
+    /// The section of the prevailing rule.
+    section: Option<Section>,
+}
+
+impl<'a> PublicSuffix<'a> {
+    /// Returns the public suffix.
+    pub fn name(&self) -> &'a Dname<[u8]> {
+        self.name
+    }
+
+    /// Returns the section of the rule that determined the suffix.
+    ///
+    /// Returns `None` if no rule of the list matched and the top-level
+    /// label was taken as the public suffix.
+    pub fn section(&self) -> Option<Section> {
+        self.section
+    }
+
+    /// Returns whether the suffix was determined by a rule of the list.
+    pub fn is_listed(&self) -> bool {
+        self.section.is_some()
+    }
+
+    /// Returns the registrable domain for the given name.
+    ///
+    /// The name must be the one this suffix was determined for.
+    fn registrable_domain<Octets>(
+        &self,
+        name: &'a Dname<Octets>,
+    ) -> Option<&'a Dname<[u8]>>
+    where
+        Octets: AsRef<[u8]> + ?Sized,
+    {
+        let skip = name.label_count().checked_sub(self.name.label_count())?;
+        Some(suffix(name, skip.checked_sub(1)?))
+    }
+}
+
+//------------ Rules ---------------------------------------------------------
+
+/// The rules for a name.
+#[derive(Clone, Debug)]
+struct Rules {
+    /// The number of labels of the name, not counting the root label.
+    labels: usize,
+
+    /// The section of the rule for the name itself, if present.
+    exact: Option<Section>,
+
+    /// The section of the wildcard rule below the name, if present.
+    wildcard: Option<Section>,
+
+    /// The section of the exception rule for the name, if present.
+    exception: Option<Section>,
+}
+
+impl Rules {
+    fn new(labels: usize) -> Self {
+        Rules {
+            labels,
+            exact: None,
+            wildcard: None,
+            exception: None,
+        }
+    }
+}
+
+//------------ RuleKind ------------------------------------------------------
+
+/// The kinds of rules.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum RuleKind {
+    Exact,
+    Wildcard,
+    Exception,
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Converts the name portion of a rule into a domain name.
+fn rule_name(name: &str) -> Result<Dname<Vec<u8>>, InvalidRule> {
+    if name.is_ascii() {
+        if name.contains('*') || name.contains('\\') {
+            return Err(InvalidRule);
+        }
+        Dname::from_str(name).map_err(|_| InvalidRule)
+    } else {
+        unicode_rule_name(name)
+    }
+}
+
+#[cfg(feature = "idn")]
+fn unicode_rule_name(name: &str) -> Result<Dname<Vec<u8>>, InvalidRule> {
+    Dname::from_unicode(name).map_err(|_| InvalidRule)
+}
+
+#[cfg(not(feature = "idn"))]
+fn unicode_rule_name(_name: &str) -> Result<Dname<Vec<u8>>, InvalidRule> {
+    Err(InvalidRule)
+}
+
+/// Returns the suffix of a name after skipping the given number of labels.
+fn suffix<Octets>(name: &Dname<Octets>, skip: usize) -> &Dname<[u8]>
+where
+    Octets: AsRef<[u8]> + ?Sized,
+{
+    let start = name.iter().take(skip).map(|label| label.len() + 1).sum();
+    name.slice_from(start)
+}
+
+//============ Error Types ===================================================
+
+//------------ InvalidRule ---------------------------------------------------
+
+/// A rule of the Public Suffix List was invalid.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct InvalidRule;
+
+//--- Display and Error
+
+impl std::fmt::Display for InvalidRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("invalid public suffix rule")
+    }
+}
+
+impl std::error::Error for InvalidRule {}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::string::{String, ToString};
+
+    const LIST: &str = "\
+        // ===BEGIN ICANN DOMAINS===\n\
+        com\n\
+        uk\n\
+        co.uk\n\
+        // A wildcard with exceptions.\n\
+        ck\n\
+        *.ck\n\
+        !www.ck\n\
+        *.kawasaki.jp\n\
+        !city.kawasaki.jp\n\
+        jp\n\
+        // ===END ICANN DOMAINS===\n\
+        // ===BEGIN PRIVATE DOMAINS===\n\
+        blogspot.com\n\
+        github.io    some comment\n\
+        // ===END PRIVATE DOMAINS===\n\
+        invalid..rule\n\
+    ";
+
+    fn list() -> PublicSuffixList {
+        LIST.parse().unwrap()
+    }
+
+    fn name(s: &str) -> Dname<Vec<u8>> {
+        Dname::from_str(s).unwrap()
+    }
+
+    fn suffix(s: &str) -> Option<String> {
+        let name = name(s);
+        list().public_suffix(&name).map(|s| s.name().to_string())
+    }
+
+    fn registrable(s: &str) -> Option<String> {
+        let name = name(s);
+        list().registrable_domain(&name).map(ToString::to_string)
+    }
+
+    #[test]
+    fn public_suffix() {
+        assert_eq!(suffix("www.example.com"), Some("com".into()));
+        assert_eq!(suffix("com"), Some("com".into()));
+        assert_eq!(suffix("a.b.example.co.uk"), Some("co.uk".into()));
+        assert_eq!(suffix("example.uk"), Some("uk".into()));
+        assert_eq!(suffix("a.b.ck"), Some("b.ck".into()));
+        assert_eq!(suffix("ck"), Some("ck".into()));
+        assert_eq!(suffix("www.ck"), Some("ck".into()));
+        assert_eq!(suffix("a.www.ck"), Some("ck".into()));
+        assert_eq!(suffix("a.b.kawasaki.jp"), Some("b.kawasaki.jp".into()));
+        assert_eq!(suffix("kawasaki.jp"), Some("jp".into()));
+        assert_eq!(suffix("city.kawasaki.jp"), Some("kawasaki.jp".into()));
+        assert_eq!(suffix("foo.blogspot.com"), Some("blogspot.com".into()));
+
+        let name = name("www.example.unlisted");
+        let res = list().public_suffix(&name).unwrap();
+        assert_eq!(res.name().to_string(), "unlisted");
+        assert!(!res.is_listed());
+        assert!(list().public_suffix(Dname::root_slice()).is_none());
+    }
+
+    #[test]
+    fn registrable_domain() {
+        assert_eq!(
+            registrable("www.example.com"),
+            Some("example.com".into())
+        );
+        assert_eq!(registrable("example.com"), Some("example.com".into()));
+        assert_eq!(registrable("com"), None);
+        assert_eq!(
+            registrable("a.b.example.co.uk"),
+            Some("example.co.uk".into())
+        );
+        assert_eq!(registrable("co.uk"), None);
+        assert_eq!(registrable("a.b.c.ck"), Some("b.c.ck".into()));
+        assert_eq!(registrable("b.ck"), None);
+        assert_eq!(registrable("a.www.ck"), Some("www.ck".into()));
+        assert_eq!(
+            registrable("www.city.kawasaki.jp"),
+            Some("city.kawasaki.jp".into())
+        );
+        assert_eq!(
+            registrable("Foo.GitHub.io"),
+            Some("Foo.GitHub.io".into())
+        );
+    }
+
+    #[test]
+    fn sections() {
+        let list = list();
+        let name = name("foo.blogspot.com");
+        assert_eq!(
+            list.public_suffix(&name).unwrap().section(),
+            Some(Section::Private)
+        );
+        let icann = list.icann_public_suffix(&name).unwrap();
+        assert_eq!(icann.name().to_string(), "com");
+        assert_eq!(icann.section(), Some(Section::Icann));
+        assert_eq!(
+            list.icann_registrable_domain(&name).unwrap().to_string(),
+            "blogspot.com"
+        );
+    }
+
+    #[test]
+    fn rules() {
+        let mut list = PublicSuffixList::new();
+        assert!(list.is_empty());
+        assert_eq!(list.add_rule("", Section::Icann), Err(InvalidRule));
+        assert_eq!(list.add_rule(".com", Section::Icann), Err(InvalidRule));
+        assert_eq!(
+            list.add_rule("a.*.com", Section::Icann),
+            Err(InvalidRule)
+        );
+        assert_eq!(list.add_rule("!com", Section::Icann), Err(InvalidRule));
+        assert!(list.add_rule("Co.Uk", Section::Icann).is_ok());
+        assert!(!list.is_empty());
+        assert_eq!(
+            list.registrable_domain(&name("www.example.co.uk"))
+                .unwrap()
+                .to_string(),
+            "example.co.uk"
+        );
+    }
+
+    #[cfg(feature = "idn")]
+    #[test]
+    fn unicode_rules() {
+        let mut list = PublicSuffixList::new();
+        list.add_rule("公司.cn", Section::Icann).unwrap();
+        assert_eq!(
+            list.public_suffix(&name("example.xn--55qx5d.cn"))
+                .unwrap()
+                .name()
+                .to_string(),
+            "xn--55qx5d.cn"
+        );
+    }
+}
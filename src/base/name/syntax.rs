@@ -0,0 +1,482 @@
+//! Checking domain names against syntax rules.
+//!
+//! This is a private module. Its public types are re-exported by the parent.
+
+use super::label::Label;
+use core::fmt;
+
+//------------ NameSyntax ----------------------------------------------------
+
+/// The syntax rules a domain name can be checked against.
+///
+/// On the wire, labels may contain any octets. Various uses of domain
+/// names, however, restrict them further. This type lists the rule sets
+/// supported by the [`check_syntax`] methods of [`ToDname`] and
+/// [`ToRelativeDname`].
+///
+/// [`check_syntax`]: trait.ToDname.html#method.check_syntax
+/// [`ToDname`]: trait.ToDname.html
+/// [`ToRelativeDname`]: trait.ToRelativeDname.html
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum NameSyntax {
+    /// A host name.
+    ///
+    /// All labels must be LDH labels, i.e., consist of ASCII letters,
+    /// digits, and hyphens only and neither start nor end with a hyphen.
+    /// This is the syntax of [RFC 952] as relaxed by [RFC 1123] to allow
+    /// labels starting with a digit. For absolute names, the top-level
+    /// label must not consist of digits only as required by section 2.1
+    /// of RFC 1123.
+    ///
+    /// [RFC 952]: https://tools.ietf.org/html/rfc952
+    /// [RFC 1123]: https://tools.ietf.org/html/rfc1123
+    Hostname,
+
+    /// A host name prefixed by service labels.
+    ///
+    /// The name may start with any number of underscore labels as defined
+    /// in [RFC 8552], such as `_sip._tcp`, which consist of an underscore
+    /// followed by LDH characters. The remaining labels must follow the
+    /// rules for [`Hostname`].
+    ///
+    /// [RFC 8552]: https://tools.ietf.org/html/rfc8552
+    /// [`Hostname`]: #variant.Hostname
+    Service,
+
+    /// A mailbox as defined in section 3.3 of [RFC 1035].
+    ///
+    /// The first label is the local part of the mailbox address and may
+    /// contain any octets. The remaining labels must follow the rules for
+    /// [`Hostname`].
+    ///
+    /// [RFC 1035]: https://tools.ietf.org/html/rfc1035
+    /// [`Hostname`]: #variant.Hostname
+    Mailbox,
+}
+
+//------------ SpecialUse ----------------------------------------------------
+
+/// A special-use domain name.
+///
+/// These names are reserved via the [Special-Use Domain Names] registry
+/// established by [RFC 6761] and should not be registered or, in most
+/// cases, be looked up in the global DNS.
+///
+/// A value of this type is returned by [`ToDname::special_use`].
+///
+/// [Special-Use Domain Names]: https://www.iana.org/assignments/special-use-domain-names/
+/// [RFC 6761]: https://tools.ietf.org/html/rfc6761
+/// [`ToDname::special_use`]: trait.ToDname.html#method.special_use
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum SpecialUse {
+    /// `alt.` for non-DNS name resolution, [RFC 9476].
+    ///
+    /// [RFC 9476]: https://tools.ietf.org/html/rfc9476
+    Alt,
+
+    /// `example.` and `example.com/net/org.` for documentation, [RFC 6761].
+    ///
+    /// [RFC 6761]: https://tools.ietf.org/html/rfc6761
+    Example,
+
+    /// `home.arpa.` for home networks, [RFC 8375].
+    ///
+    /// [RFC 8375]: https://tools.ietf.org/html/rfc8375
+    HomeArpa,
+
+    /// `invalid.` for names guaranteed not to exist, [RFC 6761].
+    ///
+    /// [RFC 6761]: https://tools.ietf.org/html/rfc6761
+    Invalid,
+
+    /// `local.` for Multicast DNS, [RFC 6762].
+    ///
+    /// [RFC 6762]: https://tools.ietf.org/html/rfc6762
+    Local,
+
+    /// `localhost.` for the loopback interface, [RFC 6761].
+    ///
+    /// [RFC 6761]: https://tools.ietf.org/html/rfc6761
+    Localhost,
+
+    /// `onion.` for Tor hidden services, [RFC 7686].
+    ///
+    /// [RFC 7686]: https://tools.ietf.org/html/rfc7686
+    Onion,
+
+    /// `test.` for testing, [RFC 6761].
+    ///
+    /// [RFC 6761]: https://tools.ietf.org/html/rfc6761
+    Test,
+}
+
+impl SpecialUse {
+    /// Determines the special use from the labels of an absolute name.
+    ///
+    /// The labels need to be given starting from the top, excluding the
+    /// root label.
+    pub(super) fn from_top_labels<'a>(
+        mut labels: impl Iterator<Item = &'a Label>,
+    ) -> Option<Self> {
+        let tld = labels.next()?;
+        let second = labels.next();
+        let is = |label: &Label, s: &str| {
+            label.as_slice().eq_ignore_ascii_case(s.as_bytes())
+        };
+        if is(tld, "alt") {
+            Some(SpecialUse::Alt)
+        } else if is(tld, "example") {
+            Some(SpecialUse::Example)
+        } else if is(tld, "invalid") {
+            Some(SpecialUse::Invalid)
+        } else if is(tld, "local") {
+            Some(SpecialUse::Local)
+        } else if is(tld, "localhost") {
+            Some(SpecialUse::Localhost)
+        } else if is(tld, "onion") {
+            Some(SpecialUse::Onion)
+        } else if is(tld, "test") {
+            Some(SpecialUse::Test)
+        } else if is(tld, "arpa")
+            && second.map(|l| is(l, "home")) == Some(true)
+        {
+            Some(SpecialUse::HomeArpa)
+        } else if (is(tld, "com") || is(tld, "net") || is(tld, "org"))
+            && second.map(|l| is(l, "example")) == Some(true)
+        {
+            Some(SpecialUse::Example)
+        } else {
+            None
+        }
+    }
+}
+
+//--- Display
+
+impl fmt::Display for SpecialUse {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            SpecialUse::Alt => "alt.",
+            SpecialUse::Example => "example.",
+            SpecialUse::HomeArpa => "home.arpa.",
+            SpecialUse::Invalid => "invalid.",
+            SpecialUse::Local => "local.",
+            SpecialUse::Localhost => "localhost.",
+            SpecialUse::Onion => "onion.",
+            SpecialUse::Test => "test.",
+        })
+    }
+}
+
+//------------ check_labels --------------------------------------------------
+
+/// Checks a sequence of labels against the given syntax.
+///
+/// The labels need to be given in their natural order. The root label is
+/// ignored. If `absolute` is `true`, the last label given is the top-level
+/// label.
+///
+/// Each violation is passed to `op`. If that returns `false`, checking
+/// stops.
+pub(super) fn check_labels<'a>(
+    labels: impl Iterator<Item = &'a Label>,
+    absolute: bool,
+    syntax: NameSyntax,
+    mut op: impl FnMut(Violation) -> bool,
+) {
+    let mut service = syntax == NameSyntax::Service;
+    let mut last = None;
+    for (index, label) in labels.filter(|label| !label.is_root()).enumerate()
+    {
+        last = Some((index, label));
+        let res = match syntax {
+            NameSyntax::Mailbox if index == 0 => Ok(()),
+            NameSyntax::Service if label.first() == Some(&b'_') => {
+                if service {
+                    check_service_label(label.as_slice())
+                } else {
+                    Err(ViolationKind::MisplacedServiceLabel)
+                }
+            }
+            _ => {
+                service = false;
+                check_hostname_label(label.as_slice())
+            }
+        };
+        if let Err(kind) = res {
+            if !op(Violation::new(index, kind)) {
+                return;
+            }
+        }
+    }
+    match last {
+        None => {
+            op(Violation::new(0, ViolationKind::EmptyName));
+        }
+        Some((index, label)) => {
+            if absolute && syntax == NameSyntax::Mailbox && index == 0 {
+                op(Violation::new(0, ViolationKind::MissingDomain));
+            } else if absolute && label.iter().all(u8::is_ascii_digit) {
+                op(Violation::new(index, ViolationKind::NumericTld));
+            }
+        }
+    }
+}
+
+/// Checks that a label is a valid host name label.
+pub(crate) fn check_hostname_label(
+    label: &[u8],
+) -> Result<(), ViolationKind> {
+    if let Some(&ch) = label.iter().find(|&&ch| !is_ldh(ch)) {
+        return Err(ViolationKind::IllegalOctet(ch));
+    }
+    if label.first() == Some(&b'-') {
+        return Err(ViolationKind::LeadingHyphen);
+    }
+    if label.last() == Some(&b'-') {
+        return Err(ViolationKind::TrailingHyphen);
+    }
+    Ok(())
+}
+
+/// Checks that a label is a valid underscore label.
+pub(crate) fn check_service_label(label: &[u8]) -> Result<(), ViolationKind> {
+    match label.split_first() {
+        Some((b'_', tail)) if !tail.is_empty() => {
+            match tail.iter().find(|&&ch| !is_ldh(ch)) {
+                Some(&ch) => Err(ViolationKind::IllegalOctet(ch)),
+                None => Ok(()),
+            }
+        }
+        _ => Err(ViolationKind::IllegalOctet(b'_')),
+    }
+}
+
+/// Returns whether an octet is an ASCII letter, digit, or hyphen.
+fn is_ldh(ch: u8) -> bool {
+    ch.is_ascii_alphanumeric() || ch == b'-'
+}
+
+//------------ Violation -----------------------------------------------------
+
+/// A violation of a domain name syntax.
+///
+/// This type is returned when checking a name against one of the rule sets
+/// in [`NameSyntax`]. It describes the label that violates the rules and
+/// which rule it violates.
+///
+/// [`NameSyntax`]: enum.NameSyntax.html
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Violation {
+    /// The index of the offending label.
+    label: usize,
+
+    /// The kind of violation.
+    kind: ViolationKind,
+}
+
+impl Violation {
+    /// Creates a new violation.
+    fn new(label: usize, kind: ViolationKind) -> Self {
+        Violation { label, kind }
+    }
+
+    /// Returns the index of the label violating the rules.
+    ///
+    /// Labels are counted from the start of the name, i.e., the leftmost
+    /// label has index 0.
+    pub fn label(&self) -> usize {
+        self.label
+    }
+
+    /// Returns the kind of violation.
+    pub fn kind(&self) -> ViolationKind {
+        self.kind
+    }
+}
+
+//--- Display and Error
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "label {}: {}", self.label, self.kind)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Violation {}
+
+//------------ ViolationKind -------------------------------------------------
+
+/// The kind of rule violated by a domain name.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ViolationKind {
+    /// The label contains an octet not allowed by the syntax.
+    IllegalOctet(u8),
+
+    /// The label starts with a hyphen.
+    LeadingHyphen,
+
+    /// The label ends with a hyphen.
+    TrailingHyphen,
+
+    /// The top-level label consists of digits only.
+    NumericTld,
+
+    /// An underscore label follows a regular label.
+    MisplacedServiceLabel,
+
+    /// The name doesn’t have any labels other than the root label.
+    EmptyName,
+
+    /// The mailbox has a local part but no domain.
+    MissingDomain,
+}
+
+//--- Display
+
+impl fmt::Display for ViolationKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ViolationKind::IllegalOctet(ch) => {
+                if ch.is_ascii_graphic() {
+                    write!(f, "illegal character '{}'", ch as char)
+                } else {
+                    write!(f, "illegal octet \\{:03}", ch)
+                }
+            }
+            ViolationKind::LeadingHyphen => {
+                f.write_str("label starts with a hyphen")
+            }
+            ViolationKind::TrailingHyphen => {
+                f.write_str("label ends with a hyphen")
+            }
+            ViolationKind::NumericTld => {
+                f.write_str("all-numeric top-level label")
+            }
+            ViolationKind::MisplacedServiceLabel => {
+                f.write_str("underscore label after regular label")
+            }
+            ViolationKind::EmptyName => f.write_str("empty name"),
+            ViolationKind::MissingDomain => {
+                f.write_str("mailbox without domain")
+            }
+        }
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+#[cfg(feature = "std")]
+mod test {
+    use super::*;
+    use crate::base::name::{Dname, RelativeDname, ToDname, ToRelativeDname};
+    use core::str::FromStr;
+    use std::vec::Vec;
+
+    fn dname(s: &str) -> Dname<Vec<u8>> {
+        Dname::from_str(s).unwrap()
+    }
+
+    fn check(s: &str, syntax: NameSyntax) -> Vec<(usize, ViolationKind)> {
+        dname(s)
+            .syntax_violations(syntax)
+            .into_iter()
+            .map(|v| (v.label(), v.kind()))
+            .collect()
+    }
+
+    #[test]
+    fn hostname() {
+        use self::NameSyntax::Hostname;
+        use self::ViolationKind::*;
+
+        assert!(dname("www.example.com").check_syntax(Hostname).is_ok());
+        assert!(dname("3com.example").check_syntax(Hostname).is_ok());
+        assert!(dname("xn--bcher-kva.example")
+            .check_syntax(Hostname)
+            .is_ok());
+        assert_eq!(
+            check("-a.b-.c_d.e\\ f.example", Hostname),
+            [
+                (0, LeadingHyphen),
+                (1, TrailingHyphen),
+                (2, IllegalOctet(b'_')),
+                (3, IllegalOctet(b' ')),
+            ]
+        );
+        assert_eq!(check("www.123", Hostname), [(1, NumericTld)]);
+        assert_eq!(
+            Dname::root_slice().check_syntax(Hostname),
+            Err(Violation::new(0, EmptyName))
+        );
+        assert_eq!(
+            dname("*.example").check_syntax(Hostname),
+            Err(Violation::new(0, IllegalOctet(b'*')))
+        );
+        assert_eq!(
+            RelativeDname::from_octets(b"\x03www\x03123".as_ref())
+                .unwrap()
+                .check_syntax(Hostname),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn service() {
+        use self::NameSyntax::Service;
+        use self::ViolationKind::*;
+
+        assert!(dname("_sip._tcp.example.com").check_syntax(Service).is_ok());
+        assert!(dname("_xmpp-server._tcp.example.com")
+            .check_syntax(Service)
+            .is_ok());
+        assert!(dname("example.com").check_syntax(Service).is_ok());
+        assert_eq!(
+            check("_sip.www._tcp.example", Service),
+            [(2, MisplacedServiceLabel)]
+        );
+        assert_eq!(
+            check("_.a_b.example", Service),
+            [(0, IllegalOctet(b'_')), (1, IllegalOctet(b'_'))]
+        );
+    }
+
+    #[test]
+    fn mailbox() {
+        use self::NameSyntax::Mailbox;
+        use self::ViolationKind::*;
+
+        assert!(dname("john\\.doe.example.com")
+            .check_syntax(Mailbox)
+            .is_ok());
+        assert!(dname("hostmaster.example").check_syntax(Mailbox).is_ok());
+        assert_eq!(
+            check("hostmaster.ex_ample", Mailbox),
+            [(1, IllegalOctet(b'_'))]
+        );
+        assert_eq!(check("hostmaster", Mailbox), [(0, MissingDomain)]);
+    }
+
+    #[test]
+    fn special_use() {
+        assert_eq!(dname("foo.LOCAL").special_use(), Some(SpecialUse::Local));
+        assert_eq!(
+            dname("www.example.com").special_use(),
+            Some(SpecialUse::Example)
+        );
+        assert_eq!(
+            dname("router.home.arpa").special_use(),
+            Some(SpecialUse::HomeArpa)
+        );
+        assert_eq!(dname("example").special_use(), Some(SpecialUse::Example));
+        assert_eq!(dname("x.onion").special_use(), Some(SpecialUse::Onion));
+        assert_eq!(dname("arpa").special_use(), None);
+        assert_eq!(dname("example.de").special_use(), None);
+        assert_eq!(dname("test.example.de").special_use(), None);
+        assert_eq!(Dname::root_slice().special_use(), None);
+    }
+}
@@ -8,6 +8,7 @@ use super::chain::{Chain, LongChainError};
 use super::dname::Dname;
 use super::label::Label;
 use super::relative::RelativeDname;
+use super::syntax::{check_labels, NameSyntax, SpecialUse, Violation};
 #[cfg(feature = "bytes")]
 use bytes::Bytes;
 /// This is a private module. Its public traits are re-exported by the parent.
@@ -278,6 +279,49 @@ pub trait ToDname: Compose + for<'a> ToLabelIter<'a> {
             labels.count() as u8
         }
     }
+
+    /// Checks whether the name follows the given syntax rules.
+    ///
+    /// Returns the first violation of the rules found, if any. See
+    /// [`NameSyntax`] for the available rule sets.
+    ///
+    /// [`NameSyntax`]: enum.NameSyntax.html
+    fn check_syntax(&self, syntax: NameSyntax) -> Result<(), Violation> {
+        let mut res = Ok(());
+        check_labels(self.iter_labels(), true, syntax, |violation| {
+            res = Err(violation);
+            false
+        });
+        res
+    }
+
+    /// Returns all violations of the given syntax rules.
+    ///
+    /// The violations are ordered by the labels they occur in. If the
+    /// name follows the rules, the returned vec is empty.
+    #[cfg(feature = "std")]
+    fn syntax_violations(
+        &self,
+        syntax: NameSyntax,
+    ) -> std::vec::Vec<Violation> {
+        let mut res = std::vec::Vec::new();
+        check_labels(self.iter_labels(), true, syntax, |violation| {
+            res.push(violation);
+            true
+        });
+        res
+    }
+
+    /// Returns whether the name is a special-use domain name.
+    ///
+    /// If the name is at or below one of the names registered as
+    /// special-use domain names by [RFC 6761] and later documents, returns
+    /// which one.
+    ///
+    /// [RFC 6761]: https://tools.ietf.org/html/rfc6761
+    fn special_use(&self) -> Option<SpecialUse> {
+        SpecialUse::from_top_labels(self.iter_labels().rev().skip(1))
+    }
 }
 
 impl<'a, N: ToDname + ?Sized + 'a> ToDname for &'a N {}
@@ -434,6 +478,40 @@ pub trait ToRelativeDname: Compose + for<'a> ToLabelIter<'a> {
             }
         }
     }
+
+    /// Checks whether the name follows the given syntax rules.
+    ///
+    /// Returns the first violation of the rules found, if any. See
+    /// [`NameSyntax`] for the available rule sets. Since the top-level
+    /// label of a relative name is not known, rules concerning it are not
+    /// checked.
+    ///
+    /// [`NameSyntax`]: enum.NameSyntax.html
+    fn check_syntax(&self, syntax: NameSyntax) -> Result<(), Violation> {
+        let mut res = Ok(());
+        check_labels(self.iter_labels(), false, syntax, |violation| {
+            res = Err(violation);
+            false
+        });
+        res
+    }
+
+    /// Returns all violations of the given syntax rules.
+    ///
+    /// The violations are ordered by the labels they occur in. If the
+    /// name follows the rules, the returned vec is empty.
+    #[cfg(feature = "std")]
+    fn syntax_violations(
+        &self,
+        syntax: NameSyntax,
+    ) -> std::vec::Vec<Violation> {
+        let mut res = std::vec::Vec::new();
+        check_labels(self.iter_labels(), false, syntax, |violation| {
+            res.push(violation);
+            true
+        });
+        res
+    }
 }
 
 impl<'a, N: ToRelativeDname + ?Sized + 'a> ToRelativeDname for &'a N {}
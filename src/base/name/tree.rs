@@ -0,0 +1,594 @@
+//! A tree of values keyed by domain names.
+//!
+//! This is a private module. Its public types are re-exported by the parent.
+#![cfg(feature = "std")]
+#![cfg_attr(docsrs, doc(cfg(feature = "std")))]
+
+use super::dname::Dname;
+use super::label::{Label, OwnedLabel};
+use super::traits::ToDname;
+use core::fmt;
+use core::iter::FromIterator;
+use core::ops::Bound;
+use std::collections::btree_map::{self, BTreeMap};
+use std::vec::Vec;
+
+//------------ NameTree ------------------------------------------------------
+
+/// A tree of values keyed by absolute domain names.
+///
+/// The tree mirrors the structure of the domain name space: each node
+/// represents a domain name and has the names one label longer as its
+/// children. Any node may carry a value. Nodes without a value only exist
+/// as long as there are values further down the tree.
+///
+/// This makes the tree suitable for all the lookups that need to consider
+/// the hierarchy of names. In addition to exact lookups via [`get`], the
+/// value for the longest suffix of a name present in the tree – such as
+/// the closest enclosing zone or delegation point – can be found via
+/// [`closest_encloser`].
+///
+/// Iterating over the tree via [`iter`] produces the entries in canonical
+/// order as defined in [section 6.1 of RFC 4034][RFC4034-6.1] and used
+/// by the [`CanonicalOrd`] implementations of the name types. Using the
+/// same order, [`predecessor`] and [`successor`] return the entries
+/// immediately before or after a given name whether or not it is present
+/// in the tree. This is what is needed to find the NSEC record covering a
+/// name.
+///
+/// As with comparing domain names, the case of ASCII letters is ignored
+/// when looking up names. Each entry keeps the name as it was given when
+/// the entry was first inserted.
+///
+/// [`get`]: #method.get
+/// [`closest_encloser`]: #method.closest_encloser
+/// [`iter`]: #method.iter
+/// [`predecessor`]: #method.predecessor
+/// [`successor`]: #method.successor
+/// [`CanonicalOrd`]: ../cmp/trait.CanonicalOrd.html
+/// [RFC4034-6.1]: https://tools.ietf.org/html/rfc4034#section-6.1
+#[derive(Clone)]
+pub struct NameTree<V> {
+    /// The node for the root name.
+    root: Node<V>,
+
+    /// The number of entries in the tree.
+    len: usize,
+}
+
+impl<V> NameTree<V> {
+    /// Creates a new, empty tree.
+    pub fn new() -> Self {
+        NameTree {
+            root: Node::default(),
+            len: 0,
+        }
+    }
+
+    /// Returns the number of entries in the tree.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether the tree is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Removes all entries from the tree.
+    pub fn clear(&mut self) {
+        self.root = Node::default();
+        self.len = 0;
+    }
+
+    /// Inserts a value for a name.
+    ///
+    /// If there already was a value for the name, it is replaced and
+    /// returned. The name stored with the entry is not changed in this
+    /// case.
+    pub fn insert<N: ToDname + ?Sized>(
+        &mut self,
+        name: &N,
+        value: V,
+    ) -> Option<V> {
+        let mut node = &mut self.root;
+        for label in name.iter_labels().rev().skip(1) {
+            node = node.children.entry(label.into()).or_default();
+        }
+        match node.entry {
+            Some((_, ref mut old)) => Some(core::mem::replace(old, value)),
+            None => {
+                node.entry = Some((name.to_vec(), value));
+                self.len += 1;
+                None
+            }
+        }
+    }
+
+    /// Returns a reference to the value for a name.
+    pub fn get<N: ToDname + ?Sized>(&self, name: &N) -> Option<&V> {
+        self.get_entry(name).map(|(_, value)| value)
+    }
+
+    /// Returns a reference to the entry for a name.
+    ///
+    /// The entry consists of the name as it was originally inserted and
+    /// the value.
+    pub fn get_entry<N: ToDname + ?Sized>(
+        &self,
+        name: &N,
+    ) -> Option<(&Dname<Vec<u8>>, &V)> {
+        let mut node = &self.root;
+        for label in name.iter_labels().rev().skip(1) {
+            node = node.children.get(label)?;
+        }
+        node.entry()
+    }
+
+    /// Returns a mutable reference to the value for a name.
+    pub fn get_mut<N: ToDname + ?Sized>(
+        &mut self,
+        name: &N,
+    ) -> Option<&mut V> {
+        let mut node = &mut self.root;
+        for label in name.iter_labels().rev().skip(1) {
+            node = node.children.get_mut(label)?;
+        }
+        node.entry.as_mut().map(|(_, value)| value)
+    }
+
+    /// Returns whether there is a value for the name.
+    pub fn contains<N: ToDname + ?Sized>(&self, name: &N) -> bool {
+        self.get_entry(name).is_some()
+    }
+
+    /// Removes the value for a name and returns it.
+    pub fn remove<N: ToDname + ?Sized>(&mut self, name: &N) -> Option<V> {
+        let labels: Vec<_> = name.iter_labels().rev().skip(1).collect();
+        let res = self.root.remove(&labels).map(|(_, value)| value);
+        if res.is_some() {
+            self.len -= 1;
+        }
+        res
+    }
+
+    /// Returns the entry for the longest suffix of a name.
+    ///
+    /// This is the entry for the name itself if it is present or the
+    /// entry of its nearest ancestor that is. If not even the root name has
+    /// a value, returns `None`.
+    ///
+    /// If the tree contains zone apexes, this finds the zone a name
+    /// belongs to. If it contains the domain names of an access control
+    /// list, it finds the most specific rule applying to a name.
+    pub fn closest_encloser<N: ToDname + ?Sized>(
+        &self,
+        name: &N,
+    ) -> Option<(&Dname<Vec<u8>>, &V)> {
+        let mut node = &self.root;
+        let mut res = node.entry();
+        for label in name.iter_labels().rev().skip(1) {
+            node = match node.children.get(label) {
+                Some(node) => node,
+                None => break,
+            };
+            if let Some(entry) = node.entry() {
+                res = Some(entry);
+            }
+        }
+        res
+    }
+
+    /// Returns all entries for suffixes of a name.
+    ///
+    /// The entries are returned starting with the one closest to the root.
+    /// The entry for the name itself is included if present.
+    pub fn enclosers<N: ToDname + ?Sized>(
+        &self,
+        name: &N,
+    ) -> Vec<(&Dname<Vec<u8>>, &V)> {
+        let mut node = &self.root;
+        let mut res: Vec<_> = node.entry().into_iter().collect();
+        for label in name.iter_labels().rev().skip(1) {
+            node = match node.children.get(label) {
+                Some(node) => node,
+                None => break,
+            };
+            res.extend(node.entry());
+        }
+        res
+    }
+
+    /// Returns the entry immediately before a name in canonical order.
+    ///
+    /// The name itself does not need to be present in the tree. If it is,
+    /// its own entry is not considered. If there is no entry before the
+    /// name, returns `None`. Wrapping around to the last entry, as needed
+    /// for NSEC records, is left to the caller.
+    pub fn predecessor<N: ToDname + ?Sized>(
+        &self,
+        name: &N,
+    ) -> Option<(&Dname<Vec<u8>>, &V)> {
+        // At each level of the path to the name, the entries before the
+        // name are the node itself and the subtrees of the children
+        // before the name’s label. The latter come after the former and
+        // each level comes after all levels above it, so the last
+        // candidate found is the one we want.
+        let mut node = &self.root;
+        let mut res = None;
+        for label in name.iter_labels().rev().skip(1) {
+            let before = node
+                .children
+                .range::<Label, _>((Bound::Unbounded, Bound::Excluded(label)))
+                .next_back();
+            match before {
+                Some((_, child)) => res = child.last(),
+                None => {
+                    if let Some(entry) = node.entry() {
+                        res = Some(entry)
+                    }
+                }
+            }
+            node = match node.children.get(label) {
+                Some(node) => node,
+                None => break,
+            };
+        }
+        res
+    }
+
+    /// Returns the entry immediately after a name in canonical order.
+    ///
+    /// The name itself does not need to be present in the tree. If it is,
+    /// its own entry is not considered. If there is no entry after the
+    /// name, returns `None`. Wrapping around to the first entry, as needed
+    /// for NSEC records, is left to the caller.
+    pub fn successor<N: ToDname + ?Sized>(
+        &self,
+        name: &N,
+    ) -> Option<(&Dname<Vec<u8>>, &V)> {
+        // Descendants of the name come right after it. Failing those, the
+        // subtrees of the children after the name’s label are next,
+        // starting at the deepest level.
+        let mut node = &self.root;
+        let mut res = None;
+        for label in name.iter_labels().rev().skip(1) {
+            let after = node
+                .children
+                .range::<Label, _>((Bound::Excluded(label), Bound::Unbounded))
+                .next();
+            if let Some((_, child)) = after {
+                res = child.first();
+            }
+            node = match node.children.get(label) {
+                Some(node) => node,
+                None => return res,
+            };
+        }
+        match node.children.values().next() {
+            Some(child) => child.first(),
+            None => res,
+        }
+    }
+
+    /// Returns the first entry in canonical order.
+    pub fn first(&self) -> Option<(&Dname<Vec<u8>>, &V)> {
+        self.root.first()
+    }
+
+    /// Returns the last entry in canonical order.
+    pub fn last(&self) -> Option<(&Dname<Vec<u8>>, &V)> {
+        self.root.last()
+    }
+
+    /// Returns an iterator over the entries in canonical order.
+    pub fn iter(&self) -> Iter<'_, V> {
+        Iter {
+            root: Some(&self.root),
+            stack: Vec::new(),
+            len: self.len,
+        }
+    }
+}
+
+//--- Default
+
+impl<V> Default for NameTree<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//--- FromIterator and Extend
+
+impl<N: ToDname, V> FromIterator<(N, V)> for NameTree<V> {
+    fn from_iter<I: IntoIterator<Item = (N, V)>>(iter: I) -> Self {
+        let mut res = Self::new();
+        res.extend(iter);
+        res
+    }
+}
+
+impl<N: ToDname, V> Extend<(N, V)> for NameTree<V> {
+    fn extend<I: IntoIterator<Item = (N, V)>>(&mut self, iter: I) {
+        for (name, value) in iter {
+            self.insert(&name, value);
+        }
+    }
+}
+
+//--- IntoIterator
+
+impl<'a, V> IntoIterator for &'a NameTree<V> {
+    type Item = (&'a Dname<Vec<u8>>, &'a V);
+    type IntoIter = Iter<'a, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+//--- Debug
+
+impl<V: fmt::Debug> fmt::Debug for NameTree<V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+//------------ Node ----------------------------------------------------------
+
+/// A node in the name tree.
+#[derive(Clone)]
+struct Node<V> {
+    /// The name and value if there is an entry for this node.
+    entry: Option<(Dname<Vec<u8>>, V)>,
+
+    /// The child nodes keyed by their label.
+    children: BTreeMap<OwnedLabel, Node<V>>,
+}
+
+impl<V> Node<V> {
+    /// Returns the entry of the node.
+    fn entry(&self) -> Option<(&Dname<Vec<u8>>, &V)> {
+        self.entry.as_ref().map(|(name, value)| (name, value))
+    }
+
+    /// Returns the first entry of the subtree starting at this node.
+    fn first(&self) -> Option<(&Dname<Vec<u8>>, &V)> {
+        let mut node = self;
+        loop {
+            if let Some(entry) = node.entry() {
+                return Some(entry);
+            }
+            node = node.children.values().next()?;
+        }
+    }
+
+    /// Returns the last entry of the subtree starting at this node.
+    fn last(&self) -> Option<(&Dname<Vec<u8>>, &V)> {
+        let mut node = self;
+        while let Some(child) = node.children.values().next_back() {
+            node = child;
+        }
+        node.entry()
+    }
+
+    /// Removes the entry for the node at the end of the given labels.
+    ///
+    /// Drops all nodes that end up with neither an entry nor children.
+    fn remove(&mut self, labels: &[&Label]) -> Option<(Dname<Vec<u8>>, V)> {
+        let (label, tail) = match labels.split_first() {
+            Some(some) => some,
+            None => return self.entry.take(),
+        };
+        let child = self.children.get_mut(*label)?;
+        let res = child.remove(tail);
+        if child.entry.is_none() && child.children.is_empty() {
+            self.children.remove(*label);
+        }
+        res
+    }
+}
+
+impl<V> Default for Node<V> {
+    fn default() -> Self {
+        Node {
+            entry: None,
+            children: BTreeMap::new(),
+        }
+    }
+}
+
+//------------ Iter ----------------------------------------------------------
+
+/// An iterator over the entries of a name tree in canonical order.
+pub struct Iter<'a, V> {
+    /// The root node if it hasn’t been visited yet.
+    root: Option<&'a Node<V>>,
+
+    /// The children still to be visited for each level.
+    stack: Vec<btree_map::Values<'a, OwnedLabel, Node<V>>>,
+
+    /// The number of entries remaining.
+    len: usize,
+}
+
+impl<'a, V> Iterator for Iter<'a, V> {
+    type Item = (&'a Dname<Vec<u8>>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(root) = self.root.take() {
+            self.stack.push(root.children.values());
+            if let Some(entry) = root.entry() {
+                self.len -= 1;
+                return Some(entry);
+            }
+        }
+        loop {
+            match self.stack.last_mut()?.next() {
+                Some(node) => {
+                    self.stack.push(node.children.values());
+                    if let Some(entry) = node.entry() {
+                        self.len -= 1;
+                        return Some(entry);
+                    }
+                }
+                None => {
+                    self.stack.pop();
+                }
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, V> ExactSizeIterator for Iter<'a, V> {}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::cmp::CanonicalOrd;
+    use core::str::FromStr;
+    use std::string::ToString;
+
+    fn name(s: &str) -> Dname<Vec<u8>> {
+        Dname::from_str(s).unwrap()
+    }
+
+    fn tree(names: &[&str]) -> NameTree<usize> {
+        names
+            .iter()
+            .enumerate()
+            .map(|(i, s)| (name(s), i))
+            .collect()
+    }
+
+    fn key<V>(
+        entry: Option<(&Dname<Vec<u8>>, V)>,
+    ) -> Option<std::string::String> {
+        entry.map(|(name, _)| name.to_string())
+    }
+
+    #[test]
+    fn insert_get_remove() {
+        let mut tree = tree(&["example.com", "www.example.com", "org"]);
+        assert_eq!(tree.len(), 3);
+        assert_eq!(tree.get(&name("WWW.Example.com")), Some(&1));
+        assert_eq!(tree.get(&name("com")), None);
+        assert_eq!(tree.get(&name("ftp.example.com")), None);
+        assert_eq!(tree.insert(&name("www.EXAMPLE.com"), 7), Some(1));
+        assert_eq!(
+            key(tree.get_entry(&name("www.example.com"))),
+            Some("www.example.com".into())
+        );
+        *tree.get_mut(&name("org")).unwrap() += 1;
+        assert_eq!(tree.get(&name("org")), Some(&3));
+
+        assert_eq!(tree.remove(&name("com")), None);
+        assert_eq!(tree.remove(&name("example.com")), Some(0));
+        assert_eq!(tree.len(), 2);
+        assert!(tree.contains(&name("www.example.com")));
+        assert_eq!(tree.remove(&name("www.example.com")), Some(7));
+        assert!(tree.root.children.get(&*name("com").first()).is_none());
+        assert_eq!(tree.len(), 1);
+    }
+
+    #[test]
+    fn closest_encloser() {
+        let tree = tree(&["example.com", "sub.example.com", "net"]);
+        assert_eq!(
+            key(tree.closest_encloser(&name("a.b.sub.example.com"))),
+            Some("sub.example.com".into())
+        );
+        assert_eq!(
+            key(tree.closest_encloser(&name("other.example.com"))),
+            Some("example.com".into())
+        );
+        assert_eq!(
+            key(tree.closest_encloser(&name("example.com"))),
+            Some("example.com".into())
+        );
+        assert_eq!(key(tree.closest_encloser(&name("com"))), None);
+        assert_eq!(
+            tree.enclosers(&name("www.sub.example.com"))
+                .into_iter()
+                .map(|(_, v)| *v)
+                .collect::<Vec<_>>(),
+            [0, 1]
+        );
+
+        let mut tree = tree;
+        tree.insert(&Dname::root_slice(), 9);
+        assert!(tree.closest_encloser(&name("com")).unwrap().0.is_root());
+    }
+
+    #[test]
+    fn canonical_order() {
+        // The example from section 6.1 of RFC 4034.
+        let names = [
+            "example",
+            "a.example",
+            "yljkjljk.a.example",
+            "Z.a.example",
+            "zABC.a.EXAMPLE",
+            "z.example",
+            "\\001.z.example",
+            "*.z.example",
+            "\\200.z.example",
+        ];
+        let mut shuffled = names;
+        shuffled.reverse();
+        shuffled.swap(2, 5);
+        let tree = tree(&shuffled);
+        let order: Vec<_> =
+            tree.iter().map(|(name, _)| name.to_string()).collect();
+        assert_eq!(order, names);
+        assert_eq!(tree.iter().len(), names.len());
+        let sorted = tree.iter().map(|(name, _)| name).collect::<Vec<_>>();
+        assert!(sorted.windows(2).all(|w| w[0].canonical_cmp(w[1]).is_lt()));
+        assert_eq!(key(tree.first()), Some("example".into()));
+        assert_eq!(key(tree.last()), Some("\\200.z.example".into()));
+    }
+
+    #[test]
+    fn predecessor_successor() {
+        let tree = tree(&[
+            "example",
+            "a.example",
+            "z.a.example",
+            "d.example",
+            "x.d.example",
+            "z.example",
+        ]);
+        let pred = |s| key(tree.predecessor(&name(s)));
+        let succ = |s| key(tree.successor(&name(s)));
+
+        // Names in the tree.
+        assert_eq!(pred("example"), None);
+        assert_eq!(succ("example"), Some("a.example".into()));
+        assert_eq!(pred("d.example"), Some("z.a.example".into()));
+        assert_eq!(succ("z.a.example"), Some("d.example".into()));
+        assert_eq!(succ("z.example"), None);
+
+        // Names not in the tree.
+        assert_eq!(pred("b.example"), Some("z.a.example".into()));
+        assert_eq!(succ("b.example"), Some("d.example".into()));
+        assert_eq!(pred("b.a.example"), Some("a.example".into()));
+        assert_eq!(succ("b.a.example"), Some("z.a.example".into()));
+        assert_eq!(pred("y.d.example"), Some("x.d.example".into()));
+        assert_eq!(succ("y.d.example"), Some("z.example".into()));
+        assert_eq!(pred("a.z.example"), Some("z.example".into()));
+        assert_eq!(pred("zz.example"), Some("z.example".into()));
+        assert_eq!(pred("a.org"), Some("z.example".into()));
+        assert_eq!(pred("a.com"), None);
+        assert_eq!(succ("com"), Some("example".into()));
+        assert_eq!(pred("com"), None);
+        assert_eq!(succ("zz.example"), None);
+    }
+}
@@ -90,6 +90,11 @@ impl<Octets: AsRef<[u8]>> Opt<Octets> {
         Ok(Opt { octets })
     }
 
+    /// Returns a slice of the raw record data.
+    pub fn as_slice(&self) -> &[u8] {
+        self.octets.as_ref()
+    }
+
     /// Returns an iterator over options of a given type.
     ///
     /// The returned iterator will return only options represented by type
@@ -54,6 +54,82 @@ impl ClientSubnet {
     pub fn addr(&self) -> IpAddr {
         self.addr
     }
+
+    /// Returns whether an address is covered by the source prefix.
+    ///
+    /// A server can use this to check whether a client address falls into
+    /// the subnet given by the option.
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        prefix_matches(self.addr, addr, self.source_prefix_len)
+    }
+
+    /// Returns whether an answer with this option applies to an address.
+    ///
+    /// This is to be used with the option of a response. The answer is
+    /// valid for all clients whose address matches the option’s address
+    /// in the number of bits given by the scope prefix length. Since the
+    /// address only carries as many bits as the source prefix length, a
+    /// longer scope is treated as the source prefix length.
+    pub fn scope_contains(&self, addr: IpAddr) -> bool {
+        prefix_matches(
+            self.addr,
+            addr,
+            core::cmp::min(self.source_prefix_len, self.scope_prefix_len),
+        )
+    }
+
+    /// Returns the option for a response to a query with this option.
+    ///
+    /// As required by RFC 7871, the response option repeats family,
+    /// source prefix length, and address of the query’s option and only
+    /// changes the scope prefix length.
+    pub fn response(&self, scope_prefix_len: u8) -> ClientSubnet {
+        ClientSubnet {
+            source_prefix_len: self.source_prefix_len,
+            scope_prefix_len: normalize_prefix_len(
+                self.addr,
+                scope_prefix_len,
+            ),
+            addr: self.addr,
+        }
+    }
+
+    /// Returns whether this option is a valid response to a query option.
+    ///
+    /// As required by section 7.3 of RFC 7871, the family, the source
+    /// prefix length, and the address bits covered by the source prefix
+    /// have to be the same as in the query’s option. If they aren’t, the
+    /// whole response has to be dropped.
+    pub fn is_response_to(&self, query: &ClientSubnet) -> bool {
+        self.source_prefix_len == query.source_prefix_len
+            && prefix_matches(self.addr, query.addr, self.source_prefix_len)
+    }
+
+    /// Matches the option against a network a server tailors answers for.
+    ///
+    /// If the option’s address lies within the network given by `network`
+    /// and `prefix_len`, returns the option to be included in the
+    /// response. Its scope prefix length is the length of the network
+    /// prefix. If the source prefix is shorter than the network prefix,
+    /// only the bits covered by the source prefix are compared.
+    ///
+    /// Returns `None` if the address is not within the network or if the
+    /// address families differ.
+    pub fn match_network(
+        &self,
+        network: IpAddr,
+        prefix_len: u8,
+    ) -> Option<ClientSubnet> {
+        if prefix_matches(
+            self.addr,
+            network,
+            core::cmp::min(self.source_prefix_len, prefix_len),
+        ) {
+            Some(self.response(prefix_len))
+        } else {
+            None
+        }
+    }
 }
 
 //--- Parse and Compose
@@ -217,6 +293,18 @@ fn addr_apply_mask(addr: IpAddr, len: u8) -> (IpAddr, bool) {
     }
 }
 
+/// Returns whether two addresses share the same prefix.
+///
+/// Addresses of different families never match.
+fn prefix_matches(left: IpAddr, right: IpAddr, len: u8) -> bool {
+    match (left, right) {
+        (IpAddr::V4(_), IpAddr::V4(_)) | (IpAddr::V6(_), IpAddr::V6(_)) => {
+            addr_apply_mask(left, len).0 == addr_apply_mask(right, len).0
+        }
+        _ => false,
+    }
+}
+
 fn normalize_prefix_len(addr: IpAddr, len: u8) -> u8 {
     let max = match addr {
         IpAddr::V4(_) => 32,
@@ -267,4 +355,61 @@ mod tests {
     check!(prefix_min, "192.0.2.0", 0, "0.0.0.0", true);
     check!(prefix_max, "192.0.2.0", 32, "192.0.2.0", true);
     check!(prefix_too_long, "192.0.2.0", 100, "192.0.2.0", false);
+
+    fn addr(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn contains() {
+        let opt = ClientSubnet::new(24, 0, addr("192.0.2.77"));
+        assert!(opt.contains(addr("192.0.2.1")));
+        assert!(!opt.contains(addr("192.0.3.1")));
+        assert!(!opt.contains(addr("::ffff:192.0.2.1")));
+
+        let opt = ClientSubnet::new(0, 0, addr("192.0.2.77"));
+        assert!(opt.contains(addr("198.51.100.1")));
+    }
+
+    #[test]
+    fn scope() {
+        let opt = ClientSubnet::new(24, 16, addr("192.0.2.0"));
+        assert!(opt.scope_contains(addr("192.0.3.1")));
+        assert!(!opt.scope_contains(addr("192.1.2.1")));
+
+        // A scope longer than the source is limited to the source.
+        let opt = ClientSubnet::new(24, 32, addr("192.0.2.0"));
+        assert!(opt.scope_contains(addr("192.0.2.1")));
+    }
+
+    #[test]
+    fn is_response_to() {
+        let query = ClientSubnet::new(24, 0, addr("192.0.2.77"));
+        assert!(query.response(16).is_response_to(&query));
+        assert!(ClientSubnet::new(24, 24, addr("192.0.2.1"))
+            .is_response_to(&query));
+        assert!(!ClientSubnet::new(25, 24, addr("192.0.2.0"))
+            .is_response_to(&query));
+        assert!(!ClientSubnet::new(24, 24, addr("192.0.3.0"))
+            .is_response_to(&query));
+        assert!(!ClientSubnet::new(24, 24, addr("::"))
+            .is_response_to(&ClientSubnet::new(24, 0, addr("0.0.0.0"))));
+    }
+
+    #[test]
+    fn match_network() {
+        let query = ClientSubnet::new(56, 0, addr("2001:db8:1:2::"));
+        let response = query.match_network(addr("2001:db8::"), 32).unwrap();
+        assert_eq!(response.source_prefix_len(), 56);
+        assert_eq!(response.scope_prefix_len(), 32);
+        assert_eq!(response.addr(), query.addr());
+        assert!(query.match_network(addr("2001:db9::"), 32).is_none());
+        assert!(query.match_network(addr("192.0.2.0"), 24).is_none());
+
+        // The network is more specific than the source prefix.
+        let query = ClientSubnet::new(16, 0, addr("192.0.0.0"));
+        let response = query.match_network(addr("192.0.2.0"), 24).unwrap();
+        assert_eq!(response.scope_prefix_len(), 24);
+        assert!(query.match_network(addr("192.1.2.0"), 24).is_none());
+    }
 }
@@ -0,0 +1,610 @@
+//! Composing responses.
+//!
+//! The [`MessageBuilder`] writes exactly what it is given and returns an
+//! error once the target is full. When answering a query, a server has to
+//! do a bit more than that: it needs to stay within the size the requester
+//! is able to receive, keep RRsets intact when space runs out, set the TC
+//! bit if required data had to be left out, and fill the additional section
+//! with the addresses of the name servers, mail exchangers, and services
+//! mentioned in the answer and authority sections.
+//!
+//! The [`ResponseComposer`] provided by this module takes care of all of
+//! this. You collect the RRsets for the answer and authority sections and,
+//! optionally, any further additional RRsets in the composer and then
+//! compose the response for a request via [`compose`] or, if you want the
+//! composer to add address records to the additional section, via
+//! [`compose_with`].
+//!
+//! The size of the response is limited to 512 octets if the request does
+//! not contain an OPT record. Otherwise, the UDP payload size announced by
+//! the requester is used, but never less than 512 octets and never more
+//! than the composer’s own maximum. Responses sent over a stream transport
+//! can be limited to the full 65,535 octets via
+//! [`stream`][ResponseComposer::stream] instead.
+//!
+//! The sections are filled as follows:
+//!
+//! * RRsets are only ever added as a whole. If an RRset of the answer or
+//!   authority section does not fit, it and all the following RRsets of
+//!   these sections are left out and the TC bit is set.
+//! * The additional section starts with the address records for the targets
+//!   of NS records in the authority section. If the target is below the
+//!   owner of the NS record, these records are glue that is required to
+//!   follow the referral and the TC bit is set if they do not fit (see
+//!   RFC 9471). All other glue is optional.
+//! * Then the address records for the targets of NS, MX, and SRV records in
+//!   the answer section follow and finally any RRsets explicitly added to
+//!   the additional section. All of these are optional and are silently
+//!   dropped if they do not fit.
+//! * If the request contained an OPT record, the response will contain one,
+//!   too. Space for it is reserved up front.
+//!
+//! [`MessageBuilder`]: super::message_builder::MessageBuilder
+//! [`ResponseComposer`]: struct.ResponseComposer.html
+//! [`compose`]: struct.ResponseComposer.html#method.compose
+//! [`compose_with`]: struct.ResponseComposer.html#method.compose_with
+#![cfg(feature = "std")]
+
+use super::iana::{Rcode, Rtype};
+use super::message::Message;
+use super::message_builder::{MessageBuilder, RecordSectionBuilder};
+use super::name::{Dname, ParsedDname, ToDname};
+use super::octets::{OctetsBuilder, OctetsRef, ShortBuf};
+use super::rdata::RecordData;
+use super::record::Record;
+use crate::rdata::{Mx, Ns, Srv};
+use core::cmp;
+use core::ops::DerefMut;
+use std::vec::Vec;
+
+//------------ ResponseComposer ----------------------------------------------
+
+/// A composer for responses with additional section processing.
+///
+/// A value of this type collects the RRsets for a response. It is created
+/// via [`new`][Self::new] with the response code and configured through
+/// its builder-style methods. RRsets are added via
+/// [`push_answer`][Self::push_answer],
+/// [`push_authority`][Self::push_authority], and
+/// [`push_additional`][Self::push_additional]. Finally, the response is
+/// composed via [`compose`][Self::compose] or
+/// [`compose_with`][Self::compose_with].
+///
+/// See the [module documentation][self] for the rules applied when
+/// composing the response.
+#[derive(Clone, Debug)]
+pub struct ResponseComposer<N, D> {
+    /// The response code of the response.
+    rcode: Rcode,
+
+    /// The RRsets of the answer section.
+    answer: Vec<Vec<Record<N, D>>>,
+
+    /// The RRsets of the authority section.
+    authority: Vec<Vec<Record<N, D>>>,
+
+    /// Explicitly added RRsets of the additional section.
+    additional: Vec<Vec<Record<N, D>>>,
+
+    /// The largest UDP payload size we are willing to send.
+    max_udp_size: u16,
+
+    /// Whether the response is sent over a stream transport.
+    stream: bool,
+}
+
+impl<N, D> ResponseComposer<N, D> {
+    /// The default maximum UDP payload size.
+    ///
+    /// This is the value recommended by the DNS flag day 2020 to avoid IP
+    /// fragmentation.
+    pub const DEFAULT_MAX_UDP_SIZE: u16 = 1232;
+
+    /// Creates a new, empty composer for a response with the given rcode.
+    pub fn new(rcode: Rcode) -> Self {
+        ResponseComposer {
+            rcode,
+            answer: Vec::new(),
+            authority: Vec::new(),
+            additional: Vec::new(),
+            max_udp_size: Self::DEFAULT_MAX_UDP_SIZE,
+            stream: false,
+        }
+    }
+
+    /// Sets the largest UDP payload size we are willing to send.
+    ///
+    /// This value is also announced in the OPT record of the response.
+    /// Values below 512 are treated as 512.
+    pub fn max_udp_size(mut self, size: u16) -> Self {
+        self.max_udp_size = cmp::max(size, 512);
+        self
+    }
+
+    /// Sets whether the response is sent over a stream transport.
+    ///
+    /// If this is `true`, the response may use the full 65,535 octets
+    /// regardless of the UDP payload size of the request.
+    pub fn stream(mut self, stream: bool) -> Self {
+        self.stream = stream;
+        self
+    }
+
+    /// Adds an RRset to the answer section.
+    ///
+    /// Empty RRsets are ignored.
+    pub fn push_answer(
+        &mut self,
+        rrset: impl IntoIterator<Item = Record<N, D>>,
+    ) {
+        Self::push_rrset(&mut self.answer, rrset)
+    }
+
+    /// Adds an RRset to the authority section.
+    ///
+    /// Empty RRsets are ignored.
+    pub fn push_authority(
+        &mut self,
+        rrset: impl IntoIterator<Item = Record<N, D>>,
+    ) {
+        Self::push_rrset(&mut self.authority, rrset)
+    }
+
+    /// Adds an optional RRset to the additional section.
+    ///
+    /// These RRsets are added after all the address records determined by
+    /// additional section processing and are dropped if they don’t fit.
+    /// Empty RRsets are ignored.
+    pub fn push_additional(
+        &mut self,
+        rrset: impl IntoIterator<Item = Record<N, D>>,
+    ) {
+        Self::push_rrset(&mut self.additional, rrset)
+    }
+
+    /// Adds a non-empty RRset to a list of RRsets.
+    fn push_rrset(
+        section: &mut Vec<Vec<Record<N, D>>>,
+        rrset: impl IntoIterator<Item = Record<N, D>>,
+    ) {
+        let rrset: Vec<_> = rrset.into_iter().collect();
+        if !rrset.is_empty() {
+            section.push(rrset)
+        }
+    }
+
+    /// Returns the size limit for a response to the given request.
+    pub fn size_limit<Octs>(&self, request: &Message<Octs>) -> usize
+    where
+        Octs: AsRef<[u8]>,
+        for<'a> &'a Octs: OctetsRef,
+    {
+        if self.stream {
+            return 0xFFFF;
+        }
+        match request.opt() {
+            Some(opt) => usize::from(cmp::max(
+                cmp::min(opt.udp_payload_size(), self.max_udp_size),
+                512,
+            )),
+            None => 512,
+        }
+    }
+}
+
+impl<N: ToDname, D: RecordData> ResponseComposer<N, D> {
+    /// Composes the response to a request without address lookups.
+    ///
+    /// This is identical to [`compose_with`][Self::compose_with] with a
+    /// lookup function that never finds any records.
+    pub fn compose<Octs, Target>(
+        &self,
+        request: &Message<Octs>,
+        target: Target,
+    ) -> Result<Target, ShortBuf>
+    where
+        Octs: AsRef<[u8]>,
+        for<'a> &'a Octs: OctetsRef,
+        Target: OctetsBuilder + AsRef<[u8]> + AsMut<[u8]>,
+    {
+        self.compose_with(request, target, |_, _| Vec::new())
+    }
+
+    /// Composes the response to a request.
+    ///
+    /// The response is written into `target` which should be empty and
+    /// should perform name compression, e.g., a [`StaticCompressor`].
+    ///
+    /// The `lookup` closure is called for the targets of NS, MX, and SRV
+    /// records in the answer and authority sections with the record types
+    /// A and AAAA in turn. It should return the records of the given type
+    /// for the given name that it wants added to the additional section,
+    /// or an empty vec if there are none.
+    ///
+    /// The method only fails if the header and question section don’t fit
+    /// into the target.
+    ///
+    /// [`StaticCompressor`]: super::message_builder::StaticCompressor
+    pub fn compose_with<Octs, Target, F>(
+        &self,
+        request: &Message<Octs>,
+        target: Target,
+        mut lookup: F,
+    ) -> Result<Target, ShortBuf>
+    where
+        Octs: AsRef<[u8]>,
+        for<'a> &'a Octs: OctetsRef,
+        Target: OctetsBuilder + AsRef<[u8]> + AsMut<[u8]>,
+        F: FnMut(&Dname<Vec<u8>>, Rtype) -> Vec<Record<N, D>>,
+    {
+        let request_opt = request.opt();
+
+        // Reserve space for the OPT record: root name, type, class, TTL,
+        // and an empty rdlen.
+        let limit = self.size_limit(request)
+            - if request_opt.is_some() { 11 } else { 0 };
+
+        let mut builder = MessageBuilder::from_target(target)?
+            .start_answer(request, self.rcode)?;
+        let mut truncated = false;
+        for rrset in &self.answer {
+            if !push_rrset(&mut builder, rrset, limit) {
+                truncated = true;
+                break;
+            }
+        }
+
+        let mut builder = builder.authority();
+        if !truncated {
+            for rrset in &self.authority {
+                if !push_rrset(&mut builder, rrset, limit) {
+                    truncated = true;
+                    break;
+                }
+            }
+        }
+
+        let mut builder = builder.additional();
+        if !truncated {
+            'names: for (name, kind) in
+                additional_names(&builder.as_message())
+            {
+                for rtype in [Rtype::A, Rtype::Aaaa] {
+                    let rrset = lookup(&name, rtype);
+                    if !push_rrset(&mut builder, &rrset, limit)
+                        && kind == AdditionalKind::RequiredGlue
+                    {
+                        truncated = true;
+                        break 'names;
+                    }
+                }
+            }
+        }
+        if !truncated {
+            for rrset in &self.additional {
+                push_rrset(&mut builder, rrset, limit);
+            }
+        }
+
+        if truncated {
+            builder.header_mut().set_tc(true);
+        }
+        if let Some(opt) = request_opt {
+            builder.opt(|builder| {
+                builder.set_udp_payload_size(self.max_udp_size);
+                builder.set_dnssec_ok(opt.dnssec_ok());
+                Ok(())
+            })?;
+        }
+        Ok(builder.finish())
+    }
+}
+
+//------------ AdditionalKind ------------------------------------------------
+
+/// The reason for adding address records to the additional section.
+///
+/// The variants are ordered by priority.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+enum AdditionalKind {
+    /// Glue for a name server below the delegation point.
+    RequiredGlue,
+
+    /// Glue for any other name server of a delegation.
+    OptionalGlue,
+
+    /// Addresses for the targets of records in the answer section.
+    Answer,
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Pushes an RRset to a section if it fits.
+///
+/// If any record of the RRset fails to be added or the message grows
+/// beyond `limit` octets, the whole RRset is removed again and `false` is
+/// returned.
+fn push_rrset<B, Target, N, D>(
+    builder: &mut B,
+    rrset: &[Record<N, D>],
+    limit: usize,
+) -> bool
+where
+    B: RecordSectionBuilder + DerefMut<Target = MessageBuilder<Target>>,
+    Target: OctetsBuilder + AsRef<[u8]> + AsMut<[u8]>,
+    N: ToDname,
+    D: RecordData,
+{
+    let len = builder.as_target().len();
+    let counts = builder.counts();
+    for record in rrset {
+        if builder.push(record).is_err() || builder.as_slice().len() > limit {
+            builder.roll_back(len, counts);
+            return false;
+        }
+    }
+    true
+}
+
+/// Collects the names that need address records in the additional section.
+///
+/// The names are returned in the order they should be processed in.
+fn additional_names(
+    msg: &Message<&[u8]>,
+) -> Vec<(Dname<Vec<u8>>, AdditionalKind)> {
+    let mut res = Vec::new();
+    let answer = match msg.answer() {
+        Ok(answer) => answer,
+        Err(_) => return res,
+    };
+    for record in answer.limit_to::<Ns<_>>().flatten() {
+        add_name(&mut res, record.data().nsdname(), AdditionalKind::Answer);
+    }
+    for record in answer.limit_to::<Mx<_>>().flatten() {
+        add_name(&mut res, record.data().exchange(), AdditionalKind::Answer);
+    }
+    for record in answer.limit_to::<Srv<_>>().flatten() {
+        add_name(&mut res, record.data().target(), AdditionalKind::Answer);
+    }
+    if let Ok(Some(authority)) = answer.next_section() {
+        for record in authority.limit_to::<Ns<_>>().flatten() {
+            let name = record.data().nsdname();
+            let kind = if name.ends_with(record.owner()) {
+                AdditionalKind::RequiredGlue
+            } else {
+                AdditionalKind::OptionalGlue
+            };
+            add_name(&mut res, name, kind);
+        }
+    }
+    res.sort_by_key(|item| item.1);
+    res
+}
+
+/// Adds a name to the list of names for additional section processing.
+///
+/// If the name is already present, its kind is upgraded if necessary.
+fn add_name(
+    names: &mut Vec<(Dname<Vec<u8>>, AdditionalKind)>,
+    name: &ParsedDname<&&[u8]>,
+    kind: AdditionalKind,
+) {
+    if name.is_root() {
+        return;
+    }
+    if let Some(item) = names.iter_mut().find(|item| item.0.name_eq(name)) {
+        item.1 = cmp::min(item.1, kind);
+        return;
+    }
+    if let Ok(name) = name.to_dname() {
+        names.push((name, kind))
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::Class;
+    use crate::base::message_builder::StaticCompressor;
+    use crate::rdata::{Aaaa, ZoneRecordData, A};
+    use core::str::FromStr;
+    use std::string::{String, ToString};
+    use std::{format, vec};
+
+    type Data = ZoneRecordData<Vec<u8>, Dname<Vec<u8>>>;
+
+    fn name(s: &str) -> Dname<Vec<u8>> {
+        Dname::from_str(s).unwrap()
+    }
+
+    fn record(
+        owner: &str,
+        data: impl Into<Data>,
+    ) -> Record<Dname<Vec<u8>>, Data> {
+        Record::new(name(owner), Class::In, 3600, data.into())
+    }
+
+    fn request(
+        qname: &str,
+        qtype: Rtype,
+        udp_size: Option<u16>,
+    ) -> Message<Vec<u8>> {
+        let mut msg = MessageBuilder::new_vec().question();
+        msg.push((name(qname), qtype)).unwrap();
+        let mut msg = msg.additional();
+        if let Some(size) = udp_size {
+            msg.opt(|opt| {
+                opt.set_udp_payload_size(size);
+                Ok(())
+            })
+            .unwrap();
+        }
+        msg.into_message()
+    }
+
+    fn lookup(
+        name: &Dname<Vec<u8>>,
+        rtype: Rtype,
+    ) -> Vec<Record<Dname<Vec<u8>>, Data>> {
+        let owner = name.to_string();
+        match rtype {
+            Rtype::A => vec![record(&owner, A::from_octets(192, 0, 2, 1))],
+            Rtype::Aaaa => vec![record(
+                &owner,
+                Aaaa::new("2001:db8::1".parse().unwrap()),
+            )],
+            _ => Vec::new(),
+        }
+    }
+
+    fn compose(
+        composer: &ResponseComposer<Dname<Vec<u8>>, Data>,
+        request: &Message<Vec<u8>>,
+    ) -> Message<Vec<u8>> {
+        Message::from_octets(
+            composer
+                .compose_with(
+                    request,
+                    StaticCompressor::new(Vec::new()),
+                    lookup,
+                )
+                .unwrap()
+                .into_target(),
+        )
+        .unwrap()
+    }
+
+    fn additional_owners(msg: &Message<Vec<u8>>) -> Vec<(String, Rtype)> {
+        msg.additional()
+            .unwrap()
+            .flatten()
+            .map(|record| (record.owner().to_string(), record.rtype()))
+            .collect()
+    }
+
+    #[test]
+    fn referral_glue() {
+        let mut composer = ResponseComposer::new(Rcode::NoError);
+        composer.push_authority(vec![
+            record("example.com", Ns::new(name("ns.example.net"))),
+            record("example.com", Ns::new(name("ns1.example.com"))),
+        ]);
+        composer.push_additional(vec![record(
+            "other.example.com",
+            A::from_octets(192, 0, 2, 7),
+        )]);
+        let req = request("www.example.com", Rtype::A, None);
+        let msg = compose(&composer, &req);
+        assert!(!msg.header().tc());
+        assert_eq!(msg.header().id(), req.header().id());
+        assert_eq!(msg.header_counts().nscount(), 2);
+        assert_eq!(
+            additional_owners(&msg),
+            vec![
+                ("ns1.example.com".into(), Rtype::A),
+                ("ns1.example.com".into(), Rtype::Aaaa),
+                ("ns.example.net".into(), Rtype::A),
+                ("ns.example.net".into(), Rtype::Aaaa),
+                ("other.example.com".into(), Rtype::A),
+            ]
+        );
+    }
+
+    #[test]
+    fn answer_targets() {
+        let mut composer = ResponseComposer::new(Rcode::NoError);
+        composer.push_answer(vec![
+            record("example.com", Mx::new(10, name("mail.example.com"))),
+            record("example.com", Mx::new(20, Dname::root_vec())),
+        ]);
+        let msg =
+            compose(&composer, &request("example.com", Rtype::Mx, None));
+        assert_eq!(msg.header_counts().ancount(), 2);
+        assert_eq!(
+            additional_owners(&msg),
+            vec![
+                ("mail.example.com".into(), Rtype::A),
+                ("mail.example.com".into(), Rtype::Aaaa),
+            ]
+        );
+    }
+
+    #[test]
+    fn truncate_answer() {
+        let mut composer = ResponseComposer::new(Rcode::NoError);
+        composer.push_answer(vec![record(
+            "example.com",
+            A::from_octets(192, 0, 2, 1),
+        )]);
+        composer
+            .push_answer((0..40u8).map(|i| {
+                record("example.com", A::from_octets(192, 0, 2, i))
+            }));
+        composer.push_authority(vec![record(
+            "example.com",
+            Ns::new(name("ns1.example.com")),
+        )]);
+
+        // Without EDNS, the second RRset doesn’t fit.
+        let msg = compose(&composer, &request("example.com", Rtype::A, None));
+        assert!(msg.header().tc());
+        assert_eq!(msg.header_counts().ancount(), 1);
+        assert_eq!(msg.header_counts().nscount(), 0);
+        assert_eq!(msg.header_counts().arcount(), 0);
+        assert!(msg.as_slice().len() <= 512);
+
+        // With EDNS, everything fits and we get an OPT record back.
+        let msg =
+            compose(&composer, &request("example.com", Rtype::A, Some(4096)));
+        assert!(!msg.header().tc());
+        assert_eq!(msg.header_counts().ancount(), 41);
+        assert_eq!(msg.header_counts().nscount(), 1);
+        assert_eq!(
+            msg.opt().unwrap().udp_payload_size(),
+            ResponseComposer::<Dname<Vec<u8>>, Data>::DEFAULT_MAX_UDP_SIZE
+        );
+        assert!(msg.as_slice().len() <= 1232);
+
+        // Over a stream, there is no UDP limit.
+        let composer = composer.stream(true);
+        let msg = compose(&composer, &request("example.com", Rtype::A, None));
+        assert!(!msg.header().tc());
+        assert_eq!(msg.header_counts().ancount(), 41);
+    }
+
+    #[test]
+    fn drop_optional_additionals() {
+        let mut composer = ResponseComposer::new(Rcode::NoError);
+        composer.push_answer((0..20u8).map(|i| {
+            record(
+                "example.com",
+                Mx::new(i.into(), name(&format!("mx{}.example.org", i))),
+            )
+        }));
+        let msg =
+            compose(&composer, &request("example.com", Rtype::Mx, None));
+        assert!(!msg.header().tc());
+        assert_eq!(msg.header_counts().ancount(), 20);
+        let additional = additional_owners(&msg);
+        assert!(!additional.is_empty());
+        assert!(additional.len() < 40);
+        assert!(msg.as_slice().len() <= 512);
+    }
+
+    #[test]
+    fn truncate_required_glue() {
+        let mut composer = ResponseComposer::new(Rcode::NoError);
+        composer.push_authority((0..20u8).map(|i| {
+            record(
+                "example.com",
+                Ns::new(name(&format!("ns{}.example.com", i))),
+            )
+        }));
+        let msg = compose(&composer, &request("example.com", Rtype::A, None));
+        assert!(msg.header().tc());
+        assert_eq!(msg.header_counts().nscount(), 20);
+        assert!(msg.as_slice().len() <= 512);
+    }
+}
@@ -27,10 +27,14 @@
 //!
 //! Currently, there are the following modules:
 //!
-#![cfg_attr(feature = "resolv", doc = "* [resolv]:")]
-#![cfg_attr(not(feature = "resolv"), doc = "* resolv:")]
+#![cfg_attr(feature = "resolv-blocking", doc = "* [resolv]:")]
+#![cfg_attr(not(feature = "resolv-blocking"), doc = "* resolv:")]
 //!   An asynchronous DNS resolver based on the
-//!   [Tokio](https://tokio.rs/) async runtime.
+//!   [Tokio](https://tokio.rs/) async runtime and a synchronous one using
+//!   blocking sockets.
+#![cfg_attr(feature = "sig0", doc = "* [sig0]:")]
+#![cfg_attr(not(feature = "sig0"), doc = "* sig0:")]
+//!   Experimental support for SIG(0) transaction signatures.
 #![cfg_attr(feature = "sign", doc = "* [sign]:")]
 #![cfg_attr(not(feature = "sign"), doc = "* sign:")]
 //!   Experimental support for DNSSEC signing.
@@ -58,6 +62,9 @@
 //! * `heapless`: enables the use of the `Vec` type from the
 //!   [heapless](https://github.com/japaric/heapless) crate as octet
 //!   sequences.
+//! * `idn`: Enables converting internationalized domain names from and to
+//!   their Unicode representation via the
+//!   [idna](https://github.com/servo/rust-url/tree/master/idna) crate.
 //! * `interop`: Activate interoperability tests that rely on other software
 //!   to be installed in the system (currently NSD and dig) and will fail if
 //!   it isn’t. This feature is not meaningful for users of the crate.
@@ -66,11 +73,27 @@
 //! * `resolv`: Enables the asynchronous stub resolver via the
 #![cfg_attr(feature = "resolv", doc = "  [resolv]")]
 #![cfg_attr(not(feature = "resolv"), doc = "  resolv")]
-//!   module.
-//! * `resolv-sync`: Enables the synchronous version of the stub resolver.
+//!   module. This also enables the `resolv-blocking` feature.
+//! * `resolv-blocking`: Enables the
+#![cfg_attr(feature = "resolv-blocking", doc = "  [resolv]")]
+#![cfg_attr(not(feature = "resolv-blocking"), doc = "  resolv")]
+//!   module with only the synchronous stub resolver which uses blocking
+//!   sockets from the standard library and doesn’t depend on Tokio.
+//! * `resolv-https`: Enables the DNS-over-HTTPS transport for the stub
+//!   resolver via the [hyper](https://hyper.rs/) crate. This also enables
+//!   the `resolv-tls` feature.
+//! * `resolv-sync`: Enables running the asynchronous stub resolver
+//!   synchronously on a Tokio runtime of its own.
+//! * `resolv-tls`: Enables the DNS-over-TLS transport for the stub resolver
+//!   via the [rustls](https://github.com/rustls/rustls) crate.
 //! * `ring`: Enables crypto functionality via the
 //!   [ring](https://github.com/briansmith/ring) crate.
 //! * `serde`: Enables serde serialization for a number of basic types.
+//! * `sig0`: support for SIG(0) transaction signatures. This enables the
+#![cfg_attr(feature = "sig0", doc = "  [sig0]")]
+#![cfg_attr(not(feature = "sig0"), doc = "  sig0")]
+//!   module and currently also enables the `sign`, `smallvec`, and
+//!   `validate` features.
 //! * `sign`: basic DNSSEC signing support. This will enable the
 #![cfg_attr(feature = "sign", doc = "  [sign]")]
 #![cfg_attr(not(feature = "sign"), doc = "  sign")]
@@ -83,6 +106,10 @@
 //!   sequences.
 //! * `std`: support for the Rust std library. This feature is enabled by
 //!   default.
+//! * `tkey`: support for negotiating TSIG keys via TKEY. This enables the
+#![cfg_attr(feature = "tkey", doc = "  [tsig::tkey]")]
+#![cfg_attr(not(feature = "tkey"), doc = "  tsig::tkey")]
+//!   module and currently also enables the `tsig` and `std` features.
 //! * `tsig`: support for signing and validating message exchanges via TSIG
 //!   signatures. This enables the
 #![cfg_attr(feature = "tsig", doc = "  [tsig]")]
@@ -115,6 +142,7 @@ extern crate core;
 pub mod base;
 pub mod rdata;
 pub mod resolv;
+pub mod sig0;
 pub mod sign;
 pub mod test;
 pub mod tsig;
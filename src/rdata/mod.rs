@@ -28,8 +28,10 @@
 mod macros;
 
 pub mod rfc1035;
+pub mod rfc2535;
 pub mod rfc2782;
 pub mod rfc2845;
+pub mod rfc2930;
 pub mod rfc3596;
 pub mod rfc4034;
 pub mod rfc5155;
@@ -74,6 +76,12 @@ rdata_types! {
             Null<O>,
         }
     }
+    rfc2535::{
+        zone {
+            Key<O>,
+            Sig<O, N>,
+        }
+    }
     rfc2782::{
         zone {
             Srv<N>,
@@ -84,6 +92,11 @@ rdata_types! {
             Tsig<O, N>,
         }
     }
+    rfc2930::{
+        pseudo {
+            Tkey<O, N>,
+        }
+    }
     rfc3596::{
         zone {
             Aaaa,
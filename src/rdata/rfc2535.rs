@@ -0,0 +1,803 @@
+//! Record data from [RFC 2535]: KEY and SIG records.
+//!
+//! This RFC defined the original version of DNSSEC which has since been
+//! replaced by the version defined in [RFC 4034]. The KEY record type
+//! defined here is, however, still in use to store public keys for
+//! transaction security as specified in [RFC 2930] and [RFC 2931]. Its
+//! use is restricted by [RFC 3445]. The SIG record type is still used for
+//! the transaction signatures defined in [RFC 2931].
+//!
+//! [RFC 2535]: https://tools.ietf.org/html/rfc2535
+//! [RFC 2930]: https://tools.ietf.org/html/rfc2930
+//! [RFC 2931]: https://tools.ietf.org/html/rfc2931
+//! [RFC 3445]: https://tools.ietf.org/html/rfc3445
+//! [RFC 4034]: https://tools.ietf.org/html/rfc4034
+
+use crate::base::cmp::CanonicalOrd;
+use crate::base::iana::{Rtype, SecAlg};
+use crate::base::name::{Dname, ParsedDname, PushError, ToDname};
+use crate::base::octets::{
+    Compose, EmptyBuilder, FromBuilder, OctetsBuilder, OctetsFrom,
+    OctetsInto, OctetsRef, Parse, ParseError, Parser, ShortBuf,
+};
+use crate::base::rdata::RtypeRecordData;
+use crate::base::scan::{Scan, Scanner};
+use crate::base::serial::Serial;
+use crate::utils::base64;
+use core::cmp::Ordering;
+use core::{fmt, hash};
+
+//------------ Key -----------------------------------------------------------
+
+/// KEY record data.
+///
+/// The KEY record has the same wire format as the DNSKEY record: a set of
+/// flags, a protocol, the algorithm of the key, and the public key itself.
+/// Its interpretation depends on the protocol and algorithm. For instance,
+/// Diffie-Hellman keys used by TKEY are stored in the format defined in
+/// [RFC 2539].
+///
+/// [RFC 2539]: https://tools.ietf.org/html/rfc2539
+#[derive(Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(
+        serialize = "
+            Octets: crate::base::octets::SerializeOctets + AsRef<[u8]>
+        ",
+        deserialize = "
+            Octets: FromBuilder + crate::base::octets::DeserializeOctets<'de>,
+            <Octets as FromBuilder>::Builder:
+                OctetsBuilder<Octets = Octets> + EmptyBuilder,
+        ",
+    ))
+)]
+pub struct Key<Octets> {
+    flags: u16,
+    protocol: u8,
+    algorithm: SecAlg,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::utils::base64::serde")
+    )]
+    public_key: Octets,
+}
+
+impl<Octets> Key<Octets> {
+    /// The protocol value for keys used with DNSSEC.
+    ///
+    /// Since [RFC 3445], this is the only allowed value.
+    ///
+    /// [RFC 3445]: https://tools.ietf.org/html/rfc3445
+    pub const DNSSEC_PROTOCOL: u8 = 3;
+
+    pub fn new(
+        flags: u16,
+        protocol: u8,
+        algorithm: SecAlg,
+        public_key: Octets,
+    ) -> Self {
+        Key {
+            flags,
+            protocol,
+            algorithm,
+            public_key,
+        }
+    }
+
+    pub fn flags(&self) -> u16 {
+        self.flags
+    }
+
+    pub fn protocol(&self) -> u8 {
+        self.protocol
+    }
+
+    pub fn algorithm(&self) -> SecAlg {
+        self.algorithm
+    }
+
+    pub fn public_key(&self) -> &Octets {
+        &self.public_key
+    }
+
+    pub fn into_public_key(self) -> Octets {
+        self.public_key
+    }
+
+    /// Returns whether the record says that there is no key.
+    ///
+    /// This is the case if both the A/C bits of the flags are set. See
+    /// [RFC 2535, Section 3.1.2].
+    ///
+    /// [RFC 2535, Section 3.1.2]: https://tools.ietf.org/html/rfc2535#section-3.1.2
+    pub fn is_no_key(&self) -> bool {
+        self.flags & 0xC000 == 0xC000
+    }
+
+    /// Returns the key tag for this KEY data.
+    ///
+    /// The key tag is calculated in the same way as for DNSKEY records.
+    #[allow(clippy::while_let_loop)] // I find this clearer with a loop.
+    pub fn key_tag(&self) -> u16
+    where
+        Octets: AsRef<[u8]>,
+    {
+        if self.algorithm == SecAlg::RsaMd5 {
+            let key = self.public_key.as_ref();
+            let len = key.len();
+            if len > 2 {
+                u16::from_be_bytes([key[len - 3], key[len - 2]])
+            } else {
+                0
+            }
+        } else {
+            let mut res = u32::from(self.flags);
+            res += u32::from(self.protocol) << 8;
+            res += u32::from(self.algorithm.to_int());
+            let mut iter = self.public_key.as_ref().iter();
+            loop {
+                match iter.next() {
+                    Some(&x) => res += u32::from(x) << 8,
+                    None => break,
+                }
+                match iter.next() {
+                    Some(&x) => res += u32::from(x),
+                    None => break,
+                }
+            }
+            res += (res >> 16) & 0xFFFF;
+            (res & 0xFFFF) as u16
+        }
+    }
+}
+
+impl<SrcOctets> Key<SrcOctets> {
+    pub fn flatten_into<Octets>(self) -> Result<Key<Octets>, PushError>
+    where
+        Octets: OctetsFrom<SrcOctets>,
+    {
+        let Self {
+            flags,
+            protocol,
+            algorithm,
+            public_key,
+        } = self;
+
+        Ok(Key::new(
+            flags,
+            protocol,
+            algorithm,
+            public_key.octets_into()?,
+        ))
+    }
+}
+
+//--- OctetsFrom
+
+impl<Octets, SrcOctets> OctetsFrom<Key<SrcOctets>> for Key<Octets>
+where
+    Octets: OctetsFrom<SrcOctets>,
+{
+    fn octets_from(source: Key<SrcOctets>) -> Result<Self, ShortBuf> {
+        Ok(Key::new(
+            source.flags,
+            source.protocol,
+            source.algorithm,
+            Octets::octets_from(source.public_key)?,
+        ))
+    }
+}
+
+//--- PartialEq and Eq
+
+impl<Octets, Other> PartialEq<Key<Other>> for Key<Octets>
+where
+    Octets: AsRef<[u8]>,
+    Other: AsRef<[u8]>,
+{
+    fn eq(&self, other: &Key<Other>) -> bool {
+        self.flags == other.flags
+            && self.protocol == other.protocol
+            && self.algorithm == other.algorithm
+            && self.public_key.as_ref() == other.public_key.as_ref()
+    }
+}
+
+impl<Octets: AsRef<[u8]>> Eq for Key<Octets> {}
+
+//--- PartialOrd, CanonicalOrd, and Ord
+
+impl<Octets, Other> PartialOrd<Key<Other>> for Key<Octets>
+where
+    Octets: AsRef<[u8]>,
+    Other: AsRef<[u8]>,
+{
+    fn partial_cmp(&self, other: &Key<Other>) -> Option<Ordering> {
+        Some(self.canonical_cmp(other))
+    }
+}
+
+impl<Octets, Other> CanonicalOrd<Key<Other>> for Key<Octets>
+where
+    Octets: AsRef<[u8]>,
+    Other: AsRef<[u8]>,
+{
+    fn canonical_cmp(&self, other: &Key<Other>) -> Ordering {
+        match self.flags.cmp(&other.flags) {
+            Ordering::Equal => {}
+            other => return other,
+        }
+        match self.protocol.cmp(&other.protocol) {
+            Ordering::Equal => {}
+            other => return other,
+        }
+        match self.algorithm.cmp(&other.algorithm) {
+            Ordering::Equal => {}
+            other => return other,
+        }
+        self.public_key.as_ref().cmp(other.public_key.as_ref())
+    }
+}
+
+impl<Octets: AsRef<[u8]>> Ord for Key<Octets> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.canonical_cmp(other)
+    }
+}
+
+//--- Hash
+
+impl<Octets: AsRef<[u8]>> hash::Hash for Key<Octets> {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        self.flags.hash(state);
+        self.protocol.hash(state);
+        self.algorithm.hash(state);
+        self.public_key.as_ref().hash(state);
+    }
+}
+
+//--- Parse and Compose
+
+impl<Ref: OctetsRef> Parse<Ref> for Key<Ref::Range> {
+    fn parse(parser: &mut Parser<Ref>) -> Result<Self, ParseError> {
+        let len = match parser.remaining().checked_sub(4) {
+            Some(len) => len,
+            None => return Err(ParseError::ShortInput),
+        };
+        Ok(Self::new(
+            u16::parse(parser)?,
+            u8::parse(parser)?,
+            SecAlg::parse(parser)?,
+            parser.parse_octets(len)?,
+        ))
+    }
+
+    fn skip(parser: &mut Parser<Ref>) -> Result<(), ParseError> {
+        if parser.remaining() < 4 {
+            return Err(ParseError::ShortInput);
+        }
+        parser.advance_to_end();
+        Ok(())
+    }
+}
+
+impl<Octets: AsRef<[u8]>> Compose for Key<Octets> {
+    fn compose<T: OctetsBuilder + AsMut<[u8]>>(
+        &self,
+        target: &mut T,
+    ) -> Result<(), ShortBuf> {
+        target.append_all(|buf| {
+            self.flags.compose(buf)?;
+            self.protocol.compose(buf)?;
+            self.algorithm.compose(buf)?;
+            buf.append_slice(self.public_key.as_ref())
+        })
+    }
+}
+
+//--- Scan and Display
+
+impl<Octets, S: Scanner<Octets = Octets>> Scan<S> for Key<Octets> {
+    fn scan(scanner: &mut S) -> Result<Self, S::Error> {
+        Ok(Self::new(
+            u16::scan(scanner)?,
+            u8::scan(scanner)?,
+            SecAlg::scan(scanner)?,
+            scanner.convert_entry(base64::SymbolConverter::new())?,
+        ))
+    }
+}
+
+impl<Octets: AsRef<[u8]>> fmt::Display for Key<Octets> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {} ", self.flags, self.protocol, self.algorithm)?;
+        base64::display(&self.public_key, f)
+    }
+}
+
+//--- Debug
+
+impl<Octets: AsRef<[u8]>> fmt::Debug for Key<Octets> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Key")
+            .field("flags", &self.flags)
+            .field("protocol", &self.protocol)
+            .field("algorithm", &self.algorithm)
+            .field("public_key", &self.public_key.as_ref())
+            .finish()
+    }
+}
+
+//--- RtypeRecordData
+
+impl<Octets> RtypeRecordData for Key<Octets> {
+    const RTYPE: Rtype = Rtype::Key;
+}
+
+//------------ Sig -----------------------------------------------------------
+
+/// SIG record data.
+///
+/// The SIG record has the same wire format as the RRSIG record. Since
+/// [RFC 3755], its only remaining use is for transaction signatures, the
+/// so-called SIG(0) defined in [RFC 2931]. For these, the type covered,
+/// labels, and original TTL fields are all zero and the signature covers
+/// the whole message rather than a record set.
+///
+/// [RFC 2931]: https://tools.ietf.org/html/rfc2931
+/// [RFC 3755]: https://tools.ietf.org/html/rfc3755
+#[derive(Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(
+        serialize = "
+            Octets: crate::base::octets::SerializeOctets + AsRef<[u8]>,
+            Name: serde::Serialize,
+        ",
+        deserialize = "
+            Octets: FromBuilder + crate::base::octets::DeserializeOctets<'de>,
+            <Octets as FromBuilder>::Builder:
+                OctetsBuilder<Octets = Octets> + EmptyBuilder,
+            Name: serde::Deserialize<'de>,
+        ",
+    ))
+)]
+pub struct Sig<Octets, Name> {
+    type_covered: Rtype,
+    algorithm: SecAlg,
+    labels: u8,
+    original_ttl: u32,
+    expiration: Serial,
+    inception: Serial,
+    key_tag: u16,
+    signer_name: Name,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::utils::base64::serde")
+    )]
+    signature: Octets,
+}
+
+impl<Octets, Name> Sig<Octets, Name> {
+    #[allow(clippy::too_many_arguments)] // XXX Consider changing.
+    pub fn new(
+        type_covered: Rtype,
+        algorithm: SecAlg,
+        labels: u8,
+        original_ttl: u32,
+        expiration: Serial,
+        inception: Serial,
+        key_tag: u16,
+        signer_name: Name,
+        signature: Octets,
+    ) -> Self {
+        Sig {
+            type_covered,
+            algorithm,
+            labels,
+            original_ttl,
+            expiration,
+            inception,
+            key_tag,
+            signer_name,
+            signature,
+        }
+    }
+
+    pub fn type_covered(&self) -> Rtype {
+        self.type_covered
+    }
+
+    pub fn algorithm(&self) -> SecAlg {
+        self.algorithm
+    }
+
+    pub fn labels(&self) -> u8 {
+        self.labels
+    }
+
+    pub fn original_ttl(&self) -> u32 {
+        self.original_ttl
+    }
+
+    pub fn expiration(&self) -> Serial {
+        self.expiration
+    }
+
+    pub fn inception(&self) -> Serial {
+        self.inception
+    }
+
+    pub fn key_tag(&self) -> u16 {
+        self.key_tag
+    }
+
+    pub fn signer_name(&self) -> &Name {
+        &self.signer_name
+    }
+
+    pub fn signature(&self) -> &Octets {
+        &self.signature
+    }
+
+    pub fn set_signature(&mut self, signature: Octets) {
+        self.signature = signature
+    }
+}
+
+impl<SrcOctets, Ref> Sig<SrcOctets, ParsedDname<Ref>>
+where
+    SrcOctets: AsRef<[u8]>,
+    Ref: OctetsRef,
+{
+    pub fn flatten_into<Octets>(
+        self,
+    ) -> Result<Sig<Octets, Dname<Octets>>, PushError>
+    where
+        Octets: OctetsFrom<SrcOctets> + FromBuilder,
+        <Octets as FromBuilder>::Builder: EmptyBuilder,
+    {
+        let Self {
+            type_covered,
+            algorithm,
+            labels,
+            original_ttl,
+            expiration,
+            inception,
+            key_tag,
+            signer_name,
+            signature,
+        } = self;
+
+        Ok(Sig::new(
+            type_covered,
+            algorithm,
+            labels,
+            original_ttl,
+            expiration,
+            inception,
+            key_tag,
+            signer_name.to_dname()?,
+            Octets::octets_from(signature)?,
+        ))
+    }
+}
+
+//--- OctetsFrom
+
+impl<Octets, SrcOctets, Name, SrcName> OctetsFrom<Sig<SrcOctets, SrcName>>
+    for Sig<Octets, Name>
+where
+    Octets: OctetsFrom<SrcOctets>,
+    Name: OctetsFrom<SrcName>,
+{
+    fn octets_from(
+        source: Sig<SrcOctets, SrcName>,
+    ) -> Result<Self, ShortBuf> {
+        Ok(Sig::new(
+            source.type_covered,
+            source.algorithm,
+            source.labels,
+            source.original_ttl,
+            source.expiration,
+            source.inception,
+            source.key_tag,
+            Name::octets_from(source.signer_name)?,
+            Octets::octets_from(source.signature)?,
+        ))
+    }
+}
+
+//--- PartialEq and Eq
+
+impl<N, NN, O, OO> PartialEq<Sig<OO, NN>> for Sig<O, N>
+where
+    N: ToDname,
+    NN: ToDname,
+    O: AsRef<[u8]>,
+    OO: AsRef<[u8]>,
+{
+    fn eq(&self, other: &Sig<OO, NN>) -> bool {
+        self.type_covered == other.type_covered
+            && self.algorithm == other.algorithm
+            && self.labels == other.labels
+            && self.original_ttl == other.original_ttl
+            && self.expiration.into_int() == other.expiration.into_int()
+            && self.inception.into_int() == other.inception.into_int()
+            && self.key_tag == other.key_tag
+            && self.signer_name.name_eq(&other.signer_name)
+            && self.signature.as_ref() == other.signature.as_ref()
+    }
+}
+
+impl<Octets, Name> Eq for Sig<Octets, Name>
+where
+    Octets: AsRef<[u8]>,
+    Name: ToDname,
+{
+}
+
+//--- PartialOrd, CanonicalOrd, and Ord
+
+impl<N, NN, O, OO> PartialOrd<Sig<OO, NN>> for Sig<O, N>
+where
+    N: ToDname,
+    NN: ToDname,
+    O: AsRef<[u8]>,
+    OO: AsRef<[u8]>,
+{
+    fn partial_cmp(&self, other: &Sig<OO, NN>) -> Option<Ordering> {
+        match self.type_covered.partial_cmp(&other.type_covered) {
+            Some(Ordering::Equal) => {}
+            other => return other,
+        }
+        match self.algorithm.partial_cmp(&other.algorithm) {
+            Some(Ordering::Equal) => {}
+            other => return other,
+        }
+        match self.labels.partial_cmp(&other.labels) {
+            Some(Ordering::Equal) => {}
+            other => return other,
+        }
+        match self.original_ttl.partial_cmp(&other.original_ttl) {
+            Some(Ordering::Equal) => {}
+            other => return other,
+        }
+        match self.expiration.partial_cmp(&other.expiration) {
+            Some(Ordering::Equal) => {}
+            other => return other,
+        }
+        match self.inception.partial_cmp(&other.inception) {
+            Some(Ordering::Equal) => {}
+            other => return other,
+        }
+        match self.key_tag.partial_cmp(&other.key_tag) {
+            Some(Ordering::Equal) => {}
+            other => return other,
+        }
+        match self.signer_name.name_cmp(&other.signer_name) {
+            Ordering::Equal => {}
+            other => return Some(other),
+        }
+        self.signature
+            .as_ref()
+            .partial_cmp(other.signature.as_ref())
+    }
+}
+
+impl<N, NN, O, OO> CanonicalOrd<Sig<OO, NN>> for Sig<O, N>
+where
+    N: ToDname,
+    NN: ToDname,
+    O: AsRef<[u8]>,
+    OO: AsRef<[u8]>,
+{
+    fn canonical_cmp(&self, other: &Sig<OO, NN>) -> Ordering {
+        match self.type_covered.cmp(&other.type_covered) {
+            Ordering::Equal => {}
+            other => return other,
+        }
+        match self.algorithm.cmp(&other.algorithm) {
+            Ordering::Equal => {}
+            other => return other,
+        }
+        match self.labels.cmp(&other.labels) {
+            Ordering::Equal => {}
+            other => return other,
+        }
+        match self.original_ttl.cmp(&other.original_ttl) {
+            Ordering::Equal => {}
+            other => return other,
+        }
+        match self.expiration.canonical_cmp(&other.expiration) {
+            Ordering::Equal => {}
+            other => return other,
+        }
+        match self.inception.canonical_cmp(&other.inception) {
+            Ordering::Equal => {}
+            other => return other,
+        }
+        match self.key_tag.cmp(&other.key_tag) {
+            Ordering::Equal => {}
+            other => return other,
+        }
+        match self.signer_name.lowercase_composed_cmp(&other.signer_name) {
+            Ordering::Equal => {}
+            other => return other,
+        }
+        self.signature.as_ref().cmp(other.signature.as_ref())
+    }
+}
+
+impl<O: AsRef<[u8]>, N: ToDname> Ord for Sig<O, N> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.canonical_cmp(other)
+    }
+}
+
+//--- Hash
+
+impl<O: AsRef<[u8]>, N: hash::Hash> hash::Hash for Sig<O, N> {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        self.type_covered.hash(state);
+        self.algorithm.hash(state);
+        self.labels.hash(state);
+        self.original_ttl.hash(state);
+        self.expiration.into_int().hash(state);
+        self.inception.into_int().hash(state);
+        self.key_tag.hash(state);
+        self.signer_name.hash(state);
+        self.signature.as_ref().hash(state);
+    }
+}
+
+//--- Parse and Compose
+
+impl<Ref: OctetsRef> Parse<Ref> for Sig<Ref::Range, ParsedDname<Ref>> {
+    fn parse(parser: &mut Parser<Ref>) -> Result<Self, ParseError> {
+        let type_covered = Rtype::parse(parser)?;
+        let algorithm = SecAlg::parse(parser)?;
+        let labels = u8::parse(parser)?;
+        let original_ttl = u32::parse(parser)?;
+        let expiration = Serial::parse(parser)?;
+        let inception = Serial::parse(parser)?;
+        let key_tag = u16::parse(parser)?;
+        let signer_name = ParsedDname::parse(parser)?;
+        let len = parser.remaining();
+        let signature = parser.parse_octets(len)?;
+        Ok(Self::new(
+            type_covered,
+            algorithm,
+            labels,
+            original_ttl,
+            expiration,
+            inception,
+            key_tag,
+            signer_name,
+            signature,
+        ))
+    }
+
+    fn skip(parser: &mut Parser<Ref>) -> Result<(), ParseError> {
+        Rtype::skip(parser)?;
+        SecAlg::skip(parser)?;
+        u8::skip(parser)?;
+        u32::skip(parser)?;
+        Serial::skip(parser)?;
+        Serial::skip(parser)?;
+        u16::skip(parser)?;
+        ParsedDname::skip(parser)?;
+        parser.advance_to_end();
+        Ok(())
+    }
+}
+
+impl<Octets: AsRef<[u8]>, Name: Compose> Compose for Sig<Octets, Name> {
+    fn compose<T: OctetsBuilder + AsMut<[u8]>>(
+        &self,
+        target: &mut T,
+    ) -> Result<(), ShortBuf> {
+        target.append_all(|buf| {
+            self.type_covered.compose(buf)?;
+            self.algorithm.compose(buf)?;
+            self.labels.compose(buf)?;
+            self.original_ttl.compose(buf)?;
+            self.expiration.compose(buf)?;
+            self.inception.compose(buf)?;
+            self.key_tag.compose(buf)?;
+            self.signer_name.compose(buf)?;
+            buf.append_slice(self.signature.as_ref())
+        })
+    }
+
+    fn compose_canonical<T: OctetsBuilder + AsMut<[u8]>>(
+        &self,
+        target: &mut T,
+    ) -> Result<(), ShortBuf> {
+        target.append_all(|buf| {
+            self.type_covered.compose(buf)?;
+            self.algorithm.compose(buf)?;
+            self.labels.compose(buf)?;
+            self.original_ttl.compose(buf)?;
+            self.expiration.compose(buf)?;
+            self.inception.compose(buf)?;
+            self.key_tag.compose(buf)?;
+            self.signer_name.compose_canonical(buf)?;
+            buf.append_slice(self.signature.as_ref())
+        })
+    }
+}
+
+//--- Scan and Display
+
+impl<Octets, Name, S> Scan<S> for Sig<Octets, Name>
+where
+    S: Scanner<Octets = Octets, Dname = Name>,
+{
+    fn scan(scanner: &mut S) -> Result<Self, S::Error> {
+        Ok(Self::new(
+            Rtype::scan(scanner)?,
+            SecAlg::scan(scanner)?,
+            u8::scan(scanner)?,
+            u32::scan(scanner)?,
+            Serial::scan_rrsig(scanner)?,
+            Serial::scan_rrsig(scanner)?,
+            u16::scan(scanner)?,
+            scanner.scan_dname()?,
+            scanner.convert_entry(base64::SymbolConverter::new())?,
+        ))
+    }
+}
+
+impl<Octets, Name> fmt::Display for Sig<Octets, Name>
+where
+    Octets: AsRef<[u8]>,
+    Name: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} {} {} {} {}. ",
+            self.type_covered,
+            self.algorithm,
+            self.labels,
+            self.original_ttl,
+            self.expiration,
+            self.inception,
+            self.key_tag,
+            self.signer_name
+        )?;
+        base64::display(&self.signature, f)
+    }
+}
+
+//--- Debug
+
+impl<Octets, Name> fmt::Debug for Sig<Octets, Name>
+where
+    Octets: AsRef<[u8]>,
+    Name: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Sig")
+            .field("type_covered", &self.type_covered)
+            .field("algorithm", &self.algorithm)
+            .field("labels", &self.labels)
+            .field("original_ttl", &self.original_ttl)
+            .field("expiration", &self.expiration)
+            .field("inception", &self.inception)
+            .field("key_tag", &self.key_tag)
+            .field("signer_name", &self.signer_name)
+            .field("signature", &self.signature.as_ref())
+            .finish()
+    }
+}
+
+//--- RtypeRecordData
+
+impl<Octets, Name> RtypeRecordData for Sig<Octets, Name> {
+    const RTYPE: Rtype = Rtype::Sig;
+}
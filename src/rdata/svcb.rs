@@ -689,6 +689,11 @@ pub mod param {
         pub fn new(port: u16) -> Self {
             Self(port)
         }
+
+        /// Returns the port number.
+        pub fn port(&self) -> u16 {
+            self.0
+        }
     }
     impl<Ref: OctetsRef> Parse<Ref> for Port {
         fn parse(parser: &mut Parser<Ref>) -> Result<Self, ParseError> {
@@ -0,0 +1,1254 @@
+//! A caching resolver.
+//!
+//! This module provides the [`CachingResolver`], a resolver that wraps
+//! another resolver – the upstream – and keeps the answers it receives
+//! from it around for as long as their TTL allows. Questions that can be
+//! answered from the cache are answered without asking the upstream.
+//!
+//! The cache stores resource record sets keyed by their owner name, class,
+//! and record type. Answers that follow a CNAME chain are broken up into
+//! the individual record sets, so that later questions for any name along
+//! the chain can use them. Negative answers, i.e., NXDOMAIN and NODATA
+//! responses, are cached as described in [RFC 2308] using the SOA record
+//! from the authority section of the response.
+//!
+//! The cache is limited to a configurable amount of memory. Once it is
+//! full, the least recently used entries are evicted. Optionally, expired
+//! entries can be used to answer questions if the upstream fails to
+//! provide an answer as described in [RFC 8767].
+//!
+//! The cache can be configured via [`CacheConf`].
+//!
+//! [RFC 2308]: https://tools.ietf.org/html/rfc2308
+//! [RFC 8767]: https://tools.ietf.org/html/rfc8767
+//! [`CacheConf`]: struct.CacheConf.html
+//! [`CachingResolver`]: struct.CachingResolver.html
+
+use super::resolver::{Resolver, SearchNames};
+use super::stub::Answer;
+use crate::base::iana::{Class, Rcode, Rtype};
+use crate::base::message::{Message, RecordSection};
+use crate::base::message_builder::MessageBuilder;
+use crate::base::name::{Dname, ParsedDname, ToDname};
+use crate::base::octets::ShortBuf;
+use crate::base::question::Question;
+use crate::base::record::{AsRecord, Record};
+use crate::rdata::AllRecordData;
+use bytes::Bytes;
+use std::boxed::Box;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use std::vec::Vec;
+use std::{cmp, io};
+
+/// The maximum number of CNAMEs we follow.
+const MAX_CHAIN_LEN: usize = 8;
+
+/// The number of octets we add to the size of each entry.
+///
+/// This is a rough estimate of the overhead of the data structures.
+const ENTRY_OVERHEAD: usize = 128;
+
+/// The domain names we store.
+type StoredName = Dname<Bytes>;
+
+/// The record data we store.
+type StoredData = AllRecordData<Bytes, StoredName>;
+
+/// The records we store.
+type StoredRecord = Record<StoredName, StoredData>;
+
+//------------ CacheConf -----------------------------------------------------
+
+/// Configuration of a resolver cache.
+#[derive(Clone, Debug)]
+pub struct CacheConf {
+    /// The minimum TTL in seconds for cached positive answers.
+    ///
+    /// Record sets with a smaller TTL will be kept for this long.
+    pub min_ttl: u32,
+
+    /// The maximum TTL in seconds for cached positive answers.
+    ///
+    /// Record sets with a larger TTL will only be kept for this long.
+    pub max_ttl: u32,
+
+    /// The maximum TTL in seconds for cached negative answers.
+    ///
+    /// [RFC 2308] recommends a value between one and three hours.
+    ///
+    /// [RFC 2308]: https://tools.ietf.org/html/rfc2308
+    pub max_negative_ttl: u32,
+
+    /// The maximum size of the cache in octets.
+    ///
+    /// The size is estimated from the wire format size of the cached
+    /// records plus some overhead per entry.
+    pub max_size: usize,
+
+    /// Whether to serve expired entries if the upstream fails.
+    pub serve_stale: bool,
+
+    /// How long in seconds expired entries may be served.
+    pub max_stale: u32,
+
+    /// The TTL in seconds to use for records in a stale answer.
+    ///
+    /// [RFC 8767] recommends 30 seconds.
+    ///
+    /// [RFC 8767]: https://tools.ietf.org/html/rfc8767
+    pub stale_ttl: u32,
+}
+
+impl Default for CacheConf {
+    fn default() -> Self {
+        CacheConf {
+            min_ttl: 0,
+            max_ttl: 604_800,
+            max_negative_ttl: 10_800,
+            max_size: 4 * 1024 * 1024,
+            serve_stale: false,
+            max_stale: 86_400,
+            stale_ttl: 30,
+        }
+    }
+}
+
+//------------ CachingResolver -----------------------------------------------
+
+/// A resolver that caches the answers of an upstream resolver.
+///
+/// The upstream resolver can be any type that implements [`Resolver`] for
+/// references and uses [`Bytes`] for its messages, such as the
+/// [`StubResolver`]. The caching resolver also provides the search list of
+/// the upstream resolver, so all lookups can be used with it.
+///
+/// Answers from the cache are synthesized from the cached records. Their
+/// TTLs are decreased by the time the records have spent in the cache.
+///
+/// [`Resolver`]: ../resolver/trait.Resolver.html
+/// [`StubResolver`]: ../stub/struct.StubResolver.html
+/// [`Bytes`]: ../../../bytes/struct.Bytes.html
+#[derive(Debug)]
+pub struct CachingResolver<R> {
+    /// The upstream resolver.
+    upstream: R,
+
+    /// The cache.
+    cache: Mutex<Cache>,
+}
+
+impl<R> CachingResolver<R> {
+    /// Creates a new caching resolver with the default configuration.
+    pub fn new(upstream: R) -> Self {
+        Self::from_conf(upstream, CacheConf::default())
+    }
+
+    /// Creates a new caching resolver with the given configuration.
+    pub fn from_conf(upstream: R, conf: CacheConf) -> Self {
+        CachingResolver {
+            upstream,
+            cache: Mutex::new(Cache::new(conf)),
+        }
+    }
+
+    /// Returns a reference to the upstream resolver.
+    pub fn upstream(&self) -> &R {
+        &self.upstream
+    }
+
+    /// Returns the configuration of the cache.
+    pub fn conf(&self) -> CacheConf {
+        self.cache.lock().unwrap().conf.clone()
+    }
+
+    /// Returns the estimated size of the cache in octets.
+    pub fn size(&self) -> usize {
+        self.cache.lock().unwrap().size
+    }
+
+    /// Removes all entries from the cache.
+    pub fn clear(&self) {
+        self.cache.lock().unwrap().clear()
+    }
+}
+
+impl<R> CachingResolver<R>
+where
+    R: Sync,
+{
+    /// Answers a question either from the cache or via the upstream.
+    async fn resolve<'a>(
+        &'a self,
+        question: Question<StoredName>,
+    ) -> Result<Answer, io::Error>
+    where
+        &'a R: Resolver<Octets = Bytes>,
+    {
+        if let Some(message) =
+            self.cache.lock().unwrap().lookup(&question, Instant::now())
+        {
+            return Ok(message.into());
+        }
+        match (&self.upstream).query(question.clone()).await {
+            Ok(answer) => {
+                let message = answer.as_ref().clone();
+                if message.header().rcode() == Rcode::ServFail {
+                    if let Some(message) = self.stale(&question) {
+                        return Ok(message.into());
+                    }
+                } else {
+                    self.cache.lock().unwrap().insert(
+                        &question,
+                        &message,
+                        Instant::now(),
+                    );
+                }
+                Ok(message.into())
+            }
+            Err(err) => match self.stale(&question) {
+                Some(message) => Ok(message.into()),
+                None => Err(err),
+            },
+        }
+    }
+
+    /// Returns a stale answer if serving those is enabled.
+    fn stale(
+        &self,
+        question: &Question<StoredName>,
+    ) -> Option<Message<Bytes>> {
+        self.cache
+            .lock()
+            .unwrap()
+            .lookup_stale(question, Instant::now())
+    }
+}
+
+//--- Resolver and SearchNames
+
+impl<'a, R> Resolver for &'a CachingResolver<R>
+where
+    R: Sync,
+    &'a R: Resolver<Octets = Bytes>,
+{
+    type Octets = Bytes;
+    type Answer = Answer;
+    type Query =
+        Pin<Box<dyn Future<Output = Result<Answer, io::Error>> + Send + 'a>>;
+
+    fn query<N, Q>(&self, question: Q) -> Self::Query
+    where
+        N: ToDname,
+        Q: Into<Question<N>>,
+    {
+        let question = question.into();
+        let question = Question::new(
+            question.qname().to_bytes(),
+            question.qtype(),
+            question.qclass(),
+        );
+        Box::pin(CachingResolver::<R>::resolve(*self, question))
+    }
+}
+
+impl<'a, R> SearchNames for &'a CachingResolver<R>
+where
+    &'a R: SearchNames,
+{
+    type Name = <&'a R as SearchNames>::Name;
+    type Iter = <&'a R as SearchNames>::Iter;
+
+    fn search_iter(&self) -> Self::Iter {
+        (&self.upstream).search_iter()
+    }
+}
+
+//------------ Cache ---------------------------------------------------------
+
+/// The actual cache.
+///
+/// All methods take the current time so they can be tested without having
+/// to wait.
+#[derive(Debug)]
+struct Cache {
+    /// The configuration.
+    conf: CacheConf,
+
+    /// The entries.
+    entries: HashMap<Key, Entry>,
+
+    /// The keys of all entries ordered by when they were last used.
+    lru: BTreeMap<u64, Key>,
+
+    /// The next use stamp to hand out.
+    next_stamp: u64,
+
+    /// The estimated size of all entries in octets.
+    size: usize,
+}
+
+impl Cache {
+    /// Creates a new, empty cache.
+    fn new(conf: CacheConf) -> Self {
+        Cache {
+            conf,
+            entries: HashMap::new(),
+            lru: BTreeMap::new(),
+            next_stamp: 0,
+            size: 0,
+        }
+    }
+
+    /// Removes all entries.
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.lru.clear();
+        self.size = 0;
+    }
+
+    /// Adds the content of a response to a question to the cache.
+    ///
+    /// Only the record sets needed to answer the question are added. If
+    /// the response is negative, a negative entry is added if the response
+    /// contains an SOA record in its authority section.
+    fn insert(
+        &mut self,
+        question: &Question<StoredName>,
+        message: &Message<Bytes>,
+        now: Instant,
+    ) {
+        let rcode = message.header().rcode();
+        if message.header().tc()
+            || (rcode != Rcode::NoError && rcode != Rcode::NXDomain)
+            || !is_cacheable(question.qtype())
+        {
+            return;
+        }
+        let records = match message.answer().map(stored_records) {
+            Ok(Some(records)) => records,
+            _ => return,
+        };
+        let class = question.qclass();
+        let qtype = question.qtype();
+        let mut name = question.qname().clone();
+        for _ in 0..MAX_CHAIN_LEN {
+            let rrset = select(&records, &name, class, qtype);
+            if !rrset.is_empty() {
+                self.insert_rrset(rrset, now);
+                return;
+            }
+            if qtype == Rtype::Cname {
+                break;
+            }
+            let rrset = select(&records, &name, class, Rtype::Cname);
+            let target = match rrset.first().map(Record::data) {
+                Some(AllRecordData::Cname(cname)) => cname.cname().clone(),
+                _ => break,
+            };
+            self.insert_rrset(rrset, now);
+            name = target;
+        }
+
+        // We didn’t find an answer for `name`, so this is negative.
+        let soa = match message.authority().map(stored_records) {
+            Ok(Some(records)) => records
+                .into_iter()
+                .find(|record| record.rtype() == Rtype::Soa),
+            _ => None,
+        };
+        if let Some(soa) = soa {
+            let rtype = if rcode == Rcode::NXDomain {
+                None
+            } else {
+                Some(qtype)
+            };
+            self.insert_negative(name, class, rtype, soa, now)
+        }
+    }
+
+    /// Adds a record set.
+    ///
+    /// The TTL of the record set is the smallest TTL of all records,
+    /// clamped to the configured range.
+    fn insert_rrset(&mut self, rrset: Vec<StoredRecord>, now: Instant) {
+        let first = match rrset.first() {
+            Some(first) => first,
+            None => return,
+        };
+        let ttl = rrset.iter().map(Record::ttl).min().unwrap_or(0);
+        let ttl =
+            cmp::min(cmp::max(ttl, self.conf.min_ttl), self.conf.max_ttl);
+        if ttl == 0 {
+            return;
+        }
+        let name = first.owner().clone();
+        let class = first.class();
+        let rtype = first.rtype();
+
+        // The name exists now, so a cached NXDOMAIN is wrong.
+        self.remove(&Key::new(name.clone(), class, None));
+        self.insert_entry(
+            Key::new(name, class, Some(rtype)),
+            EntryData::Records(rrset),
+            ttl,
+            now,
+        )
+    }
+
+    /// Adds a negative entry.
+    ///
+    /// If `rtype` is `None`, the entry marks the name as not existing.
+    /// Otherwise it marks that there are no records of this type. As
+    /// described in RFC 2308, section 5, the TTL is the smaller of the TTL
+    /// of the SOA record and its minimum field.
+    fn insert_negative(
+        &mut self,
+        name: StoredName,
+        class: Class,
+        rtype: Option<Rtype>,
+        soa: StoredRecord,
+        now: Instant,
+    ) {
+        let minimum = match soa.data() {
+            AllRecordData::Soa(data) => data.minimum(),
+            _ => return,
+        };
+        let ttl = cmp::min(soa.ttl(), minimum);
+        let ttl = cmp::min(
+            cmp::max(ttl, self.conf.min_ttl),
+            self.conf.max_negative_ttl,
+        );
+        if ttl == 0 {
+            return;
+        }
+        self.insert_entry(
+            Key::new(name, class, rtype),
+            EntryData::Negative(Box::new(soa)),
+            ttl,
+            now,
+        )
+    }
+
+    /// Adds an entry, evicting old entries if necessary.
+    fn insert_entry(
+        &mut self,
+        key: Key,
+        data: EntryData,
+        ttl: u32,
+        now: Instant,
+    ) {
+        self.remove(&key);
+        let size = key.name.as_slice().len() + data.size() + ENTRY_OVERHEAD;
+        let stamp = self.stamp();
+        self.lru.insert(stamp, key.clone());
+        self.size += size;
+        self.entries.insert(
+            key,
+            Entry {
+                data,
+                inserted: now,
+                ttl,
+                stamp,
+                size,
+            },
+        );
+        while self.size > self.conf.max_size {
+            let key = match self.lru.values().next() {
+                Some(key) => key.clone(),
+                None => break,
+            };
+            self.remove(&key);
+        }
+    }
+
+    /// Removes an entry.
+    fn remove(&mut self, key: &Key) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.lru.remove(&entry.stamp);
+            self.size -= entry.size;
+        }
+    }
+
+    /// Returns a new use stamp.
+    fn stamp(&mut self) -> u64 {
+        let res = self.next_stamp;
+        self.next_stamp += 1;
+        res
+    }
+
+    /// Returns an answer to the question from unexpired entries.
+    fn lookup(
+        &mut self,
+        question: &Question<StoredName>,
+        now: Instant,
+    ) -> Option<Message<Bytes>> {
+        self.answer(question, now, false)
+    }
+
+    /// Returns an answer to the question including expired entries.
+    ///
+    /// Returns `None` right away if serving stale answers is disabled.
+    fn lookup_stale(
+        &mut self,
+        question: &Question<StoredName>,
+        now: Instant,
+    ) -> Option<Message<Bytes>> {
+        if !self.conf.serve_stale {
+            return None;
+        }
+        self.answer(question, now, true)
+    }
+
+    /// Synthesizes an answer from the cache.
+    fn answer(
+        &mut self,
+        question: &Question<StoredName>,
+        now: Instant,
+        stale: bool,
+    ) -> Option<Message<Bytes>> {
+        if !is_cacheable(question.qtype()) {
+            return None;
+        }
+        let class = question.qclass();
+        let qtype = question.qtype();
+        let mut name = question.qname().clone();
+        let mut answer = Vec::new();
+        let mut rcode = Rcode::NoError;
+        let mut authority = None;
+        let mut found = false;
+        for _ in 0..MAX_CHAIN_LEN {
+            if let Some((data, ttl)) =
+                self.get(&Key::new(name.clone(), class, None), now, stale)
+            {
+                if let EntryData::Negative(soa) = data {
+                    rcode = Rcode::NXDomain;
+                    authority = Some((*soa, ttl));
+                }
+                found = true;
+                break;
+            }
+            if let Some((data, ttl)) = self.get(
+                &Key::new(name.clone(), class, Some(qtype)),
+                now,
+                stale,
+            ) {
+                match data {
+                    EntryData::Records(records) => {
+                        answer.push((records, ttl))
+                    }
+                    EntryData::Negative(soa) => authority = Some((*soa, ttl)),
+                }
+                found = true;
+                break;
+            }
+            if qtype == Rtype::Cname {
+                break;
+            }
+            let (records, ttl) = match self.get(
+                &Key::new(name.clone(), class, Some(Rtype::Cname)),
+                now,
+                stale,
+            ) {
+                Some((EntryData::Records(records), ttl)) => (records, ttl),
+                _ => break,
+            };
+            let target = match records.first().map(Record::data) {
+                Some(AllRecordData::Cname(cname)) => cname.cname().clone(),
+                _ => break,
+            };
+            answer.push((records, ttl));
+            name = target;
+        }
+        if !found {
+            return None;
+        }
+        build_answer(question, rcode, answer, authority).ok()
+    }
+
+    /// Returns the data and remaining TTL of an entry.
+    ///
+    /// If `stale` is `true`, expired entries are returned, too, as long as
+    /// they haven’t been expired for too long. Their TTL is set to the
+    /// configured stale TTL. Entries that are too old are removed.
+    fn get(
+        &mut self,
+        key: &Key,
+        now: Instant,
+        stale: bool,
+    ) -> Option<(EntryData, u32)> {
+        let stamp = self.stamp();
+        let max_stale = if self.conf.serve_stale {
+            self.conf.max_stale
+        } else {
+            0
+        };
+        let entry = self.entries.get_mut(key)?;
+        let elapsed = now.saturating_duration_since(entry.inserted);
+        let ttl = if elapsed < Duration::from_secs(entry.ttl.into()) {
+            entry.ttl - elapsed.as_secs() as u32
+        } else if elapsed
+            < Duration::from_secs(u64::from(entry.ttl) + u64::from(max_stale))
+        {
+            if !stale {
+                return None;
+            }
+            self.conf.stale_ttl
+        } else {
+            self.remove(key);
+            return None;
+        };
+        self.lru.remove(&entry.stamp);
+        self.lru.insert(stamp, key.clone());
+        entry.stamp = stamp;
+        Some((entry.data.clone(), ttl))
+    }
+}
+
+//------------ Key -----------------------------------------------------------
+
+/// The key of a cache entry.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+struct Key {
+    /// The owner name of the cached records.
+    name: StoredName,
+
+    /// The class of the cached records.
+    class: Class,
+
+    /// The record type of the cached records.
+    ///
+    /// This is `None` for an entry marking the name as non-existing.
+    rtype: Option<Rtype>,
+}
+
+impl Key {
+    fn new(name: StoredName, class: Class, rtype: Option<Rtype>) -> Self {
+        Key { name, class, rtype }
+    }
+}
+
+//------------ Entry ---------------------------------------------------------
+
+/// A cache entry.
+#[derive(Debug)]
+struct Entry {
+    /// The cached data.
+    data: EntryData,
+
+    /// When the entry was added.
+    inserted: Instant,
+
+    /// The TTL of the entry in seconds at the time it was added.
+    ttl: u32,
+
+    /// The use stamp of the entry.
+    stamp: u64,
+
+    /// The estimated size of the entry in octets.
+    size: usize,
+}
+
+/// The data of a cache entry.
+#[derive(Clone, Debug)]
+enum EntryData {
+    /// A record set.
+    Records(Vec<StoredRecord>),
+
+    /// A negative answer with the SOA record from the response.
+    Negative(Box<StoredRecord>),
+}
+
+impl EntryData {
+    /// Returns the size of the wire format of the records.
+    fn size(&self) -> usize {
+        let mut buf = Vec::new();
+        let res = match *self {
+            EntryData::Records(ref records) => records
+                .iter()
+                .try_for_each(|record| record.compose_record(&mut buf)),
+            EntryData::Negative(ref soa) => soa.compose_record(&mut buf),
+        };
+        // Composing into a vec can’t fail.
+        debug_assert!(res.is_ok());
+        buf.len()
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Converts the records of a section into records we can store.
+///
+/// Returns `None` if the section cannot be parsed.
+fn stored_records(
+    section: RecordSection<&Bytes>,
+) -> Option<Vec<StoredRecord>> {
+    let mut res = Vec::new();
+    for record in
+        section.limit_to::<AllRecordData<Bytes, ParsedDname<&Bytes>>>()
+    {
+        let record = record.ok()?;
+        if record.rtype() == Rtype::Opt {
+            continue;
+        }
+        let owner = record.owner().to_bytes();
+        let class = record.class();
+        let ttl = record.ttl();
+        let data = record.into_data().flatten_into().ok()?;
+        res.push(Record::new(owner, class, ttl, data));
+    }
+    Some(res)
+}
+
+/// Returns whether answers for the record type can be cached.
+///
+/// This excludes the types that can only appear in questions as well as
+/// the pseudo types that only make sense for a single message.
+fn is_cacheable(rtype: Rtype) -> bool {
+    !matches!(
+        rtype,
+        Rtype::Any
+            | Rtype::Axfr
+            | Rtype::Ixfr
+            | Rtype::Mailb
+            | Rtype::Maila
+            | Rtype::Opt
+            | Rtype::Tkey
+            | Rtype::Tsig
+    )
+}
+
+/// Returns the record set for the given name, class, and type.
+fn select(
+    records: &[StoredRecord],
+    name: &StoredName,
+    class: Class,
+    rtype: Rtype,
+) -> Vec<StoredRecord> {
+    records
+        .iter()
+        .filter(|record| {
+            record.owner() == name
+                && record.class() == class
+                && record.rtype() == rtype
+        })
+        .cloned()
+        .collect()
+}
+
+/// Builds an answer message from cached data.
+fn build_answer(
+    question: &Question<StoredName>,
+    rcode: Rcode,
+    answer: Vec<(Vec<StoredRecord>, u32)>,
+    authority: Option<(StoredRecord, u32)>,
+) -> Result<Message<Bytes>, ShortBuf> {
+    let mut msg = MessageBuilder::new_bytes();
+    msg.header_mut().set_qr(true);
+    msg.header_mut().set_rd(true);
+    msg.header_mut().set_ra(true);
+    msg.header_mut().set_rcode(rcode);
+    let mut msg = msg.question();
+    msg.push(question)?;
+    let mut msg = msg.answer();
+    for (records, ttl) in answer {
+        for mut record in records {
+            record.set_ttl(ttl);
+            msg.push(record)?;
+        }
+    }
+    let mut msg = msg.authority();
+    if let Some((mut soa, ttl)) = authority {
+        soa.set_ttl(ttl);
+        msg.push(soa)?;
+    }
+    Ok(msg.into_message())
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::header::Header;
+    use crate::base::serial::Serial;
+    use crate::rdata::{Cname, Soa, A};
+    use std::str::FromStr;
+    use std::string::{String, ToString};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn name(s: &str) -> StoredName {
+        Dname::from_str(s).unwrap()
+    }
+
+    fn question(qname: &str, qtype: Rtype) -> Question<StoredName> {
+        Question::new_in(name(qname), qtype)
+    }
+
+    fn a(owner: &str, ttl: u32, addr: &str) -> StoredRecord {
+        Record::new(
+            name(owner),
+            Class::In,
+            ttl,
+            A::from_str(addr).unwrap().into(),
+        )
+    }
+
+    fn cname(owner: &str, ttl: u32, target: &str) -> StoredRecord {
+        Record::new(
+            name(owner),
+            Class::In,
+            ttl,
+            Cname::new(name(target)).into(),
+        )
+    }
+
+    fn soa(ttl: u32, minimum: u32) -> StoredRecord {
+        Record::new(
+            name("example.com."),
+            Class::In,
+            ttl,
+            Soa::new(
+                name("ns.example.com."),
+                name("hostmaster.example.com."),
+                Serial(1),
+                3600,
+                600,
+                86400,
+                minimum,
+            )
+            .into(),
+        )
+    }
+
+    fn response(
+        question: &Question<StoredName>,
+        rcode: Rcode,
+        answer: &[StoredRecord],
+        authority: &[StoredRecord],
+    ) -> Message<Bytes> {
+        let mut msg = MessageBuilder::new_bytes();
+        msg.header_mut().set_qr(true);
+        msg.header_mut().set_rcode(rcode);
+        let mut msg = msg.question();
+        msg.push(question).unwrap();
+        let mut msg = msg.answer();
+        for record in answer {
+            msg.push(record).unwrap();
+        }
+        let mut msg = msg.authority();
+        for record in authority {
+            msg.push(record).unwrap();
+        }
+        msg.into_message()
+    }
+
+    /// Returns the rcode and the owner and TTL of all answer records.
+    fn summary(message: &Message<Bytes>) -> (Rcode, Vec<(String, u32)>) {
+        (
+            message.header().rcode(),
+            message
+                .answer()
+                .unwrap()
+                .limit_to::<AllRecordData<_, _>>()
+                .map(|record| {
+                    let record = record.unwrap();
+                    (record.owner().to_string(), record.ttl())
+                })
+                .collect(),
+        )
+    }
+
+    fn authority_ttl(message: &Message<Bytes>) -> Option<u32> {
+        message
+            .authority()
+            .unwrap()
+            .limit_to::<AllRecordData<_, _>>()
+            .next()
+            .map(|record| record.unwrap().ttl())
+    }
+
+    fn secs(secs: u64) -> Duration {
+        Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn positive() {
+        let mut cache = Cache::new(CacheConf::default());
+        let now = Instant::now();
+        let q = question("www.example.com.", Rtype::A);
+        cache.insert(
+            &q,
+            &response(
+                &q,
+                Rcode::NoError,
+                &[
+                    a("www.example.com.", 300, "192.0.2.1"),
+                    a("www.example.com.", 200, "192.0.2.2"),
+                ],
+                &[],
+            ),
+            now,
+        );
+        let answer = cache.lookup(&q, now + secs(100)).unwrap();
+        assert_eq!(
+            summary(&answer),
+            (
+                Rcode::NoError,
+                vec![
+                    ("www.example.com".into(), 100),
+                    ("www.example.com".into(), 100)
+                ]
+            )
+        );
+        assert_eq!(answer.sole_question().unwrap(), q);
+        assert!(cache
+            .lookup(&question("www.example.com.", Rtype::Aaaa), now)
+            .is_none());
+        assert!(cache.lookup(&q, now + secs(200)).is_none());
+    }
+
+    #[test]
+    fn cname_chain() {
+        let mut cache = Cache::new(CacheConf::default());
+        let now = Instant::now();
+        let q = question("www.example.com.", Rtype::A);
+        cache.insert(
+            &q,
+            &response(
+                &q,
+                Rcode::NoError,
+                &[
+                    cname("www.example.com.", 600, "host.example.net."),
+                    a("host.example.net.", 60, "192.0.2.1"),
+                    a("unrelated.example.net.", 60, "192.0.2.2"),
+                ],
+                &[],
+            ),
+            now,
+        );
+        assert_eq!(
+            summary(&cache.lookup(&q, now).unwrap()),
+            (
+                Rcode::NoError,
+                vec![
+                    ("www.example.com".into(), 600),
+                    ("host.example.net".into(), 60)
+                ]
+            )
+        );
+        assert_eq!(
+            summary(
+                &cache
+                    .lookup(&question("host.example.net.", Rtype::A), now)
+                    .unwrap()
+            ),
+            (Rcode::NoError, vec![("host.example.net".into(), 60)])
+        );
+        assert_eq!(
+            summary(
+                &cache
+                    .lookup(&question("www.example.com.", Rtype::Cname), now)
+                    .unwrap()
+            ),
+            (Rcode::NoError, vec![("www.example.com".into(), 600)])
+        );
+        assert!(cache
+            .lookup(&question("unrelated.example.net.", Rtype::A), now)
+            .is_none());
+
+        // Once the target expired, the chain is incomplete.
+        assert!(cache.lookup(&q, now + secs(60)).is_none());
+    }
+
+    #[test]
+    fn nxdomain() {
+        let mut cache = Cache::new(CacheConf::default());
+        let now = Instant::now();
+        let q = question("missing.example.com.", Rtype::A);
+        cache.insert(
+            &q,
+            &response(&q, Rcode::NXDomain, &[], &[soa(3600, 60)]),
+            now,
+        );
+        let answer = cache
+            .lookup(&question("missing.example.com.", Rtype::Aaaa), now)
+            .unwrap();
+        assert_eq!(summary(&answer), (Rcode::NXDomain, vec![]));
+        assert_eq!(authority_ttl(&answer), Some(60));
+        assert!(cache.lookup(&q, now + secs(60)).is_none());
+    }
+
+    #[test]
+    fn nodata() {
+        let mut cache = Cache::new(CacheConf::default());
+        let now = Instant::now();
+        let q = question("www.example.com.", Rtype::Aaaa);
+        cache.insert(
+            &q,
+            &response(&q, Rcode::NoError, &[], &[soa(30, 600)]),
+            now,
+        );
+        let answer = cache.lookup(&q, now + secs(10)).unwrap();
+        assert_eq!(summary(&answer), (Rcode::NoError, vec![]));
+        assert_eq!(authority_ttl(&answer), Some(20));
+        assert!(cache
+            .lookup(&question("www.example.com.", Rtype::A), now)
+            .is_none());
+
+        // Without an SOA, nothing is cached.
+        let q = question("other.example.com.", Rtype::Aaaa);
+        cache.insert(&q, &response(&q, Rcode::NoError, &[], &[]), now);
+        assert!(cache.lookup(&q, now).is_none());
+    }
+
+    #[test]
+    fn ttl_clamps() {
+        let mut cache = Cache::new(CacheConf {
+            min_ttl: 60,
+            max_ttl: 100,
+            max_negative_ttl: 30,
+            ..Default::default()
+        });
+        let now = Instant::now();
+        let short = question("short.example.com.", Rtype::A);
+        cache.insert(
+            &short,
+            &response(
+                &short,
+                Rcode::NoError,
+                &[a("short.example.com.", 10, "192.0.2.1")],
+                &[],
+            ),
+            now,
+        );
+        let long = question("long.example.com.", Rtype::A);
+        cache.insert(
+            &long,
+            &response(
+                &long,
+                Rcode::NoError,
+                &[a("long.example.com.", 1000, "192.0.2.1")],
+                &[],
+            ),
+            now,
+        );
+        let neg = question("neg.example.com.", Rtype::A);
+        cache.insert(
+            &neg,
+            &response(&neg, Rcode::NXDomain, &[], &[soa(3600, 3600)]),
+            now,
+        );
+        assert_eq!(
+            summary(&cache.lookup(&short, now).unwrap()).1,
+            vec![("short.example.com".into(), 60)]
+        );
+        assert_eq!(
+            summary(&cache.lookup(&long, now).unwrap()).1,
+            vec![("long.example.com".into(), 100)]
+        );
+        assert_eq!(
+            authority_ttl(&cache.lookup(&neg, now).unwrap()),
+            Some(30)
+        );
+    }
+
+    #[test]
+    fn uncacheable() {
+        let mut cache = Cache::new(CacheConf::default());
+        let now = Instant::now();
+        let q = question("www.example.com.", Rtype::A);
+        let records = [a("www.example.com.", 300, "192.0.2.1")];
+        cache.insert(&q, &response(&q, Rcode::ServFail, &records, &[]), now);
+        assert!(cache.lookup(&q, now).is_none());
+        let message = response(&q, Rcode::NoError, &records, &[]);
+        let mut octets = Vec::from(message.as_slice());
+        Header::for_message_slice_mut(&mut octets).set_tc(true);
+        let message = Message::from_octets(Bytes::from(octets)).unwrap();
+        cache.insert(&q, &message, now);
+        assert!(cache.lookup(&q, now).is_none());
+        let q = question("www.example.com.", Rtype::Any);
+        cache.insert(&q, &response(&q, Rcode::NoError, &records, &[]), now);
+        assert!(cache.lookup(&q, now).is_none());
+    }
+
+    #[test]
+    fn lru_eviction() {
+        let mut cache = Cache::new(CacheConf::default());
+        let now = Instant::now();
+        let insert = |cache: &mut Cache, qname: &str| {
+            let q = question(qname, Rtype::A);
+            cache.insert(
+                &q,
+                &response(
+                    &q,
+                    Rcode::NoError,
+                    &[a(qname, 300, "192.0.2.1")],
+                    &[],
+                ),
+                now,
+            );
+            q
+        };
+        let one = insert(&mut cache, "one.example.com.");
+        let entry_size = cache.size;
+        cache.conf.max_size = 2 * entry_size;
+        let two = insert(&mut cache, "two.example.com.");
+        assert!(cache.lookup(&one, now).is_some());
+        let six = insert(&mut cache, "six.example.com.");
+        assert!(cache.lookup(&one, now).is_some());
+        assert!(cache.lookup(&two, now).is_none());
+        assert!(cache.lookup(&six, now).is_some());
+        assert!(cache.size <= cache.conf.max_size);
+    }
+
+    #[test]
+    fn stale() {
+        let mut cache = Cache::new(CacheConf {
+            serve_stale: true,
+            max_stale: 100,
+            ..Default::default()
+        });
+        let now = Instant::now();
+        let q = question("www.example.com.", Rtype::A);
+        cache.insert(
+            &q,
+            &response(
+                &q,
+                Rcode::NoError,
+                &[a("www.example.com.", 60, "192.0.2.1")],
+                &[],
+            ),
+            now,
+        );
+        assert!(cache.lookup(&q, now + secs(100)).is_none());
+        assert_eq!(
+            summary(&cache.lookup_stale(&q, now + secs(100)).unwrap()).1,
+            vec![("www.example.com".into(), 30)]
+        );
+        assert!(cache.lookup_stale(&q, now + secs(160)).is_none());
+        assert!(cache.entries.is_empty());
+    }
+
+    //--- Resolver tests
+
+    /// An upstream resolver returning a fixed response.
+    #[derive(Default)]
+    struct Upstream {
+        /// The response or `None` to fail.
+        response: Mutex<Option<Message<Bytes>>>,
+
+        /// The number of queries received.
+        queries: AtomicUsize,
+    }
+
+    impl Upstream {
+        fn set(&self, response: Option<Message<Bytes>>) {
+            *self.response.lock().unwrap() = response;
+        }
+
+        fn queries(&self) -> usize {
+            self.queries.load(Ordering::SeqCst)
+        }
+    }
+
+    impl<'a> Resolver for &'a Upstream {
+        type Octets = Bytes;
+        type Answer = Answer;
+        type Query = futures::future::Ready<Result<Answer, io::Error>>;
+
+        fn query<N, Q>(&self, _question: Q) -> Self::Query
+        where
+            N: ToDname,
+            Q: Into<Question<N>>,
+        {
+            self.queries.fetch_add(1, Ordering::SeqCst);
+            futures::future::ready(
+                match self.response.lock().unwrap().clone() {
+                    Some(message) => Ok(message.into()),
+                    None => Err(io::Error::new(io::ErrorKind::Other, "down")),
+                },
+            )
+        }
+    }
+
+    #[tokio::test]
+    async fn resolver() {
+        let resolver = CachingResolver::new(Upstream::default());
+        let q = question("www.example.com.", Rtype::A);
+        resolver.upstream().set(Some(response(
+            &q,
+            Rcode::NoError,
+            &[a("www.example.com.", 300, "192.0.2.1")],
+            &[],
+        )));
+        let answer = (&resolver).query(q.clone()).await.unwrap();
+        assert_eq!(summary(&answer).1.len(), 1);
+        let answer = (&resolver)
+            .query((name("WWW.example.com."), Rtype::A))
+            .await
+            .unwrap();
+        assert_eq!(summary(&answer).1.len(), 1);
+        assert_eq!(resolver.upstream().queries(), 1);
+        resolver.clear();
+        assert_eq!(resolver.size(), 0);
+        (&resolver).query(q).await.unwrap();
+        assert_eq!(resolver.upstream().queries(), 2);
+    }
+
+    #[tokio::test]
+    async fn resolver_stale() {
+        let q = question("www.example.com.", Rtype::A);
+        let message = response(
+            &q,
+            Rcode::NoError,
+            &[a("www.example.com.", 60, "192.0.2.1")],
+            &[],
+        );
+        // The monotonic clock may have started less than two minutes ago.
+        let expired = match Instant::now().checked_sub(secs(120)) {
+            Some(expired) => expired,
+            None => return,
+        };
+        for &serve_stale in &[false, true] {
+            let resolver = CachingResolver::from_conf(
+                Upstream::default(),
+                CacheConf {
+                    serve_stale,
+                    ..Default::default()
+                },
+            );
+            resolver.cache.lock().unwrap().insert(&q, &message, expired);
+            let res = (&resolver).query(q.clone()).await;
+            assert_eq!(resolver.upstream().queries(), 1);
+            if serve_stale {
+                assert_eq!(
+                    summary(&res.unwrap()).1,
+                    vec![("www.example.com".into(), 30)]
+                );
+            } else {
+                assert!(res.is_err());
+            }
+
+            // SERVFAIL counts as failure, too.
+            resolver.upstream().set(Some(response(
+                &q,
+                Rcode::ServFail,
+                &[],
+                &[],
+            )));
+            let answer = (&resolver).query(q.clone()).await.unwrap();
+            assert_eq!(
+                answer.header().rcode(),
+                if serve_stale {
+                    Rcode::NoError
+                } else {
+                    Rcode::ServFail
+                }
+            );
+        }
+    }
+}
@@ -0,0 +1,854 @@
+//! Local name sources.
+//!
+//! Before asking the DNS, the name resolution of most systems consults
+//! local sources, most importantly the hosts file at `/etc/hosts`. Which
+//! sources are consulted in which order is configured via the `hosts`
+//! line in `/etc/nsswitch.conf`.
+//!
+//! This module provides [`Hosts`], the parsed content of a hosts file,
+//! and the [`HostsResolver`] which wraps another resolver – the upstream
+//! – and answers questions from the configured sources. Questions for
+//! addresses and reverse questions for the addresses in the hosts file are
+//! answered locally, everything else is passed on to the upstream. The
+//! resolver re-reads the hosts file when it changes.
+//!
+//! The resolver is configured via [`HostsConf`]. Only the `files` and
+//! `dns` services of the `hosts` line of `nsswitch.conf` are supported.
+//! Other services as well as action items are ignored.
+//!
+//! [`Hosts`]: struct.Hosts.html
+//! [`HostsConf`]: struct.HostsConf.html
+//! [`HostsResolver`]: struct.HostsResolver.html
+
+use super::lookup::addr::dname_from_addr;
+use super::resolver::{Resolver, SearchNames};
+use super::stub::Answer;
+use crate::base::iana::{Class, Rcode, Rtype};
+use crate::base::message::Message;
+use crate::base::message_builder::{AnswerBuilder, MessageBuilder};
+use crate::base::name::{Dname, ToDname};
+use crate::base::octets::ShortBuf;
+use crate::base::question::Question;
+use crate::rdata::{Aaaa, Ptr, A};
+use bytes::{Bytes, BytesMut};
+use std::boxed::Box;
+use std::collections::HashMap;
+use std::fs;
+use std::future::Future;
+use std::io::{self, BufRead, Read};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use std::vec::Vec;
+
+/// The domain names we store.
+type StoredName = Dname<Bytes>;
+
+//------------ Source --------------------------------------------------------
+
+/// A source for answering questions.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Source {
+    /// The hosts file.
+    Files,
+
+    /// The DNS via the upstream resolver.
+    Dns,
+}
+
+//------------ HostsConf -----------------------------------------------------
+
+/// Configuration of a hosts resolver.
+#[derive(Clone, Debug)]
+pub struct HostsConf {
+    /// The path of the hosts file.
+    pub path: PathBuf,
+
+    /// The sources to consult in this order.
+    pub sources: Vec<Source>,
+
+    /// How often to check whether the hosts file has changed.
+    pub check_interval: Duration,
+}
+
+impl HostsConf {
+    /// Creates the configuration for this system.
+    ///
+    /// Starts with the default configuration and then applies the `hosts`
+    /// line of `/etc/nsswitch.conf` if that file exists.
+    ///
+    /// XXX This currently only works for Unix-y systems.
+    pub fn system() -> Self {
+        let mut res = HostsConf::default();
+        let _ = res.parse_nsswitch_file("/etc/nsswitch.conf");
+        res
+    }
+
+    /// Parses the sources from an nsswitch.conf file.
+    pub fn parse_nsswitch_file<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+    ) -> Result<(), io::Error> {
+        let mut file = fs::File::open(path)?;
+        self.parse_nsswitch(&mut file)
+    }
+
+    /// Parses the sources from a reader.
+    ///
+    /// The format is that of the /etc/nsswitch.conf file. Only the `hosts`
+    /// line is considered. If there is one, the sources are replaced with
+    /// the services given on this line that we support.
+    pub fn parse_nsswitch<R: Read>(
+        &mut self,
+        reader: &mut R,
+    ) -> Result<(), io::Error> {
+        for line in io::BufReader::new(reader).lines() {
+            let line = line?;
+            let line = match line.find('#') {
+                Some(pos) => &line[..pos],
+                None => line.as_str(),
+            };
+            let line = match line.trim_start().strip_prefix("hosts:") {
+                Some(line) => line,
+                None => continue,
+            };
+            let mut sources = Vec::new();
+            let mut in_action = false;
+            for word in line.split_whitespace() {
+                // Action items look like `[NOTFOUND=return]` but may
+                // contain white space.
+                if in_action || word.starts_with('[') {
+                    in_action = !word.ends_with(']');
+                    continue;
+                }
+                let source = match word {
+                    "files" => Source::Files,
+                    "dns" => Source::Dns,
+                    _ => continue,
+                };
+                if !sources.contains(&source) {
+                    sources.push(source)
+                }
+            }
+            self.sources = sources;
+        }
+        Ok(())
+    }
+}
+
+impl Default for HostsConf {
+    fn default() -> Self {
+        HostsConf {
+            path: "/etc/hosts".into(),
+            sources: vec![Source::Files, Source::Dns],
+            check_interval: Duration::from_secs(5),
+        }
+    }
+}
+
+//------------ Hosts ---------------------------------------------------------
+
+/// The content of a hosts file.
+///
+/// The value maps host names to addresses and addresses back to host
+/// names. All names are considered absolute and are compared
+/// case-insensitively.
+#[derive(Clone, Debug, Default)]
+pub struct Hosts {
+    /// The addresses for each host name.
+    names: HashMap<StoredName, Vec<IpAddr>>,
+
+    /// The canonical names for the reverse name of each address.
+    addrs: HashMap<StoredName, Vec<StoredName>>,
+}
+
+impl Hosts {
+    /// Creates a new, empty value.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses a hosts file.
+    pub fn parse_file<P: AsRef<Path>>(path: P) -> Result<Self, io::Error> {
+        let mut res = Self::new();
+        res.parse(&mut fs::File::open(path)?)?;
+        Ok(res)
+    }
+
+    /// Parses the content of a hosts file from a reader.
+    ///
+    /// Each line contains an IPv4 or IPv6 address followed by the
+    /// canonical name of the host and optionally any number of aliases.
+    /// Everything following a `#` is a comment. Like glibc, the method
+    /// skips lines it doesn’t understand, so only reading can fail.
+    pub fn parse<R: Read>(
+        &mut self,
+        reader: &mut R,
+    ) -> Result<(), io::Error> {
+        for line in io::BufReader::new(reader).lines() {
+            let line = line?;
+            let line = match line.find('#') {
+                Some(pos) => &line[..pos],
+                None => line.as_str(),
+            };
+            let mut words = line.split_whitespace();
+            let addr = match words.next().map(IpAddr::from_str) {
+                Some(Ok(addr)) => addr,
+                _ => continue,
+            };
+            let names: Result<Vec<StoredName>, _> =
+                words.map(StoredName::from_str).collect();
+            if let Ok(names) = names {
+                self.insert(addr, names)
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds an address for a host.
+    ///
+    /// The first name is the canonical name of the host, all other names
+    /// are aliases. Nothing happens if `names` is empty.
+    pub fn insert<I>(&mut self, addr: IpAddr, names: I)
+    where
+        I: IntoIterator<Item = StoredName>,
+    {
+        let mut names = names.into_iter();
+        let canonical = match names.next() {
+            Some(name) => name,
+            None => return,
+        };
+        let reverse = dname_from_addr(addr).to_bytes();
+        let reverse = self.addrs.entry(reverse).or_default();
+        if !reverse.contains(&canonical) {
+            reverse.push(canonical.clone())
+        }
+        for name in Some(canonical).into_iter().chain(names) {
+            let addrs = self.names.entry(name).or_default();
+            if !addrs.contains(&addr) {
+                addrs.push(addr)
+            }
+        }
+    }
+
+    /// Returns whether there are no entries.
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Returns whether the host name appears in the hosts file.
+    pub fn contains_name(&self, name: &impl ToDname) -> bool {
+        self.names.contains_key(&name.to_bytes())
+    }
+
+    /// Returns the addresses for a host name.
+    ///
+    /// The addresses are returned in the order they appear in the file.
+    pub fn lookup_name(&self, name: &impl ToDname) -> &[IpAddr] {
+        self.names
+            .get(&name.to_bytes())
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Returns the canonical host names for an address.
+    pub fn lookup_addr(&self, addr: IpAddr) -> &[StoredName] {
+        self.addrs
+            .get(&dname_from_addr(addr).to_bytes())
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Returns an answer to a question if the hosts file has one.
+    ///
+    /// Questions for A, AAAA, and ANY records of a name from the file are
+    /// answered with the addresses of the name. If there are no addresses
+    /// of the requested family, the answer is empty. Questions for PTR
+    /// records of the reverse names of addresses from the file are
+    /// answered with the canonical host names. All records have a TTL of
+    /// zero.
+    pub fn answer(
+        &self,
+        question: &Question<impl ToDname>,
+    ) -> Option<Message<Bytes>> {
+        if question.qclass() != Class::In {
+            return None;
+        }
+        let qname = question.qname().to_bytes();
+        match question.qtype() {
+            Rtype::A | Rtype::Aaaa | Rtype::Any => {
+                let addrs = self.names.get(&qname)?;
+                build_answer(question, Rcode::NoError, |msg| {
+                    for addr in addrs {
+                        match (*addr, question.qtype()) {
+                            (IpAddr::V4(addr), Rtype::A | Rtype::Any) => {
+                                msg.push((&qname, 0, A::new(addr)))?
+                            }
+                            (IpAddr::V6(addr), Rtype::Aaaa | Rtype::Any) => {
+                                msg.push((&qname, 0, Aaaa::new(addr)))?
+                            }
+                            _ => {}
+                        }
+                    }
+                    Ok(())
+                })
+            }
+            Rtype::Ptr => {
+                let names = self.addrs.get(&qname)?;
+                build_answer(question, Rcode::NoError, |msg| {
+                    for name in names {
+                        msg.push((&qname, 0, Ptr::new(name)))?
+                    }
+                    Ok(())
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+//------------ HostsResolver -------------------------------------------------
+
+/// A resolver consulting local sources before the DNS.
+///
+/// The upstream resolver can be any type that implements [`Resolver`] for
+/// references and uses [`Bytes`] for its messages, such as the
+/// [`StubResolver`]. The sources are consulted in the order given by the
+/// configuration. If the DNS answers with NXDOMAIN or fails, the next
+/// source is tried. If no source has an answer, the last answer or error
+/// from the DNS is returned or, if the DNS wasn’t asked, an NXDOMAIN
+/// answer.
+///
+/// The resolver also provides the search list of the upstream resolver,
+/// so all lookups can be used with it.
+///
+/// [`Resolver`]: ../resolver/trait.Resolver.html
+/// [`StubResolver`]: ../stub/struct.StubResolver.html
+/// [`Bytes`]: ../../../bytes/struct.Bytes.html
+#[derive(Debug)]
+pub struct HostsResolver<R> {
+    /// The upstream resolver.
+    upstream: R,
+
+    /// The sources to consult.
+    sources: Vec<Source>,
+
+    /// The hosts file.
+    hosts: HostsFile,
+}
+
+impl<R> HostsResolver<R> {
+    /// Creates a new resolver using the system’s configuration.
+    pub fn new(upstream: R) -> Self {
+        Self::from_conf(upstream, HostsConf::system())
+    }
+
+    /// Creates a new resolver with the given configuration.
+    pub fn from_conf(upstream: R, conf: HostsConf) -> Self {
+        HostsResolver {
+            upstream,
+            sources: conf.sources,
+            hosts: HostsFile::new(conf.path, conf.check_interval),
+        }
+    }
+
+    /// Creates a new resolver with fixed hosts data.
+    ///
+    /// The resolver consults `hosts` first and then the DNS.
+    pub fn from_hosts(upstream: R, hosts: Hosts) -> Self {
+        HostsResolver {
+            upstream,
+            sources: vec![Source::Files, Source::Dns],
+            hosts: HostsFile::fixed(hosts),
+        }
+    }
+
+    /// Returns a reference to the upstream resolver.
+    pub fn upstream(&self) -> &R {
+        &self.upstream
+    }
+
+    /// Returns the sources consulted by the resolver.
+    pub fn sources(&self) -> &[Source] {
+        &self.sources
+    }
+
+    /// Returns the current content of the hosts file.
+    ///
+    /// The file is re-read first if it has changed.
+    pub fn hosts(&self) -> Arc<Hosts> {
+        self.hosts.get()
+    }
+
+    /// Re-reads the hosts file right away.
+    ///
+    /// If the file cannot be read, the resolver continues with an empty
+    /// hosts file and the error is returned.
+    pub fn reload(&self) -> Result<(), io::Error> {
+        self.hosts.reload()
+    }
+}
+
+impl<R> HostsResolver<R>
+where
+    R: Sync,
+{
+    /// Answers a question from the configured sources.
+    async fn resolve<'a>(
+        &'a self,
+        question: Question<StoredName>,
+    ) -> Result<Answer, io::Error>
+    where
+        &'a R: Resolver<Octets = Bytes>,
+    {
+        let mut res = None;
+        for source in &self.sources {
+            match *source {
+                Source::Files => {
+                    if let Some(message) =
+                        self.hosts.get_async().await.answer(&question)
+                    {
+                        return Ok(message.into());
+                    }
+                }
+                Source::Dns => {
+                    match (&self.upstream).query(question.clone()).await {
+                        Ok(answer) => {
+                            let message = answer.as_ref().clone();
+                            if message.header().rcode() != Rcode::NXDomain {
+                                return Ok(message.into());
+                            }
+                            res = Some(Ok(message.into()));
+                        }
+                        Err(err) => res = Some(Err(err)),
+                    }
+                }
+            }
+        }
+        match res {
+            Some(res) => res,
+            None => build_answer(&question, Rcode::NXDomain, |_| Ok(()))
+                .map(Into::into)
+                .ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::Other, "short buf")
+                }),
+        }
+    }
+}
+
+//--- Resolver and SearchNames
+
+impl<'a, R> Resolver for &'a HostsResolver<R>
+where
+    R: Sync,
+    &'a R: Resolver<Octets = Bytes>,
+{
+    type Octets = Bytes;
+    type Answer = Answer;
+    type Query =
+        Pin<Box<dyn Future<Output = Result<Answer, io::Error>> + Send + 'a>>;
+
+    fn query<N, Q>(&self, question: Q) -> Self::Query
+    where
+        N: ToDname,
+        Q: Into<Question<N>>,
+    {
+        let question = question.into();
+        let question = Question::new(
+            question.qname().to_bytes(),
+            question.qtype(),
+            question.qclass(),
+        );
+        Box::pin(HostsResolver::<R>::resolve(*self, question))
+    }
+}
+
+impl<'a, R> SearchNames for &'a HostsResolver<R>
+where
+    &'a R: SearchNames,
+{
+    type Name = <&'a R as SearchNames>::Name;
+    type Iter = <&'a R as SearchNames>::Iter;
+
+    fn search_iter(&self) -> Self::Iter {
+        (&self.upstream).search_iter()
+    }
+}
+
+//------------ HostsFile -----------------------------------------------------
+
+/// A hosts file that is re-read when it changes.
+#[derive(Debug)]
+struct HostsFile {
+    /// The path of the file or `None` if the content is fixed.
+    path: Option<PathBuf>,
+
+    /// How often to check whether the file has changed.
+    check_interval: Duration,
+
+    /// The current state.
+    state: Mutex<HostsState>,
+}
+
+#[derive(Debug)]
+struct HostsState {
+    /// The current content.
+    hosts: Arc<Hosts>,
+
+    /// The version of the file when it was read.
+    ///
+    /// This is `None` if the file couldn’t be read.
+    version: Option<FileVersion>,
+
+    /// When we last checked whether the file has changed.
+    checked: Instant,
+}
+
+/// The modification time and size of a file.
+type FileVersion = (SystemTime, u64);
+
+/// The outcome of reading a file: its version and its parsed content.
+type ReadHosts = (Option<FileVersion>, Result<Hosts, io::Error>);
+
+impl HostsFile {
+    fn new(path: PathBuf, check_interval: Duration) -> Self {
+        let res = HostsFile {
+            path: Some(path),
+            check_interval,
+            state: Mutex::new(HostsState {
+                hosts: Arc::new(Hosts::new()),
+                version: None,
+                checked: Instant::now(),
+            }),
+        };
+        let _ = res.reload();
+        res
+    }
+
+    fn fixed(hosts: Hosts) -> Self {
+        HostsFile {
+            path: None,
+            check_interval: Duration::from_secs(0),
+            state: Mutex::new(HostsState {
+                hosts: Arc::new(hosts),
+                version: None,
+                checked: Instant::now(),
+            }),
+        }
+    }
+
+    /// Returns the current content, re-reading the file if necessary.
+    ///
+    /// The file is checked and read without holding the lock.
+    fn get(&self) -> Arc<Hosts> {
+        match self.due() {
+            Some((path, version)) => self.update(Self::check(path, version)),
+            None => self.current(),
+        }
+    }
+
+    /// Returns the current content, re-reading the file if necessary.
+    ///
+    /// When running inside a Tokio runtime, the file is checked and read
+    /// on its blocking thread pool so the executor isn’t held up.
+    async fn get_async(&self) -> Arc<Hosts> {
+        let (path, version) = match self.due() {
+            Some(due) => due,
+            None => return self.current(),
+        };
+        #[cfg(feature = "resolv")]
+        if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            let path = path.to_path_buf();
+            return match handle
+                .spawn_blocking(move || Self::check(&path, version))
+                .await
+            {
+                Ok(read) => self.update(read),
+                Err(_) => self.current(),
+            };
+        }
+        self.update(Self::check(path, version))
+    }
+
+    fn reload(&self) -> Result<(), io::Error> {
+        match self.path {
+            Some(ref path) => {
+                let read = Self::read(path);
+                let mut state = self.state.lock().unwrap();
+                state.checked = Instant::now();
+                Self::store(&mut state, read)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the current content.
+    fn current(&self) -> Arc<Hosts> {
+        self.state.lock().unwrap().hosts.clone()
+    }
+
+    /// Returns the path and the version last read if a check is due.
+    ///
+    /// Marks the file as checked, so concurrent callers won’t check it,
+    /// too.
+    fn due(&self) -> Option<(&Path, Option<FileVersion>)> {
+        let path = self.path.as_ref()?;
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        if now.saturating_duration_since(state.checked) < self.check_interval
+        {
+            return None;
+        }
+        state.checked = now;
+        Some((path, state.version))
+    }
+
+    /// Reads the file if it differs from `version`.
+    fn check(path: &Path, version: Option<FileVersion>) -> Option<ReadHosts> {
+        if file_version(path) == version {
+            None
+        } else {
+            Some(Self::read(path))
+        }
+    }
+
+    /// Stores newly read content, if any, and returns the current content.
+    fn update(&self, read: Option<ReadHosts>) -> Arc<Hosts> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(read) = read {
+            let _ = Self::store(&mut state, read);
+        }
+        state.hosts.clone()
+    }
+
+    fn read(path: &Path) -> ReadHosts {
+        (file_version(path), Hosts::parse_file(path))
+    }
+
+    fn store(
+        state: &mut HostsState,
+        read: ReadHosts,
+    ) -> Result<(), io::Error> {
+        let (version, hosts) = read;
+        state.version = version;
+        let (hosts, res) = match hosts {
+            Ok(hosts) => (hosts, Ok(())),
+            Err(err) => (Hosts::new(), Err(err)),
+        };
+        state.hosts = Arc::new(hosts);
+        res
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Returns the modification time and size of a file.
+fn file_version(path: &Path) -> Option<FileVersion> {
+    let meta = fs::metadata(path).ok()?;
+    Some((meta.modified().ok()?, meta.len()))
+}
+
+/// Builds an answer message for a question.
+///
+/// The closure is given the builder for the answer section.
+fn build_answer<F>(
+    question: &Question<impl ToDname>,
+    rcode: Rcode,
+    answer: F,
+) -> Option<Message<Bytes>>
+where
+    F: FnOnce(&mut AnswerBuilder<BytesMut>) -> Result<(), ShortBuf>,
+{
+    let mut msg = MessageBuilder::new_bytes();
+    msg.header_mut().set_qr(true);
+    msg.header_mut().set_rd(true);
+    msg.header_mut().set_ra(true);
+    msg.header_mut().set_aa(true);
+    msg.header_mut().set_rcode(rcode);
+    let mut msg = msg.question();
+    msg.push(question).ok()?;
+    let mut msg = msg.answer();
+    answer(&mut msg).ok()?;
+    Some(msg.into_message())
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::resolv::lookup::{lookup_addr, lookup_host};
+    use std::string::ToString;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const HOSTS: &str = "\
+        # The usual suspects\n\
+        127.0.0.1   localhost\n\
+        ::1         localhost ip6-localhost  # IPv6\n\
+        192.0.2.1   host.example.com host\n\
+        192.0.2.1   other.example.com\n\
+        2001:db8::1 Host.Example.Com\n\
+        garbage     here\n\
+        192.0.2.2\n";
+
+    fn hosts() -> Hosts {
+        let mut res = Hosts::new();
+        res.parse(&mut io::Cursor::new(HOSTS)).unwrap();
+        res
+    }
+
+    fn name(s: &str) -> StoredName {
+        StoredName::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn parse_hosts() {
+        let hosts = hosts();
+        assert_eq!(
+            hosts.lookup_name(&name("localhost")),
+            &[
+                IpAddr::from_str("127.0.0.1").unwrap(),
+                IpAddr::from_str("::1").unwrap()
+            ]
+        );
+        assert_eq!(
+            hosts.lookup_name(&name("HOST.example.com")),
+            &[
+                IpAddr::from_str("192.0.2.1").unwrap(),
+                IpAddr::from_str("2001:db8::1").unwrap()
+            ]
+        );
+        assert_eq!(hosts.lookup_name(&name("host")).len(), 1);
+        assert!(hosts.lookup_name(&name("here")).is_empty());
+        assert!(!hosts.contains_name(&name("garbage")));
+        assert_eq!(
+            hosts.lookup_addr(IpAddr::from_str("192.0.2.1").unwrap()),
+            &[name("host.example.com"), name("other.example.com")]
+        );
+        assert!(hosts
+            .lookup_addr(IpAddr::from_str("192.0.2.2").unwrap())
+            .is_empty());
+    }
+
+    #[test]
+    fn parse_nsswitch() {
+        let mut conf = HostsConf::default();
+        conf.parse_nsswitch(&mut io::Cursor::new(
+            "passwd: files\n\
+             # hosts: files\n\
+             hosts: mymachines [ !UNAVAIL=return ] dns [NOTFOUND=return] \
+             files myhostname\n",
+        ))
+        .unwrap();
+        assert_eq!(conf.sources, [Source::Dns, Source::Files]);
+    }
+
+    #[derive(Default)]
+    struct Upstream {
+        queries: AtomicUsize,
+    }
+
+    impl<'a> Resolver for &'a Upstream {
+        type Octets = Bytes;
+        type Answer = Answer;
+        type Query = futures::future::Ready<Result<Answer, io::Error>>;
+
+        fn query<N, Q>(&self, question: Q) -> Self::Query
+        where
+            N: ToDname,
+            Q: Into<Question<N>>,
+        {
+            self.queries.fetch_add(1, Ordering::SeqCst);
+            let question = question.into();
+            futures::future::ready(
+                build_answer(&question, Rcode::NXDomain, |_| Ok(()))
+                    .map(Into::into)
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "")),
+            )
+        }
+    }
+
+    #[tokio::test]
+    async fn resolver() {
+        let resolver =
+            HostsResolver::from_hosts(Upstream::default(), hosts());
+        let found = lookup_host(&&resolver, name("localhost")).await.unwrap();
+        assert_eq!(
+            found
+                .iter()
+                .map(|addr| addr.to_string())
+                .collect::<Vec<_>>(),
+            ["::1", "127.0.0.1"]
+        );
+        let found =
+            lookup_addr(&&resolver, IpAddr::from_str("2001:db8::1").unwrap())
+                .await
+                .unwrap();
+        assert_eq!(
+            found
+                .iter()
+                .map(|name| name.to_string())
+                .collect::<Vec<_>>(),
+            ["Host.Example.Com"]
+        );
+        assert_eq!(resolver.upstream().queries.load(Ordering::SeqCst), 0);
+
+        let answer = (&resolver)
+            .query((name("www.example.com"), Rtype::A))
+            .await
+            .unwrap();
+        assert_eq!(answer.as_ref().header().rcode(), Rcode::NXDomain);
+        assert_eq!(resolver.upstream().queries.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn reload() {
+        let dir = std::env::temp_dir()
+            .join(format!("domain-hosts-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("hosts");
+        fs::write(&path, "192.0.2.1 host.example.com\n").unwrap();
+        let resolver = HostsResolver::from_conf(
+            Upstream::default(),
+            HostsConf {
+                path: path.clone(),
+                sources: vec![Source::Files],
+                check_interval: Duration::from_secs(0),
+            },
+        );
+        assert_eq!(
+            resolver
+                .hosts()
+                .lookup_name(&name("host.example.com"))
+                .len(),
+            1
+        );
+
+        // Different length, so we notice even if the time stamp is the
+        // same.
+        fs::write(&path, "192.0.2.2 other.example.com\n\n").unwrap();
+        assert!(resolver
+            .hosts()
+            .lookup_name(&name("host.example.com"))
+            .is_empty());
+        let answer = (&resolver)
+            .query((name("other.example.com"), Rtype::A))
+            .await
+            .unwrap();
+        assert_eq!(answer.as_ref().header_counts().ancount(), 1);
+
+        fs::remove_file(&path).unwrap();
+        assert!(resolver.reload().is_err());
+        assert!(resolver.hosts().is_empty());
+        let answer = (&resolver)
+            .query((name("other.example.com"), Rtype::A))
+            .await
+            .unwrap();
+        assert_eq!(answer.as_ref().header().rcode(), Rcode::NXDomain);
+        assert_eq!(resolver.upstream().queries.load(Ordering::SeqCst), 0);
+        let _ = fs::remove_dir(&dir);
+    }
+}
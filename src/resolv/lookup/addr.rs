@@ -94,7 +94,7 @@ impl<Ref: OctetsRef> Iterator for FoundAddrsIter<Ref> {
 //------------ Helper Functions ---------------------------------------------
 
 /// Translates an IP address into a domain name.
-fn dname_from_addr(addr: IpAddr) -> Dname<Octets128> {
+pub(crate) fn dname_from_addr(addr: IpAddr) -> Dname<Octets128> {
     match addr {
         IpAddr::V4(addr) => {
             let octets = addr.octets();
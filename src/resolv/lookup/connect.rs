@@ -0,0 +1,650 @@
+//! Connecting to hosts using Happy Eyeballs.
+//!
+//! The functions in this module establish a TCP connection to a host whose
+//! addresses are to be determined via the DNS. Instead of trying the
+//! addresses one after another, they follow the Happy Eyeballs algorithm
+//! defined in [RFC 8305] which avoids long delays if connections via one
+//! of the address families are broken:
+//!
+//! * The AAAA and A queries are sent at the same time. Connecting starts as
+//!   soon as the answer to the AAAA query has arrived. If the answer to the
+//!   A query arrives first, connecting starts after a short resolution
+//!   delay unless the AAAA answer arrives in the meantime.
+//!
+//! * The addresses are interleaved by address family, starting with IPv6.
+//!   Addresses arriving later are added to the list.
+//!
+//! * Connection attempts are started one after another, separated by the
+//!   connection attempt delay, without cancelling earlier attempts. If an
+//!   attempt fails, the next one is started right away. The first
+//!   successful connection wins and all other attempts are abandoned.
+//!
+//! The connection attempt delay is derived from the time it took to get
+//! the first answer from the DNS which serves as an estimate of the round
+//! trip time. It is kept within the range given by [`HappyEyeballs`].
+//!
+//! [RFC 8305]: https://tools.ietf.org/html/rfc8305
+//! [`HappyEyeballs`]: struct.HappyEyeballs.html
+
+use super::host::FoundHosts;
+use super::srv::FoundSrvs;
+use crate::base::iana::Rtype;
+use crate::base::name::ToDname;
+use crate::base::octets::OctetsRef;
+use crate::resolv::resolver::Resolver;
+use futures::future::{self, BoxFuture, FutureExt};
+use futures::stream::{FuturesUnordered, StreamExt};
+use std::boxed::Box;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
+use std::task::Poll;
+use std::time::Duration;
+use std::vec::Vec;
+use std::{cmp, io};
+use tokio::net::TcpStream;
+use tokio::time::{sleep_until, Instant};
+
+//------------ connect_host --------------------------------------------------
+
+/// Connects to a host using Happy Eyeballs with the default settings.
+///
+/// The function looks up the IPv6 and IPv4 addresses of `qname` using
+/// `resolver` and returns the first TCP connection to `port` on any of
+/// these addresses that could be established.
+///
+/// See [`HappyEyeballs::connect_host`] for details.
+///
+/// [`HappyEyeballs::connect_host`]: struct.HappyEyeballs.html#method.connect_host
+pub async fn connect_host<R: Resolver>(
+    resolver: &R,
+    qname: impl ToDname,
+    port: u16,
+) -> Result<TcpStream, io::Error>
+where
+    for<'a> &'a R::Octets: OctetsRef,
+{
+    HappyEyeballs::new()
+        .connect_host(resolver, qname, port)
+        .await
+}
+
+//------------ connect_srv ---------------------------------------------------
+
+/// Connects to a service using Happy Eyeballs with the default settings.
+///
+/// See [`HappyEyeballs::connect_srv`] for details.
+///
+/// [`HappyEyeballs::connect_srv`]: struct.HappyEyeballs.html#method.connect_srv
+pub async fn connect_srv<R: Resolver>(
+    resolver: &R,
+    srvs: FoundSrvs,
+) -> Result<TcpStream, io::Error>
+where
+    for<'a> &'a R::Octets: OctetsRef,
+{
+    HappyEyeballs::new().connect_srv(resolver, srvs).await
+}
+
+//------------ HappyEyeballs -------------------------------------------------
+
+/// The settings for connecting via Happy Eyeballs.
+///
+/// The default values for the delays are those given in the field
+/// descriptions.
+#[derive(Clone, Copy, Debug)]
+pub struct HappyEyeballs {
+    /// How long to wait for the AAAA answer after the A answer arrived.
+    ///
+    /// Defaults to 250 ms.
+    pub resolution_delay: Duration,
+
+    /// The minimum delay between starting two connection attempts.
+    ///
+    /// Defaults to 50 ms.
+    pub min_attempt_delay: Duration,
+
+    /// The maximum delay between starting two connection attempts.
+    ///
+    /// This delay is used if there is no estimate of the round trip time.
+    /// Defaults to 250 ms.
+    pub max_attempt_delay: Duration,
+}
+
+impl HappyEyeballs {
+    /// Creates a value with the default settings.
+    pub fn new() -> Self {
+        HappyEyeballs {
+            resolution_delay: Duration::from_millis(250),
+            min_attempt_delay: Duration::from_millis(50),
+            max_attempt_delay: Duration::from_millis(250),
+        }
+    }
+
+    /// Connects to a host.
+    ///
+    /// The method sends AAAA and A queries for `qname` via `resolver` and
+    /// starts connecting to `port` on the addresses as they arrive. It
+    /// returns the first connection that could be established. If no
+    /// connection can be established at all, the error of the last failed
+    /// attempt or, if there weren’t any addresses, of the queries is
+    /// returned.
+    pub async fn connect_host<R: Resolver>(
+        &self,
+        resolver: &R,
+        qname: impl ToDname,
+        port: u16,
+    ) -> Result<TcpStream, io::Error>
+    where
+        for<'a> &'a R::Octets: OctetsRef,
+    {
+        let aaaa = resolver
+            .query((&qname, Rtype::Aaaa))
+            .map(move |res| addrs::<R>(res, true, port));
+        let a = resolver
+            .query((&qname, Rtype::A))
+            .map(move |res| addrs::<R>(res, false, port));
+        Attempts::new(self, Vec::new())
+            .run(Some(Box::pin(aaaa)), Some(Box::pin(a)))
+            .await
+    }
+
+    /// Connects to a service.
+    ///
+    /// The method tries the targets of the SRV records in `srvs` in the
+    /// order given by their priorities and weights. For each target, it
+    /// tries to connect to its addresses via Happy Eyeballs. The first
+    /// connection established is returned.
+    pub async fn connect_srv<R: Resolver>(
+        &self,
+        resolver: &R,
+        srvs: FoundSrvs,
+    ) -> Result<TcpStream, io::Error>
+    where
+        for<'a> &'a R::Octets: OctetsRef,
+    {
+        let mut items = Box::pin(srvs.into_stream(resolver));
+        let mut err = not_found();
+        while let Some(item) = items.next().await {
+            let res = match item {
+                Ok(item) => self.connect_addrs(item.resolved()).await,
+                Err(item_err) => Err(item_err),
+            };
+            match res {
+                Ok(stream) => return Ok(stream),
+                Err(item_err) => err = item_err,
+            }
+        }
+        Err(err)
+    }
+
+    /// Connects to one of the given socket addresses.
+    ///
+    /// The addresses are interleaved by address family, starting with
+    /// IPv6, and then tried one after another with the maximum connection
+    /// attempt delay between them.
+    pub async fn connect_addrs(
+        &self,
+        addrs: &[SocketAddr],
+    ) -> Result<TcpStream, io::Error> {
+        Attempts::new(self, addrs.to_vec()).run(None, None).await
+    }
+}
+
+impl Default for HappyEyeballs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//------------ Attempts ------------------------------------------------------
+
+/// A future resolving into the socket addresses of one address family.
+type AddrsFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<Vec<SocketAddr>, io::Error>> + 'a>>;
+
+/// The state of a Happy Eyeballs run.
+struct Attempts {
+    /// The settings.
+    conf: HappyEyeballs,
+
+    /// The IPv6 addresses not yet tried.
+    v6: VecDeque<SocketAddr>,
+
+    /// The IPv4 addresses not yet tried.
+    v4: VecDeque<SocketAddr>,
+
+    /// Whether the next address should be an IPv6 address.
+    prefer_v6: bool,
+
+    /// The delay between connection attempts.
+    attempt_delay: Duration,
+
+    /// The error to return if everything fails.
+    err: io::Error,
+}
+
+impl Attempts {
+    fn new(conf: &HappyEyeballs, addrs: Vec<SocketAddr>) -> Self {
+        let mut res = Attempts {
+            conf: *conf,
+            v6: VecDeque::new(),
+            v4: VecDeque::new(),
+            prefer_v6: true,
+            attempt_delay: conf.max_attempt_delay,
+            err: not_found(),
+        };
+        res.add(addrs);
+        res
+    }
+
+    /// Adds addresses to the end of the queues.
+    fn add(&mut self, addrs: Vec<SocketAddr>) {
+        for addr in addrs {
+            match addr.ip() {
+                IpAddr::V6(_) => self.v6.push_back(addr),
+                IpAddr::V4(_) => self.v4.push_back(addr),
+            }
+        }
+    }
+
+    /// Returns the next address to try.
+    fn next_addr(&mut self) -> Option<SocketAddr> {
+        let (first, second) = if self.prefer_v6 {
+            (&mut self.v6, &mut self.v4)
+        } else {
+            (&mut self.v4, &mut self.v6)
+        };
+        let res = first.pop_front().or_else(|| second.pop_front())?;
+        self.prefer_v6 = res.is_ipv4();
+        Some(res)
+    }
+
+    /// Sets the connection attempt delay from a round trip time estimate.
+    fn set_rtt(&mut self, rtt: Duration) {
+        self.attempt_delay = cmp::max(
+            cmp::min(rtt * 2, self.conf.max_attempt_delay),
+            self.conf.min_attempt_delay,
+        );
+    }
+
+    /// Runs the algorithm.
+    ///
+    /// The futures for the IPv6 and IPv4 addresses are optional. If they
+    /// are missing, only the addresses already added are used.
+    async fn run(
+        mut self,
+        mut aaaa: Option<AddrsFuture<'_>>,
+        mut a: Option<AddrsFuture<'_>>,
+    ) -> Result<TcpStream, io::Error> {
+        let start = Instant::now();
+        let mut attempts: FuturesUnordered<BoxFuture<_>> =
+            FuturesUnordered::new();
+
+        // When to start the next attempt at the latest.
+        let mut next_attempt = start;
+
+        // Until when to wait for the AAAA answer.
+        //
+        // If we are still waiting for both answers, this is `None`.
+        let mut resolution_deadline =
+            if aaaa.is_some() { None } else { Some(start) };
+
+        loop {
+            let now = Instant::now();
+            let may_start = match resolution_deadline {
+                Some(deadline) => aaaa.is_none() || deadline <= now,
+                None => false,
+            };
+            if may_start && (attempts.is_empty() || next_attempt <= now) {
+                if let Some(addr) = self.next_addr() {
+                    attempts.push(TcpStream::connect(addr).boxed());
+                    next_attempt = now + self.attempt_delay;
+                    continue;
+                }
+            }
+            if attempts.is_empty()
+                && aaaa.is_none()
+                && a.is_none()
+                && self.v6.is_empty()
+                && self.v4.is_empty()
+            {
+                return Err(self.err);
+            }
+
+            // What are we waiting for?
+            let wakeup = if !may_start {
+                resolution_deadline
+            } else if !attempts.is_empty()
+                && (!self.v6.is_empty() || !self.v4.is_empty())
+            {
+                Some(next_attempt)
+            } else {
+                None
+            };
+
+            let mut wakeup =
+                wakeup.map(|wakeup| Box::pin(sleep_until(wakeup)));
+            let event = future::poll_fn(|cx| {
+                if let Some(fut) = aaaa.as_mut() {
+                    if let Poll::Ready(res) = fut.poll_unpin(cx) {
+                        return Poll::Ready(Event::Aaaa(res));
+                    }
+                }
+                if let Some(fut) = a.as_mut() {
+                    if let Poll::Ready(res) = fut.poll_unpin(cx) {
+                        return Poll::Ready(Event::A(res));
+                    }
+                }
+                if !attempts.is_empty() {
+                    if let Poll::Ready(Some(res)) =
+                        attempts.poll_next_unpin(cx)
+                    {
+                        return Poll::Ready(Event::Attempt(res));
+                    }
+                }
+                if let Some(wakeup) = wakeup.as_mut() {
+                    if wakeup.as_mut().poll(cx).is_ready() {
+                        return Poll::Ready(Event::Wakeup);
+                    }
+                }
+                Poll::Pending
+            })
+            .await;
+
+            match event {
+                Event::Aaaa(res) => {
+                    aaaa = None;
+                    if resolution_deadline.is_none() {
+                        self.set_rtt(start.elapsed());
+                    }
+                    resolution_deadline = Some(Instant::now());
+                    match res {
+                        Ok(addrs) => self.add(addrs),
+                        Err(err) => self.err = err,
+                    }
+                }
+                Event::A(res) => {
+                    a = None;
+                    if resolution_deadline.is_none() {
+                        self.set_rtt(start.elapsed());
+                        resolution_deadline =
+                            Some(Instant::now() + self.conf.resolution_delay);
+                    }
+                    match res {
+                        Ok(addrs) => self.add(addrs),
+                        Err(err) => self.err = err,
+                    }
+                }
+                Event::Attempt(Ok(stream)) => return Ok(stream),
+                Event::Attempt(Err(err)) => {
+                    // Start the next attempt right away.
+                    self.err = err;
+                    next_attempt = Instant::now();
+                }
+                Event::Wakeup => {}
+            }
+        }
+    }
+}
+
+//------------ Event ---------------------------------------------------------
+
+/// Something the algorithm has been waiting for.
+enum Event {
+    /// The AAAA query has finished.
+    Aaaa(Result<Vec<SocketAddr>, io::Error>),
+
+    /// The A query has finished.
+    A(Result<Vec<SocketAddr>, io::Error>),
+
+    /// A connection attempt has finished.
+    Attempt(Result<TcpStream, io::Error>),
+
+    /// It is time to check whether to start the next attempt.
+    Wakeup,
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Extracts the socket addresses from the answer to an A or AAAA query.
+fn addrs<R: Resolver>(
+    answer: Result<R::Answer, io::Error>,
+    v6: bool,
+    port: u16,
+) -> Result<Vec<SocketAddr>, io::Error>
+where
+    for<'a> &'a R::Octets: OctetsRef,
+{
+    let answer = answer?;
+    let found = if v6 {
+        FoundHosts::<R>::new(Ok(answer), Err(not_found()))?
+    } else {
+        FoundHosts::<R>::new(Err(not_found()), Ok(answer))?
+    };
+    let res = found.port_iter(port).collect();
+    Ok(res)
+}
+
+fn not_found() -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, "no addresses found")
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::Class;
+    use crate::base::message::Message;
+    use crate::base::message_builder::MessageBuilder;
+    use crate::base::name::{Dname, RelativeDname};
+    use crate::base::question::Question;
+    use crate::rdata::{Aaaa, A};
+    use crate::resolv::lookup::srv::lookup_srv;
+    use crate::resolv::stub::Answer;
+    use bytes::Bytes;
+    use futures::future::BoxFuture;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+    use std::str::FromStr;
+    use tokio::net::TcpListener;
+
+    /// A resolver answering with fixed addresses after a delay.
+    ///
+    /// A missing address results in an error. SRV queries are answered
+    /// right away without any records.
+    struct Fake {
+        aaaa: Option<(Ipv6Addr, Duration)>,
+        a: Option<(Ipv4Addr, Duration)>,
+    }
+
+    impl<'a> Resolver for &'a Fake {
+        type Octets = Bytes;
+        type Answer = Answer;
+        type Query = BoxFuture<'static, Result<Answer, io::Error>>;
+
+        fn query<N, Q>(&self, question: Q) -> Self::Query
+        where
+            N: ToDname,
+            Q: Into<Question<N>>,
+        {
+            let question = question.into();
+            let qname: Dname<Bytes> = question.qname().to_bytes();
+            let qtype = question.qtype();
+            let aaaa = self.aaaa;
+            let a = self.a;
+            Box::pin(async move {
+                let mut msg = MessageBuilder::new_bytes();
+                msg.header_mut().set_qr(true);
+                let mut msg = msg.question();
+                msg.push((&qname, qtype)).unwrap();
+                let mut msg = msg.answer();
+                let delay = match (qtype, aaaa, a) {
+                    (Rtype::Aaaa, Some((addr, delay)), _) => {
+                        msg.push((&qname, Class::In, 60, Aaaa::new(addr)))
+                            .unwrap();
+                        delay
+                    }
+                    (Rtype::A, _, Some((addr, delay))) => {
+                        msg.push((&qname, Class::In, 60, A::new(addr)))
+                            .unwrap();
+                        delay
+                    }
+                    (Rtype::Srv, _, _) => Duration::from_millis(0),
+                    _ => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::Other,
+                            "no answer",
+                        ))
+                    }
+                };
+                tokio::time::sleep(delay).await;
+                let msg: Message<Bytes> = msg.into_message();
+                Ok(msg.into())
+            })
+        }
+    }
+
+    fn name() -> Dname<Bytes> {
+        Dname::from_str("www.example.com.").unwrap()
+    }
+
+    /// Returns the address of a local port nobody listens on.
+    async fn closed_port() -> SocketAddr {
+        TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap()
+    }
+
+    #[test]
+    fn interleave() {
+        let addrs: Vec<SocketAddr> = [
+            "192.0.2.1:53",
+            "192.0.2.2:53",
+            "192.0.2.3:53",
+            "[2001:db8::1]:53",
+        ]
+        .iter()
+        .map(|s| s.parse().unwrap())
+        .collect();
+        let mut attempts =
+            Attempts::new(&HappyEyeballs::new(), addrs.clone());
+        assert_eq!(attempts.next_addr(), Some(addrs[3]));
+        assert_eq!(attempts.next_addr(), Some(addrs[0]));
+        attempts.add(vec!["[2001:db8::2]:53".parse().unwrap()]);
+        assert_eq!(
+            attempts.next_addr(),
+            Some("[2001:db8::2]:53".parse().unwrap())
+        );
+        assert_eq!(attempts.next_addr(), Some(addrs[1]));
+        assert_eq!(attempts.next_addr(), Some(addrs[2]));
+        assert_eq!(attempts.next_addr(), None);
+    }
+
+    #[test]
+    fn attempt_delay() {
+        let conf = HappyEyeballs::new();
+        let mut attempts = Attempts::new(&conf, Vec::new());
+        assert_eq!(attempts.attempt_delay, conf.max_attempt_delay);
+        attempts.set_rtt(Duration::from_millis(1));
+        assert_eq!(attempts.attempt_delay, conf.min_attempt_delay);
+        attempts.set_rtt(Duration::from_millis(60));
+        assert_eq!(attempts.attempt_delay, Duration::from_millis(120));
+        attempts.set_rtt(Duration::from_secs(1));
+        assert_eq!(attempts.attempt_delay, conf.max_attempt_delay);
+    }
+
+    #[tokio::test]
+    async fn addrs_skip_failed() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let good = listener.local_addr().unwrap();
+        let bad = closed_port().await;
+        let stream = HappyEyeballs::new()
+            .connect_addrs(&[bad, bad, good])
+            .await
+            .unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), good);
+
+        let err = HappyEyeballs::new().connect_addrs(&[bad]).await;
+        assert!(err.is_err());
+        let err = HappyEyeballs::new().connect_addrs(&[]).await;
+        assert_eq!(err.unwrap_err().kind(), io::ErrorKind::NotFound);
+    }
+
+    #[tokio::test]
+    async fn host() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let good = listener.local_addr().unwrap();
+
+        // IPv6 is broken: the address is in the discard-only prefix.
+        let resolver = &Fake {
+            aaaa: Some(("100::1".parse().unwrap(), Duration::from_millis(0))),
+            a: Some((Ipv4Addr::LOCALHOST, Duration::from_millis(10))),
+        };
+        let stream =
+            connect_host(&resolver, name(), good.port()).await.unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), good);
+
+        // The AAAA answer is slow. We must not wait for it.
+        let resolver = &Fake {
+            aaaa: Some(("100::1".parse().unwrap(), Duration::from_secs(5))),
+            a: Some((Ipv4Addr::LOCALHOST, Duration::from_millis(0))),
+        };
+        let start = Instant::now();
+        let stream =
+            connect_host(&resolver, name(), good.port()).await.unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), good);
+        assert!(start.elapsed() < Duration::from_secs(2));
+
+        // No AAAA at all.
+        let resolver = &Fake {
+            aaaa: None,
+            a: Some((Ipv4Addr::LOCALHOST, Duration::from_millis(0))),
+        };
+        let stream =
+            connect_host(&resolver, name(), good.port()).await.unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), good);
+
+        // Nothing works.
+        let bad = closed_port().await;
+        let resolver = &Fake {
+            aaaa: None,
+            a: Some((Ipv4Addr::LOCALHOST, Duration::from_millis(0))),
+        };
+        assert!(connect_host(&resolver, name(), bad.port()).await.is_err());
+        let resolver = &Fake {
+            aaaa: None,
+            a: None,
+        };
+        assert_eq!(
+            connect_host(&resolver, name(), bad.port())
+                .await
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::Other
+        );
+    }
+
+    #[tokio::test]
+    async fn srv() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let good = listener.local_addr().unwrap();
+
+        // Without SRV records, the bare host and fallback port are used.
+        let resolver = &Fake {
+            aaaa: None,
+            a: Some((Ipv4Addr::LOCALHOST, Duration::from_millis(0))),
+        };
+        let service =
+            RelativeDname::from_slice(b"\x05_http\x04_tcp").unwrap();
+        let srvs = lookup_srv(&resolver, service, name(), good.port())
+            .await
+            .unwrap()
+            .unwrap();
+        let stream = connect_srv(&resolver, srvs).await.unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), good);
+    }
+}
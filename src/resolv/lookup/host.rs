@@ -7,7 +7,7 @@ use crate::base::octets::OctetsRef;
 use crate::rdata::{Aaaa, A};
 use crate::resolv::resolver::{Resolver, SearchNames};
 use std::io;
-use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs};
 
 //------------ lookup_host ---------------------------------------------------
 
@@ -24,7 +24,7 @@ pub async fn lookup_host<R: Resolver>(
     resolver: &R,
     qname: impl ToDname,
 ) -> Result<FoundHosts<R>, io::Error> {
-    let (a, aaaa) = tokio::join!(
+    let (a, aaaa) = futures::join!(
         resolver.query((&qname, Rtype::A)),
         resolver.query((&qname, Rtype::Aaaa)),
     );
@@ -66,6 +66,9 @@ pub struct FoundHosts<R: Resolver> {
 
     /// The answer to the A query.
     a: Result<R::Answer, io::Error>,
+
+    /// Whether to return IPv4 addresses as IPv4-mapped IPv6 addresses.
+    map_ipv4: bool,
 }
 
 impl<R: Resolver> FoundHosts<R> {
@@ -80,7 +83,22 @@ impl<R: Resolver> FoundHosts<R> {
             }
         }
 
-        Ok(FoundHosts { aaaa, a })
+        Ok(FoundHosts {
+            aaaa,
+            a,
+            map_ipv4: false,
+        })
+    }
+
+    /// Converts the value into one returning IPv4-mapped IPv6 addresses.
+    ///
+    /// When iterating over the addresses, IPv4 addresses will be returned
+    /// as IPv4-mapped IPv6 addresses as defined in RFC 4291.
+    pub fn map_ipv4(self) -> Self {
+        FoundHosts {
+            map_ipv4: true,
+            ..self
+        }
     }
 
     pub fn is_empty(&self) -> bool {
@@ -155,6 +173,7 @@ where
                     .and_then(|msg| msg.as_ref().answer().ok())
                     .map(|answer| answer.limit_to::<A>())
             },
+            map_ipv4: self.map_ipv4,
         }
     }
 
@@ -180,6 +199,7 @@ pub struct FoundHostsIter<Ref: OctetsRef> {
     a_name: Option<ParsedDname<Ref>>,
     aaaa: Option<RecordIter<Ref, Aaaa>>,
     a: Option<RecordIter<Ref, A>>,
+    map_ipv4: bool,
 }
 
 impl<Ref: OctetsRef> Iterator for FoundHostsIter<Ref> {
@@ -196,7 +216,11 @@ impl<Ref: OctetsRef> Iterator for FoundHostsIter<Ref> {
         while let Some(res) = self.a.as_mut().and_then(Iterator::next) {
             if let Ok(record) = res {
                 if Some(*record.owner()) == self.a_name {
-                    return Some(record.data().addr().into());
+                    let addr = record.data().addr();
+                    if self.map_ipv4 {
+                        return Some(map_ipv4(addr).into());
+                    }
+                    return Some(addr.into());
                 }
             }
         }
@@ -204,6 +228,21 @@ impl<Ref: OctetsRef> Iterator for FoundHostsIter<Ref> {
     }
 }
 
+/// Converts an IPv4 address into an IPv4-mapped IPv6 address.
+fn map_ipv4(addr: Ipv4Addr) -> Ipv6Addr {
+    let [a, b, c, d] = addr.octets();
+    Ipv6Addr::new(
+        0,
+        0,
+        0,
+        0,
+        0,
+        0xffff,
+        u16::from_be_bytes([a, b]),
+        u16::from_be_bytes([c, d]),
+    )
+}
+
 //------------ FoundHostsSocketIter ------------------------------------------
 
 /// An iterator over socket addresses derived from a host lookup.
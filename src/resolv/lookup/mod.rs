@@ -4,9 +4,16 @@
 //! implement applications of the DNS.
 
 pub use self::addr::lookup_addr;
+#[cfg(feature = "resolv")]
+pub use self::connect::{connect_host, connect_srv};
 pub use self::host::{lookup_host, search_host};
 pub use self::srv::lookup_srv;
+pub use self::svcb::{lookup_https, lookup_svcb};
 
 pub mod addr;
+#[cfg(feature = "resolv")]
+#[cfg_attr(docsrs, doc(cfg(feature = "resolv")))]
+pub mod connect;
 pub mod host;
 pub mod srv;
+pub mod svcb;
@@ -0,0 +1,831 @@
+//! Looking up service bindings via SVCB and HTTPS records.
+//!
+//! The functions in this module look up the SVCB or HTTPS records for a
+//! service as defined in [RFC 9460] and turn them into a list of
+//! connection endpoints in the order in which they should be tried.
+//!
+//! AliasMode records are followed to their target. The ServiceMode records
+//! found at the end of the chain are ordered by their priority, with
+//! records of equal priority shuffled randomly. Records with mandatory
+//! parameters not understood by this module are skipped. For each
+//! remaining record an endpoint is created that contains the target host
+//! and port, the set of ALPN protocol identifiers, and an ECH
+//! configuration if present.
+//!
+//! If there are no usable records, a single fallback endpoint for the
+//! origin host or the target of the last AliasMode record is provided
+//! instead.
+//!
+//! [RFC 9460]: https://tools.ietf.org/html/rfc9460
+
+use super::host::lookup_host;
+use crate::base::iana::{Class, Rtype, SvcbParamKey};
+use crate::base::message::Message;
+use crate::base::name::{
+    Dname, DnameBuilder, LongChainError, ParsedDname, PushError,
+    PushNameError, ToDname, ToRelativeDname,
+};
+use crate::base::octets::{OctetsRef, OctetsVec, Parse, ParseError, Parser};
+use crate::base::rdata::UnknownRecordData;
+use crate::rdata::svcb::param::AllParams;
+use crate::rdata::{Aaaa, Svcb, A};
+use crate::resolv::resolver::Resolver;
+use futures::stream;
+use futures::stream::{Stream, StreamExt};
+use rand::seq::SliceRandom;
+use std::net::{IpAddr, SocketAddr};
+use std::string::ToString;
+use std::vec::Vec;
+use std::{fmt, io, ops};
+
+/// The maximum number of AliasMode records we follow.
+const MAX_ALIASES: usize = 8;
+
+/// The default ALPN protocol of the HTTPS record.
+const HTTPS_DEFAULT_ALPN: &[u8] = b"http/1.1";
+
+//------------ lookup_svcb ---------------------------------------------------
+
+/// Looks up the SVCB records for a service.
+///
+/// The function uses `resolver` to query the DNS for SVCB records at the
+/// name formed by prefixing `host` with `service`. The latter typically
+/// consists of the port and scheme labels of the service as described in
+/// section 2.3 of RFC 9460, e.g., `_8053._foo`. The fallback endpoint
+/// refers to `host` and uses `fallback_port` which also serves as the port
+/// for endpoints that don’t specify one.
+///
+/// Since the default ALPN protocol depends on the protocol using the SVCB
+/// record, the endpoints only contain the protocols explicitly listed in
+/// the records.
+///
+/// The function returns `Ok(None)` if the service is “decidedly not
+/// available,” i.e., if there is an AliasMode record with the root name
+/// as its target.
+pub async fn lookup_svcb(
+    resolver: &impl Resolver,
+    service: impl ToRelativeDname,
+    host: impl ToDname,
+    fallback_port: u16,
+) -> Result<Option<FoundSvcb>, SvcbError> {
+    let qname = (&service).chain(&host)?.to_dname()?;
+    let host = host.to_dname()?;
+    lookup(resolver, Rtype::Svcb, qname, host, fallback_port, None).await
+}
+
+//------------ lookup_https --------------------------------------------------
+
+/// Looks up the HTTPS records for an HTTPS origin.
+///
+/// The function uses `resolver` to query the DNS for HTTPS records for the
+/// origin given by `host` and `port`. Following section 9.1 of RFC 9460,
+/// the record is looked up at `host` itself if `port` is 443 and at the
+/// port-prefixed name `_<port>._https.<host>` otherwise.
+///
+/// All endpoints except those with the no-default-alpn parameter include
+/// the default protocol `"http/1.1"` in their ALPN set.
+///
+/// The function returns `Ok(None)` if the service is “decidedly not
+/// available,” i.e., if there is an AliasMode record with the root name
+/// as its target.
+pub async fn lookup_https(
+    resolver: &impl Resolver,
+    host: impl ToDname,
+    port: u16,
+) -> Result<Option<FoundSvcb>, SvcbError> {
+    let host: Dname<OctetsVec> = host.to_dname()?;
+    let qname = if port == 443 {
+        host.clone()
+    } else {
+        let mut builder = DnameBuilder::<OctetsVec>::new();
+        builder.append_label(["_", &port.to_string()].concat().as_bytes())?;
+        builder.append_label(b"_https")?;
+        builder.append_origin(&host)?
+    };
+    lookup(
+        resolver,
+        Rtype::Https,
+        qname,
+        host,
+        port,
+        Some(HTTPS_DEFAULT_ALPN),
+    )
+    .await
+}
+
+/// Performs the actual lookup.
+///
+/// The SVCB-style records of type `rtype` are looked up at `qname`. The
+/// fallback endpoint uses `host` if there aren’t any AliasMode records.
+async fn lookup(
+    resolver: &impl Resolver,
+    rtype: Rtype,
+    mut qname: Dname<OctetsVec>,
+    mut host: Dname<OctetsVec>,
+    port: u16,
+    default_alpn: Option<&[u8]>,
+) -> Result<Option<FoundSvcb>, SvcbError> {
+    let mut seen = Vec::new();
+    loop {
+        let answer = resolver.query((&qname, rtype)).await?;
+        let answer = answer.as_ref().for_slice();
+        match Records::new(&answer, rtype)? {
+            Records::Alias(target) => {
+                if target.is_root() {
+                    return Ok(None);
+                }
+                if seen.len() == MAX_ALIASES || seen.contains(&target) {
+                    return Err(SvcbError::AliasChain);
+                }
+                seen.push(target.clone());
+                qname = target.clone();
+                host = target;
+            }
+            Records::Service(mut items) => {
+                FoundSvcb::process_additional(&mut items, &answer)?;
+                return Ok(Some(FoundSvcb::new(
+                    items,
+                    host,
+                    port,
+                    default_alpn,
+                )));
+            }
+        }
+    }
+}
+
+//------------ Records -------------------------------------------------------
+
+/// The usable records of an answer.
+enum Records {
+    /// There was an AliasMode record with the given target.
+    Alias(Dname<OctetsVec>),
+
+    /// These are the ServiceMode records.
+    ///
+    /// The items still need to be completed by `FoundSvcb::new`.
+    Service(Vec<SvcbItem>),
+}
+
+impl Records {
+    fn new(answer: &Message<&[u8]>, rtype: Rtype) -> Result<Self, SvcbError> {
+        let name =
+            answer.canonical_name().ok_or(SvcbError::MalformedAnswer)?;
+        let mut items = Vec::new();
+        for record in answer
+            .answer()?
+            .limit_to_in::<UnknownRecordData<_>>()
+            .flatten()
+        {
+            if record.rtype() != rtype || *record.owner() != name {
+                continue;
+            }
+            // SVCB records are not compressed, so we can parse the record
+            // data on its own. This works for all types of the SVCB family.
+            let data = match Svcb::parse(&mut Parser::from_ref(
+                *record.data().data(),
+            )) {
+                Ok(data) => data,
+                Err(_) => continue,
+            };
+            if data.priority() == 0 {
+                // AliasMode records trump everything else.
+                return Ok(Records::Alias(data.target().to_dname()?));
+            }
+            if let Some(item) = SvcbItem::from_rdata(&data, &name) {
+                items.push(item)
+            }
+        }
+        Ok(Records::Service(items))
+    }
+}
+
+//------------ FoundSvcb -----------------------------------------------------
+
+/// The endpoints found by an SVCB or HTTPS lookup.
+///
+/// The endpoints are kept in the order they should be tried in. You can
+/// look at them via the [`iter`] method or resolve their addresses via
+/// [`into_stream`].
+///
+/// [`iter`]: #method.iter
+/// [`into_stream`]: #method.into_stream
+#[derive(Clone, Debug)]
+pub struct FoundSvcb {
+    /// The endpoints.
+    ///
+    /// This is never empty.
+    items: Vec<SvcbItem>,
+}
+
+impl FoundSvcb {
+    fn new(
+        mut items: Vec<SvcbItem>,
+        host: Dname<OctetsVec>,
+        port: u16,
+        default_alpn: Option<&[u8]>,
+    ) -> Self {
+        if items.is_empty() {
+            items.push(SvcbItem::fallback(host, port));
+        }
+        for item in &mut items {
+            if item.port.is_none() {
+                item.port = Some(port)
+            }
+            if let Some(alpn) = default_alpn {
+                if !item.no_default_alpn
+                    && !item.alpn.iter().any(|x| x == alpn)
+                {
+                    item.alpn.push(alpn.into())
+                }
+            }
+        }
+        Self::reorder_items(&mut items);
+        FoundSvcb { items }
+    }
+
+    /// Returns whether the endpoint is only the fallback endpoint.
+    ///
+    /// This is the case if no usable ServiceMode records were found.
+    pub fn is_fallback(&self) -> bool {
+        self.items[0].fallback
+    }
+
+    /// Returns an iterator over the endpoints.
+    pub fn iter(&self) -> impl Iterator<Item = &SvcbItem> {
+        self.items.iter()
+    }
+
+    /// Converts the value into a stream of endpoints with their addresses.
+    ///
+    /// The addresses of an endpoint are taken from the additional section
+    /// of the answer if present and are looked up otherwise. If that
+    /// doesn’t produce any addresses, the address hints of the record are
+    /// used instead.
+    pub fn into_stream<R: Resolver>(
+        self,
+        resolver: &R,
+    ) -> impl Stream<Item = Result<ResolvedSvcbItem, io::Error>> + '_
+    where
+        for<'a> &'a R::Octets: OctetsRef,
+    {
+        stream::iter(self.items).then(move |item| item.resolve(resolver))
+    }
+
+    fn process_additional(
+        items: &mut [SvcbItem],
+        answer: &Message<&[u8]>,
+    ) -> Result<(), SvcbError> {
+        let additional = answer.additional()?;
+        for item in items {
+            let mut addrs = Vec::new();
+            for record in additional {
+                let record = match record {
+                    Ok(record) => record,
+                    Err(_) => continue,
+                };
+                if record.class() != Class::In
+                    || record.owner() != item.target()
+                {
+                    continue;
+                }
+                if let Ok(Some(record)) = record.to_record::<A>() {
+                    addrs.push(record.data().addr().into())
+                }
+                if let Ok(Some(record)) = record.to_record::<Aaaa>() {
+                    addrs.push(record.data().addr().into())
+                }
+            }
+            if !addrs.is_empty() {
+                item.resolved = Some(addrs)
+            }
+        }
+        Ok(())
+    }
+
+    /// Orders items by priority, shuffling items of equal priority.
+    fn reorder_items(items: &mut [SvcbItem]) {
+        items.sort_by_key(|item| item.priority);
+        let mut rng = rand::thread_rng();
+        let mut start = 0;
+        while start < items.len() {
+            let priority = items[start].priority;
+            let end = items[start..]
+                .iter()
+                .position(|item| item.priority != priority)
+                .map(|len| start + len)
+                .unwrap_or_else(|| items.len());
+            items[start..end].shuffle(&mut rng);
+            start = end;
+        }
+    }
+}
+
+//------------ SvcbItem ------------------------------------------------------
+
+/// A connection endpoint derived from a ServiceMode record.
+#[derive(Clone, Debug)]
+pub struct SvcbItem {
+    /// The priority of the record.
+    priority: u16,
+
+    /// The target host.
+    target: Dname<OctetsVec>,
+
+    /// The port if known.
+    ///
+    /// This is only `None` during construction.
+    port: Option<u16>,
+
+    /// The ALPN protocol identifiers.
+    alpn: Vec<Vec<u8>>,
+
+    /// Was the no-default-alpn parameter present?
+    no_default_alpn: bool,
+
+    /// The ECH configuration list.
+    ech: Option<Vec<u8>>,
+
+    /// The address hints.
+    hints: Vec<IpAddr>,
+
+    /// Is this the fallback endpoint?
+    fallback: bool,
+
+    /// Addresses from the additional section if we have any.
+    resolved: Option<Vec<IpAddr>>,
+}
+
+impl SvcbItem {
+    /// Creates an item from record data.
+    ///
+    /// Returns `None` if the record is unusable because it is broken or has
+    /// mandatory parameters we don’t understand.
+    fn from_rdata(
+        data: &Svcb<&[u8], ParsedDname<&[u8]>>,
+        owner: &impl ToDname,
+    ) -> Option<Self> {
+        let target = if data.target().is_root() {
+            owner.to_dname().ok()?
+        } else {
+            data.target().to_dname().ok()?
+        };
+        let mut res = SvcbItem {
+            priority: data.priority(),
+            target,
+            port: None,
+            alpn: Vec::new(),
+            no_default_alpn: false,
+            ech: None,
+            hints: Vec::new(),
+            fallback: false,
+            resolved: None,
+        };
+        for param in data.iter() {
+            match param.ok()? {
+                AllParams::Mandatory(keys) => {
+                    for key in keys.iter() {
+                        if !Self::is_supported(key.ok()?) {
+                            return None;
+                        }
+                    }
+                }
+                AllParams::Alpn(alpn) => {
+                    for id in alpn.iter() {
+                        res.alpn.push(id.ok()?.into())
+                    }
+                }
+                AllParams::NoDefaultAlpn(_) => res.no_default_alpn = true,
+                AllParams::Port(port) => res.port = Some(port.port()),
+                AllParams::Ech(ech) => res.ech = Some(ech.as_slice().into()),
+                AllParams::Ipv4Hint(hint) => {
+                    for addr in hint.iter() {
+                        res.hints.push(addr.ok()?.into())
+                    }
+                }
+                AllParams::Ipv6Hint(hint) => {
+                    for addr in hint.iter() {
+                        res.hints.push(addr.ok()?.into())
+                    }
+                }
+                _ => {}
+            }
+        }
+        Some(res)
+    }
+
+    fn fallback(host: Dname<OctetsVec>, port: u16) -> Self {
+        SvcbItem {
+            priority: 0,
+            target: host,
+            port: Some(port),
+            alpn: Vec::new(),
+            no_default_alpn: false,
+            ech: None,
+            hints: Vec::new(),
+            fallback: true,
+            resolved: None,
+        }
+    }
+
+    /// Returns whether we understand the parameter with the given key.
+    fn is_supported(key: SvcbParamKey) -> bool {
+        matches!(
+            key,
+            SvcbParamKey::Mandatory
+                | SvcbParamKey::Alpn
+                | SvcbParamKey::NoDefaultAlpn
+                | SvcbParamKey::Port
+                | SvcbParamKey::Ipv4Hint
+                | SvcbParamKey::Ech
+                | SvcbParamKey::Ipv6Hint
+        )
+    }
+
+    /// Returns the priority of the record.
+    ///
+    /// The fallback endpoint has a priority of 0.
+    pub fn priority(&self) -> u16 {
+        self.priority
+    }
+
+    /// Returns the host name to connect to.
+    pub fn target(&self) -> &Dname<OctetsVec> {
+        &self.target
+    }
+
+    /// Returns the port to connect to.
+    pub fn port(&self) -> u16 {
+        self.port.unwrap_or(0)
+    }
+
+    /// Returns the ALPN protocol identifiers supported by the endpoint.
+    pub fn alpn(&self) -> &[Vec<u8>] {
+        &self.alpn
+    }
+
+    /// Returns the ECH configuration list if there is one.
+    pub fn ech(&self) -> Option<&[u8]> {
+        self.ech.as_deref()
+    }
+
+    /// Returns the address hints of the record.
+    pub fn hints(&self) -> &[IpAddr] {
+        &self.hints
+    }
+
+    /// Returns whether this is the fallback endpoint.
+    ///
+    /// The fallback endpoint is used if there are no usable records. It
+    /// refers to the origin host or the target of the last AliasMode
+    /// record.
+    pub fn is_fallback(&self) -> bool {
+        self.fallback
+    }
+
+    /// Resolves the addresses of the endpoint.
+    ///
+    /// If the addresses were included in the additional section of the
+    /// answer, they are used. Otherwise, the addresses are looked up. If
+    /// that fails or doesn’t produce any addresses, the address hints are
+    /// used.
+    pub async fn resolve<R: Resolver>(
+        self,
+        resolver: &R,
+    ) -> Result<ResolvedSvcbItem, io::Error>
+    where
+        for<'a> &'a R::Octets: OctetsRef,
+    {
+        let addrs = match self.resolved.clone() {
+            Some(addrs) => addrs,
+            None => match lookup_host(resolver, self.target()).await {
+                Ok(found) => found.iter().collect(),
+                Err(err) => {
+                    if self.hints.is_empty() {
+                        return Err(err);
+                    }
+                    Vec::new()
+                }
+            },
+        };
+        let addrs = if addrs.is_empty() {
+            self.hints.clone()
+        } else {
+            addrs
+        };
+        let port = self.port();
+        Ok(ResolvedSvcbItem {
+            resolved: addrs
+                .into_iter()
+                .map(|addr| SocketAddr::new(addr, port))
+                .collect(),
+            item: self,
+        })
+    }
+}
+
+//------------ ResolvedSvcbItem ----------------------------------------------
+
+/// A connection endpoint together with its socket addresses.
+#[derive(Clone, Debug)]
+pub struct ResolvedSvcbItem {
+    item: SvcbItem,
+    resolved: Vec<SocketAddr>,
+}
+
+impl ResolvedSvcbItem {
+    /// Returns the socket addresses of the endpoint.
+    pub fn resolved(&self) -> &[SocketAddr] {
+        &self.resolved
+    }
+}
+
+impl AsRef<SvcbItem> for ResolvedSvcbItem {
+    fn as_ref(&self) -> &SvcbItem {
+        &self.item
+    }
+}
+
+impl ops::Deref for ResolvedSvcbItem {
+    type Target = SvcbItem;
+
+    fn deref(&self) -> &Self::Target {
+        self.as_ref()
+    }
+}
+
+//------------ SvcbError -----------------------------------------------------
+
+/// An error happened during an SVCB or HTTPS lookup.
+#[derive(Debug)]
+pub enum SvcbError {
+    /// A name was too long.
+    LongName,
+
+    /// The answer was malformed.
+    MalformedAnswer,
+
+    /// The AliasMode records formed a loop or a chain that was too long.
+    AliasChain,
+
+    /// The query failed.
+    Query(io::Error),
+}
+
+impl From<io::Error> for SvcbError {
+    fn from(err: io::Error) -> SvcbError {
+        SvcbError::Query(err)
+    }
+}
+
+impl From<ParseError> for SvcbError {
+    fn from(_: ParseError) -> SvcbError {
+        SvcbError::MalformedAnswer
+    }
+}
+
+impl From<PushError> for SvcbError {
+    fn from(_: PushError) -> SvcbError {
+        SvcbError::LongName
+    }
+}
+
+impl From<LongChainError> for SvcbError {
+    fn from(_: LongChainError) -> SvcbError {
+        SvcbError::LongName
+    }
+}
+
+impl From<PushNameError> for SvcbError {
+    fn from(_: PushNameError) -> SvcbError {
+        SvcbError::LongName
+    }
+}
+
+impl fmt::Display for SvcbError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SvcbError::LongName => f.write_str("name too long"),
+            SvcbError::MalformedAnswer => f.write_str("malformed answer"),
+            SvcbError::AliasChain => f.write_str("broken alias chain"),
+            SvcbError::Query(ref err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for SvcbError {}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::Rcode;
+    use crate::base::message_builder::MessageBuilder;
+    use crate::base::question::Question;
+    use crate::rdata::svcb::param::{
+        Alpn, Ipv6Hint, Mandatory, NoDefaultAlpn, Port,
+    };
+    use crate::rdata::Https;
+    use crate::resolv::stub::Answer;
+    use bytes::Bytes;
+    use std::collections::HashMap;
+    use std::str::FromStr;
+    use std::string::String;
+    use std::vec;
+
+    type Rdata = Https<Vec<u8>, Dname<Vec<u8>>>;
+
+    /// A resolver answering from a fixed set of records.
+    #[derive(Default)]
+    struct Fake {
+        https: HashMap<String, Vec<Rdata>>,
+        a: HashMap<String, A>,
+    }
+
+    impl Fake {
+        fn https(&mut self, owner: &str, data: Rdata) -> &mut Self {
+            self.https.entry(owner.into()).or_default().push(data);
+            self
+        }
+
+        fn a(&mut self, owner: &str, addr: &str) -> &mut Self {
+            self.a.insert(owner.into(), A::from_str(addr).unwrap());
+            self
+        }
+    }
+
+    impl<'a> Resolver for &'a Fake {
+        type Octets = Bytes;
+        type Answer = Answer;
+        type Query = futures::future::Ready<Result<Answer, io::Error>>;
+
+        fn query<N, Q>(&self, question: Q) -> Self::Query
+        where
+            N: ToDname,
+            Q: Into<Question<N>>,
+        {
+            let question = question.into();
+            let qname: Dname<Vec<u8>> = question.qname().to_dname().unwrap();
+            let key = qname.to_string();
+            let mut msg = MessageBuilder::new_bytes();
+            msg.header_mut().set_qr(true);
+            msg.header_mut().set_rcode(Rcode::NoError);
+            let mut msg = msg.question();
+            msg.push((&qname, question.qtype())).unwrap();
+            let mut msg = msg.answer();
+            match question.qtype() {
+                Rtype::Https => {
+                    for data in self.https.get(&key).into_iter().flatten() {
+                        msg.push((&qname, 60, data.clone())).unwrap();
+                    }
+                }
+                Rtype::A => {
+                    if let Some(data) = self.a.get(&key) {
+                        msg.push((&qname, 60, data.clone())).unwrap();
+                    }
+                }
+                _ => {}
+            }
+            let msg: Message<Bytes> = msg.into_message();
+            futures::future::ready(Ok(msg.into()))
+        }
+    }
+
+    fn name(s: &str) -> Dname<Vec<u8>> {
+        if s == "." {
+            Dname::root_vec()
+        } else {
+            Dname::from_str(s).unwrap()
+        }
+    }
+
+    fn https(priority: u16, target: &str) -> Rdata {
+        Https::new(priority, name(target), Vec::new())
+    }
+
+    fn alpn(ids: &[&str]) -> AllParams<Vec<u8>> {
+        let mut alpn = Alpn::new(Vec::new());
+        for id in ids {
+            alpn.push(id).unwrap();
+        }
+        alpn.into()
+    }
+
+    fn alpn_of(item: &SvcbItem) -> Vec<&str> {
+        item.alpn()
+            .iter()
+            .map(|id| std::str::from_utf8(id).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn service_mode() {
+        let mut resolver = Fake::default();
+        let mut first = https(1, "svc.example.net.");
+        first.push(alpn(&["h3"])).unwrap();
+        first
+            .push(AllParams::<Vec<u8>>::NoDefaultAlpn(NoDefaultAlpn))
+            .unwrap();
+        let mut hint = Ipv6Hint::new(Vec::new());
+        hint.push("2001:db8::1".parse().unwrap()).unwrap();
+        first.push(hint.into()).unwrap();
+        let mut second = https(2, ".");
+        second.push(alpn(&["h2"])).unwrap();
+        second
+            .push(AllParams::<Vec<u8>>::Port(Port::new(8443)))
+            .unwrap();
+        second
+            .push(AllParams::Ech(crate::rdata::svcb::param::Ech::new(vec![
+                1, 2, 3,
+            ])))
+            .unwrap();
+        let mut broken = https(3, "broken.example.net.");
+        let mut mandatory = Mandatory::new(Vec::new());
+        mandatory.push(SvcbParamKey::from(65000)).unwrap();
+        broken.push(mandatory.into()).unwrap();
+        resolver
+            .https("example.com", broken)
+            .https("example.com", second)
+            .https("example.com", first)
+            .a("example.com", "192.0.2.1");
+        let resolver = &resolver;
+
+        let found = lookup_https(&resolver, name("example.com."), 443)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!found.is_fallback());
+        let items: Vec<_> = found.iter().collect();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].priority(), 1);
+        assert_eq!(items[0].target(), &name("svc.example.net."));
+        assert_eq!(items[0].port(), 443);
+        assert_eq!(alpn_of(items[0]), ["h3"]);
+        assert_eq!(items[0].ech(), None);
+        assert_eq!(items[1].target(), &name("example.com."));
+        assert_eq!(items[1].port(), 8443);
+        assert_eq!(alpn_of(items[1]), ["h2", "http/1.1"]);
+        assert_eq!(items[1].ech(), Some(&[1u8, 2, 3][..]));
+
+        let resolved: Vec<_> = found
+            .into_stream(&resolver)
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert_eq!(
+            resolved[0].resolved(),
+            &["[2001:db8::1]:443".parse().unwrap()]
+        );
+        assert_eq!(
+            resolved[1].resolved(),
+            &["192.0.2.1:8443".parse().unwrap()]
+        );
+    }
+
+    #[tokio::test]
+    async fn alias_mode() {
+        let mut resolver = Fake::default();
+        resolver
+            .https("example.com", https(0, "pool.example.net."))
+            .https("example.com", https(1, "ignored.example.com."))
+            .https("_8080._https.example.com", https(0, "."))
+            .https("loop.example.com", https(0, "loop2.example.com."))
+            .https("loop2.example.com", https(0, "loop.example.com."));
+        let resolver = &resolver;
+
+        // Alias without ServiceMode records at the target: fallback.
+        let found = lookup_https(&resolver, name("example.com."), 443)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(found.is_fallback());
+        let items: Vec<_> = found.iter().collect();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].target(), &name("pool.example.net."));
+        assert_eq!(items[0].port(), 443);
+        assert_eq!(alpn_of(items[0]), ["http/1.1"]);
+
+        // Alias to root: no service.
+        assert!(lookup_https(&resolver, name("example.com."), 8080)
+            .await
+            .unwrap()
+            .is_none());
+
+        // Alias loop.
+        assert!(matches!(
+            lookup_https(&resolver, name("loop.example.com."), 443).await,
+            Err(SvcbError::AliasChain)
+        ));
+
+        // No records at all.
+        let found = lookup_https(&resolver, name("www.example.com."), 8443)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(found.is_fallback());
+        let item = found.iter().next().unwrap();
+        assert_eq!(item.target(), &name("www.example.com."));
+        assert_eq!(item.port(), 8443);
+    }
+}
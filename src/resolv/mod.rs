@@ -19,6 +19,24 @@
 //!    See the [stub] module for more information on how to use the stub
 //!    resolver.
 //!
+//! *  [`SyncStubResolver`] is a stub resolver that uses blocking sockets
+//!    instead of an async runtime. It doesn’t implement all transports but
+//!    otherwise behaves just like the stub resolver. It is available
+//!    without Tokio via the `resolv-blocking` feature.
+//!
+//!    See the [sync] module for more information.
+//!
+//! *  [`HostsResolver`] wraps another resolver and answers questions from
+//!    local sources such as the hosts file before asking the DNS.
+//!
+//!    See the [hosts] module for more information.
+//!
+//! *  [`CachingResolver`] wraps another resolver and keeps the answers it
+//!    receives in a cache so that repeated questions can be answered
+//!    without asking the other resolver again.
+//!
+//!    See the [cache] module for more information on the cache.
+//!
 //! The lookups implemented by the crate are generic over the particular
 //! resolver, so you can pick the resolver most suitable for your own
 //! application or even implement your own specialised resolver. All
@@ -26,16 +44,26 @@
 //! convenience, they are also available as methods on the [`Resolver`]
 //! trait.
 //!
+//! [cache]: cache/index.html
+//! [hosts]: hosts/index.html
 //! [lookup]: lookup/index.html
 //! [stub]: stub/index.html
+//! [sync]: stub/sync/index.html
+//! [`CachingResolver`]: cache/struct.CachingResolver.html
+//! [`HostsResolver`]: hosts/struct.HostsResolver.html
 //! [`Resolver`]: resolver/trait.Resolver.html
 //! [`StubResolver`]: stub/struct.StubResolver.html
-#![cfg(feature = "resolv")]
-#![cfg_attr(docsrs, doc(cfg(feature = "resolv")))]
+//! [`SyncStubResolver`]: stub/sync/struct.SyncStubResolver.html
+#![cfg(feature = "resolv-blocking")]
+#![cfg_attr(docsrs, doc(cfg(feature = "resolv-blocking")))]
 
+pub use self::cache::CachingResolver;
+pub use self::hosts::HostsResolver;
 pub use self::resolver::Resolver;
-pub use self::stub::StubResolver;
+pub use self::stub::{StubResolver, SyncStubResolver};
 
+pub mod cache;
+pub mod hosts;
 pub mod lookup;
 pub mod resolver;
 pub mod stub;
@@ -9,6 +9,9 @@
 //! Both parts are modeled along the lines of glibc’s resolver.
 
 use crate::base::name::{self, Dname};
+use crate::base::opt::ClientSubnet;
+#[cfg(feature = "resolv-tls")]
+use bytes::Bytes;
 use smallvec::SmallVec;
 use std::cmp::Ordering;
 use std::default::Default;
@@ -16,6 +19,8 @@ use std::io::Read;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::path::Path;
 use std::str::{self, FromStr, SplitWhitespace};
+#[cfg(feature = "resolv-https")]
+use std::string::String;
 use std::time::Duration;
 use std::vec::Vec;
 use std::{convert, error, fmt, fs, io, ops};
@@ -50,8 +55,7 @@ pub struct ResolvOptions {
     /// Only responses with the AA bit set will be considered. If there
     /// aren’t any, the query will fail.
     ///
-    /// This option is not currently implemented. It is likely to be
-    /// eventually implemented by the query.
+    /// This option is implemented by the query.
     pub aa_only: bool,
 
     /// Always use TCP.
@@ -61,8 +65,11 @@ pub struct ResolvOptions {
 
     /// Query primary name servers only.
     ///
-    /// This option is not currently implemented. It is unclear what exactly
-    /// it is supposed to mean.
+    /// If enabled, only the first configured server is asked and the
+    /// `rotate` option is ignored. If stream transports have to be used,
+    /// only the first server with a stream transport is asked.
+    ///
+    /// This option is implemented by the query.
     pub primary: bool,
 
     /// Ignore trunactions errors, don’t retry with TCP.
@@ -74,36 +81,40 @@ pub struct ResolvOptions {
     ///
     /// Enabled by default.
     ///
-    /// Implemented by the query request.
+    /// This option is implemented by the query.
     pub recurse: bool,
 
     /// Append the default domain name to single component names.
     ///
     /// Enabled by default.
     ///
-    /// This is not currently implemented. Instead, the resolver config’s
-    /// `search` and `ndots` fields govern resolution of relative names of
-    /// all kinds.
+    /// This option is implemented by the stub resolver’s `search_host`
+    /// method which applies the search list to names without dots only if
+    /// this option is set.
     pub default_names: bool,
 
     /// Keep TCP connections open between queries.
     ///
-    /// This is not currently implemented.
+    /// If enabled, the resolver keeps a single TCP connection to each
+    /// server open and sends all queries to that server over it, without
+    /// waiting for earlier responses. The connection is closed once it has
+    /// been idle for the server’s `idle_timeout` and re-established when
+    /// needed. Otherwise, a new connection is used for each query.
     pub stay_open: bool,
 
     /// Search hostnames in the current domain and parent domains.
     ///
     /// Enabled by default.
     ///
-    /// This options is not currently implemented. Instead, the resolver
-    /// config’s `search` and `ndots` fields govern resolution of relative
-    /// names.
+    /// This option is implemented by the stub resolver’s `search_host`
+    /// method which applies the search list to names with dots only if
+    /// this option is set.
     pub dn_search: bool,
 
     /// Try AAAA query before A query and map IPv4 responses to tunnel form.
     ///
-    /// This option is not currently implemented. It is only relevant for
-    /// `lookup_host`.
+    /// This option is implemented by the stub resolver’s `lookup_host`
+    /// method.
     pub use_inet6: bool,
 
     /// Use round-robin selection of name servers.
@@ -125,7 +136,9 @@ pub struct ResolvOptions {
 
     /// Send each query simultaneously to all name servers.
     ///
-    /// This is not currently implemented. It would be a query option.
+    /// The first acceptable answer is used.
+    ///
+    /// This option is implemented by the query.
     pub blast: bool,
 
     /// Use bit-label format for IPv6 reverse lookups.
@@ -144,26 +157,38 @@ pub struct ResolvOptions {
 
     /// Use EDNS0.
     ///
-    /// EDNS is not yet supported.
+    /// This option is ignored. The resolver always uses EDNS and turns it
+    /// off for a server that answers with FORMERR.
     pub use_edns0: bool,
 
     /// Perform IPv4 and IPv6 lookups sequentially instead of in parallel.
     ///
-    /// This is not yet implemented but would be an option for
-    /// `lookup_host()`.
+    /// This option is implemented by the stub resolver’s `lookup_host`
+    /// method.
     pub single_request: bool,
 
     /// Open a new socket for each request.
     ///
-    /// This is not currently implemented.
+    /// Like `single_request`, this makes the stub resolver’s `lookup_host`
+    /// method send the A and AAAA queries one after another. Since the
+    /// resolver always uses a new UDP socket for each query, the second
+    /// query is sent from a new socket.
     pub single_request_reopen: bool,
 
     /// Don’t look up unqualified names as top-level-domain.
     ///
-    /// This is not currently implemented. Instead, the resolver config’s
-    /// `search` and `ndots` fields govern resolution of relative names of
-    /// all kinds.
+    /// This option is implemented by the stub resolver’s `search_host`
+    /// method which won’t try names without dots as absolute names if this
+    /// option is set.
     pub no_tld_query: bool,
+
+    /// Include the EDNS Client Subnet option in queries.
+    ///
+    /// Disabled by default.
+    ///
+    /// This option is copied to all servers that don’t have their own
+    /// `client_subnet` when the configuration is finalized.
+    pub client_subnet: Option<ClientSubnetConf>,
 }
 
 impl Default for ResolvOptions {
@@ -198,6 +223,7 @@ impl Default for ResolvOptions {
             single_request: false,
             single_request_reopen: false,
             no_tld_query: false,
+            client_subnet: None,
         }
     }
 }
@@ -205,13 +231,41 @@ impl Default for ResolvOptions {
 //------------ Transport -----------------------------------------------------
 
 /// The transport protocol to be used for a server.
+///
+/// The encrypted transports are only available if their features are
+/// enabled. The type is marked as non-exhaustive so that enabling them
+/// doesn’t break code matching on it.
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
 pub enum Transport {
     /// Unencrypted UDP transport.
     Udp,
 
     /// Unencrypted TCP transport.
     Tcp,
+
+    /// DNS-over-TLS transport as defined in [RFC 7858].
+    ///
+    /// How the server is authenticated is determined by the server’s
+    /// [`TlsConf`].
+    ///
+    /// [RFC 7858]: https://tools.ietf.org/html/rfc7858
+    /// [`TlsConf`]: struct.TlsConf.html
+    #[cfg(feature = "resolv-tls")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "resolv-tls")))]
+    Tls,
+
+    /// DNS-over-HTTPS transport as defined in [RFC 8484].
+    ///
+    /// Queries are sent via HTTP/2 to the URI given by the server’s
+    /// [`HttpsConf`]. The server is authenticated via its [`TlsConf`].
+    ///
+    /// [RFC 8484]: https://tools.ietf.org/html/rfc8484
+    /// [`HttpsConf`]: struct.HttpsConf.html
+    /// [`TlsConf`]: struct.TlsConf.html
+    #[cfg(feature = "resolv-https")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "resolv-https")))]
+    Https,
 }
 
 impl Transport {
@@ -224,6 +278,10 @@ impl Transport {
         match self {
             Transport::Udp => true,
             Transport::Tcp => false,
+            #[cfg(feature = "resolv-tls")]
+            Transport::Tls => false,
+            #[cfg(feature = "resolv-https")]
+            Transport::Https => false,
         }
     }
 
@@ -232,6 +290,10 @@ impl Transport {
         match self {
             Transport::Udp => false,
             Transport::Tcp => true,
+            #[cfg(feature = "resolv-tls")]
+            Transport::Tls => true,
+            #[cfg(feature = "resolv-https")]
+            Transport::Https => true,
         }
     }
 }
@@ -246,7 +308,15 @@ impl Transport {
 /// request and sockets. The timeouts are used for all transports. If you
 /// need different timeouts for, say, UDP and TCP, you can always use two
 /// server entries with the same address.
+///
+/// Some fields are only available if the features for their transports
+/// are enabled. The type is marked as non-exhaustive so that enabling
+/// them doesn’t break code. Values need to be created via [`new`] and
+/// can then be modified via the public fields.
+///
+/// [`new`]: #method.new
 #[derive(Clone, Debug)]
+#[non_exhaustive]
 pub struct ServerConf {
     /// Server address.
     pub addr: SocketAddr,
@@ -277,6 +347,40 @@ pub struct ServerConf {
     /// server. It will be included both for datagram and streaming transport
     /// but really only matters for UDP.
     pub udp_payload_size: u16,
+
+    /// How long to keep an idle connection open for later queries.
+    ///
+    /// This is only used by transports that keep their connections open
+    /// which currently are TLS and HTTPS as well as TCP if the `stay_open`
+    /// option is enabled. If the server announces a shorter
+    /// timeout via the edns-tcp-keepalive option, that value is used
+    /// instead. A timeout of zero closes the connection as soon as all
+    /// outstanding responses have arrived.
+    ///
+    /// This field defaults to 10 seconds.
+    pub idle_timeout: Duration,
+
+    /// The configuration for the TLS transport.
+    ///
+    /// This is also used by the HTTPS transport and ignored for all other
+    /// transports.
+    #[cfg(feature = "resolv-tls")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "resolv-tls")))]
+    pub tls: TlsConf,
+
+    /// The configuration for the HTTPS transport.
+    ///
+    /// This is ignored for all other transports.
+    #[cfg(feature = "resolv-https")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "resolv-https")))]
+    pub https: HttpsConf,
+
+    /// The EDNS Client Subnet option to include in queries.
+    ///
+    /// If this is `None`, no option is included. This field defaults to
+    /// `None` but is set to the `client_subnet` option of the resolver
+    /// configuration when it is finalized.
+    pub client_subnet: Option<ClientSubnetConf>,
 }
 
 impl ServerConf {
@@ -291,10 +395,179 @@ impl ServerConf {
             request_timeout: Duration::from_secs(2),
             recv_size: 1232,
             udp_payload_size: 1232,
+            idle_timeout: Duration::from_secs(10),
+            #[cfg(feature = "resolv-tls")]
+            tls: TlsConf::default(),
+            #[cfg(feature = "resolv-https")]
+            https: HttpsConf::default(),
+            client_subnet: None,
         }
     }
 }
 
+//------------ ClientSubnetConf ----------------------------------------------
+
+/// Configuration for the EDNS Client Subnet option.
+///
+/// If configured, the resolver includes the EDNS Client Subnet option
+/// defined in [RFC 7871] in its queries. The option contains `addr`
+/// truncated to the prefix length for its address family. For privacy
+/// reasons, RFC 7871 recommends to use no more than 24 bits for IPv4 and
+/// 56 bits for IPv6 which are the defaults.
+///
+/// A prefix length of zero asks the server not to use the client’s
+/// address for tailoring its answer at all.
+///
+/// [RFC 7871]: https://tools.ietf.org/html/rfc7871
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct ClientSubnetConf {
+    /// The address of the client.
+    pub addr: IpAddr,
+
+    /// The source prefix length to use for an IPv4 address.
+    ///
+    /// This field defaults to 24.
+    pub ipv4_prefix_len: u8,
+
+    /// The source prefix length to use for an IPv6 address.
+    ///
+    /// This field defaults to 56.
+    pub ipv6_prefix_len: u8,
+}
+
+impl ClientSubnetConf {
+    /// Creates a new configuration for the given address.
+    ///
+    /// The prefix lengths are set to their defaults.
+    pub fn new(addr: IpAddr) -> Self {
+        ClientSubnetConf {
+            addr,
+            ipv4_prefix_len: 24,
+            ipv6_prefix_len: 56,
+        }
+    }
+
+    /// Returns the source prefix length for the address.
+    pub fn source_prefix_len(&self) -> u8 {
+        match self.addr {
+            IpAddr::V4(_) => self.ipv4_prefix_len,
+            IpAddr::V6(_) => self.ipv6_prefix_len,
+        }
+    }
+
+    /// Returns the option to include in queries.
+    pub fn to_option(&self) -> ClientSubnet {
+        ClientSubnet::new(self.source_prefix_len(), 0, self.addr)
+    }
+}
+
+//------------ TlsConf -------------------------------------------------------
+
+/// Configuration for the TLS transport of a server.
+///
+/// The server’s certificate is checked against the authentication domain
+/// name given in `auth_name` or, if that is missing, against the host of
+/// the URI for HTTPS or the server’s IP address for TLS. What happens if
+/// this check fails depends on the privacy profile used.
+#[cfg(feature = "resolv-tls")]
+#[cfg_attr(docsrs, doc(cfg(feature = "resolv-tls")))]
+#[derive(Clone, Debug, Default)]
+pub struct TlsConf {
+    /// The authentication domain name of the server.
+    pub auth_name: Option<Dname<Bytes>>,
+
+    /// The privacy profile to use with the server.
+    pub profile: PrivacyProfile,
+
+    /// The trust anchors for authenticating the server.
+    ///
+    /// If this is `None`, the Mozilla root certificates as provided by the
+    /// webpki-roots crate are used.
+    pub roots: Option<rustls::RootCertStore>,
+}
+
+//------------ PrivacyProfile ------------------------------------------------
+
+/// The usage profile for DNS-over-TLS as defined in [RFC 8310].
+///
+/// [RFC 8310]: https://tools.ietf.org/html/rfc8310
+#[cfg(feature = "resolv-tls")]
+#[cfg_attr(docsrs, doc(cfg(feature = "resolv-tls")))]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum PrivacyProfile {
+    /// Only use the server if it can be authenticated.
+    ///
+    /// If the server’s certificate cannot be verified, the connection is
+    /// abandoned and the next server is tried.
+    Strict,
+
+    /// Use encryption even if the server cannot be authenticated.
+    ///
+    /// The server’s certificate is not checked at all. This protects
+    /// against passive eavesdroppers only. If a TLS connection cannot be
+    /// established at all, the resolver moves on to the next server, which
+    /// may well be one using an unencrypted transport.
+    Opportunistic,
+}
+
+#[cfg(feature = "resolv-tls")]
+impl Default for PrivacyProfile {
+    fn default() -> Self {
+        PrivacyProfile::Strict
+    }
+}
+
+//------------ HttpsConf -----------------------------------------------------
+
+/// Configuration for the HTTPS transport of a server.
+#[cfg(feature = "resolv-https")]
+#[cfg_attr(docsrs, doc(cfg(feature = "resolv-https")))]
+#[derive(Clone, Debug, Default)]
+pub struct HttpsConf {
+    /// The URI template of the DNS-over-HTTPS service.
+    ///
+    /// This is a URI template as defined in [RFC 6570] with a single
+    /// variable `dns`, e.g., `"https://dns.example.com/dns-query{?dns}"`.
+    /// If the template doesn’t contain the variable, it is appended as a
+    /// query parameter for GET requests.
+    ///
+    /// The connection is always made to the server’s address. The host of
+    /// the URI is only used in the request and for authenticating the
+    /// server.
+    ///
+    /// If this is `None`, `https://<addr>/dns-query{?dns}` with the
+    /// server’s address is used.
+    ///
+    /// [RFC 6570]: https://tools.ietf.org/html/rfc6570
+    pub uri_template: Option<String>,
+
+    /// The HTTP method to use for queries.
+    pub method: HttpsMethod,
+}
+
+//------------ HttpsMethod ---------------------------------------------------
+
+/// The HTTP method used for DNS-over-HTTPS queries.
+#[cfg(feature = "resolv-https")]
+#[cfg_attr(docsrs, doc(cfg(feature = "resolv-https")))]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum HttpsMethod {
+    /// Send the query as the body of a POST request.
+    Post,
+
+    /// Send the query base64url encoded in the URI of a GET request.
+    ///
+    /// This is friendlier to HTTP caches.
+    Get,
+}
+
+#[cfg(feature = "resolv-https")]
+impl Default for HttpsMethod {
+    fn default() -> Self {
+        HttpsMethod::Post
+    }
+}
+
 //------------ ResolvConf ---------------------------------------------------
 
 /// Resolver configuration.
@@ -345,6 +618,9 @@ impl ResolvConf {
     /// `127.0.0.1:53`. This is exactly what glibc does. If `search` is
     /// empty, it adds the root domain `"."`. This differs from what
     /// glibc does which considers the machine’s host name.
+    ///
+    /// In addition, the timeout and client subnet options are copied
+    /// into the server configurations.
     pub fn finalize(&mut self) {
         if self.servers.is_empty() {
             // glibc just simply uses 127.0.0.1:53. Let's do that, too,
@@ -358,7 +634,10 @@ impl ResolvConf {
             self.options.search.push(Dname::root())
         }
         for server in &mut self.servers {
-            server.request_timeout = self.options.timeout
+            server.request_timeout = self.options.timeout;
+            if server.client_subnet.is_none() {
+                server.client_subnet = self.options.client_subnet;
+            }
         }
     }
 
@@ -490,6 +769,18 @@ impl ResolvConf {
                 }
                 ("no-tld-query", None) => self.options.no_tld_query = true,
                 ("use-vc", None) => self.options.use_vc = true,
+                // The following options aren’t understood by glibc but
+                // are produced by our Display impl.
+                ("aa-only", None) => self.options.aa_only = true,
+                ("primary", None) => self.options.primary = true,
+                ("ign-tc", None) => self.options.ign_tc = true,
+                ("no-recurse", None) => self.options.recurse = false,
+                ("no-default-names", None) => {
+                    self.options.default_names = false
+                }
+                ("stay-open", None) => self.options.stay_open = true,
+                ("no-dn-search", None) => self.options.dn_search = false,
+                ("blast", None) => self.options.blast = true,
                 // Ignore unknown or misformated options.
                 _ => {}
             }
@@ -773,4 +1064,25 @@ mod test {
         assert!(conf.options.use_vc);
         assert_eq!(conf.options.ndots, 122);
     }
+
+    #[test]
+    fn options_round_trip() {
+        let mut conf = ResolvConf::new();
+        conf.options.aa_only = true;
+        conf.options.primary = true;
+        conf.options.recurse = false;
+        conf.options.stay_open = true;
+        conf.options.blast = true;
+        conf.options.no_tld_query = true;
+        let mut parsed = ResolvConf::new();
+        parsed
+            .parse(&mut io::Cursor::new(conf.to_string()))
+            .unwrap();
+        assert!(parsed.options.aa_only);
+        assert!(parsed.options.primary);
+        assert!(!parsed.options.recurse);
+        assert!(parsed.options.stay_open);
+        assert!(parsed.options.blast);
+        assert!(parsed.options.no_tld_query);
+    }
 }
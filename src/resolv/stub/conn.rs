@@ -0,0 +1,594 @@
+//! Shared connections for streaming transports.
+//!
+//! A [`Connection`] keeps a single stream connection to a server open and
+//! lets any number of queries use it at the same time. The connection is
+//! driven by a task of its own. Queries are handed to this task which
+//! writes them to the stream right away, i.e., without waiting for
+//! responses to earlier queries. Responses are matched to their queries via
+//! the message ID. Since queries have no particular ID, the task assigns
+//! its own unique IDs and restores the original ones in the responses.
+//!
+//! The task is spawned and its timers are created via the resolver’s
+//! runtime, so connections work with any runtime.
+//!
+//! The task closes the connection once it has been idle for a while. The
+//! initial idle timeout is taken from the server config. If a response
+//! contains an edns-tcp-keepalive option as defined in [RFC 7828], the
+//! timeout announced by the server is used if it is shorter. A timeout of
+//! zero means that the server wants the connection closed, so no new
+//! queries are accepted and the connection is closed once all outstanding
+//! responses have arrived.
+//!
+//! If the connection breaks before a query was answered, the query is sent
+//! again once over a new connection. This covers the case where the server
+//! closed the connection just as the query was being sent.
+//!
+//! [RFC 7828]: https://tools.ietf.org/html/rfc7828
+
+use super::runtime::Runtime;
+use crate::base::message::Message;
+use crate::base::opt::TcpKeepalive;
+use bytes::Bytes;
+use futures::channel::{mpsc, oneshot};
+use futures::future::{self, FutureExt};
+use futures::io::{
+    AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadHalf,
+};
+use futures::lock::Mutex;
+use futures::stream::StreamExt;
+use std::boxed::Box;
+use std::collections::HashMap;
+use std::future::Future;
+use std::string::ToString;
+use std::sync::Arc;
+use std::task::Poll;
+use std::time::Duration;
+use std::vec::Vec;
+use std::{cmp, io};
+
+//------------ Connection ----------------------------------------------------
+
+/// A shared connection to a server.
+///
+/// The connection is only established when the first query is sent and is
+/// re-established if it has been closed in the meantime.
+#[derive(Debug)]
+pub(super) struct Connection {
+    /// The runtime for spawning the task and for its timers.
+    runtime: Arc<dyn Runtime>,
+
+    /// The channel to the task driving the current connection.
+    ///
+    /// This is `None` if there never was a connection. If the task has
+    /// finished, the sender will be closed.
+    sender: Mutex<Option<mpsc::UnboundedSender<Request>>>,
+}
+
+impl Connection {
+    /// Creates a new connection using the given runtime.
+    pub fn new(runtime: Arc<dyn Runtime>) -> Self {
+        Connection {
+            runtime,
+            sender: Default::default(),
+        }
+    }
+
+    /// Sends a query over the connection and returns the response.
+    ///
+    /// The query must be in stream format, i.e., with the two octet length
+    /// prefix. If there currently is no open connection, a new one is
+    /// established via the `connect` closure. A new connection is closed
+    /// after it was idle for `idle_timeout`.
+    pub async fn query<S, F, Fut>(
+        &self,
+        query: &[u8],
+        idle_timeout: Duration,
+        connect: F,
+    ) -> Result<Message<Bytes>, io::Error>
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<S, io::Error>>,
+    {
+        let sender = self.sender(idle_timeout, &connect, None).await?;
+        match Self::send(&sender, query).await {
+            Err(err) if is_broken(&err) => {
+                let sender = self
+                    .sender(idle_timeout, &connect, Some(&sender))
+                    .await?;
+                Self::send(&sender, query).await
+            }
+            res => res,
+        }
+    }
+
+    /// Sends a query to a connection’s task and waits for the response.
+    async fn send(
+        sender: &mpsc::UnboundedSender<Request>,
+        query: &[u8],
+    ) -> Result<Message<Bytes>, io::Error> {
+        let (tx, rx) = oneshot::channel();
+        sender
+            .unbounded_send(Request {
+                message: query.into(),
+                reply: tx,
+            })
+            .map_err(|_| closed())?;
+        rx.await.map_err(|_| closed())?
+    }
+
+    /// Returns the sender for the current connection.
+    ///
+    /// Opens a new connection if necessary. Since the lock is held while
+    /// connecting, all concurrent queries will wait for and then use the
+    /// same new connection. If `broken` is given, the current connection
+    /// is replaced if it still is the one of that sender.
+    async fn sender<S, F, Fut>(
+        &self,
+        idle_timeout: Duration,
+        connect: &F,
+        broken: Option<&mpsc::UnboundedSender<Request>>,
+    ) -> Result<mpsc::UnboundedSender<Request>, io::Error>
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<S, io::Error>>,
+    {
+        let mut sender = self.sender.lock().await;
+        if let Some(sender) = sender.as_ref() {
+            let is_broken = broken.map(|broken| broken.same_receiver(sender))
+                == Some(true);
+            if !sender.is_closed() && !is_broken {
+                return Ok(sender.clone());
+            }
+        }
+        let stream = connect().await?;
+        let (tx, rx) = mpsc::unbounded();
+        self.runtime.spawn(
+            Self::run(self.runtime.clone(), stream, rx, idle_timeout).boxed(),
+        );
+        *sender = Some(tx.clone());
+        Ok(tx)
+    }
+
+    /// Drives a connection.
+    ///
+    /// The idle timer is only reset when there is activity on the
+    /// connection. If `idle_timeout` is zero, the connection is closed as
+    /// soon as all outstanding responses have arrived.
+    async fn run<S>(
+        runtime: Arc<dyn Runtime>,
+        stream: S,
+        mut requests: mpsc::UnboundedReceiver<Request>,
+        mut idle_timeout: Duration,
+    ) where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let (read, mut write) = stream.split();
+        let mut read = Box::pin(read_message(read));
+        let mut pending = Pending::default();
+        let mut idle = runtime.sleep(idle_timeout);
+        let mut accepting = true;
+        loop {
+            let event = future::poll_fn(|cx| {
+                if accepting {
+                    if let Poll::Ready(request) = requests.poll_next_unpin(cx)
+                    {
+                        return Poll::Ready(Event::Request(request));
+                    }
+                }
+                if let Poll::Ready((half, res)) = read.as_mut().poll(cx) {
+                    return Poll::Ready(Event::Response(half, res));
+                }
+                if !idle_timeout.is_zero()
+                    && idle.as_mut().poll(cx).is_ready()
+                {
+                    return Poll::Ready(Event::Idle);
+                }
+                Poll::Pending
+            })
+            .await;
+            match event {
+                Event::Request(None) => {
+                    accepting = false;
+                    if pending.is_idle() {
+                        break;
+                    }
+                }
+                Event::Request(Some(Request { mut message, reply })) => {
+                    let id = match pending.insert(&mut message, reply) {
+                        Ok(id) => id,
+                        Err(reply) => {
+                            let _ = reply.send(Err(io::Error::new(
+                                io::ErrorKind::Other,
+                                "too many queries on connection",
+                            )));
+                            continue;
+                        }
+                    };
+                    if let Err(err) = write.write_all(&message).await {
+                        pending.fail(id, err);
+                        break;
+                    }
+                    idle = runtime.sleep(idle_timeout);
+                }
+                Event::Response(half, res) => {
+                    let message = match res {
+                        Ok(message) => message,
+                        Err(err) => {
+                            pending.fail_all(err);
+                            break;
+                        }
+                    };
+                    match keepalive(&message) {
+                        Some(keepalive) if keepalive.is_zero() => {
+                            // The server wants us to go away. Stop
+                            // accepting queries so new ones go to a new
+                            // connection.
+                            requests.close();
+                        }
+                        Some(keepalive) => {
+                            idle_timeout = cmp::min(idle_timeout, keepalive);
+                        }
+                        None => {}
+                    }
+                    pending.resolve(message);
+                    if (!accepting || idle_timeout.is_zero())
+                        && pending.is_idle()
+                    {
+                        break;
+                    }
+                    read = Box::pin(read_message(half));
+                    idle = runtime.sleep(idle_timeout);
+                }
+                Event::Idle => {
+                    if pending.is_idle() {
+                        break;
+                    }
+                    idle = runtime.sleep(idle_timeout);
+                }
+            }
+        }
+    }
+}
+
+//------------ Event ---------------------------------------------------------
+
+/// Something that happened on a connection.
+enum Event<S> {
+    /// A new request arrived or the request channel was closed.
+    Request(Option<Request>),
+
+    /// A response was read from the stream.
+    ///
+    /// This also returns the read half for reading the next response.
+    Response(ReadHalf<S>, Result<Message<Bytes>, io::Error>),
+
+    /// The idle timer expired.
+    Idle,
+}
+
+//------------ Request -------------------------------------------------------
+
+/// A query waiting to be sent by a connection’s task.
+struct Request {
+    /// The query message in stream format.
+    message: Vec<u8>,
+
+    /// Where to send the response to.
+    reply: Reply,
+}
+
+/// The sending end for the response to a query.
+type Reply = oneshot::Sender<Result<Message<Bytes>, io::Error>>;
+
+//------------ Pending -------------------------------------------------------
+
+/// The queries sent on a connection that are waiting for a response.
+#[derive(Default)]
+struct Pending {
+    /// The pending queries by the ID they were sent with.
+    ///
+    /// The value contains the original ID of the query and the sender for
+    /// the response.
+    queries: HashMap<u16, (u16, Reply)>,
+}
+
+impl Pending {
+    /// Inserts a new query and assigns it a fresh ID.
+    ///
+    /// The ID is chosen randomly, skipping IDs that are still in use. The
+    /// query message in stream format is updated with the new ID which
+    /// is also returned. If there are no free IDs left, returns the reply
+    /// sender back.
+    fn insert(
+        &mut self,
+        message: &mut [u8],
+        reply: Reply,
+    ) -> Result<u16, Reply> {
+        if message.len() < 4 {
+            return Err(reply);
+        }
+        self.queries.retain(|_, (_, reply)| !reply.is_canceled());
+        if self.queries.len() > usize::from(u16::MAX) {
+            return Err(reply);
+        }
+        let mut id: u16 = ::rand::random();
+        while self.queries.contains_key(&id) {
+            id = id.wrapping_add(1);
+        }
+
+        // The message starts with the length, so the ID is at 2..4.
+        let orig = u16::from_be_bytes([message[2], message[3]]);
+        message[2..4].copy_from_slice(&id.to_be_bytes());
+        self.queries.insert(id, (orig, reply));
+        Ok(id)
+    }
+
+    /// Delivers a response to the query it belongs to.
+    ///
+    /// Responses for unknown IDs are quietly dropped.
+    fn resolve(&mut self, message: Message<Bytes>) {
+        let (orig, reply) = match self.queries.remove(&message.header().id())
+        {
+            Some(item) => item,
+            None => return,
+        };
+        let mut octets = Vec::from(message.as_slice());
+        octets[..2].copy_from_slice(&orig.to_be_bytes());
+        let _ = reply.send(
+            Message::from_octets(Bytes::from(octets))
+                .map_err(|_| short_message()),
+        );
+    }
+
+    /// Fails the query with the given ID.
+    ///
+    /// Since this happens because the connection broke, all other queries
+    /// will fail, too.
+    fn fail(&mut self, id: u16, err: io::Error) {
+        if let Some((_, reply)) = self.queries.remove(&id) {
+            let _ = reply.send(Err(err));
+        }
+        self.fail_all(closed())
+    }
+
+    /// Fails all pending queries.
+    fn fail_all(&mut self, err: io::Error) {
+        let msg = err.to_string();
+        for (_, (_, reply)) in self.queries.drain() {
+            let _ = reply.send(Err(io::Error::new(err.kind(), msg.as_str())));
+        }
+    }
+
+    /// Returns whether nobody is waiting for a response anymore.
+    fn is_idle(&mut self) -> bool {
+        self.queries.retain(|_, (_, reply)| !reply.is_canceled());
+        self.queries.is_empty()
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Reads a single message from a stream.
+///
+/// The function takes and returns the read half so the future can be kept
+/// across iterations of the connection’s loop.
+async fn read_message<S: AsyncRead>(
+    mut read: ReadHalf<S>,
+) -> (ReadHalf<S>, Result<Message<Bytes>, io::Error>) {
+    let res = async {
+        let mut len = [0u8; 2];
+        read.read_exact(&mut len).await?;
+        let mut buf = vec![0; usize::from(u16::from_be_bytes(len))];
+        read.read_exact(&mut buf).await?;
+        Message::from_octets(Bytes::from(buf)).map_err(|_| short_message())
+    }
+    .await;
+    (read, res)
+}
+
+/// Returns the idle timeout announced in a response if present.
+fn keepalive(message: &Message<Bytes>) -> Option<Duration> {
+    let option =
+        message.opt()?.iter::<TcpKeepalive>().find_map(Result::ok)?;
+
+    // The timeout is in units of 100 milliseconds.
+    Some(Duration::from_millis(u64::from(option.timeout()) * 100))
+}
+
+/// Returns whether an error means that the connection broke.
+fn is_broken(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::BrokenPipe
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::UnexpectedEof
+    )
+}
+
+fn closed() -> io::Error {
+    io::Error::new(io::ErrorKind::ConnectionAborted, "connection closed")
+}
+
+fn short_message() -> io::Error {
+    io::Error::new(io::ErrorKind::Other, "short buf")
+}
+
+//============ Testing =======================================================
+
+#[cfg(all(test, feature = "resolv"))]
+mod test {
+    use super::super::conf::{ResolvConf, ServerConf, Transport};
+    use super::super::StubResolver;
+    use crate::base::iana::{Rcode, Rtype};
+    use crate::base::message::Message;
+    use crate::base::message_builder::MessageBuilder;
+    use crate::base::name::Dname;
+    use std::net::SocketAddr;
+    use std::str::FromStr;
+    use std::string::ToString;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use std::vec::Vec;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::sync::mpsc;
+
+    /// A DNS-over-TCP server for testing.
+    ///
+    /// The server answers every query with an empty answer. Queries for
+    /// names starting with ‘slow’ are answered with a delay. If `close` is
+    /// `true`, the server closes the connection after the first answer.
+    struct Server {
+        addr: SocketAddr,
+        accepted: Arc<AtomicUsize>,
+    }
+
+    impl Server {
+        async fn start(close: bool) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            let accepted = Arc::new(AtomicUsize::new(0));
+            let count = accepted.clone();
+            tokio::spawn(async move {
+                loop {
+                    let (sock, _) = listener.accept().await.unwrap();
+                    count.fetch_add(1, Ordering::SeqCst);
+                    tokio::spawn(Self::serve(sock, close));
+                }
+            });
+            Server { addr, accepted }
+        }
+
+        async fn serve(sock: TcpStream, close: bool) {
+            let (mut read, mut write) = sock.into_split();
+            let (tx, mut rx) = mpsc::unbounded_channel::<Vec<u8>>();
+            tokio::spawn(async move {
+                while let Some(data) = rx.recv().await {
+                    if write.write_all(&data).await.is_err() || close {
+                        return;
+                    }
+                }
+            });
+            loop {
+                let len = match read.read_u16().await {
+                    Ok(len) => len,
+                    Err(_) => return,
+                };
+                let mut buf = vec![0; usize::from(len)];
+                if read.read_exact(&mut buf).await.is_err() {
+                    return;
+                }
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    let query = Message::from_octets(buf).unwrap();
+                    let qname = query.sole_question().unwrap().into_qname();
+                    if qname.to_string().starts_with("slow") {
+                        tokio::time::sleep(Duration::from_millis(200)).await;
+                    }
+                    let answer = MessageBuilder::new_stream_vec()
+                        .start_answer(&query, Rcode::NoError)
+                        .unwrap();
+                    let _ = tx.send(answer.finish().as_stream_slice().into());
+                });
+            }
+        }
+
+        fn accepted(&self) -> usize {
+            self.accepted.load(Ordering::SeqCst)
+        }
+
+        fn resolver(&self, stay_open: bool) -> StubResolver {
+            let mut conf = ResolvConf::new();
+            conf.servers
+                .push(ServerConf::new(self.addr, Transport::Tcp));
+            conf.options.attempts = 1;
+            conf.options.stay_open = stay_open;
+            conf.finalize();
+            StubResolver::from_conf(conf)
+        }
+    }
+
+    async fn query(
+        resolver: &StubResolver,
+        qname: &str,
+    ) -> Result<Rcode, std::io::Error> {
+        let qname = Dname::<Vec<u8>>::from_str(qname).unwrap();
+        resolver
+            .query((qname, Rtype::A))
+            .await
+            .map(|answer| answer.header().rcode())
+    }
+
+    #[tokio::test]
+    async fn reuse_and_pipelining() {
+        let server = Server::start(false).await;
+        let resolver = server.resolver(true);
+        assert_eq!(
+            query(&resolver, "a.example").await.unwrap(),
+            Rcode::NoError
+        );
+        assert_eq!(
+            query(&resolver, "b.example").await.unwrap(),
+            Rcode::NoError
+        );
+        let (slow, fast) = tokio::join!(
+            query(&resolver, "slow.example"),
+            query(&resolver, "fast.example"),
+        );
+        assert_eq!(slow.unwrap(), Rcode::NoError);
+        assert_eq!(fast.unwrap(), Rcode::NoError);
+        assert_eq!(server.accepted(), 1);
+    }
+
+    #[tokio::test]
+    async fn reconnect() {
+        let server = Server::start(true).await;
+        let resolver = server.resolver(true);
+        for name in &["a.example", "b.example", "c.example"] {
+            assert_eq!(query(&resolver, name).await.unwrap(), Rcode::NoError);
+        }
+        assert_eq!(server.accepted(), 3);
+    }
+
+    #[test]
+    fn pending_ids() {
+        use super::Pending;
+        use futures::channel::oneshot;
+        use std::collections::HashSet;
+
+        let mut pending = Pending::default();
+        let mut ids = HashSet::new();
+        let mut receivers = Vec::new();
+        for orig in 0..100u16 {
+            let mut message = vec![0, 12];
+            message.extend_from_slice(&orig.to_be_bytes());
+            message.extend_from_slice(&[0; 10]);
+            let (tx, rx) = oneshot::channel();
+            receivers.push(rx);
+            let id = pending.insert(&mut message, tx).unwrap();
+            assert_eq!(&message[2..4], &id.to_be_bytes());
+            assert_eq!(pending.queries[&id].0, orig);
+            assert!(ids.insert(id));
+        }
+        assert_eq!(pending.queries.len(), 100);
+    }
+
+    #[tokio::test]
+    async fn without_stay_open() {
+        let server = Server::start(false).await;
+        let resolver = server.resolver(false);
+        assert_eq!(
+            query(&resolver, "a.example").await.unwrap(),
+            Rcode::NoError
+        );
+        assert_eq!(
+            query(&resolver, "b.example").await.unwrap(),
+            Rcode::NoError
+        );
+        assert_eq!(server.accepted(), 2);
+    }
+}
@@ -0,0 +1,682 @@
+//! The DNS-over-HTTPS transport.
+//!
+//! Queries are sent via HTTP/2 as defined in [RFC 8484]. All queries to a
+//! server share a single connection which is closed again after it has
+//! been idle for the server’s idle timeout.
+//!
+//! Since responses may have spent some time in HTTP caches, the TTLs of
+//! all records in a response are reduced by the value of the Age header
+//! and limited to the freshness lifetime given via the max-age directive
+//! of the Cache-Control header.
+//!
+//! [RFC 8484]: https://tools.ietf.org/html/rfc8484
+
+use super::conf::{HttpsMethod, ServerConf};
+use super::runtime::Runtime;
+use super::tls::TlsInfo;
+use crate::base::iana::Rtype;
+use crate::base::message::Message;
+use crate::base::name::ParsedDname;
+use crate::base::octets::{Parse, ParseError, Parser};
+use crate::base::question::Question;
+use crate::utils::base64;
+use bytes::Bytes;
+use futures::future::{self, FutureExt};
+use hyper::client::conn::{self, ResponseFuture, SendRequest};
+use hyper::header::{self, HeaderMap};
+use hyper::{Body, Request, Uri};
+use std::future::Future;
+use std::net::SocketAddr;
+use std::string::{String, ToString};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use std::vec::Vec;
+use std::{cmp, fmt, io};
+use tokio::sync::Mutex;
+
+/// The media type of DNS messages.
+const DNS_MESSAGE: &str = "application/dns-message";
+
+/// How often to check again whether a busy connection has become idle.
+const IDLE_RECHECK: Duration = Duration::from_millis(100);
+
+//------------ HttpsInfo -----------------------------------------------------
+
+/// Everything needed to send queries to a DNS-over-HTTPS server.
+pub(super) struct HttpsInfo {
+    /// The URI template for the server.
+    template: UriTemplate,
+
+    /// The HTTP method to use.
+    method: HttpsMethod,
+
+    /// The information for establishing the TLS connection.
+    tls: TlsInfo,
+
+    /// The runtime for connecting and for the connection’s tasks.
+    runtime: Arc<dyn Runtime>,
+
+    /// The sender for the current HTTP connection and its activity.
+    sender: Mutex<Option<(SendRequest<Body>, Arc<Activity>)>>,
+}
+
+impl HttpsInfo {
+    /// Creates the information for the given server.
+    ///
+    /// Fails if the URI template or the TLS configuration are invalid.
+    pub fn new(
+        conf: &ServerConf,
+        runtime: Arc<dyn Runtime>,
+    ) -> Result<Self, io::Error> {
+        let template = match conf.https.uri_template {
+            Some(ref template) => UriTemplate::new(template)?,
+            None => UriTemplate::new(&format!(
+                "https://{}/dns-query{{?dns}}",
+                conf.addr
+            ))?,
+        };
+        let tls = TlsInfo::new(conf, Some(&template.host), &[b"h2"])?;
+        Ok(HttpsInfo {
+            template,
+            method: conf.https.method,
+            tls,
+            runtime,
+            sender: Default::default(),
+        })
+    }
+
+    /// Sends a query and returns the response.
+    ///
+    /// The query needs to be in datagram format. As recommended by RFC 8484
+    /// for cache friendliness, the query is sent with a message ID of zero.
+    /// The original ID is restored in the response.
+    pub async fn query(
+        &self,
+        query: &[u8],
+        addr: SocketAddr,
+        idle_timeout: Duration,
+    ) -> Result<Message<Bytes>, io::Error> {
+        if query.len() < 2 {
+            return Err(short_buf());
+        }
+        let id = [query[0], query[1]];
+        let mut query = query.to_vec();
+        query[..2].copy_from_slice(&[0, 0]);
+        let query = query.as_slice();
+
+        let (builder, body) = match self.method {
+            HttpsMethod::Post => (
+                Request::post(self.template.post_uri())
+                    .header(header::CONTENT_TYPE, DNS_MESSAGE),
+                Body::from(query.to_vec()),
+            ),
+            HttpsMethod::Get => {
+                (Request::get(self.template.get_uri(query)), Body::empty())
+            }
+        };
+        let request = builder
+            .header(header::ACCEPT, DNS_MESSAGE)
+            .body(body)
+            .map_err(|err| {
+                io::Error::new(io::ErrorKind::InvalidInput, err)
+            })?;
+
+        let (response, _guard) =
+            self.send(request, addr, idle_timeout).await?;
+        let response = response.await.map_err(other)?;
+        if !response.status().is_success() {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("HTTP status {}", response.status()),
+            ));
+        }
+        let content_type = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok());
+        if content_type != Some(DNS_MESSAGE) {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "unexpected content type",
+            ));
+        }
+        let (max_age, age) = cache_headers(response.headers());
+        let mut body = hyper::body::to_bytes(response.into_body())
+            .await
+            .map_err(other)?
+            .to_vec();
+        if body.len() < 2 {
+            return Err(short_buf());
+        }
+        body[..2].copy_from_slice(&id);
+        let message = Message::from_octets(Bytes::from(body))
+            .map_err(|_| short_buf())?;
+        match (max_age, age) {
+            (None, 0) => Ok(message),
+            (max_age, age) => {
+                adjust_ttls(message, max_age.unwrap_or(u32::MAX), age)
+                    .map_err(|_| short_buf())
+            }
+        }
+    }
+
+    /// Sends a request to the server.
+    ///
+    /// Uses the current connection if there is one or establishes a new
+    /// one. Returns the future for the response and a guard that keeps the
+    /// connection from being closed while waiting for the response.
+    async fn send(
+        &self,
+        request: Request<Body>,
+        addr: SocketAddr,
+        idle_timeout: Duration,
+    ) -> Result<(ResponseFuture, ActivityGuard), io::Error> {
+        let mut current = self.sender.lock().await;
+        if let Some((ref mut sender, ref activity)) = *current {
+            if futures::future::poll_fn(|cx| sender.poll_ready(cx))
+                .await
+                .is_ok()
+            {
+                let guard = activity.start();
+                return Ok((sender.send_request(request), guard));
+            }
+        }
+        let stream = self.tls.connect(self.runtime.as_ref(), addr).await?;
+        let (mut sender, connection) = conn::Builder::new()
+            .http2_only(true)
+            .executor(Executor(self.runtime.clone()))
+            .handshake(stream)
+            .await
+            .map_err(other)?;
+        let activity = Arc::new(Activity::new());
+        let idle = activity.clone().idle(self.runtime.clone(), idle_timeout);
+        self.runtime.spawn(
+            async move {
+                // The connection is dropped and thereby closed when it has
+                // been idle for long enough.
+                futures::pin_mut!(idle);
+                let _ = future::select(connection, idle).await;
+            }
+            .boxed(),
+        );
+        let guard = activity.start();
+        let response = sender.send_request(request);
+        *current = Some((sender, activity));
+        Ok((response, guard))
+    }
+}
+
+impl fmt::Debug for HttpsInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("HttpsInfo")
+            .field("template", &self.template)
+            .field("method", &self.method)
+            .field("tls", &self.tls)
+            .finish()
+    }
+}
+
+//------------ UriTemplate ---------------------------------------------------
+
+/// A parsed URI template.
+///
+/// The template is split into the parts before and after the `dns`
+/// variable. Only the forms `{?dns}` and `{&dns}` are supported.
+#[derive(Clone, Debug)]
+struct UriTemplate {
+    /// The part of the template before the variable.
+    head: String,
+
+    /// The part of the template after the variable.
+    tail: String,
+
+    /// The character to start the variable with, either `?` or `&`.
+    sep: char,
+
+    /// The host of the URI.
+    host: String,
+}
+
+impl UriTemplate {
+    fn new(template: &str) -> Result<Self, io::Error> {
+        let (head, tail, sep) = if let Some(pos) = template.find("{?dns}") {
+            (&template[..pos], &template[pos + 6..], '?')
+        } else if let Some(pos) = template.find("{&dns}") {
+            (&template[..pos], &template[pos + 6..], '&')
+        } else if template.contains('?') {
+            (template, "", '&')
+        } else {
+            (template, "", '?')
+        };
+        let res = UriTemplate {
+            head: head.into(),
+            tail: tail.into(),
+            sep,
+            host: String::new(),
+        };
+        let uri = res.post_uri();
+        if uri.scheme_str() != Some("https") {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "DNS-over-HTTPS URI must use https",
+            ));
+        }
+        let host = match uri.host() {
+            Some(host) => host.trim_start_matches('[').trim_end_matches(']'),
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "DNS-over-HTTPS URI without host",
+                ))
+            }
+        };
+        Ok(UriTemplate {
+            host: host.into(),
+            ..res
+        })
+    }
+
+    /// Returns the URI for a POST request.
+    fn post_uri(&self) -> Uri {
+        let mut res =
+            String::with_capacity(self.head.len() + self.tail.len());
+        res.push_str(&self.head);
+        res.push_str(&self.tail);
+        Uri::try_from(res).unwrap_or_default()
+    }
+
+    /// Returns the URI for a GET request for the given query.
+    fn get_uri(&self, query: &[u8]) -> Uri {
+        let mut res = self.head.clone();
+        res.push(self.sep);
+        res.push_str("dns=");
+        base64::display_url(query, &mut res).unwrap();
+        res.push_str(&self.tail);
+        Uri::try_from(res).unwrap_or_default()
+    }
+}
+
+//------------ Activity ------------------------------------------------------
+
+/// Tracks the use of an HTTP connection for closing it when idle.
+#[derive(Debug)]
+struct Activity {
+    /// The number of requests currently in flight.
+    requests: AtomicUsize,
+
+    /// When the last request has finished.
+    last: std::sync::Mutex<Instant>,
+}
+
+impl Activity {
+    fn new() -> Self {
+        Activity {
+            requests: AtomicUsize::new(0),
+            last: std::sync::Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Marks the start of a request.
+    ///
+    /// The request ends when the returned value is dropped.
+    fn start(self: &Arc<Self>) -> ActivityGuard {
+        self.requests.fetch_add(1, Ordering::SeqCst);
+        ActivityGuard(self.clone())
+    }
+
+    /// Resolves once the connection has been idle for `timeout`.
+    ///
+    /// The timers are created via `runtime`.
+    async fn idle(
+        self: Arc<Self>,
+        runtime: Arc<dyn Runtime>,
+        timeout: Duration,
+    ) {
+        loop {
+            let last = *self.last.lock().unwrap();
+            if self.requests.load(Ordering::SeqCst) == 0
+                && last.elapsed() >= timeout
+            {
+                return;
+            }
+            let now = Instant::now();
+            runtime
+                .sleep(cmp::max(last + timeout, now + IDLE_RECHECK) - now)
+                .await
+        }
+    }
+}
+
+/// Marks a request as in flight while it exists.
+struct ActivityGuard(Arc<Activity>);
+
+impl Drop for ActivityGuard {
+    fn drop(&mut self) {
+        *self.0.last.lock().unwrap() = Instant::now();
+        self.0.requests.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+//------------ Executor ------------------------------------------------------
+
+/// The executor for the background tasks of HTTP/2 connections.
+///
+/// The tasks are spawned via the runtime.
+#[derive(Clone, Debug)]
+struct Executor(Arc<dyn Runtime>);
+
+impl<F> hyper::rt::Executor<F> for Executor
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    fn execute(&self, fut: F) {
+        self.0.spawn(fut.map(|_| ()).boxed());
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Returns the max-age directive and the Age header of a response.
+fn cache_headers(headers: &HeaderMap) -> (Option<u32>, u32) {
+    let max_age = headers
+        .get_all(header::CACHE_CONTROL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|directive| {
+            let (name, value) = directive.split_once('=')?;
+            if name.trim().eq_ignore_ascii_case("max-age") {
+                value.trim().parse().ok()
+            } else {
+                None
+            }
+        })
+        .min();
+    let age = headers
+        .get(header::AGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok())
+        .unwrap_or(0);
+    (max_age, age)
+}
+
+/// Limits the TTLs in a message to `max_age` and reduces them by `age`.
+///
+/// The TTL of OPT records isn’t a TTL and is left alone.
+fn adjust_ttls(
+    message: Message<Bytes>,
+    max_age: u32,
+    age: u32,
+) -> Result<Message<Bytes>, ParseError> {
+    let mut octets = Vec::from(message.as_slice());
+    let mut parser = Parser::from_ref(message.as_slice());
+    parser.advance(12)?;
+    for _ in 0..message.header_counts().qdcount() {
+        Question::<ParsedDname<_>>::skip(&mut parser)?;
+    }
+    let counts = message.header_counts();
+    let records = u32::from(counts.ancount())
+        + u32::from(counts.nscount())
+        + u32::from(counts.arcount());
+    for _ in 0..records {
+        ParsedDname::skip(&mut parser)?;
+        let rtype = Rtype::parse(&mut parser)?;
+        parser.advance(2)?;
+        let pos = parser.pos();
+        let ttl = parser.parse_u32()?;
+        let rdlen = parser.parse_u16()?;
+        parser.advance(usize::from(rdlen))?;
+        if rtype != Rtype::Opt {
+            let ttl = cmp::min(ttl, max_age).saturating_sub(age);
+            octets[pos..pos + 4].copy_from_slice(&ttl.to_be_bytes());
+        }
+    }
+    Message::from_octets(Bytes::from(octets))
+        .map_err(|_| ParseError::ShortInput)
+}
+
+fn other(err: impl ToString) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, err.to_string())
+}
+
+fn short_buf() -> io::Error {
+    io::Error::new(io::ErrorKind::Other, "short buf")
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::super::conf::{
+        HttpsMethod, ResolvConf, ServerConf, Transport,
+    };
+    use super::super::runtime::TokioRuntime;
+    use super::super::StubResolver;
+    use super::*;
+    use crate::base::iana::Rcode;
+    use crate::base::message_builder::MessageBuilder;
+    use crate::base::name::Dname;
+    use crate::rdata::A;
+    use hyper::server::conn::Http;
+    use hyper::service::service_fn;
+    use hyper::{Method, Response};
+    use rustls::{Certificate, PrivateKey, RootCertStore, ServerConfig};
+    use std::convert::Infallible;
+    use std::net::IpAddr;
+    use std::str::FromStr;
+    use tokio::net::TcpListener;
+    use tokio_rustls::TlsAcceptor;
+
+    #[test]
+    fn uri_template() {
+        let template =
+            UriTemplate::new("https://dns.example/dns-query{?dns}").unwrap();
+        assert_eq!(template.host, "dns.example");
+        assert_eq!(template.post_uri(), "https://dns.example/dns-query");
+        assert_eq!(
+            template.get_uri(b"\xfb\xff\xbf"),
+            "https://dns.example/dns-query?dns=-_-_"
+        );
+
+        let template =
+            UriTemplate::new("https://[2001:db8::1]:8443/q?a=b{&dns}")
+                .unwrap();
+        assert_eq!(template.host, "2001:db8::1");
+        assert_eq!(
+            template.get_uri(b"f"),
+            "https://[2001:db8::1]:8443/q?a=b&dns=Zg"
+        );
+
+        let template = UriTemplate::new("https://dns.example/q?a=b").unwrap();
+        assert_eq!(
+            template.get_uri(b"f"),
+            "https://dns.example/q?a=b&dns=Zg"
+        );
+        assert!(UriTemplate::new("http://dns.example/q{?dns}").is_err());
+    }
+
+    #[test]
+    fn cache_control() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(cache_headers(&headers), (None, 0));
+        headers.insert(
+            header::CACHE_CONTROL,
+            "public, max-age=100".parse().unwrap(),
+        );
+        headers.insert(header::AGE, "30".parse().unwrap());
+        assert_eq!(cache_headers(&headers), (Some(100), 30));
+
+        let message = answer(&query_message(), Some(300));
+        let message = Message::from_octets(Bytes::from(message)).unwrap();
+        assert_eq!(
+            ttls(&adjust_ttls(message.clone(), 100, 30).unwrap()),
+            [70]
+        );
+        assert_eq!(
+            ttls(&adjust_ttls(message.clone(), 500, 30).unwrap()),
+            [270]
+        );
+        assert_eq!(ttls(&adjust_ttls(message, 100, 200).unwrap()), [0]);
+    }
+
+    fn query_message() -> Message<Vec<u8>> {
+        let mut msg = MessageBuilder::new_vec().question();
+        msg.push((
+            Dname::<Vec<u8>>::from_str("a.example").unwrap(),
+            Rtype::A,
+        ))
+        .unwrap();
+        msg.into_message()
+    }
+
+    fn ttls(message: &Message<Bytes>) -> Vec<u32> {
+        message
+            .answer()
+            .unwrap()
+            .map(|record| record.unwrap().ttl())
+            .collect()
+    }
+
+    /// Creates an answer with an A record with the given TTL if any.
+    fn answer(query: &Message<Vec<u8>>, ttl: Option<u32>) -> Vec<u8> {
+        let mut answer = MessageBuilder::new_vec()
+            .start_answer(query, Rcode::NoError)
+            .unwrap();
+        let question = query.sole_question().unwrap();
+        if let Some(ttl) = ttl {
+            if question.qtype() == Rtype::A {
+                answer
+                    .push((
+                        question.qname(),
+                        ttl,
+                        A::from_octets(192, 0, 2, 1),
+                    ))
+                    .unwrap();
+            }
+        }
+        answer.finish()
+    }
+
+    /// Decodes unpadded base64url.
+    fn decode_url(s: &str) -> Vec<u8> {
+        let mut s = s.replace('-', "+").replace('_', "/");
+        while s.len() % 4 != 0 {
+            s.push('=');
+        }
+        base64::decode(&s).unwrap()
+    }
+
+    /// Answers a DoH request.
+    async fn handle(
+        request: Request<Body>,
+        method: Method,
+    ) -> Result<Response<Body>, Infallible> {
+        assert_eq!(request.method(), method);
+        assert_eq!(request.uri().path(), "/dns-query");
+        assert_eq!(request.headers()[header::ACCEPT], DNS_MESSAGE);
+        let query = if method == Method::POST {
+            assert_eq!(request.headers()[header::CONTENT_TYPE], DNS_MESSAGE);
+            hyper::body::to_bytes(request.into_body())
+                .await
+                .unwrap()
+                .to_vec()
+        } else {
+            let query = request.uri().query().unwrap();
+            decode_url(query.strip_prefix("dns=").unwrap())
+        };
+        let query = Message::from_octets(query).unwrap();
+        assert_eq!(query.header().id(), 0);
+        Ok(Response::builder()
+            .header(header::CONTENT_TYPE, DNS_MESSAGE)
+            .header(header::CACHE_CONTROL, "max-age=100")
+            .header(header::AGE, "30")
+            .body(Body::from(answer(&query, Some(300))))
+            .unwrap())
+    }
+
+    /// Starts a DoH server and returns a resolver using it.
+    async fn start(method: HttpsMethod) -> (StubResolver, Arc<AtomicUsize>) {
+        let cert =
+            rcgen::generate_simple_self_signed(vec!["dns.example".into()])
+                .unwrap();
+        let key = PrivateKey(cert.serialize_private_key_der());
+        let cert = Certificate(cert.serialize_der().unwrap());
+        let mut config = ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(vec![cert.clone()], key)
+            .unwrap();
+        config.alpn_protocols = vec![b"h2".to_vec()];
+        let acceptor = TlsAcceptor::from(Arc::new(config));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let accepted = Arc::new(AtomicUsize::new(0));
+        let count = accepted.clone();
+        let http_method = match method {
+            HttpsMethod::Post => Method::POST,
+            HttpsMethod::Get => Method::GET,
+        };
+        tokio::spawn(async move {
+            loop {
+                let (sock, _) = listener.accept().await.unwrap();
+                count.fetch_add(1, Ordering::SeqCst);
+                let acceptor = acceptor.clone();
+                let http_method = http_method.clone();
+                tokio::spawn(async move {
+                    let stream = acceptor.accept(sock).await.unwrap();
+                    let mut http = Http::new()
+                        .with_executor(Executor(Arc::new(TokioRuntime)));
+                    http.http2_only(true);
+                    let _ = http
+                        .serve_connection(
+                            stream,
+                            service_fn(move |req| {
+                                handle(req, http_method.clone())
+                            }),
+                        )
+                        .await;
+                });
+            }
+        });
+
+        let mut server = ServerConf::new(addr, Transport::Https);
+        server.https.uri_template =
+            Some("https://dns.example/dns-query{?dns}".into());
+        server.https.method = method;
+        let mut roots = RootCertStore::empty();
+        roots.add(&cert).unwrap();
+        server.tls.roots = Some(roots);
+        let mut conf = ResolvConf::new();
+        conf.servers.push(server);
+        conf.options.attempts = 1;
+        conf.finalize();
+        (StubResolver::from_conf(conf), accepted)
+    }
+
+    async fn check(method: HttpsMethod) {
+        let (resolver, accepted) = start(method).await;
+        let qname = Dname::<Vec<u8>>::from_str("a.example").unwrap();
+        let answer = resolver.query((&qname, Rtype::A)).await.unwrap();
+        assert_eq!(answer.header().rcode(), Rcode::NoError);
+        assert_eq!(ttls(answer.as_ref()), [70]);
+
+        let hosts = resolver.lookup_host(&qname).await.unwrap();
+        assert_eq!(
+            hosts.iter().collect::<Vec<_>>(),
+            [IpAddr::from([192, 0, 2, 1])]
+        );
+        assert_eq!(accepted.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn post() {
+        check(HttpsMethod::Post).await
+    }
+
+    #[tokio::test]
+    async fn get() {
+        check(HttpsMethod::Get).await
+    }
+}
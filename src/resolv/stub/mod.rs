@@ -7,24 +7,33 @@
 //! as possible, in particular in the way it is being configured.
 //!
 //! The main type is [`StubResolver`] that implements the [`Resolver`] trait
-//! and thus can be used with the various lookup functions.
+//! and thus can be used with the various lookup functions. If you don’t
+//! want to use an async runtime, the [`SyncStubResolver`] from the [sync]
+//! module performs its queries using blocking sockets instead.
 
 use self::conf::{
     ResolvConf, ResolvOptions, SearchSuffix, ServerConf, Transport,
 };
-use crate::base::iana::Rcode;
+use self::runtime::Runtime;
+#[cfg(feature = "resolv")]
+use self::runtime::TokioRuntime;
+use crate::base::iana::{OptionCode, Rcode, Rtype};
 use crate::base::message::Message;
 use crate::base::message_builder::{
     AdditionalBuilder, MessageBuilder, StreamTarget,
 };
 use crate::base::name::{ToDname, ToRelativeDname};
 use crate::base::octets::Octets512;
+use crate::base::opt::ClientSubnet;
 use crate::base::question::Question;
+use crate::rdata::Aaaa;
 use crate::resolv::lookup::addr::{lookup_addr, FoundAddrs};
-use crate::resolv::lookup::host::{lookup_host, search_host, FoundHosts};
+use crate::resolv::lookup::host::{lookup_host, FoundHosts};
 use crate::resolv::lookup::srv::{lookup_srv, FoundSrvs, SrvError};
 use crate::resolv::resolver::{Resolver, SearchNames};
 use bytes::Bytes;
+use futures::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use futures::stream::{FuturesUnordered, StreamExt};
 use std::boxed::Box;
 use std::future::Future;
 use std::net::{IpAddr, SocketAddr};
@@ -32,16 +41,20 @@ use std::pin::Pin;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::vec::Vec;
-use std::{io, ops};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::{TcpStream, UdpSocket};
-#[cfg(feature = "resolv-sync")]
-use tokio::runtime;
-use tokio::time::timeout;
+use std::{cmp, io, ops};
 
 //------------ Sub-modules ---------------------------------------------------
 
 pub mod conf;
+mod conn;
+#[cfg(feature = "resolv-https")]
+mod https;
+pub mod runtime;
+pub mod sync;
+#[cfg(feature = "resolv-tls")]
+mod tls;
+
+pub use self::sync::SyncStubResolver;
 
 //------------ Module Configuration ------------------------------------------
 
@@ -55,7 +68,8 @@ const RETRY_RANDOM_PORT: usize = 10;
 /// This type collects all information making it possible to start DNS
 /// queries. You can create a new resolver using the system’s configuration
 /// using the [`new()`] associate function or using your own configuration
-/// with [`from_conf()`].
+/// with [`from_conf()`]. Both use Tokio and need the `resolv` feature.
+/// With any other runtime, use [`from_conf_with_runtime()`].
 ///
 /// Stub resolver values can be cloned relatively cheaply as they keep all
 /// information behind an arc.
@@ -66,6 +80,7 @@ const RETRY_RANDOM_PORT: usize = 10;
 ///
 /// [`new()`]: #method.new
 /// [`from_conf()`]: #method.from_conf
+/// [`from_conf_with_runtime()`]: #method.from_conf_with_runtime
 /// [`query()`]: #method.query
 /// [`run()`]: #method.run
 /// [`run_with_conf()`]: #method.run_with_conf
@@ -81,6 +96,8 @@ pub struct StubResolver {
     options: ResolvOptions,
 }
 
+#[cfg(feature = "resolv")]
+#[cfg_attr(docsrs, doc(cfg(feature = "resolv")))]
 impl StubResolver {
     /// Creates a new resolver using the system’s default configuration.
     pub fn new() -> Self {
@@ -89,11 +106,29 @@ impl StubResolver {
 
     /// Creates a new resolver using the given configuraiton.
     pub fn from_conf(conf: ResolvConf) -> Self {
+        Self::from_conf_with_runtime(conf, TokioRuntime)
+    }
+}
+
+impl StubResolver {
+    /// Creates a new resolver using the given configuration and runtime.
+    ///
+    /// The resolver will use `runtime` for accessing the network and for
+    /// its timers. See the [runtime] module for details.
+    ///
+    /// [runtime]: runtime/index.html
+    pub fn from_conf_with_runtime(
+        conf: ResolvConf,
+        runtime: impl Runtime + 'static,
+    ) -> Self {
+        let runtime: Arc<dyn Runtime> = Arc::new(runtime);
         StubResolver {
-            preferred: ServerList::from_conf(&conf, |s| {
+            preferred: ServerList::from_conf(&conf, &runtime, |s| {
                 s.transport.is_preferred()
             }),
-            stream: ServerList::from_conf(&conf, |s| s.transport.is_stream()),
+            stream: ServerList::from_conf(&conf, &runtime, |s| {
+                s.transport.is_stream()
+            }),
             options: conf.options,
         }
     }
@@ -102,6 +137,23 @@ impl StubResolver {
         &self.options
     }
 
+    /// Returns a counter for the preferred or stream server list.
+    ///
+    /// If the `primary` option is set, the counter only covers the first
+    /// server of the list.
+    fn counter(&self, preferred: bool) -> ServerListCounter {
+        let list = if preferred {
+            &self.preferred
+        } else {
+            &self.stream
+        };
+        if self.options.primary {
+            ServerListCounter::primary(list)
+        } else {
+            list.counter(self.options.rotate)
+        }
+    }
+
     pub async fn query<N: ToDname, Q: Into<Question<N>>>(
         &self,
         question: Q,
@@ -127,18 +179,42 @@ impl StubResolver {
         lookup_addr(&self, addr).await
     }
 
+    /// Looks up the IP addresses of a host.
+    ///
+    /// Unlike the generic [`lookup_host`], this method honours the
+    /// resolver’s options: With `single_request` or `single_request_reopen`,
+    /// the AAAA query is only sent after the answer to the A query has
+    /// arrived. With `use_inet6`, the AAAA query is sent first and the A
+    /// query only if the answer contains no AAAA records. The IPv4
+    /// addresses are then returned as IPv4-mapped IPv6 addresses.
+    ///
+    /// [`lookup_host`]: ../lookup/host/fn.lookup_host.html
     pub async fn lookup_host(
         &self,
         qname: impl ToDname,
     ) -> Result<FoundHosts<&Self>, io::Error> {
-        lookup_host(&self, qname).await
+        lookup_host_with(&self, &self.options, qname).await
     }
 
+    /// Looks up the IP addresses of a host using the search list.
+    ///
+    /// This follows the rules of glibc’s resolver: If the name has at
+    /// least `ndots` dots, it is first tried as an absolute name. Then, if
+    /// the name has no dots and the `default_names` option is set or if it
+    /// has dots and the `dn_search` option is set, the suffixes of the
+    /// search list are tried. Finally, if the name hasn’t been tried as an
+    /// absolute name yet, it is tried as such unless it has no dots and the
+    /// `no_tld_query` option is set.
+    ///
+    /// All lookups are done via [`lookup_host`] and the first one
+    /// returning addresses wins.
+    ///
+    /// [`lookup_host`]: #method.lookup_host
     pub async fn search_host(
         &self,
         qname: impl ToRelativeDname,
     ) -> Result<FoundHosts<&Self>, io::Error> {
-        search_host(&self, qname).await
+        search_host_with(&self, &self.options, qname).await
     }
 
     pub async fn lookup_srv(
@@ -151,6 +227,99 @@ impl StubResolver {
     }
 }
 
+/// Looks up the IP addresses of a host honouring the resolver options.
+///
+/// This is the implementation of [`StubResolver::lookup_host`] shared
+/// with the synchronous resolver.
+///
+/// [`StubResolver::lookup_host`]: struct.StubResolver.html#method.lookup_host
+async fn lookup_host_with<R: Resolver>(
+    resolver: &R,
+    options: &ResolvOptions,
+    qname: impl ToDname,
+) -> Result<FoundHosts<R>, io::Error> {
+    if options.use_inet6 {
+        let aaaa = resolver.query((&qname, Rtype::Aaaa)).await;
+        let a = match aaaa {
+            Ok(ref answer) if has_aaaa(answer.as_ref()) => {
+                Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "IPv6 addresses found",
+                ))
+            }
+            _ => resolver.query((&qname, Rtype::A)).await,
+        };
+        FoundHosts::new(aaaa, a).map(FoundHosts::map_ipv4)
+    } else if options.single_request || options.single_request_reopen {
+        // Each UDP query uses a fresh socket, so the two options only
+        // differ in glibc and behave the same here.
+        let a = resolver.query((&qname, Rtype::A)).await;
+        let aaaa = resolver.query((&qname, Rtype::Aaaa)).await;
+        FoundHosts::new(aaaa, a)
+    } else {
+        lookup_host(resolver, qname).await
+    }
+}
+
+/// Returns whether the answer section of a message contains AAAA records.
+///
+/// A CNAME without any addresses for its target doesn’t count.
+fn has_aaaa<Octets: AsRef<[u8]>>(message: &Message<Octets>) -> bool {
+    match message.for_slice().answer() {
+        Ok(answer) => answer.limit_to::<Aaaa>().any(|record| record.is_ok()),
+        Err(_) => false,
+    }
+}
+
+/// Looks up the IP addresses of a host using the search list.
+///
+/// This is the implementation of [`StubResolver::search_host`] shared
+/// with the synchronous resolver.
+///
+/// [`StubResolver::search_host`]: struct.StubResolver.html#method.search_host
+async fn search_host_with<R>(
+    resolver: &R,
+    options: &ResolvOptions,
+    qname: impl ToRelativeDname,
+) -> Result<FoundHosts<R>, io::Error>
+where
+    R: Resolver + SearchNames<Name = SearchSuffix>,
+{
+    let dots = qname.iter_labels().count().saturating_sub(1);
+    let mut res = Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        "no lookup possible for name",
+    ));
+    let tried_absolute = dots >= options.ndots;
+    if tried_absolute {
+        res =
+            lookup_host_with(resolver, options, (&qname).chain_root()).await;
+        if matches!(res, Ok(ref found) if !found.is_empty()) {
+            return res;
+        }
+    }
+    if (dots == 0 && options.default_names) || (dots > 0 && options.dn_search)
+    {
+        // The root suffix is covered by the absolute lookup.
+        for suffix in resolver.search_iter().filter(|s| !s.is_root()) {
+            if let Ok(name) = (&qname).chain(suffix) {
+                let found = lookup_host_with(resolver, options, name).await;
+                if matches!(found, Ok(ref found) if !found.is_empty()) {
+                    return found;
+                }
+                if res.is_err() {
+                    res = found;
+                }
+            }
+        }
+    }
+    if !tried_absolute && (dots > 0 || !options.no_tld_query) {
+        return lookup_host_with(resolver, options, (&qname).chain_root())
+            .await;
+    }
+    res
+}
+
 #[cfg(feature = "resolv-sync")]
 #[cfg_attr(docsrs, doc(cfg(feature = "resolv-sync")))]
 impl StubResolver {
@@ -186,7 +355,7 @@ impl StubResolver {
         F: FnOnce(StubResolver) -> R + Send + 'static,
     {
         let resolver = Self::from_conf(conf);
-        let runtime = runtime::Builder::new_current_thread()
+        let runtime = tokio::runtime::Builder::new_current_thread()
             .enable_all()
             .build()
             .unwrap();
@@ -194,6 +363,7 @@ impl StubResolver {
     }
 }
 
+#[cfg(feature = "resolv")]
 impl Default for StubResolver {
     fn default() -> Self {
         Self::new()
@@ -263,9 +433,9 @@ impl<'a> Query<'a> {
                         "no servers available",
                     ));
                 }
-                (false, resolver.stream.counter(resolver.options().rotate))
+                (false, resolver.counter(false))
             } else {
-                (true, resolver.preferred.counter(resolver.options().rotate))
+                (true, resolver.counter(true))
             };
         Ok(Query {
             resolver,
@@ -283,36 +453,22 @@ impl<'a> Query<'a> {
         mut self,
         mut message: QueryMessage,
     ) -> Result<Answer, io::Error> {
+        message.header_mut().set_rd(self.resolver.options().recurse);
+        if self.resolver.options().blast {
+            return self.run_blast(message).await;
+        }
         loop {
-            match self.run_query(&mut message).await {
-                Ok(answer) => {
-                    if answer.header().rcode() == Rcode::FormErr
-                        && self.current_server().does_edns()
-                    {
-                        // FORMERR with EDNS: turn off EDNS and try again.
-                        self.current_server().disable_edns();
+            let server = self.current_server();
+            let res = self.run_query(&mut message).await;
+            match self.process(server, res) {
+                Step::Again => continue,
+                Step::Next => {}
+                Step::Stream => {
+                    if self.switch_to_stream() {
                         continue;
-                    } else if answer.header().rcode() == Rcode::ServFail {
-                        // SERVFAIL: go to next server.
-                        self.update_error_servfail(answer);
-                    } else if answer.header().tc()
-                        && self.preferred
-                        && !self.resolver.options().ign_tc
-                    {
-                        // Truncated. If we can, switch to stream transports
-                        // and try again. Otherwise return the truncated
-                        // answer.
-                        if self.switch_to_stream() {
-                            continue;
-                        } else {
-                            return Ok(answer);
-                        }
-                    } else {
-                        // I guess we have an answer ...
-                        return Ok(answer);
                     }
                 }
-                Err(err) => self.update_error(err),
+                Step::Return(answer) => return Ok(answer),
             }
             if !self.next_server() {
                 return self.error;
@@ -320,6 +476,105 @@ impl<'a> Query<'a> {
         }
     }
 
+    /// Runs the query by sending it to all servers at once.
+    ///
+    /// The first acceptable answer is returned. If there is none, all
+    /// servers are tried again until we run out of attempts.
+    async fn run_blast(
+        mut self,
+        message: QueryMessage,
+    ) -> Result<Answer, io::Error> {
+        loop {
+            let mut servers = Vec::new();
+            loop {
+                servers.push(self.current_server());
+                if !self.counter.next() {
+                    break;
+                }
+            }
+            let messages: Vec<_> = servers
+                .iter()
+                .map(|server| {
+                    let mut message = message.clone();
+                    server.prepare_message(&mut message);
+                    message
+                })
+                .collect();
+            let mut queries: FuturesUnordered<_> = servers
+                .iter()
+                .zip(messages.iter())
+                .map(|(server, message)| async move {
+                    (*server, server.query(message).await)
+                })
+                .collect();
+            let mut stream = false;
+            while let Some((server, res)) = queries.next().await {
+                match self.process(server, res) {
+                    Step::Again | Step::Next => {}
+                    Step::Stream => {
+                        stream = true;
+                        break;
+                    }
+                    Step::Return(answer) => return Ok(answer),
+                }
+            }
+            drop(queries);
+            if stream && self.switch_to_stream() {
+                continue;
+            }
+            self.attempt += 1;
+            if self.attempt >= self.resolver.options().attempts {
+                return self.error;
+            }
+            self.counter = self.resolver.counter(self.preferred);
+        }
+    }
+
+    /// Decides what to do with the result of sending a query to a server.
+    fn process(
+        &mut self,
+        server: &ServerInfo,
+        res: Result<Answer, io::Error>,
+    ) -> Step {
+        let answer = match res {
+            Ok(answer) => answer,
+            Err(err) => {
+                self.update_error(err);
+                return Step::Next;
+            }
+        };
+        if answer.header().rcode() == Rcode::FormErr && server.does_edns() {
+            // FORMERR with EDNS: turn off EDNS and try again.
+            server.disable_edns();
+            Step::Again
+        } else if answer.header().rcode() == Rcode::ServFail {
+            // SERVFAIL: go to next server.
+            self.update_error_servfail(answer);
+            Step::Next
+        } else if answer.header().tc()
+            && self.preferred
+            && !self.resolver.options().ign_tc
+        {
+            // Truncated. If we can, switch to stream transports and try
+            // again. Otherwise return the truncated answer.
+            if self.resolver.stream.is_empty() {
+                Step::Return(answer)
+            } else {
+                Step::Stream
+            }
+        } else if self.resolver.options().aa_only && !answer.header().aa() {
+            // Not authoritative but we only want those.
+            self.update_error(io::Error::new(
+                io::ErrorKind::Other,
+                "non-authoritative answer",
+            ));
+            Step::Next
+        } else {
+            // I guess we have an answer ...
+            Step::Return(answer)
+        }
+    }
+
     fn create_message(question: Question<impl ToDname>) -> QueryMessage {
         let mut message = MessageBuilder::from_target(
             StreamTarget::new(Octets512::new()).unwrap(),
@@ -340,7 +595,7 @@ impl<'a> Query<'a> {
         server.query(message).await
     }
 
-    fn current_server(&self) -> &ServerInfo {
+    fn current_server(&self) -> &'a ServerInfo {
         let list = if self.preferred {
             &self.resolver.preferred
         } else {
@@ -369,8 +624,7 @@ impl<'a> Query<'a> {
         }
         self.preferred = false;
         self.attempt = 0;
-        self.counter =
-            self.resolver.stream.counter(self.resolver.options().rotate);
+        self.counter = self.resolver.counter(false);
         true
     }
 
@@ -382,17 +636,28 @@ impl<'a> Query<'a> {
         if self.attempt >= self.resolver.options().attempts {
             return false;
         }
-        self.counter = if self.preferred {
-            self.resolver
-                .preferred
-                .counter(self.resolver.options().rotate)
-        } else {
-            self.resolver.stream.counter(self.resolver.options().rotate)
-        };
+        self.counter = self.resolver.counter(self.preferred);
         true
     }
 }
 
+//------------ Step ----------------------------------------------------------
+
+/// What a query should do after receiving a response from a server.
+enum Step {
+    /// Send the query to the same server again.
+    Again,
+
+    /// Try the next server.
+    Next,
+
+    /// Switch to the stream servers.
+    Stream,
+
+    /// Return the answer.
+    Return(Answer),
+}
+
 //------------ QueryMessage --------------------------------------------------
 
 // XXX This needs to be re-evaluated if we start adding OPTtions to the query.
@@ -425,6 +690,19 @@ impl Answer {
     pub fn into_message(self) -> Message<Bytes> {
         self.message
     }
+
+    /// Returns the EDNS Client Subnet option of the answer if present.
+    ///
+    /// The scope prefix length of the option tells for which clients the
+    /// answer is valid. See [`ClientSubnet::scope_contains`] for checking
+    /// a client address. The resolver drops answers whose option doesn’t
+    /// match the one sent in the query, so the option’s address is the
+    /// configured client subnet.
+    ///
+    /// [`ClientSubnet::scope_contains`]: ../../base/opt/rfc7871/struct.ClientSubnet.html#method.scope_contains
+    pub fn client_subnet(&self) -> Option<ClientSubnet> {
+        self.message.opt()?.iter::<ClientSubnet>().next()?.ok()
+    }
 }
 
 impl From<Message<Bytes>> for Answer {
@@ -444,6 +722,29 @@ struct ServerInfo {
     ///
     /// We start out with assuming it does and unset it if we get a FORMERR.
     edns: Arc<AtomicBool>,
+
+    /// The runtime for accessing the network and timers.
+    runtime: Arc<dyn Runtime>,
+
+    /// Whether to keep TCP connections open between queries.
+    stay_open: bool,
+
+    /// The connection kept open for the TCP and TLS transports.
+    conn: Arc<conn::Connection>,
+
+    /// The information for establishing TLS connections.
+    ///
+    /// This is `None` if the transport isn’t TLS or if the TLS config is
+    /// invalid.
+    #[cfg(feature = "resolv-tls")]
+    tls: Option<tls::TlsInfo>,
+
+    /// The information for the HTTPS transport.
+    ///
+    /// This is `None` if the transport isn’t HTTPS or if the HTTPS config
+    /// is invalid.
+    #[cfg(feature = "resolv-https")]
+    https: Option<Arc<https::HttpsInfo>>,
 }
 
 impl ServerInfo {
@@ -461,62 +762,110 @@ impl ServerInfo {
             query
                 .opt(|opt| {
                     opt.set_udp_payload_size(self.conf.udp_payload_size);
+                    if self.keeps_open() {
+                        // Ask for the server’s idle timeout.
+                        opt.push_raw_option(
+                            OptionCode::TcpKeepalive,
+                            |_| Ok(()),
+                        )?;
+                    }
+                    if let Some(ref subnet) = self.conf.client_subnet {
+                        opt.push(&subnet.to_option())?;
+                    }
                     Ok(())
                 })
                 .unwrap();
         }
     }
 
+    /// Returns whether the server’s connection is kept open.
+    fn keeps_open(&self) -> bool {
+        match self.conf.transport {
+            Transport::Tcp => self.stay_open,
+            #[cfg(feature = "resolv-tls")]
+            Transport::Tls => true,
+            _ => false,
+        }
+    }
+
     pub async fn query(
         &self,
         query: &QueryMessage,
     ) -> Result<Answer, io::Error> {
-        let res = match self.conf.transport {
-            Transport::Udp => {
-                timeout(
-                    self.conf.request_timeout,
-                    Self::udp_query(
-                        query,
-                        self.conf.addr,
-                        self.conf.recv_size,
-                    ),
-                )
-                .await
-            }
-            Transport::Tcp => {
-                timeout(
-                    self.conf.request_timeout,
-                    Self::tcp_query(query, self.conf.addr),
-                )
-                .await
-            }
-        };
+        let res = runtime::timeout(
+            self.runtime.as_ref(),
+            self.conf.request_timeout,
+            async {
+                match self.conf.transport {
+                    Transport::Udp => self.udp_query(query).await,
+                    Transport::Tcp if self.stay_open => {
+                        self.conn_query(query, || {
+                            self.runtime.tcp_connect(self.conf.addr)
+                        })
+                        .await
+                    }
+                    Transport::Tcp => self.tcp_query(query).await,
+                    #[cfg(feature = "resolv-tls")]
+                    Transport::Tls => self.tls_query(query).await,
+                    #[cfg(feature = "resolv-https")]
+                    Transport::Https => self.https_query(query).await,
+                }
+            },
+        )
+        .await;
         match res {
-            Ok(Ok(answer)) => Ok(answer),
-            Ok(Err(err)) => Err(err),
-            Err(_) => Err(io::Error::new(
+            Some(res) => res
+                .and_then(|answer| Self::check_client_subnet(query, answer)),
+            None => Err(io::Error::new(
                 io::ErrorKind::TimedOut,
                 "request timed out",
             )),
         }
     }
 
+    /// Checks the EDNS Client Subnet option of an answer.
+    ///
+    /// If the query contained the option, an option in the answer has to
+    /// echo family, source prefix, and address as required by RFC 7871.
+    /// If the query didn’t contain it, the answer mustn’t either. Answers
+    /// that violate this are dropped.
+    fn check_client_subnet(
+        query: &QueryMessage,
+        answer: Answer,
+    ) -> Result<Answer, io::Error> {
+        let echoed = match answer.client_subnet() {
+            Some(echoed) => echoed,
+            None => return Ok(answer),
+        };
+        let sent = query
+            .as_message()
+            .opt()
+            .and_then(|opt| opt.iter::<ClientSubnet>().next())
+            .and_then(Result::ok);
+        match sent {
+            Some(sent) if echoed.is_response_to(&sent) => Ok(answer),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "client subnet mismatch",
+            )),
+        }
+    }
+
     pub async fn tcp_query(
+        &self,
         query: &QueryMessage,
-        addr: SocketAddr,
     ) -> Result<Answer, io::Error> {
-        let mut sock = TcpStream::connect(&addr).await?;
+        let mut sock = self.runtime.tcp_connect(self.conf.addr).await?;
         sock.write_all(query.as_target().as_stream_slice()).await?;
 
         // This loop can be infinite because we have a timeout on this whole
         // thing, anyway.
         loop {
-            let mut buf = Vec::new();
-            let len = sock.read_u16().await? as u64;
-            AsyncReadExt::take(&mut sock, len)
-                .read_to_end(&mut buf)
-                .await?;
-            if let Ok(answer) = Message::from_octets(buf.into()) {
+            let mut len = [0u8; 2];
+            sock.read_exact(&mut len).await?;
+            let mut buf = vec![0; usize::from(u16::from_be_bytes(len))];
+            sock.read_exact(&mut buf).await?;
+            if let Ok(answer) = Message::from_octets(Bytes::from(buf)) {
                 if answer.is_answer(&query.as_message()) {
                     return Ok(answer.into());
                 }
@@ -530,23 +879,97 @@ impl ServerInfo {
         }
     }
 
+    #[cfg(feature = "resolv-tls")]
+    pub async fn tls_query(
+        &self,
+        query: &QueryMessage,
+    ) -> Result<Answer, io::Error> {
+        let tls = self.tls.as_ref().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid TLS authentication name",
+            )
+        })?;
+        self.conn_query(query, || async move {
+            let stream =
+                tls.connect(self.runtime.as_ref(), self.conf.addr).await?;
+            Ok(runtime::TokioStream(stream))
+        })
+        .await
+    }
+
+    /// Sends a query over the server’s shared connection.
+    ///
+    /// If there currently is no connection, a new one is established via
+    /// `connect`.
+    pub async fn conn_query<S, F, Fut>(
+        &self,
+        query: &QueryMessage,
+        connect: F,
+    ) -> Result<Answer, io::Error>
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<S, io::Error>>,
+    {
+        let answer = self
+            .conn
+            .query(
+                query.as_target().as_stream_slice(),
+                self.conf.idle_timeout,
+                connect,
+            )
+            .await?;
+        if answer.is_answer(&query.as_message()) {
+            Ok(answer.into())
+        } else {
+            Err(io::Error::new(io::ErrorKind::Other, "unexpected answer"))
+        }
+    }
+
+    #[cfg(feature = "resolv-https")]
+    pub async fn https_query(
+        &self,
+        query: &QueryMessage,
+    ) -> Result<Answer, io::Error> {
+        let https = self.https.as_ref().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid HTTPS configuration",
+            )
+        })?;
+        let answer = https
+            .query(
+                query.as_target().as_dgram_slice(),
+                self.conf.addr,
+                self.conf.idle_timeout,
+            )
+            .await?;
+        if answer.is_answer(&query.as_message()) {
+            Ok(answer.into())
+        } else {
+            Err(io::Error::new(io::ErrorKind::Other, "unexpected answer"))
+        }
+    }
+
     pub async fn udp_query(
+        &self,
         query: &QueryMessage,
-        addr: SocketAddr,
-        recv_size: usize,
     ) -> Result<Answer, io::Error> {
-        let sock = Self::udp_bind(addr.is_ipv4()).await?;
-        sock.connect(addr).await?;
-        let sent = sock.send(query.as_target().as_dgram_slice()).await?;
-        if sent != query.as_target().as_dgram_slice().len() {
+        let sock = self.udp_bind(self.conf.addr.is_ipv4()).await?;
+        sock.connect(self.conf.addr).await?;
+        let slice = query.as_target().as_dgram_slice();
+        let sent = runtime::send(sock.as_ref(), slice).await?;
+        if sent != slice.len() {
             return Err(io::Error::new(
                 io::ErrorKind::Other,
                 "short UDP send",
             ));
         }
         loop {
-            let mut buf = vec![0; recv_size]; // XXX use uninit'ed mem here.
-            let len = sock.recv(&mut buf).await?;
+            // XXX use uninit'ed mem here.
+            let mut buf = vec![0; self.conf.recv_size];
+            let len = runtime::recv(sock.as_ref(), &mut buf).await?;
             buf.truncate(len);
 
             // We ignore garbage since there is a timer on this whole thing.
@@ -561,7 +984,10 @@ impl ServerInfo {
         }
     }
 
-    async fn udp_bind(v4: bool) -> Result<UdpSocket, io::Error> {
+    async fn udp_bind(
+        &self,
+        v4: bool,
+    ) -> Result<Box<dyn runtime::AsyncDgram>, io::Error> {
         let mut i = 0;
         loop {
             let local: SocketAddr = if v4 {
@@ -569,7 +995,7 @@ impl ServerInfo {
             } else {
                 ([0u16; 8], 0).into()
             };
-            match UdpSocket::bind(&local).await {
+            match self.runtime.udp_bind(local).await {
                 Ok(sock) => return Ok(sock),
                 Err(err) => {
                     if i == RETRY_RANDOM_PORT {
@@ -583,21 +1009,37 @@ impl ServerInfo {
     }
 }
 
-impl From<ServerConf> for ServerInfo {
-    fn from(conf: ServerConf) -> Self {
+impl ServerInfo {
+    fn new(
+        conf: &ServerConf,
+        options: &ResolvOptions,
+        runtime: &Arc<dyn Runtime>,
+    ) -> Self {
+        let conf = conf.clone();
         ServerInfo {
+            runtime: runtime.clone(),
+            stay_open: options.stay_open,
+            conn: Arc::new(conn::Connection::new(runtime.clone())),
+            #[cfg(feature = "resolv-tls")]
+            tls: if conf.transport == Transport::Tls {
+                tls::TlsInfo::new(&conf, None, &[b"dot"]).ok()
+            } else {
+                None
+            },
+            #[cfg(feature = "resolv-https")]
+            https: if conf.transport == Transport::Https {
+                https::HttpsInfo::new(&conf, runtime.clone())
+                    .ok()
+                    .map(Arc::new)
+            } else {
+                None
+            },
             conf,
             edns: Arc::new(AtomicBool::new(true)),
         }
     }
 }
 
-impl<'a> From<&'a ServerConf> for ServerInfo {
-    fn from(conf: &'a ServerConf) -> Self {
-        conf.clone().into()
-    }
-}
-
 //------------ ServerList ----------------------------------------------------
 
 #[derive(Clone, Debug)]
@@ -617,7 +1059,11 @@ struct ServerList {
 }
 
 impl ServerList {
-    pub fn from_conf<F>(conf: &ResolvConf, filter: F) -> Self
+    pub fn from_conf<F>(
+        conf: &ResolvConf,
+        runtime: &Arc<dyn Runtime>,
+        filter: F,
+    ) -> Self
     where
         F: Fn(&ServerConf) -> bool,
     {
@@ -626,7 +1072,9 @@ impl ServerList {
                 conf.servers
                     .iter()
                     .filter(|f| filter(f))
-                    .map(Into::into)
+                    .map(|server| {
+                        ServerInfo::new(server, &conf.options, runtime)
+                    })
                     .collect()
             },
             start: Arc::new(AtomicUsize::new(0)),
@@ -694,6 +1142,14 @@ impl ServerListCounter {
         }
     }
 
+    /// Creates a counter that only covers the first server.
+    fn primary(list: &ServerList) -> Self {
+        ServerListCounter {
+            cur: 0,
+            end: cmp::min(list.servers.len(), 1),
+        }
+    }
+
     #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> bool {
         let next = self.cur + 1;
@@ -773,3 +1229,554 @@ impl<'a> Iterator for SearchIter<'a> {
         }
     }
 }
+
+//============ Testing =======================================================
+
+#[cfg(all(test, feature = "resolv"))]
+mod test {
+    use super::conf::{ClientSubnetConf, ResolvConf, ServerConf, Transport};
+    use super::{Query, StubResolver};
+    use crate::base::iana::{Rcode, Rtype};
+    use crate::base::message::Message;
+    use crate::base::message_builder::MessageBuilder;
+    use crate::base::name::Dname;
+    use crate::base::opt::ClientSubnet;
+    use crate::base::question::Question;
+    use crate::rdata::{Aaaa, Cname, A};
+    use std::io::Cursor;
+    use std::net::{IpAddr, SocketAddr};
+    use std::str::FromStr;
+    use std::string::{String, ToString};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+    use std::vec::Vec;
+    use tokio::net::UdpSocket;
+
+    /// How a test server behaves.
+    #[derive(Clone, Default)]
+    struct Behaviour {
+        /// The addresses of the hosts the server knows about.
+        hosts: Vec<(&'static str, &'static str)>,
+
+        /// Aliases and their targets the server knows about.
+        cnames: Vec<(&'static str, &'static str)>,
+
+        /// Whether to set the AA bit in answers.
+        aa: bool,
+
+        /// Answer with SERVFAIL.
+        servfail: bool,
+
+        /// Don’t answer at all.
+        silent: bool,
+
+        /// How long to wait before answering.
+        delay: Duration,
+
+        /// The EDNS Client Subnet option to include in answers.
+        client_subnet: Option<ClientSubnet>,
+    }
+
+    /// A UDP server for testing.
+    struct Server {
+        addr: SocketAddr,
+
+        /// The questions received by the server.
+        ///
+        /// Besides name and type, this keeps the RD bit and the port the
+        /// query was sent from.
+        log: Arc<Mutex<Vec<(String, Rtype, bool, u16)>>>,
+
+        /// The maximum number of queries in flight at the same time.
+        max_in_flight: Arc<AtomicUsize>,
+    }
+
+    impl Server {
+        async fn start(behaviour: Behaviour) -> Self {
+            let sock =
+                Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+            let addr = sock.local_addr().unwrap();
+            let log = Arc::new(Mutex::new(Vec::new()));
+            let in_flight = Arc::new(AtomicUsize::new(0));
+            let max_in_flight = Arc::new(AtomicUsize::new(0));
+            let res = Server {
+                addr,
+                log: log.clone(),
+                max_in_flight: max_in_flight.clone(),
+            };
+            let behaviour = Arc::new(behaviour);
+            tokio::spawn(async move {
+                loop {
+                    let mut buf = vec![0; 512];
+                    let (len, peer) = sock.recv_from(&mut buf).await.unwrap();
+                    buf.truncate(len);
+                    let query = Message::from_octets(buf).unwrap();
+                    let question = query.sole_question().unwrap();
+                    let qname = question.qname().to_string();
+                    log.lock().unwrap().push((
+                        qname.clone(),
+                        question.qtype(),
+                        query.header().rd(),
+                        peer.port(),
+                    ));
+                    if behaviour.silent {
+                        continue;
+                    }
+                    let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_in_flight.fetch_max(now, Ordering::SeqCst);
+                    let sock = sock.clone();
+                    let behaviour = behaviour.clone();
+                    let in_flight = in_flight.clone();
+                    tokio::spawn(async move {
+                        tokio::time::sleep(behaviour.delay).await;
+                        let answer = Self::answer(&behaviour, &query, &qname);
+                        in_flight.fetch_sub(1, Ordering::SeqCst);
+                        let _ = sock.send_to(answer.as_slice(), peer).await;
+                    });
+                }
+            });
+            res
+        }
+
+        fn answer(
+            behaviour: &Behaviour,
+            query: &Message<Vec<u8>>,
+            qname: &str,
+        ) -> Message<Vec<u8>> {
+            let question = query.sole_question().unwrap();
+            let cname = behaviour
+                .cnames
+                .iter()
+                .find(|(alias, _)| *alias == qname)
+                .map(|(_, target)| {
+                    Dname::<Vec<u8>>::from_str(target).unwrap()
+                });
+            let target = match cname {
+                Some(ref target) => target.to_string(),
+                None => qname.into(),
+            };
+            let hosts: Vec<IpAddr> = behaviour
+                .hosts
+                .iter()
+                .filter(|(name, _)| *name == target)
+                .map(|(_, addr)| IpAddr::from_str(addr).unwrap())
+                .collect();
+            let rcode = if behaviour.servfail {
+                Rcode::ServFail
+            } else if hosts.is_empty() && cname.is_none() {
+                Rcode::NXDomain
+            } else {
+                Rcode::NoError
+            };
+            let mut answer = MessageBuilder::new_vec()
+                .start_answer(query, rcode)
+                .unwrap();
+            answer.header_mut().set_aa(behaviour.aa);
+            if rcode == Rcode::NoError {
+                let owner = match cname {
+                    Some(target) => {
+                        answer
+                            .push((
+                                question.qname(),
+                                60,
+                                Cname::new(target.clone()),
+                            ))
+                            .unwrap();
+                        target
+                    }
+                    None => Dname::<Vec<u8>>::from_str(qname).unwrap(),
+                };
+                for addr in hosts {
+                    match (addr, question.qtype()) {
+                        (IpAddr::V4(addr), Rtype::A) => {
+                            answer.push((&owner, 60, A::new(addr))).unwrap()
+                        }
+                        (IpAddr::V6(addr), Rtype::Aaaa) => answer
+                            .push((&owner, 60, Aaaa::new(addr)))
+                            .unwrap(),
+                        _ => {}
+                    }
+                }
+            }
+            let mut answer = answer.additional();
+            if let Some(ref subnet) = behaviour.client_subnet {
+                answer.opt(|opt| opt.push(subnet)).unwrap();
+            }
+            answer.into_message()
+        }
+
+        /// Returns the names and types of the received questions.
+        fn questions(&self) -> Vec<(String, Rtype)> {
+            self.log
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(name, rtype, _, _)| (name.clone(), *rtype))
+                .collect()
+        }
+
+        /// Returns the distinct ports queries were sent from.
+        fn ports(&self) -> Vec<u16> {
+            let mut ports: Vec<_> =
+                self.log.lock().unwrap().iter().map(|q| q.3).collect();
+            ports.sort_unstable();
+            ports.dedup();
+            ports
+        }
+
+        fn max_in_flight(&self) -> usize {
+            self.max_in_flight.load(Ordering::SeqCst)
+        }
+    }
+
+    /// Creates a resolver for the servers from resolv.conf content.
+    fn resolver(servers: &[&Server], conf: &str) -> StubResolver {
+        let mut res = ResolvConf::new();
+        res.parse(&mut Cursor::new(conf)).unwrap();
+        for server in servers {
+            res.servers
+                .push(ServerConf::new(server.addr, Transport::Udp));
+        }
+        res.finalize();
+        StubResolver::from_conf(res)
+    }
+
+    async fn query(resolver: &StubResolver, qname: &str) -> Option<Rcode> {
+        resolver
+            .query((Dname::<Vec<u8>>::from_str(qname).unwrap(), Rtype::A))
+            .await
+            .ok()
+            .map(|answer| answer.header().rcode())
+    }
+
+    fn host(name: &'static str) -> Behaviour {
+        Behaviour {
+            hosts: vec![(name, "192.0.2.1"), (name, "2001:db8::1")],
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn aa_only() {
+        let non_auth = Server::start(host("a.example")).await;
+        let auth = Server::start(Behaviour {
+            aa: true,
+            ..host("a.example")
+        })
+        .await;
+
+        let plain = resolver(&[&non_auth], "options attempts:1");
+        assert_eq!(query(&plain, "a.example").await, Some(Rcode::NoError));
+        let only = resolver(&[&non_auth], "options aa-only attempts:1");
+        assert_eq!(query(&only, "a.example").await, None);
+        let only =
+            resolver(&[&non_auth, &auth], "options aa-only attempts:1");
+        assert_eq!(query(&only, "a.example").await, Some(Rcode::NoError));
+        assert_eq!(auth.questions().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn primary() {
+        let failing = Server::start(Behaviour {
+            servfail: true,
+            ..host("a.example")
+        })
+        .await;
+        let working = Server::start(host("a.example")).await;
+
+        let plain = resolver(&[&failing, &working], "options attempts:1");
+        assert_eq!(query(&plain, "a.example").await, Some(Rcode::NoError));
+        assert_eq!(working.questions().len(), 1);
+        let primary = resolver(
+            &[&failing, &working],
+            "options primary rotate attempts:1",
+        );
+        for _ in 0..3 {
+            assert_eq!(
+                query(&primary, "a.example").await,
+                Some(Rcode::ServFail)
+            );
+        }
+        assert_eq!(working.questions().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn blast() {
+        let silent = Server::start(Behaviour {
+            silent: true,
+            ..Default::default()
+        })
+        .await;
+        let working = Server::start(host("a.example")).await;
+        let blast = resolver(
+            &[&silent, &working],
+            "options blast timeout:5 attempts:1",
+        );
+        let start = Instant::now();
+        assert_eq!(query(&blast, "a.example").await, Some(Rcode::NoError));
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert_eq!(silent.questions().len(), 1);
+        assert_eq!(working.questions().len(), 1);
+
+        // Without any answer, all attempts are used.
+        let blast =
+            resolver(&[&silent], "options blast timeout:1 attempts:2");
+        assert_eq!(query(&blast, "a.example").await, None);
+        assert_eq!(silent.questions().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn recurse() {
+        let server = Server::start(host("a.example")).await;
+        query(&resolver(&[&server], ""), "a.example").await;
+        query(&resolver(&[&server], "options no-recurse"), "a.example").await;
+        let rd: Vec<_> =
+            server.log.lock().unwrap().iter().map(|q| q.2).collect();
+        assert_eq!(rd, vec![true, false]);
+    }
+
+    #[tokio::test]
+    async fn single_request() {
+        let server = Server::start(Behaviour {
+            delay: Duration::from_millis(100),
+            ..host("a.example")
+        })
+        .await;
+        let name = Dname::<Vec<u8>>::from_str("a.example").unwrap();
+
+        let parallel = resolver(&[&server], "");
+        let res = parallel.lookup_host(&name).await.unwrap();
+        assert_eq!(res.iter().count(), 2);
+        assert_eq!(server.max_in_flight(), 2);
+
+        let server = Server::start(Behaviour {
+            delay: Duration::from_millis(100),
+            ..host("a.example")
+        })
+        .await;
+        let single = resolver(&[&server], "options single-request");
+        let res = single.lookup_host(&name).await.unwrap();
+        assert_eq!(res.iter().count(), 2);
+        assert_eq!(server.max_in_flight(), 1);
+        assert_eq!(
+            server.questions(),
+            vec![
+                ("a.example".into(), Rtype::A),
+                ("a.example".into(), Rtype::Aaaa)
+            ]
+        );
+
+        let server = Server::start(Behaviour {
+            delay: Duration::from_millis(100),
+            ..host("a.example")
+        })
+        .await;
+        let reopen = resolver(&[&server], "options single-request-reopen");
+        let res = reopen.lookup_host(&name).await.unwrap();
+        assert_eq!(res.iter().count(), 2);
+        assert_eq!(server.max_in_flight(), 1);
+        assert_eq!(server.ports().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn inet6() {
+        let server = Server::start(Behaviour {
+            hosts: vec![
+                ("v4.example", "192.0.2.1"),
+                ("both.example", "192.0.2.1"),
+                ("both.example", "2001:db8::1"),
+            ],
+            cnames: vec![("alias.example", "v4.example")],
+            ..Default::default()
+        })
+        .await;
+        let resolver = resolver(&[&server], "options inet6");
+
+        let name = Dname::<Vec<u8>>::from_str("both.example").unwrap();
+        let res = resolver.lookup_host(&name).await.unwrap();
+        assert_eq!(
+            res.iter().collect::<Vec<_>>(),
+            vec![IpAddr::from_str("2001:db8::1").unwrap()]
+        );
+        let name = Dname::<Vec<u8>>::from_str("v4.example").unwrap();
+        let res = resolver.lookup_host(&name).await.unwrap();
+        assert_eq!(
+            res.iter().collect::<Vec<_>>(),
+            vec![IpAddr::from_str("::ffff:192.0.2.1").unwrap()]
+        );
+
+        // An answer with only a CNAME has no IPv6 addresses.
+        let name = Dname::<Vec<u8>>::from_str("alias.example").unwrap();
+        let res = resolver.lookup_host(&name).await.unwrap();
+        assert_eq!(
+            res.iter().collect::<Vec<_>>(),
+            vec![IpAddr::from_str("::ffff:192.0.2.1").unwrap()]
+        );
+        assert_eq!(
+            server.questions(),
+            vec![
+                ("both.example".into(), Rtype::Aaaa),
+                ("v4.example".into(), Rtype::Aaaa),
+                ("v4.example".into(), Rtype::A),
+                ("alias.example".into(), Rtype::Aaaa),
+                ("alias.example".into(), Rtype::A),
+            ]
+        );
+    }
+
+    /// Returns the names searched for when looking up `name`.
+    async fn searched(conf: &str, name: &str) -> Vec<String> {
+        let server = Server::start(host("host.example.com")).await;
+        let resolver = resolver(&[&server], conf);
+        let _ = resolver
+            .search_host(
+                Dname::<Vec<u8>>::from_str(name).unwrap().into_relative(),
+            )
+            .await;
+        server
+            .questions()
+            .into_iter()
+            .filter(|(_, rtype)| *rtype == Rtype::A)
+            .map(|(name, _)| name)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn search() {
+        // These are the names glibc tries for the same configuration.
+        let conf = "search example.com example.net";
+        assert_eq!(searched(conf, "host").await, vec!["host.example.com"]);
+        assert_eq!(
+            searched(conf, "missing").await,
+            vec!["missing.example.com", "missing.example.net", "missing"]
+        );
+        assert_eq!(
+            searched("search example.com\noptions no-tld-query", "missing")
+                .await,
+            vec!["missing.example.com"]
+        );
+        assert_eq!(
+            searched(conf, "a.missing").await,
+            vec![
+                "a.missing",
+                "a.missing.example.com",
+                "a.missing.example.net"
+            ]
+        );
+        assert_eq!(
+            searched("search example.com\noptions ndots:2", "a.missing")
+                .await,
+            vec!["a.missing.example.com", "a.missing"]
+        );
+        assert_eq!(
+            searched(
+                "search example.com\noptions no-tld-query ndots:2",
+                "a.missing"
+            )
+            .await,
+            vec!["a.missing.example.com", "a.missing"]
+        );
+    }
+
+    #[tokio::test]
+    async fn client_subnet_echo() {
+        let sent =
+            ClientSubnet::new(24, 0, IpAddr::from_str("192.0.2.0").unwrap());
+        let wrong = Server::start(Behaviour {
+            client_subnet: Some(ClientSubnet::new(
+                24,
+                24,
+                IpAddr::from_str("198.51.100.0").unwrap(),
+            )),
+            ..host("a.example")
+        })
+        .await;
+        let right = Server::start(Behaviour {
+            client_subnet: Some(sent.response(16)),
+            ..host("a.example")
+        })
+        .await;
+        let mut conf = ResolvConf::new();
+        conf.parse(&mut Cursor::new("options attempts:1")).unwrap();
+        conf.options.client_subnet = Some(ClientSubnetConf::new(
+            IpAddr::from_str("192.0.2.77").unwrap(),
+        ));
+        for server in [&wrong, &right] {
+            conf.servers
+                .push(ServerConf::new(server.addr, Transport::Udp));
+        }
+        conf.finalize();
+        let ecs = StubResolver::from_conf(conf);
+
+        // The answer with the wrong address is dropped.
+        let answer = ecs
+            .query((
+                Dname::<Vec<u8>>::from_str("a.example").unwrap(),
+                Rtype::A,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(answer.client_subnet(), Some(sent.response(16)));
+        assert_eq!(wrong.questions().len(), 1);
+        assert_eq!(right.questions().len(), 1);
+
+        // Without an option in the query, there mustn’t be one in the
+        // answer.
+        let plain = resolver(&[&right], "options attempts:1");
+        assert_eq!(query(&plain, "a.example").await, None);
+    }
+
+    #[test]
+    fn client_subnet() {
+        let mut conf = ResolvConf::new();
+        conf.options.client_subnet = Some(ClientSubnetConf::new(
+            IpAddr::from_str("192.0.2.77").unwrap(),
+        ));
+        conf.servers.push(ServerConf::new(
+            ([192, 0, 2, 53], 53).into(),
+            Transport::Udp,
+        ));
+        let mut own =
+            ServerConf::new(([192, 0, 2, 54], 53).into(), Transport::Udp);
+        own.client_subnet = Some(ClientSubnetConf {
+            ipv6_prefix_len: 48,
+            ..ClientSubnetConf::new(
+                IpAddr::from_str("2001:db8:1:2::1").unwrap(),
+            )
+        });
+        conf.servers.push(own);
+        conf.finalize();
+        let resolver = StubResolver::from_conf(conf);
+        let subnets: Vec<_> = resolver
+            .preferred
+            .servers
+            .iter()
+            .map(|server| {
+                let mut message = Query::create_message(Question::new_in(
+                    Dname::root_vec(),
+                    Rtype::A,
+                ));
+                server.prepare_message(&mut message);
+                let opt = message.as_message().opt().unwrap();
+                let subnet = opt.iter::<ClientSubnet>().next();
+                subnet.unwrap().unwrap()
+            })
+            .collect();
+        assert_eq!(
+            subnets,
+            [
+                ClientSubnet::new(
+                    24,
+                    0,
+                    IpAddr::from_str("192.0.2.0").unwrap()
+                ),
+                ClientSubnet::new(
+                    48,
+                    0,
+                    IpAddr::from_str("2001:db8:1::").unwrap()
+                ),
+            ]
+        );
+    }
+}
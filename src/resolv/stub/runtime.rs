@@ -0,0 +1,556 @@
+//! The async runtime used by the stub resolver.
+//!
+//! The stub resolver doesn’t access the network and the system clock
+//! directly but via the [`Runtime`] trait defined by this module. This
+//! makes it possible to use the resolver with async runtimes other than
+//! Tokio or to replace the network with something else entirely, such as
+//! a mock implementation for testing.
+//!
+//! A runtime provides four things: UDP sockets implementing the
+//! [`AsyncDgram`] trait, TCP connections implementing the asynchronous IO
+//! traits of the futures crate, timers, and a way to spawn background
+//! tasks. The latter are used for driving connections that are kept open
+//! between queries. The default runtime is [`TokioRuntime`] which uses
+//! Tokio for all of these and is available with the `resolv` feature.
+//!
+//! [`Runtime`]: trait.Runtime.html
+//! [`AsyncDgram`]: trait.AsyncDgram.html
+//! [`TokioRuntime`]: struct.TokioRuntime.html
+
+#[cfg(feature = "resolv")]
+use futures::future::FutureExt;
+use futures::future::{self, BoxFuture, Either};
+use futures::io::{AsyncRead, AsyncWrite};
+#[cfg(feature = "resolv")]
+use futures::ready;
+use std::boxed::Box;
+use std::future::Future;
+use std::net::SocketAddr;
+#[cfg(feature = "resolv")]
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use std::{fmt, io};
+
+//------------ Runtime -------------------------------------------------------
+
+/// The network and timer operations needed by the stub resolver.
+///
+/// All methods return boxed futures, so the trait can be used as a trait
+/// object.
+pub trait Runtime: fmt::Debug + Send + Sync {
+    /// Creates a UDP socket bound to the given local address.
+    ///
+    /// The resolver always uses the unspecified address and port zero of
+    /// the address family it needs.
+    fn udp_bind(
+        &self,
+        local: SocketAddr,
+    ) -> BoxFuture<'static, Result<Box<dyn AsyncDgram>, io::Error>>;
+
+    /// Establishes a TCP connection with the given address.
+    fn tcp_connect(
+        &self,
+        addr: SocketAddr,
+    ) -> BoxFuture<'static, Result<Box<dyn AsyncStream>, io::Error>>;
+
+    /// Returns a future that resolves after the given duration.
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()>;
+
+    /// Runs a future in the background.
+    ///
+    /// The future needs to be driven to completion independently of the
+    /// caller.
+    fn spawn(&self, fut: BoxFuture<'static, ()>);
+}
+
+//------------ AsyncDgram ----------------------------------------------------
+
+/// An asynchronous UDP socket.
+///
+/// The socket is connected to the server before being used, so only the
+/// connected versions of sending and receiving are needed.
+pub trait AsyncDgram: Send + Sync {
+    /// Connects the socket to the given remote address.
+    fn connect(
+        &self,
+        addr: SocketAddr,
+    ) -> BoxFuture<'_, Result<(), io::Error>>;
+
+    /// Attempts to send a datagram to the connected address.
+    fn poll_send(
+        &self,
+        cx: &mut Context,
+        buf: &[u8],
+    ) -> Poll<Result<usize, io::Error>>;
+
+    /// Attempts to receive a datagram from the connected address.
+    ///
+    /// Returns the length of the datagram written to the beginning of
+    /// `buf`.
+    fn poll_recv(
+        &self,
+        cx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, io::Error>>;
+}
+
+//------------ AsyncStream ---------------------------------------------------
+
+/// An asynchronous stream socket.
+///
+/// This trait is implemented for everything that implements the async IO
+/// traits of the futures crate.
+pub trait AsyncStream: AsyncRead + AsyncWrite + Send + Unpin {}
+
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> AsyncStream for T {}
+
+//------------ TokioRuntime --------------------------------------------------
+
+/// The runtime using Tokio.
+///
+/// This is the runtime used by default.
+#[cfg(feature = "resolv")]
+#[cfg_attr(docsrs, doc(cfg(feature = "resolv")))]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TokioRuntime;
+
+#[cfg(feature = "resolv")]
+impl Runtime for TokioRuntime {
+    fn udp_bind(
+        &self,
+        local: SocketAddr,
+    ) -> BoxFuture<'static, Result<Box<dyn AsyncDgram>, io::Error>> {
+        async move {
+            let sock = tokio::net::UdpSocket::bind(local).await?;
+            Ok(Box::new(sock) as Box<dyn AsyncDgram>)
+        }
+        .boxed()
+    }
+
+    fn tcp_connect(
+        &self,
+        addr: SocketAddr,
+    ) -> BoxFuture<'static, Result<Box<dyn AsyncStream>, io::Error>> {
+        async move {
+            let sock = tokio::net::TcpStream::connect(addr).await?;
+            Ok(Box::new(TokioStream(sock)) as Box<dyn AsyncStream>)
+        }
+        .boxed()
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        tokio::time::sleep(duration).boxed()
+    }
+
+    fn spawn(&self, fut: BoxFuture<'static, ()>) {
+        tokio::spawn(fut);
+    }
+}
+
+#[cfg(feature = "resolv")]
+impl AsyncDgram for tokio::net::UdpSocket {
+    fn connect(
+        &self,
+        addr: SocketAddr,
+    ) -> BoxFuture<'_, Result<(), io::Error>> {
+        tokio::net::UdpSocket::connect(self, addr).boxed()
+    }
+
+    fn poll_send(
+        &self,
+        cx: &mut Context,
+        buf: &[u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        tokio::net::UdpSocket::poll_send(self, cx, buf)
+    }
+
+    fn poll_recv(
+        &self,
+        cx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        let mut buf = tokio::io::ReadBuf::new(buf);
+        ready!(tokio::net::UdpSocket::poll_recv(self, cx, &mut buf))?;
+        Poll::Ready(Ok(buf.filled().len()))
+    }
+}
+
+//------------ TokioStream ---------------------------------------------------
+
+/// A Tokio stream implementing the IO traits of the futures crate.
+#[cfg(feature = "resolv")]
+pub(super) struct TokioStream<S>(pub S);
+
+#[cfg(feature = "resolv")]
+impl<S: tokio::io::AsyncRead + Unpin> AsyncRead for TokioStream<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        let mut buf = tokio::io::ReadBuf::new(buf);
+        ready!(tokio::io::AsyncRead::poll_read(
+            Pin::new(&mut self.0),
+            cx,
+            &mut buf
+        ))?;
+        Poll::Ready(Ok(buf.filled().len()))
+    }
+}
+
+#[cfg(feature = "resolv")]
+impl<S: tokio::io::AsyncWrite + Unpin> AsyncWrite for TokioStream<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &[u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        tokio::io::AsyncWrite::poll_write(Pin::new(&mut self.0), cx, buf)
+    }
+
+    fn poll_flush(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+    ) -> Poll<Result<(), io::Error>> {
+        tokio::io::AsyncWrite::poll_flush(Pin::new(&mut self.0), cx)
+    }
+
+    fn poll_close(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+    ) -> Poll<Result<(), io::Error>> {
+        tokio::io::AsyncWrite::poll_shutdown(Pin::new(&mut self.0), cx)
+    }
+}
+
+//------------ FuturesStream -------------------------------------------------
+
+/// A stream of the futures crate implementing Tokio’s IO traits.
+///
+/// This is needed for handing a stream created by a runtime to libraries
+/// that build on Tokio’s traits, such as tokio-rustls or hyper.
+#[cfg(feature = "resolv-tls")]
+pub(super) struct FuturesStream<S>(pub S);
+
+#[cfg(feature = "resolv-tls")]
+impl<S: AsyncRead + Unpin> tokio::io::AsyncRead for FuturesStream<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut tokio::io::ReadBuf,
+    ) -> Poll<Result<(), io::Error>> {
+        let len =
+            ready!(Pin::new(&mut self.0)
+                .poll_read(cx, buf.initialize_unfilled()))?;
+        buf.advance(len);
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(feature = "resolv-tls")]
+impl<S: AsyncWrite + Unpin> tokio::io::AsyncWrite for FuturesStream<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &[u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        Pin::new(&mut self.0).poll_write(cx, buf)
+    }
+
+    fn poll_flush(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+    ) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut self.0).poll_flush(cx)
+    }
+
+    fn poll_shutdown(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+    ) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut self.0).poll_close(cx)
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Sends a datagram.
+pub(super) async fn send(
+    sock: &dyn AsyncDgram,
+    buf: &[u8],
+) -> Result<usize, io::Error> {
+    future::poll_fn(|cx| sock.poll_send(cx, buf)).await
+}
+
+/// Receives a datagram.
+pub(super) async fn recv(
+    sock: &dyn AsyncDgram,
+    buf: &mut [u8],
+) -> Result<usize, io::Error> {
+    future::poll_fn(|cx| sock.poll_recv(cx, buf)).await
+}
+
+/// Runs a future with a timeout.
+///
+/// Returns `None` if the timeout expired first.
+pub(super) async fn timeout<F: Future>(
+    runtime: &dyn Runtime,
+    duration: Duration,
+    fut: F,
+) -> Option<F::Output> {
+    futures::pin_mut!(fut);
+    match future::select(fut, runtime.sleep(duration)).await {
+        Either::Left((res, _)) => Some(res),
+        Either::Right(_) => None,
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::{Rcode, Rtype};
+    use crate::base::message::Message;
+    use crate::base::message_builder::MessageBuilder;
+    use crate::base::name::Dname;
+    use crate::rdata::A;
+    use crate::resolv::stub::conf::{ResolvConf, ServerConf, Transport};
+    use crate::resolv::stub::StubResolver;
+    use futures::executor::block_on;
+    use futures::future::FutureExt;
+    use std::pin::Pin;
+    use std::str::FromStr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::task::Waker;
+    use std::vec::Vec;
+    use std::{cmp, mem, thread};
+
+    /// An in-memory runtime that answers every query itself.
+    ///
+    /// If `silent` is set, no answers are produced and timers expire
+    /// immediately. Otherwise, timers never expire. Either way, the outcome
+    /// of a query doesn’t depend on timing. Spawned tasks run on a thread
+    /// of their own. The number of TCP connections is kept in `connects`.
+    #[derive(Clone, Debug, Default)]
+    struct MockRuntime {
+        silent: bool,
+        connects: Arc<AtomicUsize>,
+    }
+
+    impl Runtime for MockRuntime {
+        fn udp_bind(
+            &self,
+            _local: SocketAddr,
+        ) -> BoxFuture<'static, Result<Box<dyn AsyncDgram>, io::Error>>
+        {
+            let sock = MockDgram {
+                silent: self.silent,
+                answer: Mutex::new(None),
+            };
+            future::ready(Ok(Box::new(sock) as Box<dyn AsyncDgram>)).boxed()
+        }
+
+        fn tcp_connect(
+            &self,
+            _addr: SocketAddr,
+        ) -> BoxFuture<'static, Result<Box<dyn AsyncStream>, io::Error>>
+        {
+            self.connects.fetch_add(1, Ordering::SeqCst);
+            let sock = MockStream {
+                written: Vec::new(),
+                answer: Vec::new(),
+                waker: None,
+            };
+            future::ready(Ok(Box::new(sock) as Box<dyn AsyncStream>)).boxed()
+        }
+
+        fn sleep(&self, _duration: Duration) -> BoxFuture<'static, ()> {
+            if self.silent {
+                future::ready(()).boxed()
+            } else {
+                future::pending().boxed()
+            }
+        }
+
+        fn spawn(&self, fut: BoxFuture<'static, ()>) {
+            thread::spawn(move || block_on(fut));
+        }
+    }
+
+    struct MockDgram {
+        silent: bool,
+        answer: Mutex<Option<Vec<u8>>>,
+    }
+
+    impl AsyncDgram for MockDgram {
+        fn connect(
+            &self,
+            _addr: SocketAddr,
+        ) -> BoxFuture<'_, Result<(), io::Error>> {
+            future::ready(Ok(())).boxed()
+        }
+
+        fn poll_send(
+            &self,
+            _cx: &mut Context,
+            buf: &[u8],
+        ) -> Poll<Result<usize, io::Error>> {
+            if !self.silent {
+                *self.answer.lock().unwrap() = Some(answer(buf));
+            }
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_recv(
+            &self,
+            _cx: &mut Context,
+            buf: &mut [u8],
+        ) -> Poll<Result<usize, io::Error>> {
+            match self.answer.lock().unwrap().take() {
+                Some(answer) => {
+                    buf[..answer.len()].copy_from_slice(&answer);
+                    Poll::Ready(Ok(answer.len()))
+                }
+                None => Poll::Pending,
+            }
+        }
+    }
+
+    /// A stream answering every complete query written to it.
+    struct MockStream {
+        written: Vec<u8>,
+        answer: Vec<u8>,
+        waker: Option<Waker>,
+    }
+
+    impl AsyncRead for MockStream {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &mut [u8],
+        ) -> Poll<Result<usize, io::Error>> {
+            if self.answer.is_empty() {
+                self.waker = Some(cx.waker().clone());
+                return Poll::Pending;
+            }
+            let len = cmp::min(buf.len(), self.answer.len());
+            buf[..len].copy_from_slice(&self.answer[..len]);
+            self.answer.drain(..len);
+            Poll::Ready(Ok(len))
+        }
+    }
+
+    impl AsyncWrite for MockStream {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context,
+            buf: &[u8],
+        ) -> Poll<Result<usize, io::Error>> {
+            self.written.extend_from_slice(buf);
+            while self.written.len() >= 2 {
+                let len = usize::from(u16::from_be_bytes([
+                    self.written[0],
+                    self.written[1],
+                ]));
+                if self.written.len() < len + 2 {
+                    break;
+                }
+                let rest = self.written.split_off(len + 2);
+                let query = mem::replace(&mut self.written, rest);
+                let msg = answer(&query[2..]);
+                self.answer
+                    .extend_from_slice(&(msg.len() as u16).to_be_bytes());
+                self.answer.extend_from_slice(&msg);
+            }
+            if let Some(waker) = self.waker.take() {
+                waker.wake();
+            }
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(
+            self: Pin<&mut Self>,
+            _cx: &mut Context,
+        ) -> Poll<Result<(), io::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(
+            self: Pin<&mut Self>,
+            _cx: &mut Context,
+        ) -> Poll<Result<(), io::Error>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    /// Produces an answer with a single A record for a query.
+    fn answer(query: &[u8]) -> Vec<u8> {
+        let query = Message::from_octets(query).unwrap();
+        let question = query.sole_question().unwrap();
+        let mut answer = MessageBuilder::new_vec()
+            .start_answer(&query, Rcode::NoError)
+            .unwrap();
+        answer
+            .push((question.qname(), 60, A::from_octets(192, 0, 2, 1)))
+            .unwrap();
+        answer.into_message().into_octets()
+    }
+
+    fn resolver(transport: Transport, silent: bool) -> StubResolver {
+        let mut conf = ResolvConf::new();
+        conf.servers
+            .push(ServerConf::new(([192, 0, 2, 53], 53).into(), transport));
+        conf.finalize();
+        StubResolver::from_conf_with_runtime(
+            conf,
+            MockRuntime {
+                silent,
+                ..Default::default()
+            },
+        )
+    }
+
+    fn query(resolver: &StubResolver) -> Result<usize, io::Error> {
+        let qname = Dname::<Vec<u8>>::from_str("example.com").unwrap();
+        block_on(resolver.query((qname, Rtype::A)))
+            .map(|answer| answer.header_counts().ancount().into())
+    }
+
+    #[test]
+    fn udp() {
+        assert_eq!(query(&resolver(Transport::Udp, false)).unwrap(), 1);
+    }
+
+    #[test]
+    fn tcp() {
+        assert_eq!(query(&resolver(Transport::Tcp, false)).unwrap(), 1);
+    }
+
+    #[test]
+    fn tcp_stay_open() {
+        let runtime = MockRuntime::default();
+        let mut conf = ResolvConf::new();
+        conf.servers.push(ServerConf::new(
+            ([192, 0, 2, 53], 53).into(),
+            Transport::Tcp,
+        ));
+        conf.options.stay_open = true;
+        conf.finalize();
+        let resolver =
+            StubResolver::from_conf_with_runtime(conf, runtime.clone());
+        assert_eq!(query(&resolver).unwrap(), 1);
+        assert_eq!(query(&resolver).unwrap(), 1);
+        assert_eq!(runtime.connects.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn timeout() {
+        assert_eq!(
+            query(&resolver(Transport::Udp, true)).unwrap_err().kind(),
+            io::ErrorKind::TimedOut
+        );
+    }
+}
@@ -0,0 +1,652 @@
+//! A synchronous stub resolver.
+//!
+//! The [`SyncStubResolver`] provided by this module performs its queries
+//! using blocking sockets from the standard library and doesn’t need an
+//! async runtime. It is intended for plain threaded code that only needs
+//! to make the occasional lookup.
+//!
+//! The resolver uses the same configuration as the asynchronous
+//! [`StubResolver`] and follows the same rules for choosing, rotating, and
+//! retrying servers, for falling back to queries without EDNS, and for
+//! applying the search list. The lookup methods return the same types as
+//! those of the asynchronous resolver.
+//!
+//! The resolver is available via the `resolv-blocking` feature which
+//! doesn’t depend on Tokio or any other async runtime.
+//!
+//! There are a few limitations, though. Only the UDP and TCP transports
+//! are supported. Queries to servers using other transports fail. TCP
+//! connections are never kept open between queries and the `blast` option
+//! is ignored – servers are always tried one after another.
+//!
+//! [`SyncStubResolver`]: struct.SyncStubResolver.html
+//! [`StubResolver`]: ../struct.StubResolver.html
+
+use super::conf::{ResolvConf, ResolvOptions, SearchSuffix, Transport};
+use super::runtime::{AsyncDgram, AsyncStream, Runtime};
+use super::{
+    lookup_host_with, search_host_with, Answer, Query, QueryMessage,
+    SearchIter, ServerInfo, Step, StubResolver, RETRY_RANDOM_PORT,
+};
+use crate::base::message::Message;
+use crate::base::name::{ToDname, ToRelativeDname};
+use crate::base::question::Question;
+use crate::resolv::lookup::addr::{lookup_addr, FoundAddrs};
+use crate::resolv::lookup::host::FoundHosts;
+use crate::resolv::lookup::srv::{
+    lookup_srv, FoundSrvs, ResolvedSrvItem, SrvError,
+};
+use crate::resolv::resolver::{Resolver, SearchNames};
+use bytes::Bytes;
+use futures::executor::block_on;
+use futures::future::{ready, BoxFuture, FutureExt, Ready};
+use futures::stream::StreamExt;
+use std::boxed::Box;
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpStream, UdpSocket};
+use std::time::{Duration, Instant};
+use std::vec::Vec;
+
+//------------ SyncStubResolver ----------------------------------------------
+
+/// A synchronous DNS stub resolver.
+///
+/// The resolver is created from a [`ResolvConf`] just like its asynchronous
+/// sibling. All its methods block the current thread until they are done.
+///
+/// Since the resolver implements the [`Resolver`] trait, it can be used
+/// with the generic lookup functions, too. The futures returned by its
+/// queries are always ready, so these can be driven by any simple
+/// executor such as `futures::executor::block_on`.
+///
+/// [`ResolvConf`]: ../conf/struct.ResolvConf.html
+/// [`Resolver`]: ../../resolver/trait.Resolver.html
+#[derive(Clone, Debug)]
+pub struct SyncStubResolver {
+    /// The asynchronous resolver providing servers and options.
+    resolver: StubResolver,
+}
+
+impl SyncStubResolver {
+    /// Creates a new resolver using the system’s default configuration.
+    pub fn new() -> Self {
+        Self::from_conf(ResolvConf::default())
+    }
+
+    /// Creates a new resolver using the given configuration.
+    pub fn from_conf(conf: ResolvConf) -> Self {
+        SyncStubResolver {
+            resolver: StubResolver::from_conf_with_runtime(conf, NoRuntime),
+        }
+    }
+
+    /// Returns the resolver options.
+    pub fn options(&self) -> &ResolvOptions {
+        self.resolver.options()
+    }
+
+    /// Answers a question.
+    pub fn query<N: ToDname, Q: Into<Question<N>>>(
+        &self,
+        question: Q,
+    ) -> Result<Answer, io::Error> {
+        Query::new(&self.resolver)?
+            .run_sync(Query::create_message(question.into()))
+    }
+
+    /// Looks up the host names of an IP address.
+    pub fn lookup_addr(
+        &self,
+        addr: IpAddr,
+    ) -> Result<FoundAddrs<&Self>, io::Error> {
+        block_on(lookup_addr(&self, addr))
+    }
+
+    /// Looks up the IP addresses of a host.
+    ///
+    /// This behaves like [`StubResolver::lookup_host`].
+    ///
+    /// [`StubResolver::lookup_host`]: ../struct.StubResolver.html#method.lookup_host
+    pub fn lookup_host(
+        &self,
+        qname: impl ToDname,
+    ) -> Result<FoundHosts<&Self>, io::Error> {
+        block_on(lookup_host_with(&self, self.options(), qname))
+    }
+
+    /// Looks up the IP addresses of a host using the search list.
+    ///
+    /// This behaves like [`StubResolver::search_host`].
+    ///
+    /// [`StubResolver::search_host`]: ../struct.StubResolver.html#method.search_host
+    pub fn search_host(
+        &self,
+        qname: impl ToRelativeDname,
+    ) -> Result<FoundHosts<&Self>, io::Error> {
+        block_on(search_host_with(&self, self.options(), qname))
+    }
+
+    /// Looks up the SRV records of a service.
+    ///
+    /// The targets of the records found can be resolved via
+    /// [`resolve_srvs`].
+    ///
+    /// [`resolve_srvs`]: #method.resolve_srvs
+    pub fn lookup_srv(
+        &self,
+        service: impl ToRelativeDname,
+        name: impl ToDname,
+        fallback_port: u16,
+    ) -> Result<Option<FoundSrvs>, SrvError> {
+        block_on(lookup_srv(&self, service, name, fallback_port))
+    }
+
+    /// Resolves the targets of the SRV records found by a lookup.
+    ///
+    /// The targets are resolved one after another in the order they
+    /// should be tried in. The result for each target is returned in the
+    /// same order.
+    pub fn resolve_srvs(
+        &self,
+        srvs: FoundSrvs,
+    ) -> Vec<Result<ResolvedSrvItem, io::Error>> {
+        block_on(srvs.into_stream(&self).collect())
+    }
+}
+
+impl Default for SyncStubResolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Resolver for &SyncStubResolver {
+    type Octets = Bytes;
+    type Answer = Answer;
+    type Query = Ready<Result<Answer, io::Error>>;
+
+    /// Answers a question.
+    ///
+    /// The query is performed right away, blocking the current thread.
+    /// The returned future is always ready.
+    fn query<N, Q>(&self, question: Q) -> Self::Query
+    where
+        N: ToDname,
+        Q: Into<Question<N>>,
+    {
+        ready(SyncStubResolver::query(self, question))
+    }
+}
+
+impl<'a> SearchNames for &'a SyncStubResolver {
+    type Name = SearchSuffix;
+    type Iter = SearchIter<'a>;
+
+    fn search_iter(&self) -> Self::Iter {
+        (&self.resolver).search_iter()
+    }
+}
+
+//------------ Query ---------------------------------------------------------
+
+impl<'a> Query<'a> {
+    /// Runs the query synchronously.
+    ///
+    /// This is the blocking version of `Query::run`. Since we can only
+    /// wait for one server at a time, the `blast` option is ignored.
+    fn run_sync(
+        mut self,
+        mut message: QueryMessage,
+    ) -> Result<Answer, io::Error> {
+        message.header_mut().set_rd(self.resolver.options().recurse);
+        loop {
+            let server = self.current_server();
+            server.prepare_message(&mut message);
+            let res = server.query_sync(&message);
+            match self.process(server, res) {
+                Step::Again => continue,
+                Step::Next => {}
+                Step::Stream => {
+                    if self.switch_to_stream() {
+                        continue;
+                    }
+                }
+                Step::Return(answer) => return Ok(answer),
+            }
+            if !self.next_server() {
+                return self.error;
+            }
+        }
+    }
+}
+
+//------------ ServerInfo ----------------------------------------------------
+
+impl ServerInfo {
+    /// Sends a query to the server and waits for the answer.
+    fn query_sync(&self, query: &QueryMessage) -> Result<Answer, io::Error> {
+        let deadline = Instant::now() + self.conf.request_timeout;
+        let res = match self.conf.transport {
+            Transport::Udp => Self::udp_query_sync(
+                query,
+                self.conf.addr,
+                self.conf.recv_size,
+                deadline,
+            ),
+            Transport::Tcp => {
+                Self::tcp_query_sync(query, self.conf.addr, deadline)
+            }
+            #[allow(unreachable_patterns)]
+            _ => Err(io::Error::new(
+                io::ErrorKind::Other,
+                "transport not supported by the synchronous resolver",
+            )),
+        };
+        res.and_then(|answer| Self::check_client_subnet(query, answer))
+    }
+
+    fn udp_query_sync(
+        query: &QueryMessage,
+        addr: SocketAddr,
+        recv_size: usize,
+        deadline: Instant,
+    ) -> Result<Answer, io::Error> {
+        let sock = Self::udp_bind_sync(addr.is_ipv4())?;
+        sock.connect(addr)?;
+        let sent = sock.send(query.as_target().as_dgram_slice())?;
+        if sent != query.as_target().as_dgram_slice().len() {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "short UDP send",
+            ));
+        }
+        loop {
+            sock.set_read_timeout(Some(remaining(deadline)?))?;
+            let mut buf = vec![0; recv_size]; // XXX use uninit'ed mem here.
+            let len = sock.recv(&mut buf).map_err(timed_out)?;
+            buf.truncate(len);
+
+            // We ignore garbage since there is a deadline on this whole
+            // thing.
+            let answer = match Message::from_octets(buf.into()) {
+                Ok(answer) => answer,
+                Err(_) => continue,
+            };
+            if !answer.is_answer(&query.as_message()) {
+                continue;
+            }
+            return Ok(answer.into());
+        }
+    }
+
+    fn udp_bind_sync(v4: bool) -> Result<UdpSocket, io::Error> {
+        let mut i = 0;
+        loop {
+            let local: SocketAddr = if v4 {
+                ([0u8; 4], 0).into()
+            } else {
+                ([0u16; 8], 0).into()
+            };
+            match UdpSocket::bind(local) {
+                Ok(sock) => return Ok(sock),
+                Err(err) => {
+                    if i == RETRY_RANDOM_PORT {
+                        return Err(err);
+                    } else {
+                        i += 1
+                    }
+                }
+            }
+        }
+    }
+
+    fn tcp_query_sync(
+        query: &QueryMessage,
+        addr: SocketAddr,
+        deadline: Instant,
+    ) -> Result<Answer, io::Error> {
+        let mut sock =
+            TcpStream::connect_timeout(&addr, remaining(deadline)?)
+                .map_err(timed_out)?;
+        sock.set_write_timeout(Some(remaining(deadline)?))?;
+        sock.write_all(query.as_target().as_stream_slice())
+            .map_err(timed_out)?;
+
+        loop {
+            sock.set_read_timeout(Some(remaining(deadline)?))?;
+            let mut len = [0u8; 2];
+            sock.read_exact(&mut len).map_err(timed_out)?;
+            let mut buf = vec![0; usize::from(u16::from_be_bytes(len))];
+            sock.read_exact(&mut buf).map_err(timed_out)?;
+            if let Ok(answer) = Message::from_octets(Bytes::from(buf)) {
+                if answer.is_answer(&query.as_message()) {
+                    return Ok(answer.into());
+                }
+            // else try with the next message.
+            } else {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    "short buf",
+                ));
+            }
+        }
+    }
+}
+
+//------------ NoRuntime -----------------------------------------------------
+
+/// The runtime of the asynchronous resolver wrapped by the sync resolver.
+///
+/// The synchronous resolver only uses the wrapped resolver for its servers
+/// and options and does all networking itself, so the runtime is never
+/// used. Should it be used anyway, all network operations fail.
+#[derive(Clone, Copy, Debug)]
+struct NoRuntime;
+
+impl Runtime for NoRuntime {
+    fn udp_bind(
+        &self,
+        _local: SocketAddr,
+    ) -> BoxFuture<'static, Result<Box<dyn AsyncDgram>, io::Error>> {
+        ready(Err(unsupported())).boxed()
+    }
+
+    fn tcp_connect(
+        &self,
+        _addr: SocketAddr,
+    ) -> BoxFuture<'static, Result<Box<dyn AsyncStream>, io::Error>> {
+        ready(Err(unsupported())).boxed()
+    }
+
+    fn sleep(&self, _duration: Duration) -> BoxFuture<'static, ()> {
+        ready(()).boxed()
+    }
+
+    fn spawn(&self, _fut: BoxFuture<'static, ()>) {}
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Returns the time left until `deadline` or a timeout error.
+fn remaining(deadline: Instant) -> Result<Duration, io::Error> {
+    match deadline.checked_duration_since(Instant::now()) {
+        Some(left) if left > Duration::ZERO => Ok(left),
+        _ => {
+            Err(io::Error::new(io::ErrorKind::TimedOut, "request timed out"))
+        }
+    }
+}
+
+/// Returns the error for using the runtime of the synchronous resolver.
+fn unsupported() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Other,
+        "no async runtime in the synchronous resolver",
+    )
+}
+
+/// Converts the errors of expired socket timeouts into timeout errors.
+///
+/// Depending on the platform, socket timeouts produce either `WouldBlock`
+/// or `TimedOut` errors. We need the latter for the query to pick the
+/// right error to return.
+fn timed_out(err: io::Error) -> io::Error {
+    match err.kind() {
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => {
+            io::Error::new(io::ErrorKind::TimedOut, "request timed out")
+        }
+        _ => err,
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::super::conf::{ResolvConf, ServerConf, Transport};
+    use super::SyncStubResolver;
+    use crate::base::iana::{Rcode, Rtype};
+    use crate::base::message::Message;
+    use crate::base::message_builder::MessageBuilder;
+    use crate::base::name::{Dname, RelativeDname};
+    use crate::rdata::{Aaaa, A};
+    use std::io::{Cursor, Read, Write};
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, UdpSocket};
+    use std::str::FromStr;
+    use std::string::ToString;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use std::vec::Vec;
+    use std::{thread, vec};
+
+    /// How a test server behaves.
+    #[derive(Clone, Copy, Default)]
+    struct Behaviour {
+        /// Answer queries with EDNS with FORMERR.
+        no_edns: bool,
+
+        /// Answer UDP queries with an empty, truncated answer.
+        truncate: bool,
+
+        /// Don’t answer UDP queries at all.
+        silent: bool,
+    }
+
+    /// A UDP and TCP server for testing that runs on plain threads.
+    ///
+    /// The server knows the addresses of `a.example` only.
+    struct Server {
+        udp: SocketAddr,
+        tcp: SocketAddr,
+
+        /// The transport and EDNS flag of received queries.
+        log: Arc<Mutex<Vec<(Transport, bool)>>>,
+    }
+
+    impl Server {
+        fn start(behaviour: Behaviour) -> Self {
+            let udp = UdpSocket::bind("127.0.0.1:0").unwrap();
+            let tcp = TcpListener::bind("127.0.0.1:0").unwrap();
+            let res = Server {
+                udp: udp.local_addr().unwrap(),
+                tcp: tcp.local_addr().unwrap(),
+                log: Default::default(),
+            };
+            let log = res.log.clone();
+            thread::spawn(move || loop {
+                let mut buf = vec![0; 512];
+                let (len, peer) = udp.recv_from(&mut buf).unwrap();
+                buf.truncate(len);
+                let query = Message::from_octets(buf).unwrap();
+                log.lock()
+                    .unwrap()
+                    .push((Transport::Udp, query.opt().is_some()));
+                if behaviour.silent {
+                    continue;
+                }
+                let answer = Self::answer(behaviour, &query, true);
+                udp.send_to(answer.as_slice(), peer).unwrap();
+            });
+            let log = res.log.clone();
+            thread::spawn(move || {
+                for sock in tcp.incoming() {
+                    let mut sock = sock.unwrap();
+                    let mut len = [0u8; 2];
+                    sock.read_exact(&mut len).unwrap();
+                    let mut buf =
+                        vec![0; usize::from(u16::from_be_bytes(len))];
+                    sock.read_exact(&mut buf).unwrap();
+                    let query = Message::from_octets(buf).unwrap();
+                    log.lock()
+                        .unwrap()
+                        .push((Transport::Tcp, query.opt().is_some()));
+                    let answer = Self::answer(behaviour, &query, false);
+                    let len = answer.as_slice().len() as u16;
+                    sock.write_all(&len.to_be_bytes()).unwrap();
+                    sock.write_all(answer.as_slice()).unwrap();
+                }
+            });
+            res
+        }
+
+        fn answer(
+            behaviour: Behaviour,
+            query: &Message<Vec<u8>>,
+            udp: bool,
+        ) -> Message<Vec<u8>> {
+            let question = query.sole_question().unwrap();
+            let known = question.qname().to_string() == "a.example";
+            let rcode = if behaviour.no_edns && query.opt().is_some() {
+                Rcode::FormErr
+            } else if known {
+                Rcode::NoError
+            } else {
+                Rcode::NXDomain
+            };
+            let mut answer = MessageBuilder::new_vec()
+                .start_answer(query, rcode)
+                .unwrap();
+            if udp && behaviour.truncate {
+                answer.header_mut().set_tc(true);
+            } else if rcode == Rcode::NoError {
+                let qname = question.qname();
+                match question.qtype() {
+                    Rtype::A => answer
+                        .push((qname, 60, A::from_octets(192, 0, 2, 1)))
+                        .unwrap(),
+                    Rtype::Aaaa => answer
+                        .push((
+                            qname,
+                            60,
+                            Aaaa::from_str("2001:db8::1").unwrap(),
+                        ))
+                        .unwrap(),
+                    _ => {}
+                }
+            }
+            answer.into_message()
+        }
+
+        fn log(&self) -> Vec<(Transport, bool)> {
+            self.log.lock().unwrap().clone()
+        }
+    }
+
+    /// Creates a resolver for the servers from resolv.conf content.
+    fn resolver(servers: &[ServerConf], conf: &str) -> SyncStubResolver {
+        let mut res = ResolvConf::new();
+        res.parse(&mut Cursor::new(conf)).unwrap();
+        res.options.timeout = Duration::from_millis(200);
+        res.servers.extend_from_slice(servers);
+        res.finalize();
+        SyncStubResolver::from_conf(res)
+    }
+
+    fn name(s: &str) -> Dname<Vec<u8>> {
+        Dname::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn lookups() {
+        let server = Server::start(Behaviour::default());
+        let resolver = resolver(
+            &[ServerConf::new(server.udp, Transport::Udp)],
+            "search example\noptions attempts:1",
+        );
+
+        let answer = resolver.query((name("a.example."), Rtype::A)).unwrap();
+        assert_eq!(answer.header().rcode(), Rcode::NoError);
+        assert_eq!(answer.header_counts().ancount(), 1);
+
+        let found = resolver.lookup_host(name("a.example.")).unwrap();
+        let mut addrs: Vec<_> = found.iter().collect();
+        addrs.sort();
+        assert_eq!(
+            addrs,
+            [
+                IpAddr::from(Ipv4Addr::new(192, 0, 2, 1)),
+                IpAddr::from_str("2001:db8::1").unwrap()
+            ]
+        );
+
+        let relative: RelativeDname<Vec<u8>> = name("a.").into_relative();
+        let found = resolver.search_host(relative).unwrap();
+        assert_eq!(found.canonical_name().to_string(), "a.example");
+        assert!(resolver.lookup_host(name("b.example.")).unwrap().is_empty());
+        assert_eq!(server.log().len(), 7);
+    }
+
+    #[test]
+    fn edns_fallback() {
+        let server = Server::start(Behaviour {
+            no_edns: true,
+            ..Default::default()
+        });
+        let resolver = resolver(
+            &[ServerConf::new(server.udp, Transport::Udp)],
+            "options attempts:1",
+        );
+        for _ in 0..2 {
+            let answer =
+                resolver.query((name("a.example."), Rtype::A)).unwrap();
+            assert_eq!(answer.header().rcode(), Rcode::NoError);
+        }
+        // The resolver remembers that the server doesn’t do EDNS.
+        assert_eq!(
+            server.log(),
+            [
+                (Transport::Udp, true),
+                (Transport::Udp, false),
+                (Transport::Udp, false)
+            ]
+        );
+    }
+
+    #[test]
+    fn truncated() {
+        let server = Server::start(Behaviour {
+            truncate: true,
+            ..Default::default()
+        });
+        let resolver = resolver(
+            &[
+                ServerConf::new(server.udp, Transport::Udp),
+                ServerConf::new(server.tcp, Transport::Tcp),
+            ],
+            "options attempts:1",
+        );
+        let answer = resolver.query((name("a.example."), Rtype::A)).unwrap();
+        assert!(!answer.header().tc());
+        assert_eq!(answer.header_counts().ancount(), 1);
+        assert_eq!(
+            server.log(),
+            [(Transport::Udp, true), (Transport::Tcp, true)]
+        );
+    }
+
+    #[test]
+    fn next_server() {
+        let silent = Server::start(Behaviour {
+            silent: true,
+            ..Default::default()
+        });
+        let server = Server::start(Behaviour::default());
+        let dead = ServerConf::new(silent.udp, Transport::Udp);
+        let both = resolver(
+            &[dead.clone(), ServerConf::new(server.udp, Transport::Udp)],
+            "options attempts:1",
+        );
+        let answer = both.query((name("a.example."), Rtype::A)).unwrap();
+        assert_eq!(answer.header().rcode(), Rcode::NoError);
+        assert_eq!(silent.log().len(), 1);
+        assert_eq!(server.log().len(), 1);
+
+        // Only the dead server times out.
+        let single = resolver(&[dead], "options attempts:2");
+        match single.query((name("a.example."), Rtype::A)) {
+            Err(err) => assert_eq!(err.kind(), std::io::ErrorKind::TimedOut),
+            Ok(_) => panic!("expected timeout"),
+        }
+        assert_eq!(silent.log().len(), 3);
+    }
+}
@@ -0,0 +1,385 @@
+//! The DNS-over-TLS transport.
+
+use super::conf::{PrivacyProfile, ServerConf};
+use super::runtime::{AsyncStream, FuturesStream, Runtime};
+use rustls::client::{ServerCertVerified, ServerCertVerifier};
+use rustls::{
+    Certificate, ClientConfig, Error, OwnedTrustAnchor, RootCertStore,
+    ServerName,
+};
+use std::boxed::Box;
+use std::convert::TryFrom;
+use std::net::SocketAddr;
+use std::string::ToString;
+use std::sync::Arc;
+use std::time::SystemTime;
+use std::{fmt, io};
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::TlsConnector;
+
+//------------ TlsInfo -------------------------------------------------------
+
+/// Everything needed to establish TLS connections to a server.
+#[derive(Clone)]
+pub(super) struct TlsInfo {
+    /// The connector with the client config for the server.
+    connector: TlsConnector,
+
+    /// The name to authenticate the server with.
+    server_name: ServerName,
+}
+
+impl TlsInfo {
+    /// Creates the TLS information for the given server.
+    ///
+    /// The server is authenticated using the authentication domain name
+    /// from the config or, if that is missing, `default_name` or, if that
+    /// is missing, too, the server’s IP address. The application protocols
+    /// to negotiate via ALPN are given in `alpn`.
+    ///
+    /// Fails if the name to authenticate with is not a valid host name.
+    pub fn new(
+        conf: &ServerConf,
+        default_name: Option<&str>,
+        alpn: &[&[u8]],
+    ) -> Result<Self, io::Error> {
+        let name = conf.tls.auth_name.as_ref().map(ToString::to_string);
+        let server_name = match name.as_deref().or(default_name) {
+            Some(name) => ServerName::try_from(name).map_err(|err| {
+                io::Error::new(io::ErrorKind::InvalidInput, err)
+            })?,
+            None => ServerName::IpAddress(conf.addr.ip()),
+        };
+        let builder = ClientConfig::builder().with_safe_defaults();
+        let mut config = match conf.tls.profile {
+            PrivacyProfile::Strict => builder
+                .with_root_certificates(
+                    conf.tls.roots.clone().unwrap_or_else(default_roots),
+                )
+                .with_no_client_auth(),
+            PrivacyProfile::Opportunistic => builder
+                .with_custom_certificate_verifier(Arc::new(
+                    OpportunisticVerifier,
+                ))
+                .with_no_client_auth(),
+        };
+        config.alpn_protocols = alpn.iter().map(|p| p.to_vec()).collect();
+        Ok(TlsInfo {
+            connector: TlsConnector::from(Arc::new(config)),
+            server_name,
+        })
+    }
+
+    /// Establishes a new TLS connection to the server.
+    ///
+    /// The TCP connection is established via `runtime`.
+    pub async fn connect(
+        &self,
+        runtime: &dyn Runtime,
+        addr: SocketAddr,
+    ) -> Result<TlsStream<FuturesStream<Box<dyn AsyncStream>>>, io::Error>
+    {
+        let sock = runtime.tcp_connect(addr).await?;
+        self.connector
+            .connect(self.server_name.clone(), FuturesStream(sock))
+            .await
+    }
+}
+
+impl fmt::Debug for TlsInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TlsInfo")
+            .field("server_name", &self.server_name)
+            .finish()
+    }
+}
+
+//------------ OpportunisticVerifier -----------------------------------------
+
+/// A certificate verifier that accepts any certificate.
+///
+/// This is used for the opportunistic privacy profile. The signatures of
+/// the handshake are still checked using the default methods.
+struct OpportunisticVerifier;
+
+impl ServerCertVerifier for OpportunisticVerifier {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Returns the default trust anchors.
+fn default_roots() -> RootCertStore {
+    let mut res = RootCertStore::empty();
+    res.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+        OwnedTrustAnchor::from_subject_spki_name_constraints(
+            ta.subject,
+            ta.spki,
+            ta.name_constraints,
+        )
+    }));
+    res
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::super::conf::{
+        PrivacyProfile, ResolvConf, ServerConf, Transport,
+    };
+    use super::super::StubResolver;
+    use crate::base::iana::{Rcode, Rtype};
+    use crate::base::message::Message;
+    use crate::base::message_builder::MessageBuilder;
+    use crate::base::name::Dname;
+    use crate::base::opt::TcpKeepalive;
+    use rustls::{Certificate, PrivateKey, RootCertStore, ServerConfig};
+    use std::net::SocketAddr;
+    use std::str::FromStr;
+    use std::string::ToString;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use std::vec::Vec;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc;
+    use tokio_rustls::TlsAcceptor;
+
+    /// A DNS-over-TLS server for testing.
+    struct Server {
+        addr: SocketAddr,
+        cert: Certificate,
+        accepted: Arc<AtomicUsize>,
+    }
+
+    impl Server {
+        /// Starts a server with a self-signed certificate for dns.example.
+        ///
+        /// The server answers every query with an empty answer, adding
+        /// the given keepalive timeout. Queries for names starting with
+        /// ‘slow’ are answered with a delay.
+        async fn start(keepalive: Option<u16>) -> Self {
+            let cert =
+                rcgen::generate_simple_self_signed(
+                    vec!["dns.example".into()],
+                )
+                .unwrap();
+            let key = PrivateKey(cert.serialize_private_key_der());
+            let cert = Certificate(cert.serialize_der().unwrap());
+            let config = ServerConfig::builder()
+                .with_safe_defaults()
+                .with_no_client_auth()
+                .with_single_cert(vec![cert.clone()], key)
+                .unwrap();
+            let acceptor = TlsAcceptor::from(Arc::new(config));
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            let accepted = Arc::new(AtomicUsize::new(0));
+            let count = accepted.clone();
+            tokio::spawn(async move {
+                loop {
+                    let (sock, _) = listener.accept().await.unwrap();
+                    count.fetch_add(1, Ordering::SeqCst);
+                    let acceptor = acceptor.clone();
+                    tokio::spawn(async move {
+                        if let Ok(stream) = acceptor.accept(sock).await {
+                            Self::serve(stream, keepalive).await
+                        }
+                    });
+                }
+            });
+            Server {
+                addr,
+                cert,
+                accepted,
+            }
+        }
+
+        async fn serve<S>(stream: S, keepalive: Option<u16>)
+        where
+            S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static,
+        {
+            let (mut read, mut write) = tokio::io::split(stream);
+            let (tx, mut rx) = mpsc::unbounded_channel::<Vec<u8>>();
+            tokio::spawn(async move {
+                while let Some(data) = rx.recv().await {
+                    if write.write_all(&data).await.is_err() {
+                        return;
+                    }
+                }
+            });
+            loop {
+                let len = match read.read_u16().await {
+                    Ok(len) => len,
+                    Err(_) => return,
+                };
+                let mut buf = vec![0; usize::from(len)];
+                if read.read_exact(&mut buf).await.is_err() {
+                    return;
+                }
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    let query = Message::from_octets(buf).unwrap();
+                    let qname = query.sole_question().unwrap().into_qname();
+                    if qname.to_string().starts_with("slow") {
+                        tokio::time::sleep(Duration::from_millis(200)).await;
+                    }
+                    let mut answer = MessageBuilder::new_stream_vec()
+                        .start_answer(&query, Rcode::NoError)
+                        .unwrap()
+                        .additional();
+                    if let Some(keepalive) = keepalive {
+                        answer
+                            .opt(|opt| TcpKeepalive::push(opt, keepalive))
+                            .unwrap();
+                    }
+                    let _ = tx.send(answer.finish().as_stream_slice().into());
+                });
+            }
+        }
+
+        fn accepted(&self) -> usize {
+            self.accepted.load(Ordering::SeqCst)
+        }
+
+        fn resolver(
+            &self,
+            auth_name: Option<&str>,
+            profile: PrivacyProfile,
+            roots: bool,
+        ) -> StubResolver {
+            let mut server = ServerConf::new(self.addr, Transport::Tls);
+            server.tls.auth_name =
+                auth_name.map(|name| Dname::from_str(name).unwrap());
+            server.tls.profile = profile;
+            if roots {
+                let mut store = RootCertStore::empty();
+                store.add(&self.cert).unwrap();
+                server.tls.roots = Some(store);
+            }
+            let mut conf = ResolvConf::new();
+            conf.servers.push(server);
+            conf.options.attempts = 1;
+            conf.finalize();
+            StubResolver::from_conf(conf)
+        }
+    }
+
+    async fn query(
+        resolver: &StubResolver,
+        qname: &str,
+    ) -> Result<Rcode, std::io::Error> {
+        let qname = Dname::<Vec<u8>>::from_str(qname).unwrap();
+        resolver
+            .query((qname, Rtype::A))
+            .await
+            .map(|answer| answer.header().rcode())
+    }
+
+    #[tokio::test]
+    async fn strict_reuse_and_pipelining() {
+        let server = Server::start(None).await;
+        let resolver = server.resolver(
+            Some("dns.example"),
+            PrivacyProfile::Strict,
+            true,
+        );
+        assert_eq!(
+            query(&resolver, "a.example").await.unwrap(),
+            Rcode::NoError
+        );
+        assert_eq!(
+            query(&resolver, "b.example").await.unwrap(),
+            Rcode::NoError
+        );
+        let (slow, fast) = tokio::join!(
+            query(&resolver, "slow.example"),
+            query(&resolver, "fast.example"),
+        );
+        assert_eq!(slow.unwrap(), Rcode::NoError);
+        assert_eq!(fast.unwrap(), Rcode::NoError);
+        assert_eq!(server.accepted(), 1);
+    }
+
+    #[tokio::test]
+    async fn strict_authentication() {
+        let server = Server::start(None).await;
+        let resolver = server.resolver(
+            Some("other.example"),
+            PrivacyProfile::Strict,
+            true,
+        );
+        assert!(query(&resolver, "a.example").await.is_err());
+        let resolver = server.resolver(
+            Some("dns.example"),
+            PrivacyProfile::Strict,
+            false,
+        );
+        assert!(query(&resolver, "a.example").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn opportunistic() {
+        let server = Server::start(None).await;
+        let resolver =
+            server.resolver(None, PrivacyProfile::Opportunistic, false);
+        assert_eq!(
+            query(&resolver, "a.example").await.unwrap(),
+            Rcode::NoError
+        );
+    }
+
+    #[tokio::test]
+    async fn keepalive_idle_timeout() {
+        // The server asks us to close after 100 ms.
+        let server = Server::start(Some(1)).await;
+        let resolver = server.resolver(
+            Some("dns.example"),
+            PrivacyProfile::Strict,
+            true,
+        );
+        assert_eq!(
+            query(&resolver, "a.example").await.unwrap(),
+            Rcode::NoError
+        );
+        assert_eq!(
+            query(&resolver, "b.example").await.unwrap(),
+            Rcode::NoError
+        );
+        assert_eq!(server.accepted(), 1);
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert_eq!(
+            query(&resolver, "c.example").await.unwrap(),
+            Rcode::NoError
+        );
+        assert_eq!(server.accepted(), 2);
+    }
+
+    #[tokio::test]
+    async fn keepalive_zero() {
+        // The server asks us to close right away, so every query needs a
+        // new connection.
+        let server = Server::start(Some(0)).await;
+        let resolver = server.resolver(
+            Some("dns.example"),
+            PrivacyProfile::Strict,
+            true,
+        );
+        for name in &["a.example", "b.example", "c.example"] {
+            assert_eq!(query(&resolver, name).await.unwrap(), Rcode::NoError);
+        }
+        assert_eq!(server.accepted(), 3);
+    }
+}
@@ -0,0 +1,777 @@
+//! Support for SIG(0) transaction signatures.
+//!
+//! **This module is experimental and likely to change significantly.**
+//!
+//! SIG(0), defined in [RFC 2931], allows signing entire messages using
+//! public key cryptography. Unlike with [TSIG], the two parties of a
+//! message exchange don’t need to share a secret. Instead, the signer
+//! uses its private key and the receiver verifies the signature using the
+//! public key which is typically published in a KEY record under the
+//! signer’s name.
+//!
+//! The signature is transmitted in a SIG record appended to the additional
+//! section of the message. This record is owned by the root name, has class
+//! ANY and a TTL of zero. Its type covered, labels, and original TTL
+//! fields are zero, too. The inception and expiration times limit the
+//! validity of the signature and can be used to prevent replay attacks.
+//!
+//! Messages are signed via the [`Sig0Key`] type which combines a
+//! [`SigningKey`] with the name of the key’s KEY record. Signatures are
+//! verified via the [`verify_request`] and [`verify_answer`] functions
+//! which accept both [`Key`] and [`Dnskey`] record data as the public
+//! key. A signature is only accepted if the current time is within its
+//! validity period extended by some _fudge_ in both directions, similar to
+//! the way TSIG deals with clock skew.
+//!
+//! [RFC 2931]: https://tools.ietf.org/html/rfc2931
+//! [TSIG]: ../tsig/index.html
+//! [`Sig0Key`]: struct.Sig0Key.html
+//! [`SigningKey`]: ../sign/key/trait.SigningKey.html
+//! [`verify_request`]: fn.verify_request.html
+//! [`verify_answer`]: fn.verify_answer.html
+//! [`Key`]: ../rdata/rfc2535/struct.Key.html
+//! [`Dnskey`]: ../rdata/rfc4034/struct.Dnskey.html
+#![cfg(feature = "sig0")]
+#![cfg_attr(docsrs, doc(cfg(feature = "sig0")))]
+
+use crate::base::header::HeaderSection;
+use crate::base::iana::{Class, Rtype, SecAlg};
+use crate::base::message::Message;
+use crate::base::message_builder::AdditionalBuilder;
+use crate::base::name::{Dname, ParsedDname, ToDname};
+use crate::base::octets::{
+    Compose, OctetsBuilder, OctetsRef, OctetsVec, ParseError, ShortBuf,
+};
+use crate::base::serial::Serial;
+use crate::rdata::rfc2535::{Key, Sig};
+use crate::rdata::Dnskey;
+use crate::sign::key::SigningKey;
+use crate::validate::{verify_signature, AlgorithmError};
+use core::cmp::Ordering;
+use core::{fmt, mem};
+use std::vec::Vec;
+
+/// The default fudge used for signing and verifying.
+///
+/// This is the same value as recommended for TSIG.
+pub const DEFAULT_FUDGE: u32 = 300;
+
+//------------ Sig0Key -------------------------------------------------------
+
+/// A key for creating SIG(0) signatures.
+///
+/// The type combines a [`SigningKey`] with the name the public key is
+/// published under as a KEY record. This name is included in the signature
+/// as the signer’s name so the receiver can find the key.
+///
+/// The key tag and algorithm placed into the signature are taken from the
+/// signing key’s DNSKEY record data. Since the key tag depends on the flags,
+/// the signing key should be created with the flags used in its KEY record.
+/// You can use [`key_record`] to create the KEY record data from the signing
+/// key.
+///
+/// [`SigningKey`]: ../sign/key/trait.SigningKey.html
+/// [`key_record`]: #method.key_record
+#[derive(Clone, Debug)]
+pub struct Sig0Key<K> {
+    /// The signing key.
+    key: K,
+
+    /// The name of the KEY record for the key.
+    name: Dname<OctetsVec>,
+}
+
+impl<K> Sig0Key<K> {
+    /// Creates a new value from a signing key and the name of its KEY.
+    pub fn new(key: K, name: Dname<OctetsVec>) -> Self {
+        Sig0Key { key, name }
+    }
+
+    /// Returns a reference to the signing key.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Returns a reference to the name of the key.
+    pub fn name(&self) -> &Dname<OctetsVec> {
+        &self.name
+    }
+
+    /// Converts the value into the signing key.
+    pub fn into_key(self) -> K {
+        self.key
+    }
+}
+
+impl<K: SigningKey> Sig0Key<K> {
+    /// Returns the KEY record data for the key.
+    ///
+    /// The record data uses the same flags, protocol, algorithm, and public
+    /// key as the DNSKEY record data of the signing key.
+    pub fn key_record(&self) -> Result<Key<K::Octets>, K::Error> {
+        let dnskey = self.key.dnskey()?;
+        Ok(Key::new(
+            dnskey.flags(),
+            dnskey.protocol(),
+            dnskey.algorithm(),
+            dnskey.into_public_key(),
+        ))
+    }
+
+    /// Signs a request.
+    ///
+    /// The method takes a complete message in the form of an additional
+    /// builder, signs it, and adds the signature as a SIG record to the
+    /// message’s additional section. The signature will be valid for
+    /// [`DEFAULT_FUDGE`] seconds before and after `now`.
+    ///
+    /// The method fails if signing fails or if the SIG record doesn’t
+    /// fit into the message anymore.
+    ///
+    /// [`DEFAULT_FUDGE`]: constant.DEFAULT_FUDGE.html
+    pub fn sign_request<Target>(
+        &self,
+        message: &mut AdditionalBuilder<Target>,
+        now: Serial,
+    ) -> Result<(), SignError<K::Error>>
+    where
+        Target: OctetsBuilder + AsRef<[u8]> + AsMut<[u8]>,
+    {
+        self.sign_request_with_fudge(message, now, DEFAULT_FUDGE)
+    }
+
+    /// Signs a request with the provided fudge.
+    ///
+    /// This is the same as [`sign_request`] except that the signature
+    /// will be valid for `fudge` seconds before and after `now`.
+    ///
+    /// [`sign_request`]: #method.sign_request
+    pub fn sign_request_with_fudge<Target>(
+        &self,
+        message: &mut AdditionalBuilder<Target>,
+        now: Serial,
+        fudge: u32,
+    ) -> Result<(), SignError<K::Error>>
+    where
+        Target: OctetsBuilder + AsRef<[u8]> + AsMut<[u8]>,
+    {
+        self.sign(message, None, now, fudge)
+    }
+
+    /// Signs an answer.
+    ///
+    /// This is similar to [`sign_request`] but, as required by RFC 2931,
+    /// the signature also covers the complete request, including its own
+    /// SIG(0) record if there was one, which needs to be provided via
+    /// `request`.
+    ///
+    /// [`sign_request`]: #method.sign_request
+    pub fn sign_answer<Target, Octets>(
+        &self,
+        message: &mut AdditionalBuilder<Target>,
+        request: &Message<Octets>,
+        now: Serial,
+    ) -> Result<(), SignError<K::Error>>
+    where
+        Target: OctetsBuilder + AsRef<[u8]> + AsMut<[u8]>,
+        Octets: AsRef<[u8]>,
+    {
+        self.sign_answer_with_fudge(message, request, now, DEFAULT_FUDGE)
+    }
+
+    /// Signs an answer with the provided fudge.
+    ///
+    /// This is the same as [`sign_answer`] except that the signature
+    /// will be valid for `fudge` seconds before and after `now`.
+    ///
+    /// [`sign_answer`]: #method.sign_answer
+    pub fn sign_answer_with_fudge<Target, Octets>(
+        &self,
+        message: &mut AdditionalBuilder<Target>,
+        request: &Message<Octets>,
+        now: Serial,
+        fudge: u32,
+    ) -> Result<(), SignError<K::Error>>
+    where
+        Target: OctetsBuilder + AsRef<[u8]> + AsMut<[u8]>,
+        Octets: AsRef<[u8]>,
+    {
+        self.sign(message, Some(request.as_slice()), now, fudge)
+    }
+
+    /// Signs a message and adds the SIG record.
+    fn sign<Target>(
+        &self,
+        message: &mut AdditionalBuilder<Target>,
+        request: Option<&[u8]>,
+        now: Serial,
+        fudge: u32,
+    ) -> Result<(), SignError<K::Error>>
+    where
+        Target: OctetsBuilder + AsRef<[u8]> + AsMut<[u8]>,
+    {
+        let dnskey = self.key.dnskey().map_err(SignError::Key)?;
+        let sig = Sig::new(
+            Rtype::Int(0),
+            dnskey.algorithm(),
+            0,
+            0,
+            now.add(fudge),
+            Serial(now.into_int().wrapping_sub(fudge)),
+            dnskey.key_tag(),
+            &self.name,
+            b"".as_ref(),
+        );
+        let data = signed_data(&sig, request, message.as_slice(), None)?;
+        let signature = self.key.sign(&data).map_err(SignError::Key)?;
+        let sig = Sig::new(
+            sig.type_covered(),
+            sig.algorithm(),
+            sig.labels(),
+            sig.original_ttl(),
+            sig.expiration(),
+            sig.inception(),
+            sig.key_tag(),
+            sig.signer_name(),
+            signature.as_ref(),
+        );
+        message.push((Dname::root_slice(), Class::Any, 0, sig))?;
+        Ok(())
+    }
+}
+
+//------------ VerificationKey -----------------------------------------------
+
+/// A public key that can be used to verify a SIG(0) signature.
+///
+/// This trait is implemented for both [`Key`] and [`Dnskey`] record data.
+///
+/// [`Key`]: ../rdata/rfc2535/struct.Key.html
+/// [`Dnskey`]: ../rdata/rfc4034/struct.Dnskey.html
+pub trait VerificationKey {
+    /// Returns the algorithm of the key.
+    fn algorithm(&self) -> SecAlg;
+
+    /// Returns the key tag of the key.
+    fn key_tag(&self) -> u16;
+
+    /// Returns the octets of the public key.
+    fn public_key_slice(&self) -> &[u8];
+
+    /// Returns whether the key can be used for verifying signatures.
+    fn is_usable(&self) -> bool {
+        true
+    }
+}
+
+impl<Octets: AsRef<[u8]>> VerificationKey for Key<Octets> {
+    fn algorithm(&self) -> SecAlg {
+        self.algorithm()
+    }
+
+    fn key_tag(&self) -> u16 {
+        self.key_tag()
+    }
+
+    fn public_key_slice(&self) -> &[u8] {
+        self.public_key().as_ref()
+    }
+
+    fn is_usable(&self) -> bool {
+        !self.is_no_key()
+    }
+}
+
+impl<Octets: AsRef<[u8]>> VerificationKey for Dnskey<Octets> {
+    fn algorithm(&self) -> SecAlg {
+        self.algorithm()
+    }
+
+    fn key_tag(&self) -> u16 {
+        self.key_tag()
+    }
+
+    fn public_key_slice(&self) -> &[u8] {
+        self.public_key().as_ref()
+    }
+}
+
+impl<K: VerificationKey> VerificationKey for &K {
+    fn algorithm(&self) -> SecAlg {
+        (*self).algorithm()
+    }
+
+    fn key_tag(&self) -> u16 {
+        (*self).key_tag()
+    }
+
+    fn public_key_slice(&self) -> &[u8] {
+        (*self).public_key_slice()
+    }
+
+    fn is_usable(&self) -> bool {
+        (*self).is_usable()
+    }
+}
+
+//------------ Verification --------------------------------------------------
+
+/// Returns the SIG(0) record data of a message.
+///
+/// The record has to be the last record of the additional section. Returns
+/// `Ok(None)` if the message isn’t signed. A receiver can use the signer’s
+/// name, algorithm, and key tag of the returned data to find the public key
+/// for verifying the message.
+#[allow(clippy::type_complexity)]
+pub fn message_sig<Octets>(
+    message: &Message<Octets>,
+) -> Result<
+    Option<Sig<<&Octets as OctetsRef>::Range, ParsedDname<&Octets>>>,
+    ValidationError,
+>
+where
+    Octets: AsRef<[u8]>,
+    for<'o> &'o Octets: OctetsRef,
+{
+    Ok(MessageSig::from_message(message)?.map(|sig| sig.sig))
+}
+
+/// Verifies the SIG(0) signature of a request.
+///
+/// The signature is checked using the public key provided via `key`. It is
+/// only accepted if `now` is within the validity period of the signature
+/// extended by [`DEFAULT_FUDGE`] seconds on either side.
+///
+/// [`DEFAULT_FUDGE`]: constant.DEFAULT_FUDGE.html
+pub fn verify_request<Octets, K>(
+    message: &Message<Octets>,
+    key: K,
+    now: Serial,
+) -> Result<(), ValidationError>
+where
+    Octets: AsRef<[u8]>,
+    for<'o> &'o Octets: OctetsRef,
+    K: VerificationKey,
+{
+    verify_request_with_fudge(message, key, now, DEFAULT_FUDGE)
+}
+
+/// Verifies the SIG(0) signature of a request with the provided fudge.
+///
+/// This is the same as [`verify_request`] except that the validity period
+/// is extended by `fudge` seconds.
+///
+/// [`verify_request`]: fn.verify_request.html
+pub fn verify_request_with_fudge<Octets, K>(
+    message: &Message<Octets>,
+    key: K,
+    now: Serial,
+    fudge: u32,
+) -> Result<(), ValidationError>
+where
+    Octets: AsRef<[u8]>,
+    for<'o> &'o Octets: OctetsRef,
+    K: VerificationKey,
+{
+    verify(message, None, key, now, fudge)
+}
+
+/// Verifies the SIG(0) signature of an answer.
+///
+/// This is similar to [`verify_request`] but the signature also has to
+/// cover the complete request, including its own SIG(0) record if there
+/// was one, which needs to be given via `request`.
+///
+/// [`verify_request`]: fn.verify_request.html
+pub fn verify_answer<Octets, Request, K>(
+    message: &Message<Octets>,
+    request: &Message<Request>,
+    key: K,
+    now: Serial,
+) -> Result<(), ValidationError>
+where
+    Octets: AsRef<[u8]>,
+    for<'o> &'o Octets: OctetsRef,
+    Request: AsRef<[u8]>,
+    K: VerificationKey,
+{
+    verify_answer_with_fudge(message, request, key, now, DEFAULT_FUDGE)
+}
+
+/// Verifies the SIG(0) signature of an answer with the provided fudge.
+///
+/// This is the same as [`verify_answer`] except that the validity period
+/// is extended by `fudge` seconds.
+///
+/// [`verify_answer`]: fn.verify_answer.html
+pub fn verify_answer_with_fudge<Octets, Request, K>(
+    message: &Message<Octets>,
+    request: &Message<Request>,
+    key: K,
+    now: Serial,
+    fudge: u32,
+) -> Result<(), ValidationError>
+where
+    Octets: AsRef<[u8]>,
+    for<'o> &'o Octets: OctetsRef,
+    Request: AsRef<[u8]>,
+    K: VerificationKey,
+{
+    verify(message, Some(request.as_slice()), key, now, fudge)
+}
+
+/// Verifies the SIG(0) signature of a message.
+fn verify<Octets, K>(
+    message: &Message<Octets>,
+    request: Option<&[u8]>,
+    key: K,
+    now: Serial,
+    fudge: u32,
+) -> Result<(), ValidationError>
+where
+    Octets: AsRef<[u8]>,
+    for<'o> &'o Octets: OctetsRef,
+    K: VerificationKey,
+{
+    let sig = match MessageSig::from_message(message)? {
+        Some(sig) => sig,
+        None => return Err(ValidationError::Unsigned),
+    };
+    if !key.is_usable()
+        || sig.sig.algorithm() != key.algorithm()
+        || sig.sig.key_tag() != key.key_tag()
+    {
+        return Err(ValidationError::BadKey);
+    }
+    sig.check_time(now, fudge)?;
+    let data = signed_data(
+        &Sig::new(
+            sig.sig.type_covered(),
+            sig.sig.algorithm(),
+            sig.sig.labels(),
+            sig.sig.original_ttl(),
+            sig.sig.expiration(),
+            sig.sig.inception(),
+            sig.sig.key_tag(),
+            sig.sig.signer_name(),
+            b"".as_ref(),
+        ),
+        request,
+        message.as_slice(),
+        Some(sig.start),
+    )?;
+    verify_signature(
+        key.algorithm(),
+        key.public_key_slice(),
+        &data,
+        sig.sig.signature().as_ref(),
+    )
+    .map_err(Into::into)
+}
+
+//------------ MessageSig ----------------------------------------------------
+
+/// The SIG(0) record of a message.
+struct MessageSig<'a, Octets>
+where
+    for<'o> &'o Octets: OctetsRef,
+{
+    /// The record data.
+    sig: Sig<<&'a Octets as OctetsRef>::Range, ParsedDname<&'a Octets>>,
+
+    /// The index of the start of the record.
+    start: usize,
+}
+
+impl<'a, Octets> MessageSig<'a, Octets>
+where
+    Octets: AsRef<[u8]>,
+    for<'o> &'o Octets: OctetsRef,
+{
+    /// Gets the SIG(0) record from a message.
+    ///
+    /// Returns `Ok(None)` if the last record of the additional section is
+    /// not a SIG(0) record.
+    fn from_message(
+        message: &'a Message<Octets>,
+    ) -> Result<Option<Self>, ValidationError> {
+        let mut section = message.additional()?;
+        let mut last = None;
+        loop {
+            let start = section.pos();
+            match section.next() {
+                Some(record) => last = Some((record?, start)),
+                None => break,
+            }
+        }
+        let (record, start) = match last {
+            Some(last) => last,
+            None => return Ok(None),
+        };
+        let record = match record.into_record::<Sig<_, _>>()? {
+            Some(record) => record,
+            None => return Ok(None),
+        };
+        if record.data().type_covered() != Rtype::Int(0) {
+            return Ok(None);
+        }
+        if !record.owner().is_root()
+            || record.class() != Class::Any
+            || record.ttl() != 0
+            || record.data().labels() != 0
+            || record.data().original_ttl() != 0
+        {
+            return Err(ValidationError::FormErr);
+        }
+        Ok(Some(MessageSig {
+            sig: record.into_data(),
+            start,
+        }))
+    }
+
+    /// Checks that `now` is within the validity period.
+    fn check_time(
+        &self,
+        now: Serial,
+        fudge: u32,
+    ) -> Result<(), ValidationError> {
+        let inception = self.sig.inception();
+        let expiration = self.sig.expiration().add(fudge);
+        let now_fudged = now.add(fudge);
+        if matches!(
+            inception.partial_cmp(&now_fudged),
+            Some(Ordering::Less | Ordering::Equal)
+        ) && matches!(
+            now.partial_cmp(&expiration),
+            Some(Ordering::Less | Ordering::Equal)
+        ) {
+            Ok(())
+        } else {
+            Err(ValidationError::BadTime)
+        }
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Assembles the data covered by a SIG(0) signature.
+///
+/// This is the SIG record data without the signature, followed by the
+/// request if `message` is an answer, followed by the message itself. If
+/// `sig_start` is given, the message already contains the SIG(0) record
+/// starting at this index. It is left out and the additional record count
+/// adjusted accordingly.
+fn signed_data<N: ToDname>(
+    sig: &Sig<&[u8], N>,
+    request: Option<&[u8]>,
+    message: &[u8],
+    sig_start: Option<usize>,
+) -> Result<Vec<u8>, ShortBuf> {
+    let mut res = Vec::new();
+    sig.compose(&mut res)?;
+    if let Some(request) = request {
+        res.append_slice(request)?;
+    }
+    match sig_start {
+        Some(start) => {
+            let mut header = *HeaderSection::for_message_slice(message);
+            header.counts_mut().dec_arcount();
+            res.append_slice(header.as_slice())?;
+            res.append_slice(
+                &message[mem::size_of::<HeaderSection>()..start],
+            )?;
+        }
+        None => res.append_slice(message)?,
+    }
+    Ok(res)
+}
+
+//============ Error Types ===================================================
+
+//------------ SignError -----------------------------------------------------
+
+/// An error happened while signing a message.
+#[derive(Clone, Copy, Debug)]
+pub enum SignError<E> {
+    /// The signing key failed to produce a signature.
+    Key(E),
+
+    /// The SIG record didn’t fit into the message.
+    ShortBuf,
+}
+
+//--- From
+
+impl<E> From<ShortBuf> for SignError<E> {
+    fn from(_: ShortBuf) -> Self {
+        SignError::ShortBuf
+    }
+}
+
+//--- Display and Error
+
+impl<E: fmt::Display> fmt::Display for SignError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SignError::Key(ref err) => write!(f, "signing failed: {}", err),
+            SignError::ShortBuf => f.write_str("short buffer"),
+        }
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> std::error::Error for SignError<E> {}
+
+//------------ ValidationError -----------------------------------------------
+
+/// An error happened while verifying a SIG(0) signature.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ValidationError {
+    /// The message is not signed.
+    Unsigned,
+
+    /// The message or its SIG(0) record is malformed.
+    FormErr,
+
+    /// The signature was not made with the given key.
+    BadKey,
+
+    /// The signature is not valid.
+    BadSig,
+
+    /// The current time is outside of the signature’s validity period.
+    BadTime,
+
+    /// The algorithm of the key is not supported.
+    Unsupported,
+}
+
+//--- From
+
+impl From<ParseError> for ValidationError {
+    fn from(_: ParseError) -> Self {
+        ValidationError::FormErr
+    }
+}
+
+impl From<ShortBuf> for ValidationError {
+    fn from(_: ShortBuf) -> Self {
+        ValidationError::FormErr
+    }
+}
+
+impl From<AlgorithmError> for ValidationError {
+    fn from(err: AlgorithmError) -> Self {
+        match err {
+            AlgorithmError::Unsupported => ValidationError::Unsupported,
+            AlgorithmError::BadSig => ValidationError::BadSig,
+            AlgorithmError::InvalidData => ValidationError::BadKey,
+        }
+    }
+}
+
+//--- Display and Error
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            ValidationError::Unsigned => "unsigned message",
+            ValidationError::FormErr => "format error",
+            ValidationError::BadKey => "unknown key",
+            ValidationError::BadSig => "bad signature",
+            ValidationError::BadTime => "signature expired or not yet valid",
+            ValidationError::Unsupported => "unsupported algorithm",
+        })
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::Opcode;
+    use crate::base::message_builder::MessageBuilder;
+    use crate::sign::ring::Key as RingKey;
+    use core::str::FromStr;
+    use ring::rand::SystemRandom;
+
+    fn request() -> AdditionalBuilder<Vec<u8>> {
+        let mut msg = MessageBuilder::new_vec();
+        msg.header_mut().set_opcode(Opcode::Update);
+        let mut msg = msg.question();
+        msg.push((
+            Dname::<Vec<u8>>::from_str("example.com.").unwrap(),
+            Rtype::Soa,
+        ))
+        .unwrap();
+        msg.additional()
+    }
+
+    #[test]
+    fn sign_and_verify() {
+        let rng = SystemRandom::new();
+        let key = Sig0Key::new(
+            RingKey::throwaway_13(0x0200, &rng).unwrap(),
+            Dname::from_str("client.example.com.").unwrap(),
+        );
+        let public = key.key_record().unwrap();
+        let now = Serial(1_000_000);
+
+        let mut msg = request();
+        key.sign_request(&mut msg, now).unwrap();
+        let request = msg.into_message();
+
+        let sig = message_sig(&request).unwrap().unwrap();
+        assert_eq!(*sig.signer_name(), *key.name());
+        assert_eq!(sig.key_tag(), public.key_tag());
+
+        verify_request(&request, &public, now).unwrap();
+        verify_request(&request, &public, now.add(DEFAULT_FUDGE * 2))
+            .unwrap();
+        assert_eq!(
+            verify_request(&request, &public, now.add(DEFAULT_FUDGE * 2 + 1)),
+            Err(ValidationError::BadTime)
+        );
+        assert_eq!(
+            verify_request(
+                &request,
+                &public,
+                Serial(now.into_int() - DEFAULT_FUDGE * 2 - 1)
+            ),
+            Err(ValidationError::BadTime)
+        );
+
+        let other = RingKey::throwaway_13(0x0200, &rng).unwrap();
+        let other = Sig0Key::new(other, key.name().clone());
+        let other = other.key_record().unwrap();
+        assert!(verify_request(&request, &other, now).is_err());
+
+        let mut tampered = request.as_slice().to_vec();
+        tampered[2] ^= 0x01;
+        let tampered = Message::from_octets(tampered).unwrap();
+        assert_eq!(
+            verify_request(&tampered, &public, now),
+            Err(ValidationError::BadSig)
+        );
+
+        let mut answer = MessageBuilder::new_vec()
+            .start_answer(&request, crate::base::iana::Rcode::NoError)
+            .unwrap()
+            .additional();
+        key.sign_answer(&mut answer, &request, now).unwrap();
+        let answer = answer.into_message();
+        verify_answer(&answer, &request, &public, now).unwrap();
+        assert_eq!(
+            verify_request(&answer, &public, now),
+            Err(ValidationError::BadSig)
+        );
+
+        let unsigned = self::request().into_message();
+        assert_eq!(
+            verify_request(&unsigned, &public, now),
+            Err(ValidationError::Unsigned)
+        );
+    }
+}
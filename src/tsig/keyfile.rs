@@ -0,0 +1,616 @@
+//! Reading TSIG keys from configuration files.
+//!
+//! This module provides functions to read the TSIG key definitions from the
+//! configuration files of some popular name servers. Currently, these are
+//! the `key` statements used by BIND in `named.conf` and in files created
+//! by `tsig-keygen` via [`parse_bind`] and the `key:` clauses of NSD’s
+//! `nsd.conf` via [`parse_nsd`].
+//!
+//! Both functions only look at the key definitions and skip over anything
+//! else, so they can be used with complete configuration files. They don’t,
+//! however, follow include statements.
+//!
+//! [`parse_bind`]: fn.parse_bind.html
+//! [`parse_nsd`]: fn.parse_nsd.html
+#![cfg(feature = "std")]
+
+use super::{Algorithm, Key};
+use crate::base::name::Dname;
+use crate::base::octets::OctetsVec;
+use crate::utils::base64;
+use core::fmt;
+use core::str::FromStr;
+use std::string::String;
+use std::vec::Vec;
+
+//------------ parse_bind ----------------------------------------------------
+
+/// Parses the key statements in a BIND configuration file.
+///
+/// The function expects the content of the file in `s` and returns all the
+/// keys defined via `key` statements such as:
+///
+/// ```text
+/// key "example.key." {
+///     algorithm hmac-sha256;
+///     secret "MTIzNDU2Nzg5MDEyMzQ1Njc4OTAxMjM0NTY3ODkwMTI=";
+/// };
+/// ```
+///
+/// All other statements are ignored but must still be syntactically
+/// correct.
+pub fn parse_bind(s: &str) -> Result<Vec<Key>, KeyFileError> {
+    let mut tokens = BindTokens::new(s);
+    let mut res = Vec::new();
+    while let Some(statement) = BindStatement::parse(&mut tokens)? {
+        if statement.args.first().map(String::as_str) != Some("key") {
+            continue;
+        }
+        if statement.args.len() != 2 {
+            return Err(KeyFileError::new(
+                statement.line,
+                "expected a single key name",
+            ));
+        }
+        let block = match statement.block {
+            Some(block) => block,
+            None => {
+                return Err(KeyFileError::new(
+                    statement.line,
+                    "missing key definition",
+                ))
+            }
+        };
+        let mut algorithm = None;
+        let mut secret = None;
+        for item in block {
+            let target = match item.args.first().map(String::as_str) {
+                Some("algorithm") => &mut algorithm,
+                Some("secret") => &mut secret,
+                _ => continue,
+            };
+            if item.args.len() != 2 {
+                return Err(KeyFileError::new(item.line, "expected a value"));
+            }
+            *target = Some((item.args[1].clone(), item.line));
+        }
+        res.push(make_key(
+            (&statement.args[1], statement.line),
+            algorithm.as_ref().map(|(s, line)| (s.as_str(), *line)),
+            secret.as_ref().map(|(s, line)| (s.as_str(), *line)),
+            statement.line,
+        )?);
+    }
+    Ok(res)
+}
+
+//------------ parse_nsd -----------------------------------------------------
+
+/// Parses the key clauses in an NSD configuration file.
+///
+/// The function expects the content of the file in `s` and returns all the
+/// keys defined via `key:` clauses such as:
+///
+/// ```text
+/// key:
+///     name: "example.key."
+///     algorithm: hmac-sha256
+///     secret: "MTIzNDU2Nzg5MDEyMzQ1Njc4OTAxMjM0NTY3ODkwMTI="
+/// ```
+///
+/// A clause starts with a line containing only the clause name followed by
+/// a colon and lasts until the next clause. All other clauses are ignored.
+pub fn parse_nsd(s: &str) -> Result<Vec<Key>, KeyFileError> {
+    let mut res = Vec::new();
+    let mut current: Option<NsdKey> = None;
+    for (line_no, line) in s.lines().enumerate() {
+        let line_no = line_no + 1;
+        let line = strip_nsd_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (attr, value) = match line.find(':') {
+            Some(pos) => (line[..pos].trim(), line[pos + 1..].trim()),
+            None => {
+                return Err(KeyFileError::new(line_no, "expected attribute"))
+            }
+        };
+        if value.is_empty() {
+            // A new clause starts.
+            if let Some(key) = current.take() {
+                res.push(key.into_key()?);
+            }
+            if attr == "key" {
+                current = Some(NsdKey::new(line_no));
+            }
+            continue;
+        }
+        let key = match current.as_mut() {
+            Some(key) => key,
+            None => continue,
+        };
+        let value = unquote_nsd(value)
+            .ok_or_else(|| KeyFileError::new(line_no, "unbalanced quotes"))?;
+        let target = match attr {
+            "name" => &mut key.name,
+            "algorithm" => &mut key.algorithm,
+            "secret" => &mut key.secret,
+            _ => continue,
+        };
+        *target = Some((String::from(value), line_no));
+    }
+    if let Some(key) = current.take() {
+        res.push(key.into_key()?);
+    }
+    Ok(res)
+}
+
+/// The attributes of a key clause in an NSD configuration file.
+struct NsdKey {
+    /// The line the clause started on.
+    line: usize,
+
+    /// The name of the key and the line it was defined on.
+    name: Option<(String, usize)>,
+
+    /// The algorithm of the key and the line it was defined on.
+    algorithm: Option<(String, usize)>,
+
+    /// The secret of the key and the line it was defined on.
+    secret: Option<(String, usize)>,
+}
+
+impl NsdKey {
+    fn new(line: usize) -> Self {
+        NsdKey {
+            line,
+            name: None,
+            algorithm: None,
+            secret: None,
+        }
+    }
+
+    fn into_key(self) -> Result<Key, KeyFileError> {
+        let name = match self.name {
+            Some(ref name) => (name.0.as_str(), name.1),
+            None => return Err(KeyFileError::new(self.line, "missing name")),
+        };
+        make_key(
+            name,
+            self.algorithm.as_ref().map(|(s, line)| (s.as_str(), *line)),
+            self.secret.as_ref().map(|(s, line)| (s.as_str(), *line)),
+            self.line,
+        )
+    }
+}
+
+/// Removes a comment from a line of an NSD configuration file.
+fn strip_nsd_comment(line: &str) -> &str {
+    let mut quoted = false;
+    for (pos, ch) in line.char_indices() {
+        match ch {
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..pos],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// Removes the quotes around an attribute value if there are any.
+///
+/// Returns `None` if the quotes are not balanced.
+fn unquote_nsd(value: &str) -> Option<&str> {
+    match value.strip_prefix('"') {
+        Some(value) => value.strip_suffix('"'),
+        None if value.ends_with('"') => None,
+        None => Some(value),
+    }
+}
+
+//------------ make_key ------------------------------------------------------
+
+/// Creates a key from the strings found in a configuration file.
+///
+/// All arguments are given as the string and the line it was found on for
+/// error reporting. The last argument is the line where the key definition
+/// started which is used if the algorithm or secret are missing.
+fn make_key(
+    name: (&str, usize),
+    algorithm: Option<(&str, usize)>,
+    secret: Option<(&str, usize)>,
+    line: usize,
+) -> Result<Key, KeyFileError> {
+    let key_name = Dname::<OctetsVec>::from_str(name.0)
+        .map_err(|_| KeyFileError::new(name.1, "invalid key name"))?;
+    let (algorithm, alg_line) = match algorithm {
+        Some(algorithm) => algorithm,
+        None => return Err(KeyFileError::new(line, "missing algorithm")),
+    };
+    let algorithm = Algorithm::from_str(&algorithm.to_ascii_lowercase())
+        .map_err(|_| KeyFileError::new(alg_line, "unknown algorithm"))?;
+    let (secret, secret_line) = match secret {
+        Some(secret) => secret,
+        None => return Err(KeyFileError::new(line, "missing secret")),
+    };
+    let secret: Vec<u8> = base64::decode(secret)
+        .map_err(|_| KeyFileError::new(secret_line, "invalid secret"))?;
+    Key::new(algorithm, &secret, key_name, None, None)
+        .map_err(|_| KeyFileError::new(line, "invalid key"))
+}
+
+//------------ BindStatement -------------------------------------------------
+
+/// A statement in a BIND configuration file.
+///
+/// A statement consists of a number of arguments, optionally followed by a
+/// block of statements in braces, and is terminated by a semicolon.
+struct BindStatement {
+    /// The line the statement started on.
+    line: usize,
+
+    /// The arguments of the statement, the first one being its name.
+    args: Vec<String>,
+
+    /// The statements in the block following the arguments, if any.
+    block: Option<Vec<BindStatement>>,
+}
+
+impl BindStatement {
+    /// Parses the next statement.
+    ///
+    /// Returns `Ok(None)` if the end of input has been reached.
+    fn parse(tokens: &mut BindTokens) -> Result<Option<Self>, KeyFileError> {
+        let (token, line) = match tokens.next_token()? {
+            Some(token) => token,
+            None => return Ok(None),
+        };
+        match token {
+            BindToken::Word(word) => Self::parse_rest(tokens, word, line),
+            _ => Err(KeyFileError::new(line, "expected statement")),
+        }
+        .map(Some)
+    }
+
+    /// Parses the next statement inside a block.
+    ///
+    /// Returns `Ok(None)` if the closing brace has been reached.
+    fn parse_in_block(
+        tokens: &mut BindTokens,
+    ) -> Result<Option<Self>, KeyFileError> {
+        let (token, line) = match tokens.next_token()? {
+            Some(token) => token,
+            None => {
+                return Err(KeyFileError::new(
+                    tokens.line,
+                    "unexpected end of file",
+                ))
+            }
+        };
+        match token {
+            BindToken::Word(word) => Self::parse_rest(tokens, word, line),
+            BindToken::Close => return Ok(None),
+            _ => Err(KeyFileError::new(line, "expected statement")),
+        }
+        .map(Some)
+    }
+
+    /// Parses the remainder of a statement after its first word.
+    fn parse_rest(
+        tokens: &mut BindTokens,
+        first: String,
+        line: usize,
+    ) -> Result<Self, KeyFileError> {
+        let mut res = BindStatement {
+            line,
+            args: vec![first],
+            block: None,
+        };
+        loop {
+            match tokens.next_token()? {
+                Some((BindToken::Word(word), _)) => res.args.push(word),
+                Some((BindToken::Semicolon, _)) => return Ok(res),
+                Some((BindToken::Open, _)) => break,
+                Some((BindToken::Close, line)) => {
+                    return Err(KeyFileError::new(line, "unexpected '}'"))
+                }
+                None => {
+                    return Err(KeyFileError::new(
+                        tokens.line,
+                        "unexpected end of file",
+                    ))
+                }
+            }
+        }
+        let mut block = Vec::new();
+        while let Some(statement) = Self::parse_in_block(tokens)? {
+            block.push(statement)
+        }
+        res.block = Some(block);
+        match tokens.next_token()? {
+            Some((BindToken::Semicolon, _)) => Ok(res),
+            _ => Err(KeyFileError::new(tokens.line, "expected ';'")),
+        }
+    }
+}
+
+//------------ BindTokens ----------------------------------------------------
+
+/// A token in a BIND configuration file.
+enum BindToken {
+    /// A word or quoted string.
+    Word(String),
+
+    /// An opening brace.
+    Open,
+
+    /// A closing brace.
+    Close,
+
+    /// A semicolon.
+    Semicolon,
+}
+
+/// The tokenizer for BIND configuration files.
+struct BindTokens<'a> {
+    /// The remaining input.
+    chars: core::iter::Peekable<core::str::Chars<'a>>,
+
+    /// The current line number.
+    line: usize,
+}
+
+impl<'a> BindTokens<'a> {
+    fn new(s: &'a str) -> Self {
+        BindTokens {
+            chars: s.chars().peekable(),
+            line: 1,
+        }
+    }
+
+    /// Returns the next character, keeping track of the line number.
+    fn next_char(&mut self) -> Option<char> {
+        let ch = self.chars.next()?;
+        if ch == '\n' {
+            self.line += 1;
+        }
+        Some(ch)
+    }
+
+    /// Skips white space and comments.
+    fn skip_space(&mut self) -> Result<(), KeyFileError> {
+        loop {
+            match self.chars.peek() {
+                Some(ch) if ch.is_whitespace() => {
+                    self.next_char();
+                }
+                Some('#') => self.skip_line(),
+                Some('/') => {
+                    let mut ahead = self.chars.clone();
+                    ahead.next();
+                    match ahead.next() {
+                        Some('/') => self.skip_line(),
+                        Some('*') => {
+                            let line = self.line;
+                            self.next_char();
+                            self.next_char();
+                            self.skip_block_comment(line)?;
+                        }
+                        _ => return Ok(()),
+                    }
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    /// Skips to the end of the current line.
+    fn skip_line(&mut self) {
+        while let Some(ch) = self.next_char() {
+            if ch == '\n' {
+                break;
+            }
+        }
+    }
+
+    /// Skips the rest of a C-style block comment.
+    fn skip_block_comment(
+        &mut self,
+        line: usize,
+    ) -> Result<(), KeyFileError> {
+        let mut star = false;
+        loop {
+            match self.next_char() {
+                Some('/') if star => return Ok(()),
+                Some(ch) => star = ch == '*',
+                None => {
+                    return Err(KeyFileError::new(
+                        line,
+                        "unterminated comment",
+                    ))
+                }
+            }
+        }
+    }
+
+    /// Returns the next token and the line it started on.
+    fn next_token(
+        &mut self,
+    ) -> Result<Option<(BindToken, usize)>, KeyFileError> {
+        self.skip_space()?;
+        let line = self.line;
+        let token = match self.next_char() {
+            None => return Ok(None),
+            Some('{') => BindToken::Open,
+            Some('}') => BindToken::Close,
+            Some(';') => BindToken::Semicolon,
+            Some('"') => {
+                let mut word = String::new();
+                loop {
+                    match self.next_char() {
+                        Some('"') => break,
+                        Some('\\') => match self.next_char() {
+                            Some(ch) => word.push(ch),
+                            None => break,
+                        },
+                        Some(ch) => word.push(ch),
+                        None => {
+                            return Err(KeyFileError::new(
+                                line,
+                                "unterminated string",
+                            ))
+                        }
+                    }
+                }
+                BindToken::Word(word)
+            }
+            Some(ch) => {
+                let mut word = String::new();
+                word.push(ch);
+                while let Some(&ch) = self.chars.peek() {
+                    if ch.is_whitespace() || "{};\"".contains(ch) {
+                        break;
+                    }
+                    word.push(ch);
+                    self.next_char();
+                }
+                BindToken::Word(word)
+            }
+        };
+        Ok(Some((token, line)))
+    }
+}
+
+//============ Error Types ===================================================
+
+//------------ KeyFileError --------------------------------------------------
+
+/// An error happened while reading keys from a configuration file.
+#[derive(Clone, Copy, Debug)]
+pub struct KeyFileError {
+    /// The line the error occurred on.
+    line: usize,
+
+    /// A description of the error.
+    msg: &'static str,
+}
+
+impl KeyFileError {
+    fn new(line: usize, msg: &'static str) -> Self {
+        KeyFileError { line, msg }
+    }
+
+    /// Returns the line number where the error occurred.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+}
+
+//--- Display and Error
+
+impl fmt::Display for KeyFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.line, self.msg)
+    }
+}
+
+impl std::error::Error for KeyFileError {}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::string::ToString;
+
+    const SECRET: &str = "MTIzNDU2Nzg5MDEyMzQ1Njc4OTAxMjM0NTY3ODkwMTI=";
+
+    #[test]
+    fn bind_keys() {
+        let conf = format!(
+            r#"
+            # A comment.
+            options {{ directory "/var/named"; }};
+            key "one.key." {{
+                algorithm hmac-sha256; // Another comment.
+                secret "{}";
+            }};
+            /* A block
+               comment. */
+            key two.key {{ algorithm HMAC-SHA512; secret "{}"; }};
+            zone "example.com" {{
+                allow-update {{ key one.key.; }};
+            }};
+            "#,
+            SECRET, SECRET
+        );
+        let keys = parse_bind(&conf).unwrap();
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys[0].name().to_string(), "one.key");
+        assert_eq!(keys[0].algorithm(), Algorithm::Sha256);
+        assert_eq!(keys[1].name().to_string(), "two.key");
+        assert_eq!(keys[1].algorithm(), Algorithm::Sha512);
+    }
+
+    #[test]
+    fn bind_errors() {
+        let err = parse_bind("key \"a\" {\n algorithm hmac-sha256;\n};")
+            .unwrap_err();
+        assert_eq!(err.line(), 1);
+        let err = parse_bind(
+            "key \"a\" {\n algorithm hmac-foo;\n secret \"AAAA\";\n};",
+        )
+        .unwrap_err();
+        assert_eq!(err.line(), 2);
+        let err =
+            parse_bind("key \"a\" {\n algorithm hmac-sha1;\n").unwrap_err();
+        assert_eq!(err.line(), 3);
+        assert!(parse_bind("key \"a\" { }").is_err());
+    }
+
+    #[test]
+    fn nsd_keys() {
+        let conf = format!(
+            r#"
+server:
+    port: 5353 # A comment.
+
+key:
+    name: "one.key."
+    algorithm: hmac-sha256
+    secret: "{}"
+
+zone:
+    name: "example.com"
+    request-xfr: 192.0.2.1 one.key.
+
+key:
+    name: two.key
+    secret: {}
+    algorithm: hmac-sha1
+            "#,
+            SECRET, SECRET
+        );
+        let keys = parse_nsd(&conf).unwrap();
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys[0].name().to_string(), "one.key");
+        assert_eq!(keys[0].algorithm(), Algorithm::Sha256);
+        assert_eq!(keys[1].name().to_string(), "two.key");
+        assert_eq!(keys[1].algorithm(), Algorithm::Sha1);
+    }
+
+    #[test]
+    fn nsd_errors() {
+        let err = parse_nsd("key:\n  name: a\n  algorithm: hmac-sha1\n")
+            .unwrap_err();
+        assert_eq!(err.line(), 1);
+        let err =
+            parse_nsd("key:\n  name: a\n  secret: \"AAAA\n").unwrap_err();
+        assert_eq!(err.line(), 3);
+        let err = parse_nsd(
+            "key:\n  name: a\n  secret: !!\n  algorithm: hmac-sha1\n",
+        )
+        .unwrap_err();
+        assert_eq!(err.line(), 3);
+    }
+}
@@ -0,0 +1,199 @@
+//! The message authentication codes used by TSIG.
+//!
+//! Most of the algorithms are implemented via ring. Since ring doesn’t
+//! support MD5, HMAC-MD5 as defined in [RFC 2104] is implemented here atop
+//! the md-5 crate.
+//!
+//! [RFC 2104]: https://tools.ietf.org/html/rfc2104
+
+use super::Algorithm;
+use core::fmt;
+use md5::{Digest, Md5};
+use ring::hmac;
+
+/// The block length of MD5.
+const MD5_BLOCK_LEN: usize = 64;
+
+/// The output length of MD5.
+const MD5_OUTPUT_LEN: usize = 16;
+
+//------------ Key -----------------------------------------------------------
+
+/// The secret bits of a TSIG key prepared for an algorithm.
+#[derive(Clone, Debug)]
+pub(super) enum Key {
+    /// A key for one of the algorithms supported by ring.
+    Ring(hmac::Key),
+
+    /// A key for HMAC-MD5.
+    Md5(Md5Key),
+}
+
+impl Key {
+    /// Creates a new key for the given algorithm from the key bits.
+    pub fn new(algorithm: Algorithm, key: &[u8]) -> Self {
+        match algorithm.ring_algorithm() {
+            Some(ring_alg) => Key::Ring(hmac::Key::new(ring_alg, key)),
+            None => Key::Md5(Md5Key::new(key)),
+        }
+    }
+
+    /// Creates a new signing context for this key.
+    pub fn context(&self) -> Context {
+        match *self {
+            Key::Ring(ref key) => Context::Ring(hmac::Context::with_key(key)),
+            Key::Md5(ref key) => Context::Md5(key.context()),
+        }
+    }
+}
+
+//------------ Md5Key --------------------------------------------------------
+
+/// A key for HMAC-MD5.
+///
+/// This keeps the key already padded to the block length.
+#[derive(Clone)]
+pub(super) struct Md5Key {
+    /// The key padded with zeros to the block length.
+    key: [u8; MD5_BLOCK_LEN],
+}
+
+impl Md5Key {
+    fn new(key: &[u8]) -> Self {
+        let mut res = [0u8; MD5_BLOCK_LEN];
+        if key.len() > MD5_BLOCK_LEN {
+            res[..MD5_OUTPUT_LEN].copy_from_slice(&Md5::digest(key));
+        } else {
+            res[..key.len()].copy_from_slice(key);
+        }
+        Md5Key { key: res }
+    }
+
+    /// Returns the key XORed with the given pad octet.
+    fn padded(&self, pad: u8) -> [u8; MD5_BLOCK_LEN] {
+        let mut res = self.key;
+        res.iter_mut().for_each(|x| *x ^= pad);
+        res
+    }
+
+    fn context(&self) -> Md5Context {
+        let mut inner = Md5::new();
+        inner.update(self.padded(0x36));
+        Md5Context {
+            inner,
+            outer: self.padded(0x5c),
+        }
+    }
+}
+
+impl fmt::Debug for Md5Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Md5Key")
+    }
+}
+
+//------------ Context -------------------------------------------------------
+
+/// A context for creating a signature.
+#[derive(Clone, Debug)]
+pub(super) enum Context {
+    /// A ring HMAC context.
+    Ring(hmac::Context),
+
+    /// A HMAC-MD5 context.
+    Md5(Md5Context),
+}
+
+impl Context {
+    /// Adds data to the signature.
+    pub fn update(&mut self, data: &[u8]) {
+        match *self {
+            Context::Ring(ref mut context) => context.update(data),
+            Context::Md5(ref mut context) => context.inner.update(data),
+        }
+    }
+
+    /// Finishes the context and returns the signature.
+    pub fn sign(self) -> Tag {
+        match self {
+            Context::Ring(context) => Tag::Ring(context.sign()),
+            Context::Md5(context) => {
+                let mut outer = Md5::new();
+                outer.update(context.outer);
+                outer.update(context.inner.finalize());
+                Tag::Md5(outer.finalize().into())
+            }
+        }
+    }
+}
+
+/// A context for HMAC-MD5.
+#[derive(Clone)]
+pub(super) struct Md5Context {
+    /// The hash of the inner padded key and the data.
+    inner: Md5,
+
+    /// The outer padded key.
+    outer: [u8; MD5_BLOCK_LEN],
+}
+
+impl fmt::Debug for Md5Context {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Md5Context")
+    }
+}
+
+//------------ Tag -----------------------------------------------------------
+
+/// A complete, untruncated signature.
+#[derive(Clone, Copy, Debug)]
+pub(super) enum Tag {
+    /// A signature created by ring.
+    Ring(hmac::Tag),
+
+    /// A HMAC-MD5 signature.
+    Md5([u8; MD5_OUTPUT_LEN]),
+}
+
+impl AsRef<[u8]> for Tag {
+    fn as_ref(&self) -> &[u8] {
+        match *self {
+            Tag::Ring(ref tag) => tag.as_ref(),
+            Tag::Md5(ref tag) => tag.as_ref(),
+        }
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn md5(key: &[u8], data: &[u8]) -> Tag {
+        let mut context = Key::new(Algorithm::Md5, key).context();
+        context.update(data);
+        context.sign()
+    }
+
+    // Test vectors from RFC 2202, section 2.
+    #[test]
+    fn hmac_md5() {
+        assert_eq!(
+            md5(&[0x0b; 16], b"Hi There").as_ref(),
+            b"\x92\x94\x72\x7a\x36\x38\xbb\x1c\x13\xf4\x8e\xf8\x15\x8b\xfc\x9d"
+        );
+        assert_eq!(
+            md5(b"Jefe", b"what do ya want for nothing?").as_ref(),
+            b"\x75\x0c\x78\x3e\x6a\xb0\xb5\x03\xea\xa8\x6e\x31\x0a\x5d\xb7\x38"
+        );
+        assert_eq!(
+            md5(
+                &[0xaa; 80],
+                b"Test Using Larger Than Block-Size Key - Hash Key First"
+            )
+            .as_ref(),
+            b"\x6b\x1a\xb7\xfe\x4b\xd7\xbf\x8f\x0b\x62\xe6\xce\x61\xb9\xd0\xcd"
+        );
+    }
+}
@@ -15,11 +15,13 @@
 //! additional section as well and can be verified by the client.
 //!
 //! TSIG supports a number of algorithms for boths signature generation; it
-//! even allows for private algorithms. The specification requires to support
-//! at least HMAC-MD5 defined in [RFC 2104]. Since MD5 is widely regarded as
-//! unsafe now, we don’t follow that rule and only support the SHA-based
-//! algorithms from [RFC 4653]. You can choose the algorithm to use for your
-//! keys via the [`Algorithm`] enum.
+//! even allows for private algorithms. The original specification requires
+//! to support HMAC-MD5 defined in [RFC 2104]. Since MD5 is widely regarded
+//! as unsafe now, its use is not recommended, but it is still supported for
+//! peers that haven’t moved on yet. In addition, the SHA-based algorithms
+//! from [RFC 4653] and their truncated variants from [RFC 8945] are
+//! supported. You can choose the algorithm to use for your keys via the
+//! [`Algorithm`] enum.
 //!
 //! Keys are managed via the [`Key`] type. While technically the actual
 //! octets of the key can be used with any algorithm, we tie together a key
@@ -38,15 +40,28 @@
 //! a [`KeyStore`], which tries to find the key used by the client. As this
 //! is a trait, you may need to implement that your particular use case. There
 //! is implementations for a hash map as well as a single key (the latter
-//! mostly for testing).
+//! mostly for testing). If keys need to be added or removed while the
+//! server is running, the [`DynamicKeyStore`] can be used instead. It also
+//! allows restricting the zones and operations each key may be used for
+//! via [`KeyPermissions`]. Keys defined in BIND or NSD configuration files
+//! can be loaded via the functions in the [`keyfile`] module.
+//!
+//! Keys can also be established between client and server at runtime
+//! using the TKEY protocol. Support for this is available in the [`tkey`]
+//! module if the `tkey` feature is enabled.
 //!
 //! [RFC 2104]: https://tools.ietf.org/html/rfc2104
 //! [RFC 2845]: https://tools.ietf.org/html/rfc2845
 //! [RFC 4635]: https://tools.ietf.org/html/rfc4653
+//! [RFC 8945]: https://tools.ietf.org/html/rfc8945
 //! [TSIG]: ../rdata/rfc2845/struct.Tsig.html
 //! [`Algorithm`]: enum.Algorithm.html
 //! [`Key`]: enum.Key.html
 //! [`KeyStore`]: trait.KeyStore.html
+//! [`DynamicKeyStore`]: struct.DynamicKeyStore.html
+//! [`KeyPermissions`]: struct.KeyPermissions.html
+//! [`keyfile`]: keyfile/index.html
+//! [`tkey`]: tkey/index.html
 //! [`ClientTransaction`]: struct.ClientTransaction.html
 //! [`ServerTransaction`]: struct.ServerTransaction.html
 //! [`ClientSequence`]: struct.ClientSequence.html
@@ -55,6 +70,13 @@
 #![cfg_attr(docsrs, doc(cfg(feature = "tsig")))]
 
 mod interop;
+pub mod keyfile;
+mod mac;
+mod store;
+pub mod tkey;
+
+#[cfg(feature = "std")]
+pub use self::store::{DynamicKeyStore, KeyPermissions, Operation};
 
 use crate::base::header::HeaderSection;
 use crate::base::iana::{Class, Rcode, TsigRcode};
@@ -68,7 +90,7 @@ use crate::base::record::Record;
 use crate::rdata::rfc2845::{Time48, Tsig};
 use bytes::{Bytes, BytesMut};
 use core::{cmp, fmt, mem, ops, str};
-use ring::{constant_time, hkdf::KeyType, hmac, rand};
+use ring::{constant_time, hmac, rand};
 #[cfg(feature = "std")]
 use std::collections::HashMap;
 
@@ -100,8 +122,11 @@ use std::collections::HashMap;
 /// [`signing_len`]: #method.signing_len
 #[derive(Debug)]
 pub struct Key {
-    /// The key’s bits and algorithm.
-    key: hmac::Key,
+    /// The key’s bits prepared for the algorithm.
+    key: mac::Key,
+
+    /// The algorithm of the key.
+    algorithm: Algorithm,
 
     /// The name of the key as a domain name.
     name: Dname<OctetsVec>,
@@ -150,7 +175,8 @@ impl Key {
         let (min_mac_len, signing_len) =
             Self::calculate_bounds(algorithm, min_mac_len, signing_len)?;
         Ok(Key {
-            key: hmac::Key::new(algorithm.into_hmac_algorithm(), key),
+            key: mac::Key::new(algorithm, key),
+            algorithm,
             name,
             min_mac_len,
             signing_len,
@@ -173,13 +199,13 @@ impl Key {
     ) -> Result<(Self, Bytes), GenerateKeyError> {
         let (min_mac_len, signing_len) =
             Self::calculate_bounds(algorithm, min_mac_len, signing_len)?;
-        let algorithm = algorithm.into_hmac_algorithm();
-        let key_len = algorithm.len();
+        let key_len = algorithm.digest_len();
         let mut bytes = BytesMut::with_capacity(key_len);
         bytes.resize(key_len, 0);
         rng.fill(&mut bytes)?;
         let key = Key {
-            key: hmac::Key::new(algorithm, &bytes),
+            key: mac::Key::new(algorithm, &bytes),
+            algorithm,
             name,
             min_mac_len,
             signing_len,
@@ -218,12 +244,12 @@ impl Key {
     }
 
     /// Creates a signing context for this key.
-    fn signing_context(&self) -> hmac::Context {
-        hmac::Context::with_key(&self.key)
+    fn signing_context(&self) -> mac::Context {
+        self.key.context()
     }
 
     /// Returns a the possibly truncated slice of the signature.
-    fn signature_slice<'a>(&self, signature: &'a hmac::Tag) -> &'a [u8] {
+    fn signature_slice<'a>(&self, signature: &'a mac::Tag) -> &'a [u8] {
         &signature.as_ref()[..self.signing_len]
     }
 }
@@ -233,7 +259,7 @@ impl Key {
 impl Key {
     /// Returns the algorithm of this key.
     pub fn algorithm(&self) -> Algorithm {
-        Algorithm::from_hmac_algorithm(self.key.algorithm())
+        self.algorithm
     }
 
     /// Returns a reference to the name of this key.
@@ -243,7 +269,7 @@ impl Key {
 
     /// Returns the native length of the signature from this key.
     pub fn native_len(&self) -> usize {
-        self.key.algorithm().len()
+        self.algorithm.native_len()
     }
 
     /// Returns the minimum acceptable length of a received signature.
@@ -277,19 +303,21 @@ impl Key {
     ///
     /// The first signature is the expected value, the second the provided
     /// one. This considers signature truncation limited to whatever is
-    /// acceptable by this key.
+    /// acceptable by this key. For the truncated algorithms, the expected
+    /// value is truncated to the algorithm’s native length first.
     fn compare_signatures(
         &self,
-        expected: &hmac::Tag,
+        expected: &mac::Tag,
         provided: &[u8],
     ) -> Result<(), ValidationError> {
         if provided.len() < self.min_mac_len {
             return Err(ValidationError::BadTrunc);
         }
-        let expected = if provided.len() < expected.as_ref().len() {
-            &expected.as_ref()[..provided.len()]
+        let expected = &expected.as_ref()[..self.native_len()];
+        let expected = if provided.len() < expected.len() {
+            &expected[..provided.len()]
         } else {
-            expected.as_ref()
+            expected
         };
         constant_time::verify_slices_are_equal(expected, provided)
             .map_err(|_| ValidationError::BadSig)
@@ -971,8 +999,8 @@ impl<K: AsRef<Key>> ServerSequence<K> {
 /// arcs and whatnots here.
 #[derive(Clone, Debug)]
 struct SigningContext<K> {
-    /// The ring signing context.
-    context: hmac::Context,
+    /// The signing context.
+    context: mac::Context,
 
     /// The key.
     ///
@@ -1194,7 +1222,7 @@ impl<K: AsRef<Key>> SigningContext<K> {
         first: &[u8],
         second: Option<&[u8]>,
         variables: &Variables,
-    ) -> (Self, hmac::Tag) {
+    ) -> (Self, mac::Tag) {
         let mut context = key.as_ref().signing_context();
         context.update(first);
         if let Some(second) = second {
@@ -1217,7 +1245,7 @@ impl<K: AsRef<Key>> SigningContext<K> {
         first: &[u8],
         second: Option<&[u8]>,
         variables: &Variables,
-    ) -> hmac::Tag {
+    ) -> mac::Tag {
         let mut context = self.context.clone();
         context.update(first);
         if let Some(second) = second {
@@ -1235,7 +1263,7 @@ impl<K: AsRef<Key>> SigningContext<K> {
         first: &[u8],
         second: Option<&[u8]>,
         variables: &Variables,
-    ) -> (hmac::Tag, K) {
+    ) -> (mac::Tag, K) {
         self.context.update(first);
         if let Some(second) = second {
             self.context.update(second)
@@ -1252,7 +1280,7 @@ impl<K: AsRef<Key>> SigningContext<K> {
         first: &[u8],
         second: Option<&[u8]>,
         variables: &Variables,
-    ) -> hmac::Tag {
+    ) -> mac::Tag {
         // Replace current context with new context.
         let mut context = self.key().signing_context();
         mem::swap(&mut self.context, &mut context);
@@ -1279,7 +1307,7 @@ impl<K: AsRef<Key>> SigningContext<K> {
         first: &[u8],
         second: Option<&[u8]>,
         variables: &Variables,
-    ) -> hmac::Tag {
+    ) -> mac::Tag {
         // Replace current context with new context.
         let mut context = self.key().signing_context();
         mem::swap(&mut self.context, &mut context);
@@ -1443,7 +1471,7 @@ impl Variables {
     /// Applies the variables to a signing context.
     ///
     /// This applies the full variables including key information.
-    fn sign(&self, key: &Key, context: &mut hmac::Context) {
+    fn sign(&self, key: &Key, context: &mut mac::Context) {
         // Key name, in canonical wire format
         for label in key.name.iter_labels().map(Label::to_canonical) {
             context.update(label.as_wire_slice());
@@ -1473,7 +1501,7 @@ impl Variables {
     }
 
     /// Applies only the timing values to the signing context.
-    fn sign_timers(&self, context: &mut hmac::Context) {
+    fn sign_timers(&self, context: &mut mac::Context) {
         // Time Signed
         context.update(&self.time_signed.into_octets());
 
@@ -1485,71 +1513,93 @@ impl Variables {
 //------------ Algorithm -----------------------------------------------------
 
 /// The supported TSIG algorithms.
+///
+/// Besides the full-length HMAC algorithms, this includes the variants with
+/// truncated output defined in [RFC 8945]. For these, the signature is
+/// always truncated to the length given in the algorithm’s name. In
+/// addition, HMAC-MD5 is supported for interoperability with older peers
+/// even though its use is not recommended anymore.
+///
+/// [RFC 8945]: https://tools.ietf.org/html/rfc8945
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum Algorithm {
+    Md5,
     Sha1,
     Sha256,
     Sha384,
     Sha512,
+    Sha256_128,
+    Sha384_192,
+    Sha512_256,
 }
 
 impl Algorithm {
+    /// All the supported algorithms.
+    const ALL: [Algorithm; 8] = [
+        Algorithm::Md5,
+        Algorithm::Sha1,
+        Algorithm::Sha256,
+        Algorithm::Sha384,
+        Algorithm::Sha512,
+        Algorithm::Sha256_128,
+        Algorithm::Sha384_192,
+        Algorithm::Sha512_256,
+    ];
+
     /// Creates a value from its domain name representation.
     ///
     /// Returns `None` if the name doesn’t represent a known algorithm.
     pub fn from_dname<N: ToDname>(name: &N) -> Option<Self> {
-        let mut labels = name.iter_labels();
-        let first = match labels.next() {
-            Some(label) => label,
-            None => return None,
-        };
-        match labels.next() {
-            Some(label) if label.is_root() => {}
-            _ => return None,
-        }
-        match first.as_slice() {
-            b"hmac-sha1" => Some(Algorithm::Sha1),
-            b"hmac-sha256" => Some(Algorithm::Sha256),
-            b"hmac-sha384" => Some(Algorithm::Sha384),
-            b"hmac-sha512" => Some(Algorithm::Sha512),
-            _ => None,
-        }
-    }
-
-    /// Creates a value from a HMAC algorithm.
-    ///
-    /// This will panic if `alg` is not one of the recognized algorithms.
-    fn from_hmac_algorithm(alg: hmac::Algorithm) -> Self {
-        if alg == hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY {
-            Algorithm::Sha1
-        } else if alg == hmac::HMAC_SHA256 {
-            Algorithm::Sha256
-        } else if alg == hmac::HMAC_SHA384 {
-            Algorithm::Sha384
-        } else if alg == hmac::HMAC_SHA512 {
-            Algorithm::Sha512
-        } else {
-            panic!("Unknown TSIG key algorithm.")
-        }
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|alg| alg.to_dname().name_eq(name))
     }
 
     /// Returns the ring HMAC algorithm for this TSIG algorithm.
-    fn into_hmac_algorithm(self) -> hmac::Algorithm {
+    ///
+    /// Returns `None` for HMAC-MD5 which isn’t supported by ring.
+    fn ring_algorithm(self) -> Option<hmac::Algorithm> {
         match self {
-            Algorithm::Sha1 => hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY,
-            Algorithm::Sha256 => hmac::HMAC_SHA256,
-            Algorithm::Sha384 => hmac::HMAC_SHA384,
-            Algorithm::Sha512 => hmac::HMAC_SHA512,
+            Algorithm::Md5 => None,
+            Algorithm::Sha1 => Some(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY),
+            Algorithm::Sha256 | Algorithm::Sha256_128 => {
+                Some(hmac::HMAC_SHA256)
+            }
+            Algorithm::Sha384 | Algorithm::Sha384_192 => {
+                Some(hmac::HMAC_SHA384)
+            }
+            Algorithm::Sha512 | Algorithm::Sha512_256 => {
+                Some(hmac::HMAC_SHA512)
+            }
         }
     }
 
     /// Returns a octet slice with the wire-format domain name for this value.
     fn into_wire_slice(self) -> &'static [u8] {
         match self {
+            Algorithm::Md5 => b"\x08hmac-md5\x07sig-alg\x03reg\x03int\0",
             Algorithm::Sha1 => b"\x09hmac-sha1\0",
             Algorithm::Sha256 => b"\x0Bhmac-sha256\0",
             Algorithm::Sha384 => b"\x0Bhmac-sha384\0",
             Algorithm::Sha512 => b"\x0Bhmac-sha512\0",
+            Algorithm::Sha256_128 => b"\x0Fhmac-sha256-128\0",
+            Algorithm::Sha384_192 => b"\x0Fhmac-sha384-192\0",
+            Algorithm::Sha512_256 => b"\x0Fhmac-sha512-256\0",
+        }
+    }
+
+    /// Returns the name of the algorithm as a string.
+    fn as_str(self) -> &'static str {
+        match self {
+            Algorithm::Md5 => "hmac-md5.sig-alg.reg.int",
+            Algorithm::Sha1 => "hmac-sha1",
+            Algorithm::Sha256 => "hmac-sha256",
+            Algorithm::Sha384 => "hmac-sha384",
+            Algorithm::Sha512 => "hmac-sha512",
+            Algorithm::Sha256_128 => "hmac-sha256-128",
+            Algorithm::Sha384_192 => "hmac-sha384-192",
+            Algorithm::Sha512_256 => "hmac-sha512-256",
         }
     }
 
@@ -1558,14 +1608,38 @@ impl Algorithm {
         unsafe { Dname::from_octets_unchecked(self.into_wire_slice()) }
     }
 
+    /// Returns the output length of the underlying hash function.
+    ///
+    /// This is also the size of newly generated keys.
+    fn digest_len(self) -> usize {
+        match self {
+            Algorithm::Md5 => 16,
+            Algorithm::Sha1 => 20,
+            Algorithm::Sha256 | Algorithm::Sha256_128 => 32,
+            Algorithm::Sha384 | Algorithm::Sha384_192 => 48,
+            Algorithm::Sha512 | Algorithm::Sha512_256 => 64,
+        }
+    }
+
     /// Returns the native length of a signature created with this algorithm.
+    ///
+    /// For the truncated algorithms, this is the truncated length.
     pub fn native_len(self) -> usize {
-        self.into_hmac_algorithm().len()
+        match self {
+            Algorithm::Sha256_128 => 16,
+            Algorithm::Sha384_192 => 24,
+            Algorithm::Sha512_256 => 32,
+            _ => self.digest_len(),
+        }
     }
 
     /// Returns the bounds for the allowed signature size.
+    ///
+    /// The signature must not be shorter than 10 octets or half the output
+    /// length of the hash function, whichever is greater, and not be longer
+    /// than the native length.
     pub fn within_len_bounds(self, len: usize) -> bool {
-        len >= cmp::max(10, self.native_len() / 2) && len <= self.native_len()
+        len >= cmp::max(10, self.digest_len() / 2) && len <= self.native_len()
     }
 }
 
@@ -1575,13 +1649,15 @@ impl str::FromStr for Algorithm {
     type Err = AlgorithmError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "hmac-sha1" => Ok(Algorithm::Sha1),
-            "hmac-sha256" => Ok(Algorithm::Sha256),
-            "hmac-sha384" => Ok(Algorithm::Sha384),
-            "hmac-sha512" => Ok(Algorithm::Sha512),
-            _ => Err(AlgorithmError),
+        let s = s.strip_suffix('.').unwrap_or(s);
+        if s == "hmac-md5" {
+            return Ok(Algorithm::Md5);
         }
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|alg| alg.as_str() == s)
+            .ok_or(AlgorithmError)
     }
 }
 
@@ -1589,16 +1665,7 @@ impl str::FromStr for Algorithm {
 
 impl fmt::Display for Algorithm {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "{}",
-            match *self {
-                Algorithm::Sha1 => "hmac-sha1",
-                Algorithm::Sha256 => "hmac-sha256",
-                Algorithm::Sha384 => "hmac-sha384",
-                Algorithm::Sha512 => "hmac-sha512",
-            }
-        )
+        f.write_str(self.as_str())
     }
 }
 
@@ -1892,3 +1959,140 @@ impl fmt::Display for ValidationError {
 
 #[cfg(feature = "std")]
 impl std::error::Error for ValidationError {}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+#[cfg(feature = "std")]
+mod test {
+    use super::*;
+    use core::str::FromStr;
+    use std::sync::Arc;
+    use std::vec::Vec;
+
+    fn now() -> Time48 {
+        Time48::from_u64(1_000_000)
+    }
+
+    fn key(
+        algorithm: Algorithm,
+        min_mac_len: Option<usize>,
+        signing_len: Option<usize>,
+    ) -> Key {
+        Key::new(
+            algorithm,
+            b"secret",
+            Dname::from_str("test.key.").unwrap(),
+            min_mac_len,
+            signing_len,
+        )
+        .unwrap()
+    }
+
+    /// Signs a request with `client` and checks it with `server`.
+    ///
+    /// Returns the MAC length and the result of the server check.
+    fn exchange(
+        client: Key,
+        server: Key,
+    ) -> (usize, Result<(), ServerError<Arc<Key>>>) {
+        let mut request = MessageBuilder::new_vec();
+        request.header_mut().set_id(7);
+        let mut request = request.additional();
+        ClientTransaction::request(client, &mut request, now()).unwrap();
+        let mut request = request.into_message();
+        let mac_len = MessageTsig::from_message(&request)
+            .unwrap()
+            .data()
+            .mac()
+            .len();
+        let server = Arc::new(server);
+        let res = ServerTransaction::request(&server, &mut request, now())
+            .map(|tran| assert!(tran.is_some()));
+        (mac_len, res)
+    }
+
+    #[test]
+    fn algorithm_names() {
+        for alg in Algorithm::ALL {
+            assert_eq!(Algorithm::from_dname(&alg.to_dname()), Some(alg));
+            assert_eq!(
+                Algorithm::from_str(&format!("{}", alg)).ok(),
+                Some(alg)
+            );
+        }
+        assert_eq!(
+            Algorithm::from_dname(
+                &Dname::<Vec<u8>>::from_str("HMAC-MD5.SIG-ALG.REG.INT.")
+                    .unwrap()
+            ),
+            Some(Algorithm::Md5)
+        );
+        assert_eq!(
+            Algorithm::from_str("hmac-md5").ok(),
+            Some(Algorithm::Md5)
+        );
+        assert_eq!(
+            Algorithm::from_str("hmac-sha256-128.").ok(),
+            Some(Algorithm::Sha256_128)
+        );
+        assert!(Algorithm::from_dname(
+            &Dname::<Vec<u8>>::from_str("hmac-sha256.example.").unwrap()
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn len_bounds() {
+        assert!(Algorithm::Md5.within_len_bounds(10));
+        assert!(!Algorithm::Md5.within_len_bounds(9));
+        assert!(Algorithm::Sha256_128.within_len_bounds(16));
+        assert!(!Algorithm::Sha256_128.within_len_bounds(17));
+        assert!(!Algorithm::Sha512_256.within_len_bounds(31));
+        assert!(Algorithm::Sha512_256.within_len_bounds(32));
+        assert!(Key::new(
+            Algorithm::Sha384_192,
+            b"secret",
+            Dname::root(),
+            Some(20),
+            None
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn sign_and_verify() {
+        for alg in Algorithm::ALL {
+            let (mac_len, res) =
+                exchange(key(alg, None, None), key(alg, None, None));
+            assert_eq!(mac_len, alg.native_len());
+            res.unwrap();
+        }
+    }
+
+    #[test]
+    fn truncation_policy() {
+        // A truncated signature is accepted if the server allows it ...
+        let (mac_len, res) = exchange(
+            key(Algorithm::Sha256, None, Some(16)),
+            key(Algorithm::Sha256, Some(16), None),
+        );
+        assert_eq!(mac_len, 16);
+        res.unwrap();
+
+        // ... but rejected if the server requires longer signatures.
+        let (_, res) = exchange(
+            key(Algorithm::Sha256, None, Some(16)),
+            key(Algorithm::Sha256, Some(20), None),
+        );
+        assert!(res.is_err());
+
+        // The same goes for MD5.
+        let (mac_len, res) = exchange(
+            key(Algorithm::Md5, None, Some(10)),
+            key(Algorithm::Md5, None, None),
+        );
+        assert_eq!(mac_len, 10);
+        assert!(res.is_err());
+    }
+}
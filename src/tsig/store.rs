@@ -0,0 +1,588 @@
+//! A key store that can be modified at runtime.
+#![cfg(feature = "std")]
+
+use super::{Algorithm, Key, KeyStore};
+use crate::base::iana::{Opcode, Rtype};
+use crate::base::message::Message;
+use crate::base::name::{Dname, ToDname};
+use crate::base::octets::{OctetsRef, OctetsVec};
+use crate::rdata::rfc2845::Time48;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::vec::Vec;
+
+//------------ DynamicKeyStore -----------------------------------------------
+
+/// A thread-safe key store that allows adding and removing keys.
+///
+/// Keys are kept as arcs so they can be shared with transactions that are
+/// still in progress while the store is being modified. Each key can
+/// optionally have an expiry time after which it will not be returned by
+/// the store anymore. Expired keys are not removed automatically. Instead,
+/// you can call [`purge_expired`] regularly to get rid of them.
+///
+/// In addition, each key has a set of [`KeyPermissions`] that determine
+/// which zones and operations a message signed with the key may be used
+/// for. Once a server has validated a signed request, it can use
+/// [`is_permitted`] or [`is_message_permitted`] to decide whether to
+/// actually process it.
+///
+/// Since the key store is protected by a lock, a shared reference is enough
+/// for all operations. This means you can share the store between a server
+/// and, for instance, the [TKEY] negotiation handling that adds keys to it.
+///
+/// [`purge_expired`]: #method.purge_expired
+/// [`is_permitted`]: #method.is_permitted
+/// [`is_message_permitted`]: #method.is_message_permitted
+/// [`KeyPermissions`]: struct.KeyPermissions.html
+/// [TKEY]: tkey/index.html
+#[derive(Debug, Default)]
+pub struct DynamicKeyStore {
+    /// The keys and their expiry times.
+    keys: RwLock<HashMap<(Dname<OctetsVec>, Algorithm), StoredKey>>,
+}
+
+/// A key stored in the dynamic key store.
+#[derive(Clone, Debug)]
+struct StoredKey {
+    /// The key itself.
+    key: Arc<Key>,
+
+    /// The time after which the key is not valid anymore.
+    expires: Option<Time48>,
+
+    /// What the key may be used for.
+    permissions: KeyPermissions,
+}
+
+impl StoredKey {
+    /// Returns whether the key is still valid at the given time.
+    fn is_valid_at(&self, now: Time48) -> bool {
+        match self.expires {
+            Some(expires) => now <= expires,
+            None => true,
+        }
+    }
+}
+
+impl DynamicKeyStore {
+    /// Creates a new, empty key store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a key to the store.
+    ///
+    /// If `expires` is not `None`, the key will only be available until the
+    /// given time. If there already is a key with the same name and
+    /// algorithm, it is replaced and returned.
+    ///
+    /// The key will not be restricted in what it can be used for. Use
+    /// [`insert_with_permissions`] to add a key with restrictions.
+    ///
+    /// [`insert_with_permissions`]: #method.insert_with_permissions
+    pub fn insert(
+        &self,
+        key: impl Into<Arc<Key>>,
+        expires: Option<Time48>,
+    ) -> Option<Arc<Key>> {
+        self.insert_with_permissions(key, expires, KeyPermissions::new())
+    }
+
+    /// Adds a key with the given permissions to the store.
+    ///
+    /// This is the same as [`insert`] but uses the given permissions for the
+    /// key.
+    ///
+    /// [`insert`]: #method.insert
+    pub fn insert_with_permissions(
+        &self,
+        key: impl Into<Arc<Key>>,
+        expires: Option<Time48>,
+        permissions: KeyPermissions,
+    ) -> Option<Arc<Key>> {
+        let key = key.into();
+        let index = (key.name().clone(), key.algorithm());
+        self.keys
+            .write()
+            .expect("poisoned lock")
+            .insert(
+                index,
+                StoredKey {
+                    key,
+                    expires,
+                    permissions,
+                },
+            )
+            .map(|stored| stored.key)
+    }
+
+    /// Removes a key from the store.
+    ///
+    /// Returns the key if it was present.
+    pub fn remove<N: ToDname>(
+        &self,
+        name: &N,
+        algorithm: Algorithm,
+    ) -> Option<Arc<Key>> {
+        let name = name.to_dname::<OctetsVec>().unwrap();
+        self.keys
+            .write()
+            .expect("poisoned lock")
+            .remove(&(name, algorithm))
+            .map(|stored| stored.key)
+    }
+
+    /// Tries to find a key that is valid at the given time.
+    ///
+    /// This is the same as [`KeyStore::get_key`] but allows to provide the
+    /// time to compare the key expiry against.
+    ///
+    /// [`KeyStore::get_key`]: trait.KeyStore.html#tymethod.get_key
+    pub fn get_key_at<N: ToDname>(
+        &self,
+        name: &N,
+        algorithm: Algorithm,
+        now: Time48,
+    ) -> Option<Arc<Key>> {
+        let name = name.to_dname::<OctetsVec>().unwrap();
+        self.keys
+            .read()
+            .expect("poisoned lock")
+            .get(&(name, algorithm))
+            .filter(|stored| stored.is_valid_at(now))
+            .map(|stored| stored.key.clone())
+    }
+
+    /// Returns the expiry time of a key.
+    ///
+    /// Returns `None` if the key isn’t present at all and `Some(None)` if
+    /// it is present but doesn’t expire.
+    pub fn expires<N: ToDname>(
+        &self,
+        name: &N,
+        algorithm: Algorithm,
+    ) -> Option<Option<Time48>> {
+        let name = name.to_dname::<OctetsVec>().unwrap();
+        self.keys
+            .read()
+            .expect("poisoned lock")
+            .get(&(name, algorithm))
+            .map(|stored| stored.expires)
+    }
+
+    /// Returns a copy of the permissions of a key.
+    ///
+    /// Returns `None` if the key isn’t present.
+    pub fn permissions<N: ToDname>(
+        &self,
+        name: &N,
+        algorithm: Algorithm,
+    ) -> Option<KeyPermissions> {
+        let name = name.to_dname::<OctetsVec>().unwrap();
+        self.keys
+            .read()
+            .expect("poisoned lock")
+            .get(&(name, algorithm))
+            .map(|stored| stored.permissions.clone())
+    }
+
+    /// Replaces the permissions of a key.
+    ///
+    /// Returns whether the key was present.
+    pub fn set_permissions<N: ToDname>(
+        &self,
+        name: &N,
+        algorithm: Algorithm,
+        permissions: KeyPermissions,
+    ) -> bool {
+        let name = name.to_dname::<OctetsVec>().unwrap();
+        match self
+            .keys
+            .write()
+            .expect("poisoned lock")
+            .get_mut(&(name, algorithm))
+        {
+            Some(stored) => {
+                stored.permissions = permissions;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns whether a key may be used for an operation on a zone.
+    ///
+    /// Returns `false` if the key isn’t present or has expired at the time
+    /// given via `now`.
+    pub fn is_permitted<N: ToDname, Z: ToDname>(
+        &self,
+        name: &N,
+        algorithm: Algorithm,
+        zone: &Z,
+        operation: Operation,
+        now: Time48,
+    ) -> bool {
+        let name = name.to_dname::<OctetsVec>().unwrap();
+        self.keys
+            .read()
+            .expect("poisoned lock")
+            .get(&(name, algorithm))
+            .filter(|stored| stored.is_valid_at(now))
+            .map(|stored| stored.permissions.permits(zone, operation))
+            .unwrap_or(false)
+    }
+
+    /// Returns whether a key may be used for the given request.
+    ///
+    /// The operation is determined from the message via
+    /// [`Operation::from_message`] and the zone is taken from the first
+    /// question. For an UPDATE request, this is the zone section and thus
+    /// the zone to be updated.
+    ///
+    /// Returns `false` if the message doesn’t have a question, if its
+    /// operation isn’t known, or if the key isn’t present or has expired.
+    /// Note that this method does not validate the signature of the
+    /// message. This needs to happen first, for instance via a
+    /// [`ServerTransaction`].
+    ///
+    /// [`Operation::from_message`]: enum.Operation.html#method.from_message
+    /// [`ServerTransaction`]: struct.ServerTransaction.html
+    pub fn is_message_permitted<Octets>(
+        &self,
+        key: &Key,
+        message: &Message<Octets>,
+        now: Time48,
+    ) -> bool
+    where
+        Octets: AsRef<[u8]>,
+        for<'o> &'o Octets: OctetsRef,
+    {
+        let operation = match Operation::from_message(message) {
+            Some(operation) => operation,
+            None => return false,
+        };
+        let question = match message.first_question() {
+            Some(question) => question,
+            None => return false,
+        };
+        self.is_permitted(
+            key.name(),
+            key.algorithm(),
+            question.qname(),
+            operation,
+            now,
+        )
+    }
+
+    /// Removes all keys that have expired at the given time.
+    ///
+    /// Returns the number of keys removed.
+    pub fn purge_expired(&self, now: Time48) -> usize {
+        let mut keys = self.keys.write().expect("poisoned lock");
+        let len = keys.len();
+        keys.retain(|_, stored| stored.is_valid_at(now));
+        len - keys.len()
+    }
+
+    /// Returns the number of keys in the store.
+    ///
+    /// This includes keys that have expired but not been purged yet.
+    pub fn len(&self) -> usize {
+        self.keys.read().expect("poisoned lock").len()
+    }
+
+    /// Returns whether the store is empty.
+    pub fn is_empty(&self) -> bool {
+        self.keys.read().expect("poisoned lock").is_empty()
+    }
+}
+
+//--- KeyStore
+
+impl KeyStore for DynamicKeyStore {
+    type Key = Arc<Key>;
+
+    fn get_key<N: ToDname>(
+        &self,
+        name: &N,
+        algorithm: Algorithm,
+    ) -> Option<Self::Key> {
+        self.get_key_at(name, algorithm, Time48::now())
+    }
+}
+
+//------------ KeyPermissions ------------------------------------------------
+
+/// The zones and operations a key may be used for.
+///
+/// Both the zones and the operations can either be unrestricted or limited
+/// to a list of values. A newly created value is unrestricted in both
+/// regards. Zones are compared exactly, i.e., permission for a zone does
+/// not extend to any of its subzones.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct KeyPermissions {
+    /// The zones the key may be used for or `None` for all zones.
+    zones: Option<Vec<Dname<OctetsVec>>>,
+
+    /// The operations the key may be used for or `None` for all of them.
+    operations: Option<Vec<Operation>>,
+}
+
+impl KeyPermissions {
+    /// Creates a new value permitting all zones and operations.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new value permitting nothing.
+    ///
+    /// Zones and operations can then be added via [`add_zone`] and
+    /// [`add_operation`].
+    ///
+    /// [`add_zone`]: #method.add_zone
+    /// [`add_operation`]: #method.add_operation
+    pub fn none() -> Self {
+        KeyPermissions {
+            zones: Some(Vec::new()),
+            operations: Some(Vec::new()),
+        }
+    }
+
+    /// Returns the permitted zones or `None` if all zones are permitted.
+    pub fn zones(&self) -> Option<&[Dname<OctetsVec>]> {
+        self.zones.as_deref()
+    }
+
+    /// Returns the permitted operations or `None` if all are permitted.
+    pub fn operations(&self) -> Option<&[Operation]> {
+        self.operations.as_deref()
+    }
+
+    /// Sets the permitted zones.
+    ///
+    /// If `zones` is `None`, all zones are permitted.
+    pub fn set_zones(&mut self, zones: Option<Vec<Dname<OctetsVec>>>) {
+        self.zones = zones
+    }
+
+    /// Sets the permitted operations.
+    ///
+    /// If `operations` is `None`, all operations are permitted.
+    pub fn set_operations(&mut self, operations: Option<Vec<Operation>>) {
+        self.operations = operations
+    }
+
+    /// Adds a zone to the permitted zones.
+    ///
+    /// If all zones were permitted before, only the given zone will be
+    /// permitted afterwards.
+    pub fn add_zone(&mut self, zone: Dname<OctetsVec>) {
+        let zones = self.zones.get_or_insert_with(Vec::new);
+        if !zones.contains(&zone) {
+            zones.push(zone)
+        }
+    }
+
+    /// Adds an operation to the permitted operations.
+    ///
+    /// If all operations were permitted before, only the given operation
+    /// will be permitted afterwards.
+    pub fn add_operation(&mut self, operation: Operation) {
+        let operations = self.operations.get_or_insert_with(Vec::new);
+        if !operations.contains(&operation) {
+            operations.push(operation)
+        }
+    }
+
+    /// Returns whether the zone is permitted.
+    pub fn permits_zone<N: ToDname + ?Sized>(&self, zone: &N) -> bool {
+        match self.zones {
+            Some(ref zones) => zones.iter().any(|item| item.name_eq(zone)),
+            None => true,
+        }
+    }
+
+    /// Returns whether the operation is permitted.
+    pub fn permits_operation(&self, operation: Operation) -> bool {
+        match self.operations {
+            Some(ref operations) => operations.contains(&operation),
+            None => true,
+        }
+    }
+
+    /// Returns whether the operation is permitted for the zone.
+    pub fn permits<N: ToDname + ?Sized>(
+        &self,
+        zone: &N,
+        operation: Operation,
+    ) -> bool {
+        self.permits_operation(operation) && self.permits_zone(zone)
+    }
+}
+
+//------------ Operation -----------------------------------------------------
+
+/// The kind of operation a signed request asks for.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Operation {
+    /// A regular query.
+    Query,
+
+    /// A zone transfer via AXFR or IXFR.
+    Transfer,
+
+    /// A NOTIFY message.
+    Notify,
+
+    /// A dynamic update.
+    Update,
+}
+
+impl Operation {
+    /// Determines the operation requested by a message.
+    ///
+    /// The operation is derived from the opcode and, for queries, the type
+    /// of the first question. Returns `None` if the opcode is not one of the
+    /// known operations or if a query doesn’t have a question.
+    pub fn from_message<Octets>(message: &Message<Octets>) -> Option<Self>
+    where
+        Octets: AsRef<[u8]>,
+        for<'o> &'o Octets: OctetsRef,
+    {
+        match message.header().opcode() {
+            Opcode::Query => match message.first_question()?.qtype() {
+                Rtype::Axfr | Rtype::Ixfr => Some(Operation::Transfer),
+                _ => Some(Operation::Query),
+            },
+            Opcode::Notify => Some(Operation::Notify),
+            Opcode::Update => Some(Operation::Update),
+            _ => None,
+        }
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::message_builder::MessageBuilder;
+    use core::str::FromStr;
+
+    fn key(name: &str) -> Key {
+        Key::new(
+            Algorithm::Sha256,
+            b"secret",
+            Dname::from_str(name).unwrap(),
+            None,
+            None,
+        )
+        .unwrap()
+    }
+
+    fn dname(name: &str) -> Dname<OctetsVec> {
+        Dname::from_str(name).unwrap()
+    }
+
+    #[test]
+    fn insert_remove_expire() {
+        let store = DynamicKeyStore::new();
+        let name = dname("one.key.");
+        assert!(store.insert(key("one.key."), None).is_none());
+        assert!(store
+            .insert(key("two.key."), Some(Time48::from_u64(1000)))
+            .is_none());
+        assert_eq!(store.len(), 2);
+
+        let two = dname("TWO.key.");
+        assert!(store
+            .get_key_at(&two, Algorithm::Sha256, Time48::from_u64(1000))
+            .is_some());
+        assert!(store
+            .get_key_at(&two, Algorithm::Sha256, Time48::from_u64(1001))
+            .is_none());
+        assert!(store
+            .get_key_at(&two, Algorithm::Sha1, Time48::from_u64(0))
+            .is_none());
+
+        assert_eq!(store.purge_expired(Time48::from_u64(1001)), 1);
+        assert_eq!(store.len(), 1);
+        assert!(store.remove(&name, Algorithm::Sha256).is_some());
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn permissions() {
+        let store = DynamicKeyStore::new();
+        let mut perms = KeyPermissions::none();
+        perms.add_zone(dname("example.com."));
+        perms.add_operation(Operation::Update);
+        let update_key = key("update.key.");
+        store.insert_with_permissions(
+            update_key,
+            Some(Time48::from_u64(1000)),
+            perms,
+        );
+        store.insert(key("any.key."), None);
+
+        let now = Time48::from_u64(10);
+        let example = dname("EXAMPLE.com.");
+        let update = dname("update.key.");
+        assert!(store.is_permitted(
+            &update,
+            Algorithm::Sha256,
+            &example,
+            Operation::Update,
+            now
+        ));
+        assert!(!store.is_permitted(
+            &update,
+            Algorithm::Sha256,
+            &example,
+            Operation::Transfer,
+            now
+        ));
+        assert!(!store.is_permitted(
+            &update,
+            Algorithm::Sha256,
+            &dname("sub.example.com."),
+            Operation::Update,
+            now
+        ));
+        assert!(!store.is_permitted(
+            &update,
+            Algorithm::Sha256,
+            &example,
+            Operation::Update,
+            Time48::from_u64(1001)
+        ));
+        assert!(store.is_permitted(
+            &dname("any.key."),
+            Algorithm::Sha256,
+            &example,
+            Operation::Transfer,
+            now
+        ));
+        assert!(!store.is_permitted(
+            &dname("missing.key."),
+            Algorithm::Sha256,
+            &example,
+            Operation::Query,
+            now
+        ));
+
+        let mut perms =
+            store.permissions(&update, Algorithm::Sha256).unwrap();
+        perms.add_operation(Operation::Transfer);
+        assert!(store.set_permissions(&update, Algorithm::Sha256, perms));
+
+        let mut msg = MessageBuilder::new_vec().question();
+        msg.push((&example, Rtype::Axfr)).unwrap();
+        let msg = msg.into_message();
+        assert_eq!(Operation::from_message(&msg), Some(Operation::Transfer));
+        let update_key =
+            store.get_key_at(&update, Algorithm::Sha256, now).unwrap();
+        assert!(store.is_message_permitted(&update_key, &msg, now));
+    }
+}
@@ -0,0 +1,1853 @@
+//! Negotiating TSIG keys with TKEY.
+//!
+//! This module implements the key establishment and deletion protocol
+//! defined in [RFC 2930]. A client sends a query for the record type TKEY
+//! with a [TKEY] record in the additional section describing the desired
+//! key. The server answers with a TKEY record of its own in the answer
+//! section. Depending on the mode, the key data of both records is then used
+//! to derive a shared secret that becomes a TSIG [`Key`].
+//!
+//! Currently, three modes are supported:
+//!
+//! * Diffie-Hellman exchanged keying (mode 2). The client adds a [KEY]
+//!   record with its Diffie-Hellman public value to the request, the server
+//!   answers with its own. The shared secret is derived from the
+//!   Diffie-Hellman value and random data provided by both parties. On the
+//!   client side, this is implemented by [`ClientDhExchange`].
+//! * GSS-API negotiation (mode 3). Tokens are exchanged in the key data
+//!   field until the GSS-API security context is established. The actual
+//!   GSS-API mechanism is not part of this crate. Instead, it is provided
+//!   through the [`GssInitiator`] and [`GssAcceptor`] traits. The session
+//!   key of the established context is used as the secret of an HMAC key
+//!   for the algorithm given in the TKEY record. The client side is
+//!   implemented by [`ClientGssExchange`].
+//! * Key deletion (mode 5). A client asks the server to forget a key. The
+//!   request has to be signed with the key to be deleted. Requests are
+//!   created via [`delete_request`].
+//!
+//! The server side for all modes is provided by [`TkeyServer`]. It adds
+//! negotiated keys to a [`DynamicKeyStore`] which can then be used with
+//! [`ServerTransaction`] and [`ServerSequence`] to verify subsequent
+//! requests. The server only deals with the TKEY part of a request. TSIG
+//! processing of the request and answer still needs to be done separately
+//! via the usual types of the parent module.
+//!
+//! [RFC 2930]: https://tools.ietf.org/html/rfc2930
+//! [TKEY]: ../../rdata/rfc2930/struct.Tkey.html
+//! [KEY]: ../../rdata/rfc2535/struct.Key.html
+//! [`Key`]: ../struct.Key.html
+//! [`DynamicKeyStore`]: ../struct.DynamicKeyStore.html
+//! [`ServerTransaction`]: ../struct.ServerTransaction.html
+//! [`ServerSequence`]: ../struct.ServerSequence.html
+//! [`ClientDhExchange`]: struct.ClientDhExchange.html
+//! [`ClientGssExchange`]: struct.ClientGssExchange.html
+//! [`GssInitiator`]: trait.GssInitiator.html
+//! [`GssAcceptor`]: trait.GssAcceptor.html
+//! [`TkeyServer`]: struct.TkeyServer.html
+//! [`delete_request`]: fn.delete_request.html
+#![cfg(feature = "tkey")]
+#![cfg_attr(docsrs, doc(cfg(feature = "tkey")))]
+
+use super::{Algorithm, DynamicKeyStore, Key, NewKeyError};
+use crate::base::iana::{Class, Opcode, Rcode, Rtype, SecAlg};
+use crate::base::iana::{TkeyMode, TsigRcode};
+use crate::base::message::Message;
+use crate::base::message_builder::{AdditionalBuilder, MessageBuilder};
+use crate::base::name::{Dname, ParsedDname, ToDname};
+use crate::base::octets::{
+    OctetsBuilder, OctetsRef, OctetsVec, ParseError, ShortBuf,
+};
+use crate::base::serial::Serial;
+use crate::rdata::rfc2845::Time48;
+use crate::rdata::{Key as KeyRecord, Tkey};
+use core::fmt;
+use md5::{Digest, Md5};
+use num_bigint::BigUint;
+use ring::rand::{SecureRandom, SystemRandom};
+use std::boxed::Box;
+use std::sync::Arc;
+use std::vec::Vec;
+
+//------------ Constants -----------------------------------------------------
+
+/// The length of the random data added to the key data by both parties.
+const NONCE_LEN: usize = 16;
+
+/// The flags used for Diffie-Hellman KEY records.
+///
+/// This marks the key as a key for an entity and usable for both
+/// authentication and confidentiality.
+const DH_KEY_FLAGS: u16 = 0x0200;
+
+/// The default maximum lifetime of a negotiated key in seconds.
+const DEFAULT_MAX_LIFETIME: u32 = 86400;
+
+/// The maximum length of a Diffie-Hellman prime in octets.
+///
+/// This limits the cost of the modular exponentiation for groups chosen by
+/// the other party to that of a 4096 bit group.
+const MAX_PRIME_LEN: usize = 512;
+
+/// The 768 bit prime of well-known group 1 defined in RFC 2539.
+const OAKLEY_768_PRIME: &[u8] = b"\
+    \xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xC9\x0F\xDA\xA2\x21\x68\xC2\x34\
+    \xC4\xC6\x62\x8B\x80\xDC\x1C\xD1\x29\x02\x4E\x08\x8A\x67\xCC\x74\
+    \x02\x0B\xBE\xA6\x3B\x13\x9B\x22\x51\x4A\x08\x79\x8E\x34\x04\xDD\
+    \xEF\x95\x19\xB3\xCD\x3A\x43\x1B\x30\x2B\x0A\x6D\xF2\x5F\x14\x37\
+    \x4F\xE1\x35\x6D\x6D\x51\xC2\x45\xE4\x85\xB5\x76\x62\x5E\x7E\xC6\
+    \xF4\x4C\x42\xE9\xA6\x3A\x36\x20\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF";
+
+/// The 1024 bit prime of well-known group 2 defined in RFC 2539.
+const OAKLEY_1024_PRIME: &[u8] = b"\
+    \xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xC9\x0F\xDA\xA2\x21\x68\xC2\x34\
+    \xC4\xC6\x62\x8B\x80\xDC\x1C\xD1\x29\x02\x4E\x08\x8A\x67\xCC\x74\
+    \x02\x0B\xBE\xA6\x3B\x13\x9B\x22\x51\x4A\x08\x79\x8E\x34\x04\xDD\
+    \xEF\x95\x19\xB3\xCD\x3A\x43\x1B\x30\x2B\x0A\x6D\xF2\x5F\x14\x37\
+    \x4F\xE1\x35\x6D\x6D\x51\xC2\x45\xE4\x85\xB5\x76\x62\x5E\x7E\xC6\
+    \xF4\x4C\x42\xE9\xA6\x37\xED\x6B\x0B\xFF\x5C\xB6\xF4\x06\xB7\xED\
+    \xEE\x38\x6B\xFB\x5A\x89\x9F\xA5\xAE\x9F\x24\x11\x7C\x4B\x1F\xE6\
+    \x49\x28\x66\x51\xEC\xE6\x53\x81\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF";
+
+//------------ DhGroup -------------------------------------------------------
+
+/// The parameters of a Diffie-Hellman group.
+///
+/// A group consists of a prime and a generator. [RFC 2539] defines two
+/// well-known groups that are referred to by index in KEY records. These
+/// are available via [`well_known`]. Other groups can be created from
+/// their prime and generator via [`new`].
+///
+/// [RFC 2539]: https://tools.ietf.org/html/rfc2539
+/// [`well_known`]: #method.well_known
+/// [`new`]: #method.new
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DhGroup {
+    /// The prime.
+    prime: BigUint,
+
+    /// The generator.
+    generator: BigUint,
+
+    /// The index if this is a well-known group.
+    well_known: Option<u16>,
+}
+
+impl DhGroup {
+    /// Creates a group from its prime and generator.
+    ///
+    /// Both values are given as big-endian unsigned integers. The prime
+    /// needs to be odd and, without leading zeros, at least three octets
+    /// long since shorter values are used to refer to the well-known
+    /// groups in KEY records. It can be at most 4096 bits long. The
+    /// generator needs to be greater than one and less than the prime
+    /// minus one. Returns [`TkeyError::BadKey`] if any of this is not the
+    /// case.
+    pub fn new(prime: &[u8], generator: &[u8]) -> Result<Self, TkeyError> {
+        let prime = BigUint::from_bytes_be(prime);
+        let generator = BigUint::from_bytes_be(generator);
+        if prime.bits() <= 16
+            || prime.bits() > (MAX_PRIME_LEN * 8) as u64
+            || !prime.bit(0)
+        {
+            return Err(TkeyError::BadKey);
+        }
+        if generator <= BigUint::from(1u8)
+            || generator >= &prime - BigUint::from(1u8)
+        {
+            return Err(TkeyError::BadKey);
+        }
+        Ok(DhGroup {
+            prime,
+            generator,
+            well_known: None,
+        })
+    }
+
+    /// Returns a well-known group.
+    ///
+    /// Index 1 is the 768 bit Oakley group, index 2 the 1024 bit Oakley
+    /// group. Returns `None` for any other index.
+    pub fn well_known(index: u16) -> Option<Self> {
+        let prime = match index {
+            1 => OAKLEY_768_PRIME,
+            2 => OAKLEY_1024_PRIME,
+            _ => return None,
+        };
+        Some(DhGroup {
+            prime: BigUint::from_bytes_be(prime),
+            generator: BigUint::from(2u8),
+            well_known: Some(index),
+        })
+    }
+
+    /// Returns the 1024 bit Oakley group.
+    ///
+    /// This is the largest group with a well-known index.
+    pub fn oakley_1024() -> Self {
+        Self::well_known(2).unwrap()
+    }
+
+    /// Returns the length of the prime in octets.
+    fn prime_len(&self) -> usize {
+        ((self.prime.bits() + 7) / 8) as usize
+    }
+}
+
+//------------ DhPublicKey ---------------------------------------------------
+
+/// A Diffie-Hellman public value and the group it belongs to.
+///
+/// Values of this type are normally created from the public key field of a
+/// KEY record via [`from_key_data`]. The data is expected in the format
+/// defined in [RFC 2539].
+///
+/// [`from_key_data`]: #method.from_key_data
+/// [RFC 2539]: https://tools.ietf.org/html/rfc2539
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DhPublicKey {
+    /// The group of the key.
+    group: DhGroup,
+
+    /// The public value.
+    value: BigUint,
+}
+
+impl DhPublicKey {
+    /// Parses the public key from the RFC 2539 format.
+    ///
+    /// Primes longer than 4096 bits are rejected.
+    pub fn from_key_data(data: &[u8]) -> Result<Self, TkeyError> {
+        fn take<'a>(data: &mut &'a [u8]) -> Result<&'a [u8], TkeyError> {
+            if data.len() < 2 {
+                return Err(TkeyError::BadKey);
+            }
+            let len = usize::from(u16::from_be_bytes([data[0], data[1]]));
+            if data.len() < len + 2 {
+                return Err(TkeyError::BadKey);
+            }
+            let res = &data[2..len + 2];
+            *data = &data[len + 2..];
+            Ok(res)
+        }
+
+        let mut data = data;
+        let prime = take(&mut data)?;
+        let generator = take(&mut data)?;
+        let value = take(&mut data)?;
+        if !data.is_empty() {
+            return Err(TkeyError::BadKey);
+        }
+        let group = match prime.len() {
+            1 | 2 => {
+                if !generator.is_empty() {
+                    return Err(TkeyError::BadKey);
+                }
+                let index = if prime.len() == 1 {
+                    u16::from(prime[0])
+                } else {
+                    u16::from_be_bytes([prime[0], prime[1]])
+                };
+                DhGroup::well_known(index).ok_or(TkeyError::BadKey)?
+            }
+            len if len > MAX_PRIME_LEN => return Err(TkeyError::BadKey),
+            _ => DhGroup::new(prime, generator)?,
+        };
+        let value = BigUint::from_bytes_be(value);
+        let one = BigUint::from(1u8);
+        if value <= one || value >= &group.prime - &one {
+            return Err(TkeyError::BadKey);
+        }
+        Ok(DhPublicKey { group, value })
+    }
+
+    /// Parses the public key from a KEY record’s data.
+    ///
+    /// Checks that the record is for the Diffie-Hellman algorithm.
+    pub fn from_key<Octets: AsRef<[u8]>>(
+        key: &KeyRecord<Octets>,
+    ) -> Result<Self, TkeyError> {
+        if key.algorithm() != SecAlg::Dh {
+            return Err(TkeyError::BadKey);
+        }
+        Self::from_key_data(key.public_key().as_ref())
+    }
+
+    /// Returns the group of the key.
+    pub fn group(&self) -> &DhGroup {
+        &self.group
+    }
+
+    /// Returns the public key in RFC 2539 format.
+    pub fn to_key_data(&self) -> Vec<u8> {
+        let mut res = Vec::new();
+        match self.group.well_known {
+            Some(index) => {
+                res.extend_from_slice(&1u16.to_be_bytes());
+                res.push(index as u8);
+                res.extend_from_slice(&0u16.to_be_bytes());
+            }
+            None => {
+                let prime = self.group.prime.to_bytes_be();
+                let generator = self.group.generator.to_bytes_be();
+                res.extend_from_slice(&(prime.len() as u16).to_be_bytes());
+                res.extend_from_slice(&prime);
+                res.extend_from_slice(
+                    &(generator.len() as u16).to_be_bytes(),
+                );
+                res.extend_from_slice(&generator);
+            }
+        }
+        let value = self.value.to_bytes_be();
+        res.extend_from_slice(&(value.len() as u16).to_be_bytes());
+        res.extend_from_slice(&value);
+        res
+    }
+
+    /// Returns the record data of a KEY record for this public key.
+    pub fn to_key_record(&self) -> KeyRecord<Vec<u8>> {
+        KeyRecord::new(
+            DH_KEY_FLAGS,
+            KeyRecord::<Vec<u8>>::DNSSEC_PROTOCOL,
+            SecAlg::Dh,
+            self.to_key_data(),
+        )
+    }
+}
+
+//------------ DhKeyPair -----------------------------------------------------
+
+/// A Diffie-Hellman key pair.
+///
+/// The key pair is generated from a group and a random number generator
+/// via [`generate`]. Its public part can be given to the other party via
+/// [`public_key`] and the shared value can be determined from the other
+/// party’s public key via [`compute_shared`].
+///
+/// [`generate`]: #method.generate
+/// [`public_key`]: #method.public_key
+/// [`compute_shared`]: #method.compute_shared
+#[derive(Clone)]
+pub struct DhKeyPair {
+    /// The private exponent.
+    private: BigUint,
+
+    /// The public key.
+    public: DhPublicKey,
+}
+
+impl DhKeyPair {
+    /// Generates a new key pair in the given group.
+    pub fn generate(
+        group: DhGroup,
+        rng: &dyn SecureRandom,
+    ) -> Result<Self, TkeyError> {
+        let mut bytes = vec![0u8; group.prime_len()];
+        rng.fill(&mut bytes)?;
+
+        // Pick the private exponent from [2, p - 2].
+        let two = BigUint::from(2u8);
+        let private = BigUint::from_bytes_be(&bytes)
+            % (&group.prime - BigUint::from(3u8))
+            + &two;
+        let value = group.generator.modpow(&private, &group.prime);
+        Ok(DhKeyPair {
+            private,
+            public: DhPublicKey { group, value },
+        })
+    }
+
+    /// Returns the public key of the key pair.
+    pub fn public_key(&self) -> &DhPublicKey {
+        &self.public
+    }
+
+    /// Computes the shared value with the other party’s public key.
+    ///
+    /// The value is returned as a big-endian integer without leading zeros.
+    /// Returns an error if the public key is for a different group.
+    pub fn compute_shared(
+        &self,
+        peer: &DhPublicKey,
+    ) -> Result<Vec<u8>, TkeyError> {
+        if peer.group.prime != self.public.group.prime
+            || peer.group.generator != self.public.group.generator
+        {
+            return Err(TkeyError::BadKey);
+        }
+        Ok(peer
+            .value
+            .modpow(&self.private, &self.public.group.prime)
+            .to_bytes_be())
+    }
+}
+
+impl fmt::Debug for DhKeyPair {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DhKeyPair")
+            .field("public", &self.public)
+            .finish()
+    }
+}
+
+//------------ NegotiatedKey -------------------------------------------------
+
+/// A key that has been negotiated through TKEY.
+#[derive(Debug)]
+pub struct NegotiatedKey {
+    /// The key.
+    key: Key,
+
+    /// The time when the key expires.
+    expires: Time48,
+}
+
+impl NegotiatedKey {
+    /// Returns a reference to the key.
+    pub fn key(&self) -> &Key {
+        &self.key
+    }
+
+    /// Returns the time the key expires.
+    pub fn expires(&self) -> Time48 {
+        self.expires
+    }
+
+    /// Converts the value into the key.
+    pub fn into_key(self) -> Key {
+        self.key
+    }
+
+    /// Adds the key to a dynamic key store.
+    ///
+    /// The key will expire in the store when the negotiated key expires.
+    /// Returns the key as it is kept in the store.
+    pub fn insert_into(self, store: &DynamicKeyStore) -> Arc<Key> {
+        let key = Arc::new(self.key);
+        store.insert(key.clone(), Some(self.expires));
+        key
+    }
+}
+
+//------------ ClientDhExchange ----------------------------------------------
+
+/// The client side of a Diffie-Hellman key exchange.
+///
+/// You create a value of this type via [`new`] which generates a new
+/// Diffie-Hellman key pair. You then create the request via [`request`]
+/// and send it to the server. Once the answer arrives, you pass it to
+/// [`answer`] to get the negotiated key.
+///
+/// The request is not signed. If the server requires the request to be
+/// authenticated, you can sign it using an existing key via
+/// [`ClientTransaction`].
+///
+/// [`new`]: #method.new
+/// [`request`]: #method.request
+/// [`answer`]: #method.answer
+/// [`ClientTransaction`]: ../struct.ClientTransaction.html
+#[derive(Debug)]
+pub struct ClientDhExchange {
+    /// The requested key name.
+    key_name: Dname<OctetsVec>,
+
+    /// The algorithm for the negotiated key.
+    algorithm: Algorithm,
+
+    /// The requested start of the key validity.
+    inception: Serial,
+
+    /// The requested end of the key validity.
+    expiration: Serial,
+
+    /// Our key pair.
+    key_pair: DhKeyPair,
+
+    /// Our random data for the key derivation.
+    nonce: [u8; NONCE_LEN],
+}
+
+impl ClientDhExchange {
+    /// Creates a new exchange.
+    ///
+    /// The key will be requested under the name `key_name` for use with the
+    /// given algorithm. It will be requested to be valid for `lifetime`
+    /// seconds starting at `now`. The server may shorten this period.
+    pub fn new(
+        key_name: Dname<OctetsVec>,
+        algorithm: Algorithm,
+        group: DhGroup,
+        lifetime: u32,
+        now: Time48,
+        rng: &dyn SecureRandom,
+    ) -> Result<Self, TkeyError> {
+        let key_pair = DhKeyPair::generate(group, rng)?;
+        let mut nonce = [0u8; NONCE_LEN];
+        rng.fill(&mut nonce)?;
+        let inception = serial_from_time(now);
+        Ok(ClientDhExchange {
+            key_name,
+            algorithm,
+            inception,
+            expiration: inception.add(lifetime),
+            key_pair,
+            nonce,
+        })
+    }
+
+    /// Returns the key pair used by the exchange.
+    pub fn key_pair(&self) -> &DhKeyPair {
+        &self.key_pair
+    }
+
+    /// Creates the request message.
+    ///
+    /// Adds the question, the TKEY record and our KEY record to the
+    /// message. Any content already present in `builder` is lost. The
+    /// message ID is left as is.
+    pub fn request<Target>(
+        &self,
+        builder: MessageBuilder<Target>,
+    ) -> Result<AdditionalBuilder<Target>, ShortBuf>
+    where
+        Target: OctetsBuilder + AsRef<[u8]> + AsMut<[u8]>,
+    {
+        let mut builder = start_request(builder, &self.key_name)?;
+        builder.push((
+            &self.key_name,
+            Class::Any,
+            0,
+            Tkey::new(
+                self.algorithm.to_dname(),
+                self.inception,
+                self.expiration,
+                TkeyMode::DiffieHellman,
+                TsigRcode::NoError,
+                self.nonce.as_ref(),
+                b"".as_ref(),
+            ),
+        ))?;
+        builder.push((
+            &self.key_name,
+            Class::In,
+            0,
+            self.key_pair.public_key().to_key_record(),
+        ))?;
+        Ok(builder)
+    }
+
+    /// Processes the answer and returns the negotiated key.
+    pub fn answer<Octets>(
+        self,
+        message: &Message<Octets>,
+    ) -> Result<NegotiatedKey, TkeyError>
+    where
+        Octets: AsRef<[u8]>,
+        for<'o> &'o Octets: OctetsRef,
+    {
+        let tkey = answer_tkey(
+            message,
+            &self.key_name,
+            self.algorithm,
+            TkeyMode::DiffieHellman,
+        )?;
+        let mut server_key = None;
+        for record in message.answer()?.limit_to::<KeyRecord<_>>() {
+            let record = record?;
+            if record.data().algorithm() == SecAlg::Dh {
+                server_key = Some(DhPublicKey::from_key(record.data())?);
+                break;
+            }
+        }
+        let server_key = server_key.ok_or(TkeyError::BadKey)?;
+        let shared = self.key_pair.compute_shared(&server_key)?;
+        let secret =
+            keying_material(&shared, &self.nonce, tkey.key().as_ref());
+        Ok(NegotiatedKey {
+            key: Key::new(
+                self.algorithm,
+                &secret,
+                self.key_name,
+                None,
+                None,
+            )?,
+            expires: time_from_serial(tkey.expiration()),
+        })
+    }
+}
+
+//------------ GssInitiator and GssAcceptor ----------------------------------
+
+/// The client side of a GSS-API security context.
+///
+/// This trait allows plugging any GSS-API mechanism into
+/// [`ClientGssExchange`]. It corresponds to repeated calls of
+/// `GSS_Init_sec_context`.
+///
+/// [`ClientGssExchange`]: struct.ClientGssExchange.html
+pub trait GssInitiator {
+    /// Performs the next step of establishing the context.
+    ///
+    /// For the first call, `input` is `None`. For subsequent calls, it
+    /// contains the token received from the server.
+    fn init_step(
+        &mut self,
+        input: Option<&[u8]>,
+    ) -> Result<GssStatus, GssError>;
+}
+
+/// The server side of GSS-API security contexts.
+///
+/// This trait allows plugging any GSS-API mechanism into [`TkeyServer`]. It
+/// corresponds to calls of `GSS_Accept_sec_context`. Since the server may
+/// be negotiating multiple contexts at the same time, these are identified
+/// by the key name requested by the client. The acceptor is responsible for
+/// keeping the context state between calls.
+///
+/// [`TkeyServer`]: struct.TkeyServer.html
+pub trait GssAcceptor {
+    /// Processes a token received from a client.
+    fn accept_token(
+        &self,
+        key_name: &Dname<OctetsVec>,
+        token: &[u8],
+    ) -> Result<GssStatus, GssError>;
+}
+
+/// The status of a GSS-API security context after a processing step.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum GssStatus {
+    /// The context needs another round trip.
+    ///
+    /// The token has to be sent to the other party.
+    Continue(Vec<u8>),
+
+    /// The context has been established.
+    Complete {
+        /// A final token to be sent to the other party.
+        ///
+        /// This may be empty.
+        token: Vec<u8>,
+
+        /// The session key of the context.
+        ///
+        /// This will become the secret of the TSIG key.
+        session_key: Vec<u8>,
+    },
+}
+
+//------------ ClientGssExchange ---------------------------------------------
+
+/// The client side of a GSS-API key negotiation.
+///
+/// You create a value of this type via [`new`] which performs the first
+/// step of the GSS-API context establishment. You then create a request via
+/// [`request`] and send it to the server. The answer is given to [`answer`]
+/// which returns `Ok(None)` if another round trip is necessary in which case
+/// you create the next request via [`request`] again.
+///
+/// [`new`]: #method.new
+/// [`request`]: #method.request
+/// [`answer`]: #method.answer
+#[derive(Debug)]
+pub struct ClientGssExchange<I> {
+    /// The requested key name.
+    key_name: Dname<OctetsVec>,
+
+    /// The algorithm for the negotiated key.
+    algorithm: Algorithm,
+
+    /// The requested start of the key validity.
+    inception: Serial,
+
+    /// The requested end of the key validity.
+    expiration: Serial,
+
+    /// The GSS-API mechanism.
+    initiator: I,
+
+    /// The token to send with the next request.
+    token: Vec<u8>,
+}
+
+impl<I: GssInitiator> ClientGssExchange<I> {
+    /// Creates a new exchange.
+    ///
+    /// The arguments are the same as for [`ClientDhExchange::new`] except
+    /// that instead of a Diffie-Hellman group, you provide the GSS-API
+    /// mechanism via `initiator`.
+    ///
+    /// [`ClientDhExchange::new`]: struct.ClientDhExchange.html#method.new
+    pub fn new(
+        key_name: Dname<OctetsVec>,
+        algorithm: Algorithm,
+        lifetime: u32,
+        now: Time48,
+        mut initiator: I,
+    ) -> Result<Self, TkeyError> {
+        let token = match initiator.init_step(None)? {
+            GssStatus::Continue(token) => token,
+            GssStatus::Complete { .. } => return Err(TkeyError::Gss),
+        };
+        let inception = serial_from_time(now);
+        Ok(ClientGssExchange {
+            key_name,
+            algorithm,
+            inception,
+            expiration: inception.add(lifetime),
+            initiator,
+            token,
+        })
+    }
+
+    /// Creates the next request message.
+    pub fn request<Target>(
+        &self,
+        builder: MessageBuilder<Target>,
+    ) -> Result<AdditionalBuilder<Target>, ShortBuf>
+    where
+        Target: OctetsBuilder + AsRef<[u8]> + AsMut<[u8]>,
+    {
+        let mut builder = start_request(builder, &self.key_name)?;
+        builder.push((
+            &self.key_name,
+            Class::Any,
+            0,
+            Tkey::new(
+                self.algorithm.to_dname(),
+                self.inception,
+                self.expiration,
+                TkeyMode::GssApi,
+                TsigRcode::NoError,
+                self.token.as_slice(),
+                b"".as_ref(),
+            ),
+        ))?;
+        Ok(builder)
+    }
+
+    /// Processes an answer.
+    ///
+    /// Returns the negotiated key if the context has been established or
+    /// `Ok(None)` if another request needs to be sent.
+    pub fn answer<Octets>(
+        &mut self,
+        message: &Message<Octets>,
+    ) -> Result<Option<NegotiatedKey>, TkeyError>
+    where
+        Octets: AsRef<[u8]>,
+        for<'o> &'o Octets: OctetsRef,
+    {
+        let tkey = answer_tkey(
+            message,
+            &self.key_name,
+            self.algorithm,
+            TkeyMode::GssApi,
+        )?;
+        match self.initiator.init_step(Some(tkey.key().as_ref()))? {
+            GssStatus::Continue(token) => {
+                self.token = token;
+                Ok(None)
+            }
+            GssStatus::Complete { session_key, .. } => {
+                Ok(Some(NegotiatedKey {
+                    key: Key::new(
+                        self.algorithm,
+                        &session_key,
+                        self.key_name.clone(),
+                        None,
+                        None,
+                    )?,
+                    expires: time_from_serial(tkey.expiration()),
+                }))
+            }
+        }
+    }
+
+    /// Returns a reference to the GSS-API mechanism.
+    pub fn initiator(&self) -> &I {
+        &self.initiator
+    }
+}
+
+//------------ Key Deletion --------------------------------------------------
+
+/// Creates a request for deleting a key.
+///
+/// The request still needs to be signed with the key to be deleted, e.g.,
+/// via [`ClientTransaction::request`]. The answer can be checked via
+/// [`check_delete_answer`] after its signature has been verified.
+///
+/// [`ClientTransaction::request`]: ../struct.ClientTransaction.html#method.request
+/// [`check_delete_answer`]: fn.check_delete_answer.html
+pub fn delete_request<Target>(
+    builder: MessageBuilder<Target>,
+    key: &Key,
+    now: Time48,
+) -> Result<AdditionalBuilder<Target>, ShortBuf>
+where
+    Target: OctetsBuilder + AsRef<[u8]> + AsMut<[u8]>,
+{
+    let mut builder = start_request(builder, key.name())?;
+    let now = serial_from_time(now);
+    builder.push((
+        key.name(),
+        Class::Any,
+        0,
+        Tkey::new(
+            key.algorithm().to_dname(),
+            now,
+            now,
+            TkeyMode::KeyDeletion,
+            TsigRcode::NoError,
+            b"".as_ref(),
+            b"".as_ref(),
+        ),
+    ))?;
+    Ok(builder)
+}
+
+/// Checks that the answer to a key deletion request reports success.
+///
+/// The `key` is the key whose deletion was requested.
+pub fn check_delete_answer<Octets>(
+    message: &Message<Octets>,
+    key: &Key,
+) -> Result<(), TkeyError>
+where
+    Octets: AsRef<[u8]>,
+    for<'o> &'o Octets: OctetsRef,
+{
+    answer_tkey(message, key.name(), key.algorithm(), TkeyMode::KeyDeletion)
+        .map(|_| ())
+}
+
+//------------ TkeyServer ----------------------------------------------------
+
+/// The server side of TKEY key negotiation.
+///
+/// The server keeps a reference to a [`DynamicKeyStore`] to which it adds
+/// negotiated keys and from which it removes deleted keys. Requests are
+/// processed via [`answer`].
+///
+/// By default, key establishment requires a request signed with an
+/// existing key. Unsigned requests can be allowed via
+/// [`set_require_signed`]. Keys negotiated this way are not authenticated,
+/// so it is up to local policy what to allow them to do. Deletion always
+/// requires a signed request.
+///
+/// For Diffie-Hellman exchange, only the well-known groups of RFC 2539 are
+/// accepted. This keeps clients from making the server work with groups of
+/// arbitrary size or quality.
+///
+/// [`DynamicKeyStore`]: ../struct.DynamicKeyStore.html
+/// [`answer`]: #method.answer
+/// [`set_require_signed`]: #method.set_require_signed
+pub struct TkeyServer<R = SystemRandom> {
+    /// The store to add negotiated keys to.
+    store: Arc<DynamicKeyStore>,
+
+    /// The random number generator for key pairs and nonces.
+    rng: R,
+
+    /// The maximum lifetime of a key in seconds.
+    max_lifetime: u32,
+
+    /// Whether key establishment requires a signed request.
+    require_signed: bool,
+
+    /// The GSS-API mechanism if GSS-API negotiation is supported.
+    gss: Option<Box<dyn GssAcceptor + Send + Sync>>,
+}
+
+impl TkeyServer<SystemRandom> {
+    /// Creates a new server using the system’s random number generator.
+    pub fn new(store: Arc<DynamicKeyStore>) -> Self {
+        Self::with_rng(store, SystemRandom::new())
+    }
+}
+
+impl<R: SecureRandom> TkeyServer<R> {
+    /// Creates a new server using the given random number generator.
+    pub fn with_rng(store: Arc<DynamicKeyStore>, rng: R) -> Self {
+        TkeyServer {
+            store,
+            rng,
+            max_lifetime: DEFAULT_MAX_LIFETIME,
+            require_signed: true,
+            gss: None,
+        }
+    }
+
+    /// Returns a reference to the key store.
+    pub fn store(&self) -> &Arc<DynamicKeyStore> {
+        &self.store
+    }
+
+    /// Sets the maximum lifetime of negotiated keys in seconds.
+    ///
+    /// If a client requests a longer lifetime, it will be shortened. The
+    /// default is one day.
+    pub fn set_max_lifetime(&mut self, lifetime: u32) {
+        self.max_lifetime = lifetime
+    }
+
+    /// Sets whether key establishment requests need to be signed.
+    ///
+    /// The default is `true`.
+    pub fn set_require_signed(&mut self, value: bool) {
+        self.require_signed = value
+    }
+
+    /// Enables GSS-API negotiation with the given mechanism.
+    pub fn set_gss_acceptor(
+        &mut self,
+        acceptor: impl GssAcceptor + Send + Sync + 'static,
+    ) {
+        self.gss = Some(Box::new(acceptor))
+    }
+
+    /// Processes a TKEY request and produces the answer.
+    ///
+    /// The `request` must already have been checked for a TSIG signature.
+    /// If it was signed, the key used needs to be given via `signer`. The
+    /// answer will be built atop `target`.
+    ///
+    /// Problems with the requested key are reported back to the client via
+    /// the error field of the TKEY record in the answer. The method only
+    /// returns an error if the request is not a TKEY request or is malformed,
+    /// in which case you should respond with a FORMERR, or if the answer
+    /// doesn’t fit into `target`.
+    pub fn answer<Octets, Target>(
+        &self,
+        request: &Message<Octets>,
+        signer: Option<&Key>,
+        now: Time48,
+        target: Target,
+    ) -> Result<AdditionalBuilder<Target>, TkeyError>
+    where
+        Octets: AsRef<[u8]>,
+        for<'o> &'o Octets: OctetsRef,
+        Target: OctetsBuilder + AsRef<[u8]> + AsMut<[u8]>,
+    {
+        if request.header().qr() || request.header().opcode() != Opcode::Query
+        {
+            return Err(TkeyError::FormErr);
+        }
+        let question = request.sole_question()?;
+        if question.qtype() != Rtype::Tkey {
+            return Err(TkeyError::FormErr);
+        }
+        let qname = question.qname();
+        let mut tkey = None;
+        for record in request.additional()?.limit_to::<Tkey<_, _>>() {
+            let record = record?;
+            if record.owner() == qname {
+                tkey = Some(record.into_data());
+                break;
+            }
+        }
+        let tkey = tkey.ok_or(TkeyError::FormErr)?;
+        let key_name = qname.to_dname::<OctetsVec>()?;
+
+        let mut answer = MessageBuilder::from_target(target)?
+            .start_answer(request, Rcode::NoError)?;
+        answer.header_mut().set_aa(true);
+        let mut reply = Reply {
+            algorithm: tkey.algorithm(),
+            inception: tkey.inception(),
+            expiration: tkey.expiration(),
+            mode: tkey.mode(),
+            error: TsigRcode::NoError,
+            key: Vec::new(),
+        };
+        let mut extra_answer = None;
+        let mut echo = None;
+
+        match tkey.mode() {
+            TkeyMode::KeyDeletion => {
+                reply.error = self.delete(&key_name, &tkey, signer);
+            }
+            TkeyMode::DiffieHellman | TkeyMode::GssApi
+                if self.require_signed && signer.is_none() =>
+            {
+                reply.error = TsigRcode::BadKey;
+            }
+            TkeyMode::DiffieHellman => {
+                match self.dh_exchange(request, &key_name, &tkey, now) {
+                    Ok((nonce, server_key, client_key, expiration)) => {
+                        reply.key = nonce;
+                        reply.expiration = expiration;
+                        extra_answer = Some(server_key);
+                        echo = Some(client_key);
+                    }
+                    Err(error) => reply.error = error,
+                }
+            }
+            TkeyMode::GssApi => {
+                match self.gss_exchange(&key_name, &tkey, now) {
+                    Ok((token, expiration)) => {
+                        reply.key = token;
+                        reply.expiration = expiration;
+                    }
+                    Err(error) => reply.error = error,
+                }
+            }
+            _ => reply.error = TsigRcode::BadMode,
+        }
+
+        answer.push((qname, Class::Any, 0, reply.to_tkey()))?;
+        if let Some(server_key) = extra_answer {
+            answer.push((qname, Class::In, 0, server_key))?;
+        }
+        let mut additional = answer.additional();
+        if let Some(client_key) = echo {
+            additional.push((qname, Class::In, 0, client_key))?;
+        }
+        Ok(additional)
+    }
+
+    /// Processes a key deletion request.
+    fn delete<Ref: OctetsRef>(
+        &self,
+        key_name: &Dname<OctetsVec>,
+        tkey: &Tkey<Ref::Range, ParsedDname<Ref>>,
+        signer: Option<&Key>,
+    ) -> TsigRcode {
+        let algorithm = match Algorithm::from_dname(tkey.algorithm()) {
+            Some(algorithm) => algorithm,
+            None => return TsigRcode::BadAlg,
+        };
+        match signer {
+            Some(signer)
+                if signer.name() == key_name
+                    && signer.algorithm() == algorithm => {}
+            _ => return TsigRcode::BadKey,
+        }
+        match self.store.remove(key_name, algorithm) {
+            Some(_) => TsigRcode::NoError,
+            None => TsigRcode::BadName,
+        }
+    }
+
+    /// Checks the requested algorithm, name, and validity for a new key.
+    ///
+    /// Returns the algorithm and the actual expiration time.
+    fn check_new_key<Ref: OctetsRef>(
+        &self,
+        key_name: &Dname<OctetsVec>,
+        tkey: &Tkey<Ref::Range, ParsedDname<Ref>>,
+        now: Time48,
+    ) -> Result<(Algorithm, Serial), TsigRcode> {
+        let algorithm = Algorithm::from_dname(tkey.algorithm())
+            .ok_or(TsigRcode::BadAlg)?;
+        if self.store.expires(key_name, algorithm).is_some() {
+            return Err(TsigRcode::BadName);
+        }
+        let now = serial_from_time(now);
+        if tkey.expiration() <= now || tkey.inception() > tkey.expiration() {
+            return Err(TsigRcode::BadTime);
+        }
+        let max = now.add(self.max_lifetime);
+        let expiration = if tkey.expiration() > max {
+            max
+        } else {
+            tkey.expiration()
+        };
+        Ok((algorithm, expiration))
+    }
+
+    /// Performs the server side of a Diffie-Hellman exchange.
+    ///
+    /// Returns the server’s nonce, the server’s and client’s KEY record
+    /// data, and the expiration time.
+    #[allow(clippy::type_complexity)]
+    fn dh_exchange<Octets, Ref>(
+        &self,
+        request: &Message<Octets>,
+        key_name: &Dname<OctetsVec>,
+        tkey: &Tkey<Ref::Range, ParsedDname<Ref>>,
+        now: Time48,
+    ) -> Result<
+        (Vec<u8>, KeyRecord<Vec<u8>>, KeyRecord<Vec<u8>>, Serial),
+        TsigRcode,
+    >
+    where
+        Octets: AsRef<[u8]>,
+        for<'o> &'o Octets: OctetsRef,
+        Ref: OctetsRef,
+    {
+        let (algorithm, expiration) =
+            self.check_new_key(key_name, tkey, now)?;
+
+        let mut client_key = None;
+        let additional =
+            request.additional().map_err(|_| TsigRcode::BadKey)?;
+        for record in additional.limit_to::<KeyRecord<_>>() {
+            let record = record.map_err(|_| TsigRcode::BadKey)?;
+            if record.data().algorithm() == SecAlg::Dh {
+                client_key = Some(record.into_data());
+                break;
+            }
+        }
+        let client_key = client_key.ok_or(TsigRcode::BadKey)?;
+        let client_public = DhPublicKey::from_key(&client_key)
+            .map_err(|_| TsigRcode::BadKey)?;
+        if client_public.group().well_known.is_none() {
+            return Err(TsigRcode::BadKey);
+        }
+
+        let key_pair =
+            DhKeyPair::generate(client_public.group().clone(), &self.rng)
+                .map_err(|_| TsigRcode::BadKey)?;
+        let mut nonce = vec![0u8; NONCE_LEN];
+        self.rng.fill(&mut nonce).map_err(|_| TsigRcode::BadKey)?;
+        let shared = key_pair
+            .compute_shared(&client_public)
+            .map_err(|_| TsigRcode::BadKey)?;
+        let secret = keying_material(&shared, tkey.key().as_ref(), &nonce);
+        let key = Key::new(algorithm, &secret, key_name.clone(), None, None)
+            .map_err(|_| TsigRcode::BadKey)?;
+        self.store.insert(key, Some(time_from_serial(expiration)));
+
+        Ok((
+            nonce,
+            key_pair.public_key().to_key_record(),
+            KeyRecord::new(
+                client_key.flags(),
+                client_key.protocol(),
+                client_key.algorithm(),
+                client_key.public_key().as_ref().into(),
+            ),
+            expiration,
+        ))
+    }
+
+    /// Performs a step of a GSS-API negotiation.
+    ///
+    /// Returns the token to send back and the expiration time.
+    fn gss_exchange<Ref: OctetsRef>(
+        &self,
+        key_name: &Dname<OctetsVec>,
+        tkey: &Tkey<Ref::Range, ParsedDname<Ref>>,
+        now: Time48,
+    ) -> Result<(Vec<u8>, Serial), TsigRcode> {
+        let gss = self.gss.as_ref().ok_or(TsigRcode::BadMode)?;
+        let (algorithm, expiration) =
+            self.check_new_key(key_name, tkey, now)?;
+        match gss.accept_token(key_name, tkey.key().as_ref()) {
+            Ok(GssStatus::Continue(token)) => Ok((token, expiration)),
+            Ok(GssStatus::Complete { token, session_key }) => {
+                let key = Key::new(
+                    algorithm,
+                    &session_key,
+                    key_name.clone(),
+                    None,
+                    None,
+                )
+                .map_err(|_| TsigRcode::BadKey)?;
+                self.store.insert(key, Some(time_from_serial(expiration)));
+                Ok((token, expiration))
+            }
+            Err(_) => Err(TsigRcode::BadKey),
+        }
+    }
+}
+
+impl<R> fmt::Debug for TkeyServer<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TkeyServer")
+            .field("store", &self.store)
+            .field("max_lifetime", &self.max_lifetime)
+            .field("require_signed", &self.require_signed)
+            .field("gss", &self.gss.is_some())
+            .finish()
+    }
+}
+
+//------------ Reply ---------------------------------------------------------
+
+/// The content of the TKEY record of an answer.
+struct Reply<'a, N> {
+    algorithm: &'a N,
+    inception: Serial,
+    expiration: Serial,
+    mode: TkeyMode,
+    error: TsigRcode,
+    key: Vec<u8>,
+}
+
+impl<'a, N> Reply<'a, N> {
+    fn to_tkey(&self) -> Tkey<&[u8], &'a N> {
+        Tkey::new(
+            self.algorithm,
+            self.inception,
+            self.expiration,
+            self.mode,
+            self.error,
+            self.key.as_slice(),
+            b"".as_ref(),
+        )
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Derives the keying material for a Diffie-Hellman exchange.
+///
+/// This implements the algorithm described in section 4.1 of RFC 2930: The
+/// keying material is the exclusive-or of the Diffie-Hellman value and the
+/// concatenation of the MD5 hashes of each party’s random data followed by
+/// the Diffie-Hellman value. The shorter of the two operands is padded with
+/// zeros.
+fn keying_material(
+    shared: &[u8],
+    query_data: &[u8],
+    server_data: &[u8],
+) -> Vec<u8> {
+    let mut digests = Vec::with_capacity(32);
+    digests.extend_from_slice(
+        &Md5::new()
+            .chain_update(query_data)
+            .chain_update(shared)
+            .finalize(),
+    );
+    digests.extend_from_slice(
+        &Md5::new()
+            .chain_update(server_data)
+            .chain_update(shared)
+            .finalize(),
+    );
+    let (mut res, other) = if shared.len() > digests.len() {
+        (shared.to_vec(), digests.as_slice())
+    } else {
+        (digests.clone(), shared)
+    };
+    for (left, right) in res.iter_mut().zip(other) {
+        *left ^= *right
+    }
+    res
+}
+
+/// Starts a TKEY request for the given name.
+///
+/// Sets the header and the question and progresses to the additional
+/// section.
+fn start_request<Target>(
+    mut builder: MessageBuilder<Target>,
+    key_name: &Dname<OctetsVec>,
+) -> Result<AdditionalBuilder<Target>, ShortBuf>
+where
+    Target: OctetsBuilder + AsRef<[u8]> + AsMut<[u8]>,
+{
+    builder.header_mut().set_opcode(Opcode::Query);
+    let mut builder = builder.question();
+    builder.push((key_name, Rtype::Tkey, Class::Any))?;
+    Ok(builder.additional())
+}
+
+/// Extracts the TKEY record from an answer.
+///
+/// Checks for errors and that the record is for the key name, algorithm,
+/// and mode of the request. Returns the record data.
+#[allow(clippy::type_complexity)]
+fn answer_tkey<'a, Octets>(
+    message: &'a Message<Octets>,
+    key_name: &Dname<OctetsVec>,
+    algorithm: Algorithm,
+    mode: TkeyMode,
+) -> Result<
+    Tkey<<&'a Octets as OctetsRef>::Range, ParsedDname<&'a Octets>>,
+    TkeyError,
+>
+where
+    Octets: AsRef<[u8]>,
+    for<'o> &'o Octets: OctetsRef,
+{
+    if message.header().rcode() != Rcode::NoError {
+        return Err(TkeyError::Rcode(message.header().rcode()));
+    }
+    let record = message
+        .answer()?
+        .limit_to::<Tkey<_, _>>()
+        .next()
+        .ok_or(TkeyError::FormErr)??;
+    if record.data().error() != TsigRcode::NoError {
+        return Err(TkeyError::Server(record.data().error()));
+    }
+    if record.owner() != key_name || record.data().mode() != mode {
+        return Err(TkeyError::FormErr);
+    }
+    if Algorithm::from_dname(record.data().algorithm()) != Some(algorithm) {
+        return Err(TkeyError::BadAlg);
+    }
+    Ok(record.into_data())
+}
+
+/// Converts a TSIG time into a TKEY time.
+fn serial_from_time(time: Time48) -> Serial {
+    Serial(u64::from(time) as u32)
+}
+
+/// Converts a TKEY time into a TSIG time.
+fn time_from_serial(serial: Serial) -> Time48 {
+    Time48::from_u64(u64::from(serial.into_int()))
+}
+
+//============ Error Types ===================================================
+
+//------------ GssError ------------------------------------------------------
+
+/// A GSS-API mechanism failed to process a token.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct GssError;
+
+//--- Display and Error
+
+impl fmt::Display for GssError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("GSS-API failure")
+    }
+}
+
+impl std::error::Error for GssError {}
+
+//------------ TkeyError -----------------------------------------------------
+
+/// An error happened while negotiating a key.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TkeyError {
+    /// A message was malformed or not a TKEY message.
+    FormErr,
+
+    /// The server answered with an error response code.
+    Rcode(Rcode),
+
+    /// The server reported an error in the TKEY record.
+    Server(TsigRcode),
+
+    /// The server answered with a different algorithm than requested.
+    BadAlg,
+
+    /// A Diffie-Hellman key was missing or invalid.
+    BadKey,
+
+    /// The negotiated key could not be created.
+    NewKey(NewKeyError),
+
+    /// The GSS-API mechanism failed.
+    Gss,
+
+    /// The random number generator failed.
+    Random,
+
+    /// A message didn’t fit into its buffer.
+    ShortBuf,
+}
+
+//--- From
+
+impl From<ParseError> for TkeyError {
+    fn from(_: ParseError) -> Self {
+        TkeyError::FormErr
+    }
+}
+
+impl From<ShortBuf> for TkeyError {
+    fn from(_: ShortBuf) -> Self {
+        TkeyError::ShortBuf
+    }
+}
+
+impl From<NewKeyError> for TkeyError {
+    fn from(err: NewKeyError) -> Self {
+        TkeyError::NewKey(err)
+    }
+}
+
+impl From<GssError> for TkeyError {
+    fn from(_: GssError) -> Self {
+        TkeyError::Gss
+    }
+}
+
+impl From<ring::error::Unspecified> for TkeyError {
+    fn from(_: ring::error::Unspecified) -> Self {
+        TkeyError::Random
+    }
+}
+
+impl From<crate::base::name::PushError> for TkeyError {
+    fn from(_: crate::base::name::PushError) -> Self {
+        TkeyError::ShortBuf
+    }
+}
+
+//--- Display and Error
+
+impl fmt::Display for TkeyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TkeyError::FormErr => f.write_str("format error"),
+            TkeyError::Rcode(rcode) => {
+                write!(f, "server responded with {}", rcode)
+            }
+            TkeyError::Server(error) => {
+                write!(f, "server reported TKEY error {}", error)
+            }
+            TkeyError::BadAlg => f.write_str("unexpected algorithm"),
+            TkeyError::BadKey => f.write_str("bad Diffie-Hellman key"),
+            TkeyError::NewKey(ref err) => err.fmt(f),
+            TkeyError::Gss => f.write_str("GSS-API failure"),
+            TkeyError::Random => {
+                f.write_str("random number generator failed")
+            }
+            TkeyError::ShortBuf => f.write_str("short buffer"),
+        }
+    }
+}
+
+impl std::error::Error for TkeyError {}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tsig::{ClientTransaction, KeyStore, ServerTransaction};
+    use core::str::FromStr;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    fn now() -> Time48 {
+        Time48::from_u64(1_600_000_000)
+    }
+
+    fn server() -> TkeyServer {
+        let mut server = TkeyServer::new(Arc::new(DynamicKeyStore::new()));
+        server.set_require_signed(false);
+        server
+    }
+
+    #[test]
+    fn dh_public_key_roundtrip() {
+        let rng = SystemRandom::new();
+        let pair = DhKeyPair::generate(DhGroup::oakley_1024(), &rng).unwrap();
+        let data = pair.public_key().to_key_data();
+        assert_eq!(&data[..5], b"\x00\x01\x02\x00\x00");
+        assert_eq!(
+            &DhPublicKey::from_key_data(&data).unwrap(),
+            pair.public_key()
+        );
+
+        let group = DhGroup::new(b"\x01\x00\x01", b"\x03").unwrap();
+        let pair = DhKeyPair::generate(group, &rng).unwrap();
+        let data = pair.public_key().to_key_data();
+        assert_eq!(
+            &DhPublicKey::from_key_data(&data).unwrap(),
+            pair.public_key()
+        );
+        assert_eq!(
+            DhPublicKey::from_key_data(b"\x00\x01\x07\x00\x00\x00\x01\x05"),
+            Err(TkeyError::BadKey)
+        );
+    }
+
+    #[test]
+    fn dh_public_key_too_large() {
+        let mut data = Vec::new();
+        data.extend_from_slice(&513u16.to_be_bytes());
+        data.extend_from_slice(&[0xFF; 513]);
+        data.extend_from_slice(b"\x00\x01\x02\x00\x01\x05");
+        assert_eq!(DhPublicKey::from_key_data(&data), Err(TkeyError::BadKey));
+    }
+
+    #[test]
+    fn dh_bad_groups() {
+        // Primes that are too small or even and bad generators.
+        assert_eq!(
+            DhGroup::new(b"\x00\x00\x00", b"\x02"),
+            Err(TkeyError::BadKey)
+        );
+        assert_eq!(
+            DhGroup::new(b"\x00\x00\x01", b"\x02"),
+            Err(TkeyError::BadKey)
+        );
+        assert_eq!(
+            DhGroup::new(b"\x00\x00\x05", b"\x02"),
+            Err(TkeyError::BadKey)
+        );
+        assert_eq!(
+            DhGroup::new(b"\x01\x00\x00", b"\x03"),
+            Err(TkeyError::BadKey)
+        );
+        assert_eq!(
+            DhGroup::new(b"\x01\x00\x01", b"\x01"),
+            Err(TkeyError::BadKey)
+        );
+        assert_eq!(
+            DhGroup::new(b"\x01\x00\x01", b"\x01\x00\x00"),
+            Err(TkeyError::BadKey)
+        );
+        assert_eq!(
+            DhPublicKey::from_key_data(
+                b"\x00\x03\x00\x00\x00\x00\x01\x02\x00\x01\x05"
+            ),
+            Err(TkeyError::BadKey)
+        );
+        assert_eq!(
+            DhPublicKey::from_key_data(
+                b"\x00\x03\x00\x00\x01\x00\x00\x00\x01\x05"
+            ),
+            Err(TkeyError::BadKey)
+        );
+    }
+
+    #[test]
+    fn dh_bad_peer_key() {
+        let rng = SystemRandom::new();
+        let bad_key = KeyRecord::new(
+            DH_KEY_FLAGS,
+            KeyRecord::<Vec<u8>>::DNSSEC_PROTOCOL,
+            SecAlg::Dh,
+            b"\x00\x03\x00\x00\x00\x00\x01\x02\x00\x01\x05".to_vec(),
+        );
+        let exchange = ClientDhExchange::new(
+            Dname::from_str("bad.key.").unwrap(),
+            Algorithm::Sha256,
+            DhGroup::oakley_1024(),
+            3600,
+            now(),
+            &rng,
+        )
+        .unwrap();
+        let tkey = Tkey::new(
+            exchange.algorithm.to_dname(),
+            exchange.inception,
+            exchange.expiration,
+            TkeyMode::DiffieHellman,
+            TsigRcode::NoError,
+            exchange.nonce.as_ref(),
+            b"".as_ref(),
+        );
+
+        // A client sending a bad key gets an error from the server.
+        let mut req =
+            start_request(MessageBuilder::new_vec(), &exchange.key_name)
+                .unwrap();
+        req.push((&exchange.key_name, Class::Any, 0, tkey.clone()))
+            .unwrap();
+        req.push((&exchange.key_name, Class::In, 0, bad_key.clone()))
+            .unwrap();
+        let req = req.into_message();
+        let server = server();
+        let answer = server
+            .answer(&req, None, now(), Vec::new())
+            .unwrap()
+            .into_message();
+        let answer_tkey = answer
+            .answer()
+            .unwrap()
+            .limit_to::<Tkey<_, _>>()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(answer_tkey.data().error(), TsigRcode::BadKey);
+        assert!(server.store().is_empty());
+
+        // A server answering with a bad key is rejected by the client.
+        let req = exchange
+            .request(MessageBuilder::new_vec())
+            .unwrap()
+            .into_message();
+        let mut answer = MessageBuilder::new_vec()
+            .start_answer(&req, Rcode::NoError)
+            .unwrap();
+        answer
+            .push((&exchange.key_name, Class::Any, 0, tkey))
+            .unwrap();
+        answer
+            .push((&exchange.key_name, Class::In, 0, bad_key.clone()))
+            .unwrap();
+        let answer = answer.into_message();
+        assert_eq!(exchange.answer(&answer).unwrap_err(), TkeyError::BadKey);
+    }
+
+    #[test]
+    fn dh_exchange_restrictions() {
+        let rng = SystemRandom::new();
+        let request = |name: &str, group: DhGroup| {
+            let exchange = ClientDhExchange::new(
+                Dname::from_str(name).unwrap(),
+                Algorithm::Sha256,
+                group,
+                3600,
+                now(),
+                &rng,
+            )
+            .unwrap();
+            let request = exchange
+                .request(MessageBuilder::new_vec())
+                .unwrap()
+                .into_message();
+            (exchange, request)
+        };
+
+        // Unsigned requests are rejected by default.
+        let strict = TkeyServer::new(Arc::new(DynamicKeyStore::new()));
+        let (exchange, req) = request("strict.key.", DhGroup::oakley_1024());
+        let answer = strict
+            .answer(&req, None, now(), Vec::new())
+            .unwrap()
+            .into_message();
+        assert_eq!(
+            exchange.answer(&answer).unwrap_err(),
+            TkeyError::Server(TsigRcode::BadKey)
+        );
+
+        // Groups other than the well-known ones are rejected.
+        let server = server();
+        let group = DhGroup::new(b"\x01\x00\x01", b"\x03").unwrap();
+        let (exchange, req) = request("custom.key.", group);
+        let answer = server
+            .answer(&req, None, now(), Vec::new())
+            .unwrap()
+            .into_message();
+        assert_eq!(
+            exchange.answer(&answer).unwrap_err(),
+            TkeyError::Server(TsigRcode::BadKey)
+        );
+        assert!(server.store().is_empty());
+
+        // An answer for a different key name is rejected by the client.
+        let (_, req) = request("one.key.", DhGroup::oakley_1024());
+        let (exchange, _) = request("two.key.", DhGroup::oakley_1024());
+        let answer = server
+            .answer(&req, None, now(), Vec::new())
+            .unwrap()
+            .into_message();
+        assert_eq!(exchange.answer(&answer).unwrap_err(), TkeyError::FormErr);
+    }
+
+    #[test]
+    fn keying_material_lengths() {
+        let short = keying_material(b"ab", b"q", b"s");
+        assert_eq!(short.len(), 32);
+        let long = keying_material(&[0u8; 128], b"q", b"s");
+        assert_eq!(long.len(), 128);
+        assert_eq!(&long[32..], &[0u8; 96][..]);
+    }
+
+    #[test]
+    fn dh_exchange_and_delete() {
+        let rng = SystemRandom::new();
+        let server = server();
+        let exchange = ClientDhExchange::new(
+            Dname::from_str("negotiated.key.").unwrap(),
+            Algorithm::Sha256,
+            DhGroup::oakley_1024(),
+            3600,
+            now(),
+            &rng,
+        )
+        .unwrap();
+        let request = exchange
+            .request(MessageBuilder::new_vec())
+            .unwrap()
+            .into_message();
+        let answer = server
+            .answer(&request, None, now(), Vec::new())
+            .unwrap()
+            .into_message();
+        let key = exchange.answer(&answer).unwrap();
+        assert_eq!(key.expires(), Time48::from_u64(1_600_003_600));
+
+        // Both sides must have the same key.
+        let key = Arc::new(key.into_key());
+        let server_key = server
+            .store()
+            .get_key_at(key.name(), Algorithm::Sha256, now())
+            .unwrap();
+        let mut request = MessageBuilder::new_vec();
+        request.header_mut().set_id(7);
+        let mut request = request.additional();
+        let tran =
+            ClientTransaction::request(key.clone(), &mut request, now())
+                .unwrap();
+        let mut request = request.into_message();
+        let _ = ServerTransaction::request(&server_key, &mut request, now())
+            .unwrap()
+            .unwrap();
+
+        // Negotiating again with the same name fails.
+        let exchange = ClientDhExchange::new(
+            Dname::from_str("negotiated.key.").unwrap(),
+            Algorithm::Sha256,
+            DhGroup::oakley_1024(),
+            3600,
+            now(),
+            &rng,
+        )
+        .unwrap();
+        let request = exchange
+            .request(MessageBuilder::new_vec())
+            .unwrap()
+            .into_message();
+        let answer = server
+            .answer(&request, None, now(), Vec::new())
+            .unwrap()
+            .into_message();
+        assert_eq!(
+            exchange.answer(&answer).unwrap_err(),
+            TkeyError::Server(TsigRcode::BadName)
+        );
+
+        // Unsigned deletion fails, signed deletion succeeds.
+        let request = delete_request(MessageBuilder::new_vec(), &key, now())
+            .unwrap()
+            .into_message();
+        let answer = server
+            .answer(&request, None, now(), Vec::new())
+            .unwrap()
+            .into_message();
+        assert_eq!(
+            check_delete_answer(&answer, &key),
+            Err(TkeyError::Server(TsigRcode::BadKey))
+        );
+        let answer = server
+            .answer(&request, Some(&server_key), now(), Vec::new())
+            .unwrap()
+            .into_message();
+        check_delete_answer(&answer, &key).unwrap();
+        assert!(server.store().is_empty());
+        drop(tran);
+    }
+
+    #[test]
+    fn dh_lifetime_is_clamped() {
+        let rng = SystemRandom::new();
+        let mut server = server();
+        server.set_max_lifetime(60);
+        let exchange = ClientDhExchange::new(
+            Dname::from_str("short.key.").unwrap(),
+            Algorithm::Sha1,
+            DhGroup::well_known(1).unwrap(),
+            3600,
+            now(),
+            &rng,
+        )
+        .unwrap();
+        let request = exchange
+            .request(MessageBuilder::new_vec())
+            .unwrap()
+            .into_message();
+        let answer = server
+            .answer(&request, None, now(), Vec::new())
+            .unwrap()
+            .into_message();
+        let key = exchange.answer(&answer).unwrap();
+        assert_eq!(key.expires(), Time48::from_u64(1_600_000_060));
+        let name = key.key().name().clone();
+        assert!(server
+            .store()
+            .get_key_at(
+                &name,
+                Algorithm::Sha1,
+                Time48::from_u64(1_600_000_061)
+            )
+            .is_none());
+    }
+
+    /// A fake GSS-API mechanism that needs two round trips.
+    #[derive(Default)]
+    struct FakeMech {
+        contexts: Mutex<HashMap<Dname<OctetsVec>, usize>>,
+    }
+
+    impl GssAcceptor for FakeMech {
+        fn accept_token(
+            &self,
+            key_name: &Dname<OctetsVec>,
+            token: &[u8],
+        ) -> Result<GssStatus, GssError> {
+            let mut contexts = self.contexts.lock().unwrap();
+            let step = contexts.entry(key_name.clone()).or_default();
+            *step += 1;
+            match (*step, token) {
+                (1, b"hello") => Ok(GssStatus::Continue(b"again".to_vec())),
+                (2, b"again") => Ok(GssStatus::Complete {
+                    token: b"done".to_vec(),
+                    session_key: b"session key".to_vec(),
+                }),
+                _ => Err(GssError),
+            }
+        }
+    }
+
+    struct FakeInitiator;
+
+    impl GssInitiator for FakeInitiator {
+        fn init_step(
+            &mut self,
+            input: Option<&[u8]>,
+        ) -> Result<GssStatus, GssError> {
+            match input {
+                None => Ok(GssStatus::Continue(b"hello".to_vec())),
+                Some(b"again") => Ok(GssStatus::Continue(b"again".to_vec())),
+                Some(b"done") => Ok(GssStatus::Complete {
+                    token: Vec::new(),
+                    session_key: b"session key".to_vec(),
+                }),
+                _ => Err(GssError),
+            }
+        }
+    }
+
+    #[test]
+    fn gss_exchange() {
+        let mut server = server();
+        let name = Dname::<OctetsVec>::from_str("gss.key.").unwrap();
+
+        // Without an acceptor, the mode is not supported.
+        let mut exchange = ClientGssExchange::new(
+            name.clone(),
+            Algorithm::Sha256,
+            3600,
+            now(),
+            FakeInitiator,
+        )
+        .unwrap();
+        let request = exchange
+            .request(MessageBuilder::new_vec())
+            .unwrap()
+            .into_message();
+        let answer = server
+            .answer(&request, None, now(), Vec::new())
+            .unwrap()
+            .into_message();
+        assert_eq!(
+            exchange.answer(&answer).unwrap_err(),
+            TkeyError::Server(TsigRcode::BadMode)
+        );
+
+        server.set_gss_acceptor(FakeMech::default());
+        let mut key = None;
+        for _ in 0..2 {
+            let request = exchange
+                .request(MessageBuilder::new_vec())
+                .unwrap()
+                .into_message();
+            let answer = server
+                .answer(&request, None, now(), Vec::new())
+                .unwrap()
+                .into_message();
+            key = exchange.answer(&answer).unwrap();
+        }
+        let key = key.unwrap();
+        assert_eq!(key.key().name(), &name);
+        assert!(server
+            .store()
+            .get_key_at(&name, Algorithm::Sha256, now())
+            .is_some());
+        assert!(server.store().get_key(&name, Algorithm::Sha1).is_none());
+    }
+}
@@ -4,7 +4,8 @@
 //! defined in the RFC, dubbed *base64* and *base64url* which are
 //! differenciated by the last two characters in the alphabet. The DNS uses
 //! only the original *base64* variant, so this is what is implemented by the
-//! module for now.
+//! module for now. The only exception is encoding into *base64url* without
+//! padding which is needed for DNS-over-HTTPS.
 //!
 //! The module defines the type [`Decoder`] which keeps the state necessary
 //! for decoding. The various functions offered use such a decoder to decode
@@ -61,23 +62,49 @@ where
     B: AsRef<[u8]> + ?Sized,
     W: fmt::Write,
 {
-    fn ch(i: u8) -> char {
-        ENCODE_ALPHABET[i as usize]
-    }
+    display_with(bytes.as_ref(), f, &ENCODE_ALPHABET, true)
+}
 
-    for chunk in bytes.as_ref().chunks(3) {
+/// Encodes binary data in *base64url* and writes it into a format stream.
+///
+/// The data is encoded using the URL and filename safe alphabet and, as
+/// required by [RFC 8484] for DNS-over-HTTPS, without any padding.
+///
+/// [RFC 8484]: https://tools.ietf.org/html/rfc8484
+pub fn display_url<B, W>(bytes: &B, f: &mut W) -> fmt::Result
+where
+    B: AsRef<[u8]> + ?Sized,
+    W: fmt::Write,
+{
+    display_with(bytes.as_ref(), f, &ENCODE_URL_ALPHABET, false)
+}
+
+/// Encodes binary data using the given alphabet.
+fn display_with<W: fmt::Write>(
+    bytes: &[u8],
+    f: &mut W,
+    alphabet: &[char; 64],
+    pad: bool,
+) -> fmt::Result {
+    let ch = |i: u8| alphabet[i as usize];
+
+    for chunk in bytes.chunks(3) {
         match chunk.len() {
             1 => {
                 f.write_char(ch(chunk[0] >> 2))?;
                 f.write_char(ch((chunk[0] & 0x03) << 4))?;
-                f.write_char('=')?;
-                f.write_char('=')?;
+                if pad {
+                    f.write_char(PAD)?;
+                    f.write_char(PAD)?;
+                }
             }
             2 => {
                 f.write_char(ch(chunk[0] >> 2))?;
                 f.write_char(ch((chunk[0] & 0x03) << 4 | chunk[1] >> 4))?;
                 f.write_char(ch((chunk[1] & 0x0F) << 2))?;
-                f.write_char('=')?;
+                if pad {
+                    f.write_char(PAD)?;
+                }
             }
             3 => {
                 f.write_char(ch(chunk[0] >> 2))?;
@@ -99,6 +126,14 @@ pub fn encode_string<B: AsRef<[u8]> + ?Sized>(bytes: &B) -> String {
     res
 }
 
+/// Encodes binary data in unpadded *base64url* and returns it as a string.
+#[cfg(feature = "std")]
+pub fn encode_url_string<B: AsRef<[u8]> + ?Sized>(bytes: &B) -> String {
+    let mut res = String::with_capacity((bytes.as_ref().len() / 3 + 1) * 4);
+    display_url(bytes, &mut res).unwrap();
+    res
+}
+
 /// Returns a placeholder value that implements `Display` for encoded data.
 pub fn encode_display<Octets: AsRef<[u8]>>(
     octets: &Octets,
@@ -493,6 +528,17 @@ const ENCODE_ALPHABET: [char; 64] = [
     '4', '5', '6', '7', '8', '9', '+', '/', // 0x38 .. 0x3F
 ];
 
+const ENCODE_URL_ALPHABET: [char; 64] = [
+    'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', // 0x00 .. 0x07
+    'I', 'J', 'K', 'L', 'M', 'N', 'O', 'P', // 0x08 .. 0x0F
+    'Q', 'R', 'S', 'T', 'U', 'V', 'W', 'X', // 0x10 .. 0x17
+    'Y', 'Z', 'a', 'b', 'c', 'd', 'e', 'f', // 0x18 .. 0x1F
+    'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', // 0x20 .. 0x27
+    'o', 'p', 'q', 'r', 's', 't', 'u', 'v', // 0x28 .. 0x2F
+    'w', 'x', 'y', 'z', '0', '1', '2', '3', // 0x30 .. 0x37
+    '4', '5', '6', '7', '8', '9', '-', '_', // 0x38 .. 0x3F
+];
+
 /// The padding character
 const PAD: char = '=';
 
@@ -552,4 +598,16 @@ mod test {
         assert_eq!(fmt(b"fooba"), "Zm9vYmE=");
         assert_eq!(fmt(b"foobar"), "Zm9vYmFy");
     }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn encode_url() {
+        use super::*;
+
+        assert_eq!(encode_url_string(b""), "");
+        assert_eq!(encode_url_string(b"f"), "Zg");
+        assert_eq!(encode_url_string(b"fo"), "Zm8");
+        assert_eq!(encode_url_string(b"foobar"), "Zm9vYmFy");
+        assert_eq!(encode_url_string(b"\xfb\xff\xbf"), "-_-_");
+    }
 }
@@ -214,84 +214,99 @@ impl<Octets: AsRef<[u8]>, Name: Compose> RrsigExt for Rrsig<Octets, Name> {
         dnskey: &Dnskey<impl AsRef<[u8]>>,
         signed_data: &impl AsRef<[u8]>,
     ) -> Result<(), AlgorithmError> {
-        let signature = self.signature().as_ref();
-        let signed_data = signed_data.as_ref();
-
         // Caller needs to ensure that the signature matches the key, but enforce the algorithm match
         if self.algorithm() != dnskey.algorithm() {
             return Err(AlgorithmError::InvalidData);
         }
 
-        // Note: Canonicalize the algorithm, otherwise matching named variants against Int(_) is not going to work
-        let sec_alg = SecAlg::from_int(self.algorithm().to_int());
-        match sec_alg {
-            SecAlg::RsaSha1
-            | SecAlg::RsaSha1Nsec3Sha1
-            | SecAlg::RsaSha256
-            | SecAlg::RsaSha512 => {
-                let (algorithm, min_bytes) = match sec_alg {
-                    SecAlg::RsaSha1 | SecAlg::RsaSha1Nsec3Sha1 => (
-                        &signature::RSA_PKCS1_1024_8192_SHA1_FOR_LEGACY_USE_ONLY,
-                        1024 / 8,
-                    ),
-                    SecAlg::RsaSha256 => (
-                        &signature::RSA_PKCS1_1024_8192_SHA256_FOR_LEGACY_USE_ONLY,
-                        1024 / 8,
-                    ),
-                    SecAlg::RsaSha512 => (
-                        &signature::RSA_PKCS1_1024_8192_SHA512_FOR_LEGACY_USE_ONLY,
-                        1024 / 8,
-                    ),
-                    _ => unreachable!(),
-                };
-
-                // The key isn't available in either PEM or DER, so use the
-                // direct RSA verifier.
-                let (e, n) = rsa_exponent_modulus(dnskey, min_bytes)?;
-                let public_key =
-                    signature::RsaPublicKeyComponents { n: &n, e: &e };
-                public_key
-                    .verify(algorithm, signed_data, signature)
-                    .map_err(|_| AlgorithmError::BadSig)
-            }
-            SecAlg::EcdsaP256Sha256 | SecAlg::EcdsaP384Sha384 => {
-                let algorithm = match sec_alg {
-                    SecAlg::EcdsaP256Sha256 => {
-                        &signature::ECDSA_P256_SHA256_FIXED
-                    }
-                    SecAlg::EcdsaP384Sha384 => {
-                        &signature::ECDSA_P384_SHA384_FIXED
-                    }
-                    _ => unreachable!(),
-                };
-
-                // Add 0x4 identifier to the ECDSA pubkey as expected by ring.
-                let public_key = dnskey.public_key().as_ref();
-                let mut key = Vec::with_capacity(public_key.len() + 1);
-                key.push(0x4);
-                key.extend_from_slice(public_key);
-
-                signature::UnparsedPublicKey::new(algorithm, &key)
-                    .verify(signed_data, signature)
-                    .map_err(|_| AlgorithmError::BadSig)
-            }
-            SecAlg::Ed25519 => {
-                let key = dnskey.public_key();
-                signature::UnparsedPublicKey::new(&signature::ED25519, &key)
-                    .verify(signed_data, signature)
-                    .map_err(|_| AlgorithmError::BadSig)
-            }
-            _ => Err(AlgorithmError::Unsupported),
+        verify_signature(
+            self.algorithm(),
+            dnskey.public_key().as_ref(),
+            signed_data.as_ref(),
+            self.signature().as_ref(),
+        )
+    }
+}
+
+//------------ verify_signature ----------------------------------------------
+
+/// Verifies a signature over some data using a public key.
+///
+/// The public key has to be in the format used in the DNSKEY record for the
+/// given algorithm. Since KEY records use the same format, this function
+/// can be used for verifying signatures with those keys, too.
+pub fn verify_signature(
+    algorithm: SecAlg,
+    public_key: &[u8],
+    signed_data: &[u8],
+    signature: &[u8],
+) -> Result<(), AlgorithmError> {
+    // Note: Canonicalize the algorithm, otherwise matching named variants against Int(_) is not going to work
+    let sec_alg = SecAlg::from_int(algorithm.to_int());
+    match sec_alg {
+        SecAlg::RsaSha1
+        | SecAlg::RsaSha1Nsec3Sha1
+        | SecAlg::RsaSha256
+        | SecAlg::RsaSha512 => {
+            let (algorithm, min_bytes) = match sec_alg {
+                SecAlg::RsaSha1 | SecAlg::RsaSha1Nsec3Sha1 => (
+                    &signature::RSA_PKCS1_1024_8192_SHA1_FOR_LEGACY_USE_ONLY,
+                    1024 / 8,
+                ),
+                SecAlg::RsaSha256 => (
+                    &signature::RSA_PKCS1_1024_8192_SHA256_FOR_LEGACY_USE_ONLY,
+                    1024 / 8,
+                ),
+                SecAlg::RsaSha512 => (
+                    &signature::RSA_PKCS1_1024_8192_SHA512_FOR_LEGACY_USE_ONLY,
+                    1024 / 8,
+                ),
+                _ => unreachable!(),
+            };
+
+            // The key isn't available in either PEM or DER, so use the
+            // direct RSA verifier.
+            let (e, n) = rsa_exponent_modulus(public_key, min_bytes)?;
+            let public_key =
+                signature::RsaPublicKeyComponents { n: &n, e: &e };
+            public_key
+                .verify(algorithm, signed_data, signature)
+                .map_err(|_| AlgorithmError::BadSig)
+        }
+        SecAlg::EcdsaP256Sha256 | SecAlg::EcdsaP384Sha384 => {
+            let algorithm = match sec_alg {
+                SecAlg::EcdsaP256Sha256 => {
+                    &signature::ECDSA_P256_SHA256_FIXED
+                }
+                SecAlg::EcdsaP384Sha384 => {
+                    &signature::ECDSA_P384_SHA384_FIXED
+                }
+                _ => unreachable!(),
+            };
+
+            // Add 0x4 identifier to the ECDSA pubkey as expected by ring.
+            let mut key = Vec::with_capacity(public_key.len() + 1);
+            key.push(0x4);
+            key.extend_from_slice(public_key);
+
+            signature::UnparsedPublicKey::new(algorithm, &key)
+                .verify(signed_data, signature)
+                .map_err(|_| AlgorithmError::BadSig)
+        }
+        SecAlg::Ed25519 => {
+            signature::UnparsedPublicKey::new(&signature::ED25519, public_key)
+                .verify(signed_data, signature)
+                .map_err(|_| AlgorithmError::BadSig)
         }
+        _ => Err(AlgorithmError::Unsupported),
     }
 }
 
-/// Return the RSA exponent and modulus components from DNSKEY record data.
+/// Return the RSA exponent and modulus components from DNSKEY public key.
 fn rsa_exponent_modulus(
-    dnskey: &Dnskey<impl AsRef<[u8]>>,
+    public_key: &[u8],
     min_len: usize,
 ) -> Result<(&[u8], &[u8]), AlgorithmError> {
-    let public_key = dnskey.public_key().as_ref();
     if public_key.len() <= 3 {
         return Err(AlgorithmError::InvalidData);
     }
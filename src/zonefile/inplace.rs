@@ -14,7 +14,10 @@
 
 use crate::base::charstr::CharStr;
 use crate::base::iana::{Class, Rtype};
-use crate::base::name::{Chain, Dname, RelativeDname, ToDname};
+use crate::base::name::{
+    check_hostname_label, check_service_label, Chain, Dname, RelativeDname,
+    ToDname,
+};
 use crate::base::record::Record;
 use crate::base::scan::{
     BadSymbol, ConvertSymbols, EntrySymbol, Scan, Scanner, ScannerError,
@@ -71,6 +74,9 @@ pub struct Zonefile {
 
     /// The last class.
     last_class: Option<Class>,
+
+    /// Whether to reject domain names with non-LDH labels.
+    ldh_only: bool,
 }
 
 impl Zonefile {
@@ -94,6 +100,7 @@ impl Zonefile {
             last_owner: None,
             last_ttl: None,
             last_class: None,
+            ldh_only: false,
         }
     }
 
@@ -165,6 +172,21 @@ impl Zonefile {
         self.origin = Some(origin)
     }
 
+    /// Sets whether only LDH labels are accepted in domain names.
+    ///
+    /// If enabled, scanning fails for any domain name in the zonefile –
+    /// owner names as well as names in record data – that contains a label
+    /// that isn’t a host name label consisting of ASCII letters, digits,
+    /// and hyphens and neither starting nor ending with a hyphen. Since
+    /// they are common in zonefiles, an initial wildcard label and
+    /// underscore labels such as `_tcp` are still accepted.
+    ///
+    /// Names given via [`set_origin`][Self::set_origin] are not checked.
+    /// By default, all labels are accepted.
+    pub fn set_ldh_only(&mut self, ldh_only: bool) {
+        self.ldh_only = ldh_only
+    }
+
     /// Returns the next entry in the zonefile.
     ///
     /// Returns `Ok(None)` if the end of the file has been reached. Returns
@@ -657,10 +679,12 @@ impl<'a> Scanner for EntryScanner<'a> {
                     if write > 254 {
                         return Err(EntryError::bad_dname());
                     }
+                    self.check_ldh_label(start, write)?;
                 }
                 Some(false) => {
                     // Reached end of token. This means we have a relative
                     // dname.
+                    self.check_ldh_label(start, write)?;
                     self.zonefile.buf.next_item()?;
                     return unsafe {
                         RelativeDname::from_octets_unchecked(
@@ -753,6 +777,29 @@ impl<'a> Scanner for EntryScanner<'a> {
 }
 
 impl<'a> EntryScanner<'a> {
+    /// Checks a converted label if only LDH labels are allowed.
+    ///
+    /// The label including its length octet is in the buffer between
+    /// `start` and `end`.
+    fn check_ldh_label(
+        &self,
+        start: usize,
+        end: usize,
+    ) -> Result<(), EntryError> {
+        if !self.zonefile.ldh_only {
+            return Ok(());
+        }
+        let label = &self.zonefile.buf.buf[start + 1..end];
+        let res = if start == 0 && label == b"*" {
+            Ok(())
+        } else if label.first() == Some(&b'_') {
+            check_service_label(label)
+        } else {
+            check_hostname_label(label)
+        };
+        res.map_err(|_| EntryError::non_ldh_label())
+    }
+
     /// Converts a single token using a token converter.
     fn convert_one_token<
         S: From<Symbol>,
@@ -1423,6 +1470,10 @@ impl EntryError {
         EntryError("bad dname")
     }
 
+    fn non_ldh_label() -> Self {
+        EntryError("non-LDH label in dname")
+    }
+
     fn unbalanced_parens() -> Self {
         EntryError("unbalanced parens")
     }
@@ -1551,6 +1602,25 @@ mod test {
         test("\"quoted\" ", b"quoted");
     }
 
+    #[test]
+    fn ldh_only() {
+        fn scan(zone: &str, ldh_only: bool) -> Result<Option<Entry>, Error> {
+            let mut zone = Zonefile::from(zone);
+            zone.set_origin(Dname::from_str("example.com").unwrap());
+            zone.set_ldh_only(ldh_only);
+            zone.next_entry()
+        }
+
+        assert!(scan("www 3600 IN A 192.0.2.1\n", true).is_ok());
+        assert!(scan("*.www 3600 IN A 192.0.2.1\n", true).is_ok());
+        assert!(scan("_sip._tcp 3600 IN SRV 0 0 5060 sip\n", true).is_ok());
+        assert!(scan("www.example.com. 3600 IN CNAME -a.\n", false).is_ok());
+        assert!(scan("www.example.com. 3600 IN CNAME -a.\n", true).is_err());
+        assert!(scan("w\\032w 3600 IN A 192.0.2.1\n", true).is_err());
+        assert!(scan("www.* 3600 IN A 192.0.2.1\n", true).is_err());
+        assert!(scan("ww_w 3600 IN A 192.0.2.1\n", true).is_err());
+    }
+
     #[derive(serde::Deserialize)]
     #[allow(clippy::type_complexity)]
     struct TestCase {
@@ -0,0 +1,1087 @@
+//! Reading DNS messages from their presentation format.
+//!
+//! Test suites commonly describe DNS exchanges as text rather than as wire
+//! data. This module provides a parser for two such descriptions that
+//! produces a wire-format [`Message`].
+//!
+//! The first is the output of the _dig_ tool as also produced by the
+//! `Display` implementation of [`Message`]. The header is taken from the
+//! `;; ->>HEADER<<-` and `;; flags:` lines, EDNS data from the
+//! `;; OPT PSEUDOSECTION:`, and the questions and records from the sections
+//! headed by lines such as `;; ANSWER SECTION:`. Any other comment lines
+//! are ignored.
+//!
+//! The second is the entry format used by _ldns-testns_ and the test
+//! harnesses modelled after it. Here, the `REPLY` keyword lists the header
+//! flags, opcode, and response code, `SECTION` starts a new section, and
+//! raw EDNS options can be given between `HEX_EDNSDATA_BEGIN` and
+//! `HEX_EDNSDATA_END`. A complete message can also be given in hex between
+//! `HEX_ANSWER_BEGIN` and `HEX_ANSWER_END`. The `ENTRY_BEGIN`, `ENTRY_END`,
+//! `MATCH`, and `ADJUST` keywords are ignored.
+//!
+//! In both formats, questions are given as a domain name, an optional class,
+//! and a record type. Records are given in zonefile format and are read via
+//! the zonefile scanner of the [`inplace`] module with the root as the
+//! origin. If the TTL of a record is missing, it defaults to 3600 seconds.
+//!
+//! The parser is available through the [`parse`] function and the
+//! `FromStr` implementation for a [`Message`] atop a `Vec<u8>`.
+//!
+//! [`inplace`]: super::inplace
+#![cfg(feature = "bytes")]
+#![cfg_attr(docsrs, doc(cfg(feature = "bytes")))]
+
+use super::inplace::{self, Entry, ScannedRecord, Zonefile};
+use crate::base::header::Header;
+use crate::base::iana::{
+    Class, Opcode, OptRcode, OptionCode, Rtype, TsigRcode,
+};
+use crate::base::message::Message;
+use crate::base::message_builder::MessageBuilder;
+use crate::base::name::Dname;
+use crate::base::octets::{OctetsBuilder, ShortBuf};
+use crate::base::question::Question;
+use core::str::FromStr;
+use std::fmt;
+use std::net::IpAddr;
+use std::vec::Vec;
+
+//------------ parse ---------------------------------------------------------
+
+/// Parses a message from its presentation format.
+///
+/// See the [module documentation][self] for the accepted formats.
+pub fn parse(s: &str) -> Result<Message<Vec<u8>>, Error> {
+    let mut reader = Reader::default();
+    let mut lines = s.lines().enumerate();
+    while let Some((idx, line)) = lines.next() {
+        reader.line = idx + 1;
+        reader
+            .scan_line(line, &mut lines)
+            .map_err(|kind| Error::new(reader.line, kind))?;
+    }
+    let line = reader.line;
+    reader.into_message().map_err(|kind| Error::new(line, kind))
+}
+
+//--- FromStr
+
+impl FromStr for Message<Vec<u8>> {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse(s)
+    }
+}
+
+//------------ Reader --------------------------------------------------------
+
+/// The state of reading a message.
+#[derive(Default)]
+struct Reader {
+    /// The number of the line currently being read.
+    line: usize,
+
+    /// The section we are currently in.
+    section: Section,
+
+    /// A hex block we are currently in.
+    hex: Option<HexBlock>,
+
+    /// The header of the message.
+    header: Header,
+
+    /// The response code of the message.
+    rcode: u16,
+
+    /// The EDNS data of the message if it has any.
+    edns: Option<Edns>,
+
+    /// The questions of the message.
+    question: Vec<Question<Dname<Vec<u8>>>>,
+
+    /// The records of the answer section.
+    answer: Vec<ScannedRecord>,
+
+    /// The records of the authority section.
+    authority: Vec<ScannedRecord>,
+
+    /// The records of the additional section.
+    additional: Vec<ScannedRecord>,
+
+    /// The complete message if it was given in hex.
+    raw: Option<Vec<u8>>,
+}
+
+impl Reader {
+    /// Scans a single line.
+    ///
+    /// If the line starts a record that continues over the following lines,
+    /// these are taken from `lines`.
+    fn scan_line<'a>(
+        &mut self,
+        line: &'a str,
+        lines: &mut impl Iterator<Item = (usize, &'a str)>,
+    ) -> Result<(), ErrorKind> {
+        let trimmed = line.trim();
+        if let Some(block) = self.hex.as_mut() {
+            if trimmed.starts_with(block.end()) {
+                return self.end_hex();
+            }
+            block.push(trimmed);
+            return Ok(());
+        }
+        if trimmed.is_empty() {
+            Ok(())
+        } else if let Some(rest) = trimmed.strip_prefix(";;") {
+            self.scan_meta(rest.trim())
+        } else if let Some(rest) = trimmed.strip_prefix(';') {
+            self.scan_comment(rest.trim())
+        } else if self.scan_keyword(trimmed)? {
+            Ok(())
+        } else {
+            match self.section {
+                Section::None | Section::Opt => {
+                    Err(ErrorKind::Syntax("data outside of a section"))
+                }
+                Section::Question => self.scan_question(trimmed),
+                Section::Answer => {
+                    let record = scan_record(line, lines)?;
+                    self.answer.push(record);
+                    Ok(())
+                }
+                Section::Authority => {
+                    let record = scan_record(line, lines)?;
+                    self.authority.push(record);
+                    Ok(())
+                }
+                Section::Additional => {
+                    let record = scan_record(line, lines)?;
+                    self.additional.push(record);
+                    Ok(())
+                }
+            }
+        }
+    }
+
+    /// Scans a line starting with a double semicolon.
+    ///
+    /// These are the header and section lines of dig output. Unknown lines
+    /// are ignored.
+    fn scan_meta(&mut self, line: &str) -> Result<(), ErrorKind> {
+        if let Some(rest) = line.strip_prefix("->>HEADER<<-") {
+            for item in rest.split(',') {
+                let (key, value) = split_item(item)?;
+                match key {
+                    "opcode" => self.header.set_opcode(
+                        Opcode::from_str(value).map_err(|_| {
+                            ErrorKind::Syntax("invalid opcode")
+                        })?,
+                    ),
+                    "status" => {
+                        self.rcode = parse_rcode(value).ok_or(
+                            ErrorKind::Syntax("invalid response code"),
+                        )?
+                    }
+                    "id" => self.header.set_id(
+                        value
+                            .parse()
+                            .map_err(|_| ErrorKind::Syntax("invalid ID"))?,
+                    ),
+                    _ => {}
+                }
+            }
+            Ok(())
+        } else if let Some(rest) = line.strip_prefix("flags:") {
+            let flags = rest.split(';').next().unwrap_or("");
+            for flag in flags.split_whitespace() {
+                if !self.set_flag(flag) {
+                    return Err(ErrorKind::Syntax("unknown header flag"));
+                }
+            }
+            Ok(())
+        } else if line == "OPT PSEUDOSECTION:" {
+            self.section = Section::Opt;
+            self.edns.get_or_insert_with(Default::default);
+            Ok(())
+        } else if let Some(name) = line.strip_suffix(" SECTION:") {
+            self.section = Section::from_name(name)
+                .ok_or(ErrorKind::Syntax("unknown section"))?;
+            Ok(())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Scans a line starting with a single semicolon.
+    ///
+    /// In the OPT pseudosection, these lines contain the EDNS data and in
+    /// the question section they contain the questions. Anywhere else, they
+    /// are comments.
+    fn scan_comment(&mut self, line: &str) -> Result<(), ErrorKind> {
+        match self.section {
+            Section::Opt => match line.strip_prefix("EDNS:") {
+                Some(rest) => self.scan_edns(rest),
+                None => self.scan_option(line),
+            },
+            Section::Question => self.scan_question(line),
+            _ => Ok(()),
+        }
+    }
+
+    /// Scans the EDNS line of the OPT pseudosection.
+    fn scan_edns(&mut self, line: &str) -> Result<(), ErrorKind> {
+        let edns = self.edns.get_or_insert_with(Default::default);
+        for item in line.split([',', ';']) {
+            let (key, value) = split_item(item)?;
+            match key {
+                "version" => {
+                    edns.version = value.parse().map_err(|_| {
+                        ErrorKind::Syntax("invalid EDNS version")
+                    })?
+                }
+                "udp" => {
+                    edns.udp_payload_size = value.parse().map_err(|_| {
+                        ErrorKind::Syntax("invalid UDP payload size")
+                    })?
+                }
+                "flags" => {
+                    for flag in value.split_whitespace() {
+                        if flag.eq_ignore_ascii_case("do") {
+                            edns.dnssec_ok = true
+                        } else {
+                            return Err(ErrorKind::Syntax(
+                                "unknown EDNS flag",
+                            ));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Scans an option line of the OPT pseudosection.
+    fn scan_option(&mut self, line: &str) -> Result<(), ErrorKind> {
+        let (name, value) = match line.split_once(':') {
+            Some((name, value)) => (name.trim(), value.trim()),
+            None => (line, ""),
+        };
+        let (code, data) = match name {
+            "NSID" => (OptionCode::Nsid, parse_hex_dump(value)?),
+            "PADDING" => (OptionCode::Padding, parse_hex_dump(value)?),
+            "COOKIE" => (OptionCode::Cookie, parse_hex_dump(value)?),
+            "CLIENT-SUBNET" => (
+                OptionCode::ClientSubnet,
+                parse_client_subnet(value)
+                    .ok_or(ErrorKind::Syntax("invalid CLIENT-SUBNET"))?,
+            ),
+            "EXPIRE" => (
+                OptionCode::Expire,
+                match value.split_whitespace().next() {
+                    Some(expire) => expire
+                        .parse::<u32>()
+                        .map_err(|_| ErrorKind::Syntax("invalid EXPIRE"))?
+                        .to_be_bytes()
+                        .to_vec(),
+                    None => Vec::new(),
+                },
+            ),
+            "TCP-KEEPALIVE" => (
+                OptionCode::TcpKeepalive,
+                match value.split_whitespace().next() {
+                    Some(timeout) => parse_keepalive(timeout)
+                        .ok_or(ErrorKind::Syntax("invalid TCP-KEEPALIVE"))?
+                        .to_be_bytes()
+                        .to_vec(),
+                    None => Vec::new(),
+                },
+            ),
+            "EDE" => (
+                OptionCode::ExtendedError,
+                parse_ede(value).ok_or(ErrorKind::Syntax("invalid EDE"))?,
+            ),
+            _ => match name.strip_prefix("OPT=") {
+                Some(code) => (
+                    OptionCode::from_int(code.parse().map_err(|_| {
+                        ErrorKind::Syntax("invalid option code")
+                    })?),
+                    parse_hex_dump(value)?,
+                ),
+                None => return Err(ErrorKind::Syntax("unknown EDNS option")),
+            },
+        };
+        self.edns
+            .get_or_insert_with(Default::default)
+            .options
+            .push((code, data));
+        Ok(())
+    }
+
+    /// Scans a question.
+    ///
+    /// The question consists of the name, an optional class, and the type.
+    fn scan_question(&mut self, line: &str) -> Result<(), ErrorKind> {
+        let mut words = line.split_whitespace();
+        let (qname, first, second) =
+            match (words.next(), words.next(), words.next(), words.next()) {
+                (Some(qname), Some(first), second, None) => {
+                    (qname, first, second)
+                }
+                _ => return Err(ErrorKind::Syntax("invalid question")),
+            };
+        let qname = parse_name(qname)?;
+        let (qclass, qtype) = match second {
+            Some(qtype) => (
+                Class::from_str(first)
+                    .map_err(|_| ErrorKind::Syntax("invalid class"))?,
+                qtype,
+            ),
+            None => (Class::In, first),
+        };
+        let qtype = Rtype::from_str(qtype)
+            .map_err(|_| ErrorKind::Syntax("invalid record type"))?;
+        self.question.push(Question::new(qname, qtype, qclass));
+        Ok(())
+    }
+
+    /// Scans a line for a keyword of the ldns-testns format.
+    ///
+    /// Returns whether the line started with a keyword.
+    fn scan_keyword(&mut self, line: &str) -> Result<bool, ErrorKind> {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("ENTRY_BEGIN" | "ENTRY_END" | "MATCH" | "ADJUST") => {}
+            Some("REPLY") => {
+                for word in words {
+                    self.scan_reply_word(word)?;
+                }
+            }
+            Some("SECTION") => {
+                self.section = words
+                    .next()
+                    .and_then(Section::from_name)
+                    .ok_or(ErrorKind::Syntax("unknown section"))?;
+            }
+            Some("HEX_ANSWER_BEGIN") => {
+                self.hex = Some(HexBlock::new(HexKind::Answer))
+            }
+            Some("HEX_EDNSDATA_BEGIN") => {
+                self.hex = Some(HexBlock::new(HexKind::EdnsData))
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// Scans a word following the `REPLY` keyword.
+    fn scan_reply_word(&mut self, word: &str) -> Result<(), ErrorKind> {
+        if self.set_flag(word) {
+            Ok(())
+        } else if word.eq_ignore_ascii_case("DO") {
+            self.edns.get_or_insert_with(Default::default).dnssec_ok = true;
+            Ok(())
+        } else if let Some(opcode) = Opcode::from_mnemonic(word.as_bytes()) {
+            self.header.set_opcode(opcode);
+            Ok(())
+        } else if let Some(rcode) = parse_rcode(word) {
+            self.rcode = rcode;
+            Ok(())
+        } else {
+            Err(ErrorKind::Syntax("unknown REPLY value"))
+        }
+    }
+
+    /// Sets the header flag with the given name.
+    ///
+    /// Returns whether the name was a known flag.
+    fn set_flag(&mut self, flag: &str) -> bool {
+        let header = &mut self.header;
+        let set: fn(&mut Header, bool) = match flag.to_ascii_lowercase() {
+            ref flag if flag == "qr" => Header::set_qr,
+            ref flag if flag == "aa" => Header::set_aa,
+            ref flag if flag == "tc" => Header::set_tc,
+            ref flag if flag == "rd" => Header::set_rd,
+            ref flag if flag == "ra" => Header::set_ra,
+            ref flag if flag == "ad" => Header::set_ad,
+            ref flag if flag == "cd" => Header::set_cd,
+            _ => return false,
+        };
+        set(header, true);
+        true
+    }
+
+    /// Finishes the current hex block.
+    fn end_hex(&mut self) -> Result<(), ErrorKind> {
+        let block = match self.hex.take() {
+            Some(block) => block,
+            None => return Ok(()),
+        };
+        let data = parse_hex(&block.data)
+            .ok_or(ErrorKind::Syntax("invalid hex data"))?;
+        match block.kind {
+            HexKind::Answer => self.raw = Some(data),
+            HexKind::EdnsData => {
+                let edns = self.edns.get_or_insert_with(Default::default);
+                let mut data = data.as_slice();
+                while !data.is_empty() {
+                    if data.len() < 4 {
+                        return Err(ErrorKind::Syntax("short EDNS option"));
+                    }
+                    let code = u16::from_be_bytes([data[0], data[1]]);
+                    let len =
+                        usize::from(u16::from_be_bytes([data[2], data[3]]));
+                    if data.len() < len + 4 {
+                        return Err(ErrorKind::Syntax("short EDNS option"));
+                    }
+                    edns.options.push((
+                        OptionCode::from_int(code),
+                        data[4..len + 4].to_vec(),
+                    ));
+                    data = &data[len + 4..];
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Converts the data read into the final message.
+    fn into_message(mut self) -> Result<Message<Vec<u8>>, ErrorKind> {
+        if let Some(block) = self.hex.as_ref() {
+            return Err(ErrorKind::Syntax(block.missing_end()));
+        }
+        if let Some(raw) = self.raw.take() {
+            if !self.is_empty() {
+                return Err(ErrorKind::Syntax(
+                    "hex message mixed with message content",
+                ));
+            }
+            return Message::from_octets(raw)
+                .map_err(|_| ErrorKind::Syntax("short hex message"));
+        }
+
+        let rcode = OptRcode::from_int(self.rcode);
+        if self.edns.is_none() && rcode.ext() != 0 {
+            return Err(ErrorKind::Syntax(
+                "extended response code without EDNS",
+            ));
+        }
+        self.header.set_rcode(rcode.rcode());
+
+        let mut msg = MessageBuilder::new_vec();
+        *msg.header_mut() = self.header;
+        let mut msg = msg.question();
+        for question in &self.question {
+            msg.push(question)?;
+        }
+        let mut msg = msg.answer();
+        for record in &self.answer {
+            msg.push(record)?;
+        }
+        let mut msg = msg.authority();
+        for record in &self.authority {
+            msg.push(record)?;
+        }
+        let mut msg = msg.additional();
+        for record in &self.additional {
+            msg.push(record)?;
+        }
+        if let Some(edns) = self.edns {
+            msg.opt(|opt| {
+                opt.set_udp_payload_size(edns.udp_payload_size);
+                opt.set_version(edns.version);
+                opt.set_dnssec_ok(edns.dnssec_ok);
+                opt.set_rcode(rcode);
+                for (code, data) in &edns.options {
+                    opt.push_raw_option(*code, |target| {
+                        target.append_slice(data)
+                    })?;
+                }
+                Ok(())
+            })?;
+        }
+        Ok(msg.into_message())
+    }
+
+    /// Returns whether no content other than a hex message has been read.
+    fn is_empty(&self) -> bool {
+        self.header == Header::default()
+            && self.rcode == 0
+            && self.edns.is_none()
+            && self.question.is_empty()
+            && self.answer.is_empty()
+            && self.authority.is_empty()
+            && self.additional.is_empty()
+    }
+}
+
+//------------ Section -------------------------------------------------------
+
+/// The section of the message currently being read.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Section {
+    /// Before any section.
+    None,
+
+    /// The OPT pseudosection of dig output.
+    Opt,
+
+    /// The question section.
+    Question,
+
+    /// The answer section.
+    Answer,
+
+    /// The authority section.
+    Authority,
+
+    /// The additional section.
+    Additional,
+}
+
+impl Section {
+    /// Returns the section for the given name.
+    ///
+    /// The names of the sections of UPDATE messages are accepted, too.
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "QUESTION" | "ZONE" => Some(Section::Question),
+            "ANSWER" | "PREREQUISITE" => Some(Section::Answer),
+            "AUTHORITY" | "UPDATE" => Some(Section::Authority),
+            "ADDITIONAL" => Some(Section::Additional),
+            _ => None,
+        }
+    }
+}
+
+impl Default for Section {
+    fn default() -> Self {
+        Section::None
+    }
+}
+
+//------------ Edns ----------------------------------------------------------
+
+/// The EDNS data of the message.
+#[derive(Clone, Debug)]
+struct Edns {
+    /// The UDP payload size.
+    udp_payload_size: u16,
+
+    /// The EDNS version.
+    version: u8,
+
+    /// The DNSSEC OK bit.
+    dnssec_ok: bool,
+
+    /// The options in the order given.
+    options: Vec<(OptionCode, Vec<u8>)>,
+}
+
+impl Default for Edns {
+    fn default() -> Self {
+        Edns {
+            udp_payload_size: 4096,
+            version: 0,
+            dnssec_ok: false,
+            options: Vec::new(),
+        }
+    }
+}
+
+//------------ HexBlock ------------------------------------------------------
+
+/// A block of hex data in the ldns-testns format.
+struct HexBlock {
+    /// What the data is for.
+    kind: HexKind,
+
+    /// The data collected so far with comments removed.
+    data: std::string::String,
+}
+
+/// The kind of data in a hex block.
+#[derive(Clone, Copy)]
+enum HexKind {
+    /// A complete message.
+    Answer,
+
+    /// The options of the OPT record.
+    EdnsData,
+}
+
+impl HexBlock {
+    /// Creates a new, empty block.
+    fn new(kind: HexKind) -> Self {
+        HexBlock {
+            kind,
+            data: std::string::String::new(),
+        }
+    }
+
+    /// Returns the keyword that ends the block.
+    fn end(&self) -> &'static str {
+        match self.kind {
+            HexKind::Answer => "HEX_ANSWER_END",
+            HexKind::EdnsData => "HEX_EDNSDATA_END",
+        }
+    }
+
+    /// Returns the error message for a block that doesn’t end.
+    fn missing_end(&self) -> &'static str {
+        match self.kind {
+            HexKind::Answer => "missing HEX_ANSWER_END",
+            HexKind::EdnsData => "missing HEX_EDNSDATA_END",
+        }
+    }
+
+    /// Adds a line to the block.
+    fn push(&mut self, line: &str) {
+        self.data.push_str(line.split(';').next().unwrap_or(""));
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Splits an item of the form `key: value`.
+fn split_item(item: &str) -> Result<(&str, &str), ErrorKind> {
+    item.split_once(':')
+        .map(|(key, value)| (key.trim(), value.trim()))
+        .ok_or(ErrorKind::Syntax("expected key and value"))
+}
+
+/// Parses a domain name.
+///
+/// The name is always considered absolute.
+fn parse_name(s: &str) -> Result<Dname<Vec<u8>>, ErrorKind> {
+    if s == "." {
+        Ok(Dname::root_vec())
+    } else {
+        Dname::from_str(s).map_err(|_| ErrorKind::Syntax("invalid name"))
+    }
+}
+
+/// Parses a response code.
+///
+/// Besides the mnemonics and decimal values, this also accepts the names
+/// used by dig.
+fn parse_rcode(s: &str) -> Option<u16> {
+    let upper = s.to_ascii_uppercase();
+    let value = match upper.as_str() {
+        "NOTIMP" => 4,
+        "NOAUTH" => 9,
+        "BADVERS" | "BADVER" => 16,
+        _ => match upper.strip_prefix("RESERVED") {
+            Some(value) => value.parse().ok()?,
+            None => match TsigRcode::from_mnemonic(upper.as_bytes()) {
+                Some(rcode) => rcode.to_int(),
+                None => upper.parse().ok()?,
+            },
+        },
+    };
+    if value > 0x0FFF {
+        None
+    } else {
+        Some(value)
+    }
+}
+
+/// Parses a string of hex digits ignoring any white space.
+fn parse_hex(s: &str) -> Option<Vec<u8>> {
+    let mut res = Vec::new();
+    let mut high = None;
+    for ch in s.chars().filter(|ch| !ch.is_whitespace()) {
+        let digit = ch.to_digit(16)? as u8;
+        match high.take() {
+            Some(high) => res.push(high << 4 | digit),
+            None => high = Some(digit),
+        }
+    }
+    if high.is_some() {
+        None
+    } else {
+        Some(res)
+    }
+}
+
+/// Parses option data given as hex digits.
+///
+/// Any text in parentheses following the digits is ignored.
+fn parse_hex_dump(s: &str) -> Result<Vec<u8>, ErrorKind> {
+    let digits = match s.find('(') {
+        Some(pos) => &s[..pos],
+        None => s,
+    };
+    parse_hex(digits).ok_or(ErrorKind::Syntax("invalid hex data"))
+}
+
+/// Parses the data of a client subnet option.
+///
+/// The option is given as address, source prefix length, and scope prefix
+/// length separated by slashes.
+fn parse_client_subnet(s: &str) -> Option<Vec<u8>> {
+    let mut parts = s.split('/');
+    let addr = IpAddr::from_str(parts.next()?).ok()?;
+    let source: u8 = parts.next()?.parse().ok()?;
+    let scope: u8 = parts.next().unwrap_or("0").parse().ok()?;
+    let (family, octets) = match addr {
+        IpAddr::V4(addr) => (1u16, addr.octets().to_vec()),
+        IpAddr::V6(addr) => (2u16, addr.octets().to_vec()),
+    };
+    let len = (usize::from(source) + 7) / 8;
+    if len > octets.len() {
+        return None;
+    }
+    let mut res = Vec::with_capacity(4 + len);
+    res.extend_from_slice(&family.to_be_bytes());
+    res.push(source);
+    res.push(scope);
+    res.extend_from_slice(&octets[..len]);
+    Some(res)
+}
+
+/// Parses a TCP keepalive timeout given in seconds with one decimal place.
+fn parse_keepalive(s: &str) -> Option<u16> {
+    let (secs, tenths) = s.split_once('.').unwrap_or((s, "0"));
+    let secs: u16 = secs.parse().ok()?;
+    let tenths: u16 = match tenths.len() {
+        1 => tenths.parse().ok()?,
+        _ => return None,
+    };
+    secs.checked_mul(10)?.checked_add(tenths)
+}
+
+/// Parses the data of an extended error option.
+///
+/// The option is given as the decimal error code, optionally followed by
+/// its mnemonic in parentheses and the extra text in parentheses after a
+/// colon.
+fn parse_ede(s: &str) -> Option<Vec<u8>> {
+    let end = s.find(|ch: char| !ch.is_ascii_digit()).unwrap_or(s.len());
+    let code: u16 = s[..end].parse().ok()?;
+    let rest = &s[end..];
+    let text = match rest.find(": (") {
+        Some(pos) => rest[pos + 3..].strip_suffix(')')?,
+        None => "",
+    };
+    let mut res = Vec::with_capacity(2 + text.len());
+    res.extend_from_slice(&code.to_be_bytes());
+    res.extend_from_slice(text.as_bytes());
+    Some(res)
+}
+
+/// Scans a record starting at the given line.
+///
+/// If the record contains unbalanced parentheses, it continues on the
+/// following lines which are then taken from `lines`.
+fn scan_record<'a>(
+    line: &'a str,
+    lines: &mut impl Iterator<Item = (usize, &'a str)>,
+) -> Result<ScannedRecord, ErrorKind> {
+    let mut zonefile = Zonefile::new();
+    zonefile.set_origin(Dname::root_bytes());
+    zonefile.extend_from_slice(b"$TTL 3600\n");
+    let mut depth = paren_depth(line, 0);
+    zonefile.extend_from_slice(line.as_bytes());
+    zonefile.extend_from_slice(b"\n");
+    while depth > 0 {
+        let (_, line) = lines
+            .next()
+            .ok_or(ErrorKind::Syntax("unbalanced parentheses"))?;
+        depth = paren_depth(line, depth);
+        zonefile.extend_from_slice(line.as_bytes());
+        zonefile.extend_from_slice(b"\n");
+    }
+    match zonefile.next_entry()? {
+        Some(Entry::Record(record)) => match zonefile.next_entry()? {
+            None => Ok(record),
+            Some(_) => Err(ErrorKind::Syntax("trailing data after record")),
+        },
+        _ => Err(ErrorKind::Syntax("expected a record")),
+    }
+}
+
+/// Returns the depth of parentheses after the given line.
+///
+/// Parentheses in quoted strings, escaped parentheses and those in comments
+/// are skipped.
+fn paren_depth(line: &str, mut depth: usize) -> usize {
+    let mut quoted = false;
+    let mut chars = line.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => {
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ';' if !quoted => break,
+            '(' if !quoted => depth += 1,
+            ')' if !quoted => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    depth
+}
+
+//============ Error Types ===================================================
+
+//------------ Error ---------------------------------------------------------
+
+/// An error happened while parsing a message.
+#[derive(Debug)]
+pub struct Error {
+    /// The line number where the error happened.
+    line: usize,
+
+    /// What went wrong.
+    kind: ErrorKind,
+}
+
+/// The kinds of errors that can happen.
+#[derive(Debug)]
+enum ErrorKind {
+    /// The text is malformed.
+    Syntax(&'static str),
+
+    /// A record could not be scanned.
+    Record(inplace::Error),
+
+    /// The message became too large.
+    ShortBuf,
+}
+
+impl Error {
+    /// Creates a new error.
+    fn new(line: usize, kind: ErrorKind) -> Self {
+        Error { line, kind }
+    }
+
+    /// Returns the number of the line where the error happened.
+    ///
+    /// Lines are counted starting at 1.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+}
+
+impl From<inplace::Error> for ErrorKind {
+    fn from(err: inplace::Error) -> Self {
+        ErrorKind::Record(err)
+    }
+}
+
+impl From<ShortBuf> for ErrorKind {
+    fn from(_: ShortBuf) -> Self {
+        ErrorKind::ShortBuf
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        match self.kind {
+            ErrorKind::Syntax(msg) => f.write_str(msg),
+            ErrorKind::Record(ref err) => {
+                write!(f, "invalid record ({})", err)
+            }
+            ErrorKind::ShortBuf => f.write_str("message too large"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::ExtendedErrorCode;
+    use crate::base::opt::{Cookie, ExtendedError, Nsid};
+    use crate::rdata::{Aaaa, Ns, A};
+    use std::string::ToString;
+
+    fn parse_ok(s: &str) -> Message<Vec<u8>> {
+        match parse(s) {
+            Ok(msg) => msg,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    fn name(s: &str) -> Dname<Vec<u8>> {
+        Dname::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn dig_round_trip() {
+        let mut msg = MessageBuilder::new_vec();
+        msg.header_mut().set_id(4711);
+        msg.header_mut().set_qr(true);
+        msg.header_mut().set_rd(true);
+        msg.header_mut().set_cd(true);
+        let mut msg = msg.question();
+        msg.push((name("example.com"), Rtype::A)).unwrap();
+        let mut msg = msg.answer();
+        msg.push((name("example.com"), 300, A::from_octets(192, 0, 2, 1)))
+            .unwrap();
+        let mut msg = msg.authority();
+        msg.push((
+            name("example.com"),
+            86400,
+            Ns::new(name("ns.example.com")),
+        ))
+        .unwrap();
+        let mut msg = msg.additional();
+        msg.push((
+            name("ns.example.com"),
+            86400,
+            Aaaa::new("2001:db8::53".parse().unwrap()),
+        ))
+        .unwrap();
+        msg.opt(|opt| {
+            opt.set_udp_payload_size(1232);
+            opt.set_dnssec_ok(true);
+            opt.set_rcode(OptRcode::BadCookie);
+            opt.push(&Nsid::from_octets("ns1".as_bytes()))?;
+            opt.push(&Cookie::new([1, 2, 3, 4, 5, 6, 7, 8]))?;
+            opt.push(
+                &ExtendedError::try_from((
+                    ExtendedErrorCode::Prohibited,
+                    "not (really) allowed".as_bytes(),
+                ))
+                .unwrap(),
+            )?;
+            opt.push_raw_option(OptionCode::ClientSubnet, |target| {
+                target.append_slice(&[0, 1, 24, 0, 192, 0, 2])
+            })?;
+            opt.push_raw_option(OptionCode::Expire, |target| {
+                target.append_slice(&1234u32.to_be_bytes())
+            })?;
+            opt.push_raw_option(OptionCode::TcpKeepalive, |target| {
+                target.append_slice(&305u16.to_be_bytes())
+            })?;
+            opt.push_raw_option(OptionCode::Padding, |target| {
+                target.append_slice(&[0; 3])
+            })?;
+            opt.push_raw_option(OptionCode::from_int(65001), |target| {
+                target.append_slice(b"A\n")
+            })
+        })
+        .unwrap();
+        let msg = msg.into_message();
+
+        let parsed = parse_ok(&msg.to_string());
+        assert_eq!(parsed.as_slice(), msg.as_slice());
+    }
+
+    #[test]
+    fn dig_output() {
+        let msg = parse_ok(
+            "; <<>> DiG 9.18.1 <<>> example.com\n\
+             ;; global options: +cmd\n\
+             ;; Got answer:\n\
+             ;; ->>HEADER<<- opcode: QUERY, status: NXDOMAIN, id: 12\n\
+             ;; flags: qr aa; QUERY: 1, ANSWER: 0, AUTHORITY: 1, \
+             ADDITIONAL: 0\n\
+             \n\
+             ;; QUESTION SECTION:\n\
+             ;nx.example.com.\t\tIN\tA\n\
+             \n\
+             ;; AUTHORITY SECTION:\n\
+             example.com.\t\t3600\tIN\tSOA\tns.example.com. \
+             hostmaster.example.com. 1 7200 3600 1209600 3600\n\
+             \n\
+             ;; Query time: 0 msec\n",
+        );
+        let header = msg.header();
+        assert_eq!(header.id(), 12);
+        assert!(header.qr() && header.aa() && !header.rd());
+        assert_eq!(header.rcode(), crate::base::iana::Rcode::NXDomain);
+        assert!(msg.opt().is_none());
+        let question = msg.first_question().unwrap();
+        assert_eq!(question.qname(), &name("nx.example.com"));
+        assert_eq!(question.qtype(), Rtype::A);
+        let counts = msg.header_counts();
+        assert_eq!(
+            (counts.ancount(), counts.nscount(), counts.arcount()),
+            (0, 1, 0)
+        );
+    }
+
+    #[test]
+    fn testns_entry() {
+        let msg = parse_ok(
+            "ENTRY_BEGIN\n\
+             MATCH opcode qtype qname\n\
+             ADJUST copy_id\n\
+             REPLY QR AA NOERROR DO\n\
+             SECTION QUESTION\n\
+             www.example.com. IN A\n\
+             SECTION ANSWER\n\
+             www.example.com. IN A 192.0.2.1\n\
+             www.example.com. 60 IN TXT ( \"first (part)\"\n\
+             \t\"second\" )\n\
+             SECTION ADDITIONAL\n\
+             HEX_EDNSDATA_BEGIN\n\
+             ; NSID\n\
+             00 03 00 03\n\
+             6e 73 31\n\
+             HEX_EDNSDATA_END\n\
+             ENTRY_END\n",
+        );
+        assert!(msg.header().qr() && msg.header().aa());
+        let mut answer = msg.answer().unwrap();
+        let record = answer.next().unwrap().unwrap();
+        assert_eq!(record.ttl(), 3600);
+        assert_eq!(record.rtype(), Rtype::A);
+        let record = answer.next().unwrap().unwrap();
+        assert_eq!(record.ttl(), 60);
+        assert_eq!(record.rtype(), Rtype::Txt);
+        assert!(answer.next().is_none());
+        let opt = msg.opt().unwrap();
+        assert!(opt.dnssec_ok());
+        assert_eq!(opt.udp_payload_size(), 4096);
+        assert_eq!(opt.as_opt().as_slice(), b"\x00\x03\x00\x03ns1");
+    }
+
+    #[test]
+    fn testns_hex_answer() {
+        let msg = parse_ok(
+            "ENTRY_BEGIN\n\
+             HEX_ANSWER_BEGIN\n\
+             00 0c 81 80 ; ID and flags\n\
+             00 00 00 00 00 00 00 00\n\
+             HEX_ANSWER_END\n\
+             ENTRY_END\n",
+        );
+        assert_eq!(msg.header().id(), 12);
+        assert!(msg.header().qr() && msg.header().rd() && msg.header().ra());
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            parse(";; QUESTION SECTION:\n;example.com. IN BOGUS\n")
+                .err()
+                .unwrap()
+                .line(),
+            2
+        );
+        assert_eq!(
+            parse(";; OPT PSEUDOSECTION:\n; EDNS: version: 0; udp: 512\n; FOO: 1\n")
+                .err().unwrap()
+                .line(),
+            3
+        );
+        assert_eq!(
+            parse("REPLY QR BADVERS\n").err().unwrap().to_string(),
+            "line 1: extended response code without EDNS"
+        );
+        assert!(
+            parse("SECTION ANSWER\nexample.com. IN A 192.0.2.300\n").is_err()
+        );
+        assert!(parse("example.com. IN A 192.0.2.1\n").is_err());
+        assert!(parse("HEX_ANSWER_BEGIN\n00 00\n").is_err());
+    }
+}